@@ -80,8 +80,32 @@ fn build_with_pyoxidizer_exe(exe: Option<String>, resolve_target: Option<&str>)
     }
 }
 
+/// Export the version of oside being built against, from Cargo.lock, as
+/// OSIDE_VERSION.
+fn export_oside_version() {
+    println!("cargo:rerun-if-changed=Cargo.lock");
+    let lock = std::fs::read_to_string("Cargo.lock").unwrap_or_default();
+    let version = lock
+        .split("[[package]]")
+        .find(|p| p.lines().any(|l| l == "name = \"oside\""))
+        .and_then(|p| p.lines().find_map(|l| l.strip_prefix("version = \"")))
+        .map(|v| v.trim_end_matches('"'))
+        .unwrap_or("unknown");
+    println!("cargo:rustc-env=OSIDE_VERSION={}", version);
+}
+
+/// Export the target triple being built for as BUILD_TARGET, the default
+/// target of the `package` subcommand.
+fn export_build_target() {
+    let target = std::env::var("TARGET").expect("TARGET not defined");
+    println!("cargo:rustc-env=BUILD_TARGET={}", target);
+}
+
 #[allow(clippy::if_same_then_else)]
 fn main() {
+    export_oside_version();
+    export_build_target();
+
     if std::env::var("CARGO_FEATURE_BUILD_MODE_STANDALONE").is_ok() {
         let path = PathBuf::from(std::env::var("OUT_DIR").expect("OUT_DIR not defined"));
         let path = path.join(DEFAULT_PYTHON_CONFIG_FILENAME);
@@ -144,11 +168,14 @@ fn main() {
         std::env::var("CARGO_FEATURE_GLOBAL_ALLOCATOR_MIMALLOC").is_ok();
     let global_allocator_snmalloc =
         std::env::var("CARGO_FEATURE_GLOBAL_ALLOCATOR_SNMALLOC").is_ok();
+    let global_allocator_counting =
+        std::env::var("CARGO_FEATURE_GLOBAL_ALLOCATOR_COUNTING").is_ok();
 
     let global_allocator_count = vec![
         global_allocator_jemalloc,
         global_allocator_mimalloc,
         global_allocator_snmalloc,
+        global_allocator_counting,
     ]
     .into_iter()
     .filter(|x| *x)
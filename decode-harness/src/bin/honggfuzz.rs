@@ -0,0 +1,12 @@
+//! honggfuzz frontend: `cargo hfuzz run honggfuzz`, with a corpus directory
+//! as `HFUZZ_INPUT`.
+
+use honggfuzz::fuzz;
+
+fn main() {
+    loop {
+        fuzz!(|data: &[u8]| {
+            oside_decode_harness::fuzz::fuzz_one(data);
+        });
+    }
+}
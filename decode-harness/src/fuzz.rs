@@ -0,0 +1,16 @@
+//! The fuzzing entry point shared by the fuzzer frontends.
+//!
+//! Inputs are raw frames, the same bytes as the `.bin` files of a corpus,
+//! so a corpus directory can seed any frontend.
+
+use crate::decode;
+
+/// Decode an input starting at each entry layer and encode whatever
+/// decodes again; any panic is a finding.
+pub fn fuzz_one(data: &[u8]) {
+    for start in decode::ENTRY_LAYERS {
+        if decode::oside_json(start, data).is_some() {
+            decode::oside_encode(start, data);
+        }
+    }
+}
@@ -0,0 +1,13 @@
+//! Corpus and decoding code shared with oside-tests, plus the fuzzing entry
+//! point used by the fuzzer frontends.
+
+#[path = "../../src/alloc_stats.rs"]
+pub mod alloc_stats;
+#[path = "../../src/corpus.rs"]
+pub mod corpus;
+#[path = "../../src/decode.rs"]
+pub mod decode;
+#[path = "../../src/schema.rs"]
+pub mod schema;
+
+pub mod fuzz;
@@ -0,0 +1,74 @@
+//! Decode-only harness: runs oside over corpus files with no Python
+//! involved, for AddressSanitizer builds and Miri runs.
+//!
+//! The corpus and decoding code is shared with oside-tests.
+
+#[cfg(feature = "global-allocator-counting")]
+#[global_allocator]
+static GLOBAL: oside_decode_harness::alloc_stats::Counting =
+    oside_decode_harness::alloc_stats::Counting;
+
+use clap::Parser as ClapParser;
+use oside_decode_harness::{corpus, decode};
+use std::path::Path;
+
+/// Decode corpus cases with oside, without the embedded interpreter
+#[derive(Debug, ClapParser)]
+#[clap(version = "0.0.1", author = "Andrew Yourtchenko <ayourtch@gmail.com>")]
+struct Opts {
+    /// Layer decoding starts at: ether, ip, ipv6, udp, tcp, icmp, arp or dns
+    #[clap(long, default_value = "ether")]
+    start_layer: String,
+
+    /// Also encode each decoded case again, reporting when the bytes differ
+    #[clap(long)]
+    encode: bool,
+
+    /// Corpus directories or single case files (`.bin`)
+    #[clap(required = true)]
+    paths: Vec<String>,
+
+    /// A level of verbosity, and can be used multiple times
+    #[clap(short, long, parse(from_occurrences))]
+    verbose: i32,
+}
+
+/// Decode, and with --encode re-encode, one case.
+fn run_case(opts: &Opts, name: &str, data: &[u8]) {
+    let json = decode::oside_json(&opts.start_layer, data);
+    if opts.verbose > 0 {
+        println!("{}: {}", name, json.clone().unwrap_or_default());
+    }
+    if json.is_none() {
+        println!("{}: oside failed to decode", name);
+        return;
+    }
+    if opts.encode {
+        match decode::oside_encode(&opts.start_layer, data) {
+            Some(encoded) if encoded != data => {
+                println!("{}: encoding the decoded case gives different bytes", name)
+            }
+            Some(_) => {}
+            None => println!("{}: oside failed to decode", name),
+        }
+    }
+}
+
+fn main() {
+    let opts = Opts::parse();
+    let mut count = 0;
+    for path in &opts.paths {
+        if Path::new(path).is_dir() {
+            let cases = corpus::load(path).unwrap_or_else(|e| panic!("{}: {}", path, e));
+            for (meta, data) in cases {
+                run_case(&opts, &meta.source, &data);
+                count += 1;
+            }
+        } else {
+            let data = std::fs::read(path).unwrap_or_else(|e| panic!("{}: {}", path, e));
+            run_case(&opts, path, &data);
+            count += 1;
+        }
+    }
+    eprintln!("{} cases decoded", count);
+}
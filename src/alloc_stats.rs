@@ -0,0 +1,87 @@
+//! Allocation counting for Rust code.
+//!
+//! With the `global-allocator-counting` feature, `Counting` is installed as
+//! the global allocator and tallies every allocation made through it. The
+//! Python interpreter has its own allocator, so only Rust-side allocations,
+//! such as oside's, are counted.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static ALLOCS: AtomicUsize = AtomicUsize::new(0);
+static BYTES: AtomicUsize = AtomicUsize::new(0);
+static LIVE: AtomicUsize = AtomicUsize::new(0);
+
+/// The system allocator, counting allocations.
+pub struct Counting;
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCS.fetch_add(1, Ordering::Relaxed);
+        BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        LIVE.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        ALLOCS.fetch_add(1, Ordering::Relaxed);
+        BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        LIVE.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        LIVE.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCS.fetch_add(1, Ordering::Relaxed);
+        BYTES.fetch_add(new_size, Ordering::Relaxed);
+        LIVE.fetch_add(new_size, Ordering::Relaxed);
+        LIVE.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+/// Allocation counters at a point in time, or the difference of two.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Snapshot {
+    pub allocs: usize,
+    pub bytes: usize,
+    /// Bytes allocated and not yet freed.
+    pub live: usize,
+}
+
+impl std::ops::Sub for Snapshot {
+    type Output = Snapshot;
+    fn sub(self, other: Snapshot) -> Snapshot {
+        Snapshot {
+            allocs: self.allocs.wrapping_sub(other.allocs),
+            bytes: self.bytes.wrapping_sub(other.bytes),
+            live: self.live.wrapping_sub(other.live),
+        }
+    }
+}
+
+/// Whether the counting allocator is compiled in; without it all counts
+/// stay at zero.
+pub const fn enabled() -> bool {
+    cfg!(feature = "global-allocator-counting")
+}
+
+pub fn snapshot() -> Snapshot {
+    Snapshot {
+        allocs: ALLOCS.load(Ordering::Relaxed),
+        bytes: BYTES.load(Ordering::Relaxed),
+        live: LIVE.load(Ordering::Relaxed),
+    }
+}
+
+/// Run `f`, returning its result and the allocations made meanwhile.
+/// Allocations by other threads are included.
+pub fn measure<R>(f: impl FnOnce() -> R) -> (R, Snapshot) {
+    let before = snapshot();
+    let r = f();
+    (r, snapshot() - before)
+}
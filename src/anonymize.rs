@@ -0,0 +1,354 @@
+//! Anonymization of frames before they are written out.
+//!
+//! Frames starting at Ethernet, IPv4, IPv6 or ARP are anonymized layer by
+//! layer; those starting at any other layer are zeroed whole, as there is
+//! no telling where their addresses are.
+//!
+//! MAC addresses are replaced by random locally administered ones, IPv4
+//! addresses by addresses from 10.0.0.0/8 and IPv6 addresses by addresses
+//! from fd00::/8. The mapping is consistent for the lifetime of the
+//! `Anonymizer`, so flows stay recognizable. Payloads above the transport
+//! header are zeroed with their length preserved, and the IPv4, TCP and UDP
+//! checksums are recomputed.
+
+use std::collections::HashMap;
+use std::time::SystemTime;
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_ARP: u16 = 0x0806;
+const ETHERTYPE_VLAN: u16 = 0x8100;
+const ETHERTYPE_QINQ: u16 = 0x88a8;
+const ETHERTYPE_IPV6: u16 = 0x86dd;
+
+const PROTO_ICMP: u8 = 1;
+const PROTO_TCP: u8 = 6;
+const PROTO_UDP: u8 = 17;
+const PROTO_ICMPV6: u8 = 58;
+
+pub struct Anonymizer {
+    macs: HashMap<[u8; 6], [u8; 6]>,
+    ip4: HashMap<[u8; 4], [u8; 4]>,
+    ip6: HashMap<[u8; 16], [u8; 16]>,
+    rng: u64,
+}
+
+impl Default for Anonymizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Anonymizer {
+    pub fn new() -> Anonymizer {
+        let seed = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+        Anonymizer {
+            macs: HashMap::new(),
+            ip4: HashMap::new(),
+            ip6: HashMap::new(),
+            rng: seed | 1,
+        }
+    }
+
+    fn next_random(&mut self) -> u64 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        self.rng
+    }
+
+    fn mac(&mut self, b: &mut [u8]) {
+        let orig: [u8; 6] = b[0..6].try_into().unwrap();
+        if orig == [0xff; 6] || orig[0] & 1 != 0 {
+            // broadcast and multicast addresses carry no identity
+            return;
+        }
+        let new = match self.macs.get(&orig) {
+            Some(new) => *new,
+            None => {
+                let r = self.next_random().to_be_bytes();
+                let new = [0x02, r[0], r[1], r[2], r[3], r[4]];
+                self.macs.insert(orig, new);
+                new
+            }
+        };
+        b[0..6].copy_from_slice(&new);
+    }
+
+    fn ip4(&mut self, b: &mut [u8]) {
+        let orig: [u8; 4] = b[0..4].try_into().unwrap();
+        if orig == [0; 4] || orig == [0xff; 4] {
+            return;
+        }
+        let n = self.ip4.len() as u32 + 1;
+        let new = *self
+            .ip4
+            .entry(orig)
+            .or_insert_with(|| (0x0a00_0000 | (n & 0x00ff_ffff)).to_be_bytes());
+        b[0..4].copy_from_slice(&new);
+    }
+
+    fn ip6(&mut self, b: &mut [u8]) {
+        let orig: [u8; 16] = b[0..16].try_into().unwrap();
+        if orig == [0; 16] || orig[0] == 0xff {
+            return;
+        }
+        let n = self.ip6.len() as u128 + 1;
+        let new = *self
+            .ip6
+            .entry(orig)
+            .or_insert_with(|| ((0xfdu128 << 120) | n).to_be_bytes());
+        b[0..16].copy_from_slice(&new);
+    }
+
+    /// Anonymize in place a frame starting at the layer, as for
+    /// --start-layer.
+    pub fn frame(&mut self, start: &str, f: &mut [u8]) {
+        match start {
+            "ether" => self.ether_frame(f),
+            "ip" | "ipv6" => match f.first().map(|b| b >> 4) {
+                Some(4) => self.ipv4_packet(f),
+                Some(6) => self.ipv6_packet(f),
+                _ => f.iter_mut().for_each(|b| *b = 0),
+            },
+            "arp" => self.arp_packet(f),
+            _ => f.iter_mut().for_each(|b| *b = 0),
+        }
+    }
+
+    fn ether_frame(&mut self, f: &mut [u8]) {
+        if f.len() < 14 {
+            f.iter_mut().for_each(|b| *b = 0);
+            return;
+        }
+        self.mac(&mut f[0..6]);
+        self.mac(&mut f[6..12]);
+        let mut pos = 12;
+        let mut ethertype = u16::from_be_bytes([f[12], f[13]]);
+        while (ethertype == ETHERTYPE_VLAN || ethertype == ETHERTYPE_QINQ) && f.len() >= pos + 6 {
+            pos += 4;
+            ethertype = u16::from_be_bytes([f[pos], f[pos + 1]]);
+        }
+        let l3 = &mut f[pos + 2..];
+        match ethertype {
+            ETHERTYPE_IPV4 => self.ipv4_packet(l3),
+            ETHERTYPE_IPV6 => self.ipv6_packet(l3),
+            ETHERTYPE_ARP => self.arp_packet(l3),
+            _ => l3.iter_mut().for_each(|b| *b = 0),
+        }
+    }
+
+    fn arp_packet(&mut self, p: &mut [u8]) {
+        // Ethernet/IPv4 ARP only
+        if p.len() < 28 || p[4] != 6 || p[5] != 4 {
+            p.iter_mut().for_each(|b| *b = 0);
+            return;
+        }
+        self.mac(&mut p[8..14]);
+        self.ip4(&mut p[14..18]);
+        self.mac(&mut p[18..24]);
+        self.ip4(&mut p[24..28]);
+    }
+
+    fn ipv4_packet(&mut self, p: &mut [u8]) {
+        let ihl = (p.first().copied().unwrap_or(0) & 0x0f) as usize * 4;
+        if ihl < 20 || p.len() < ihl {
+            p.iter_mut().for_each(|b| *b = 0);
+            return;
+        }
+        self.ip4(&mut p[12..16]);
+        self.ip4(&mut p[16..20]);
+        // options may carry addresses too
+        p[20..ihl].iter_mut().for_each(|b| *b = 0);
+        p[10] = 0;
+        p[11] = 0;
+        let sum = checksum(&[&p[0..ihl]]);
+        p[10..12].copy_from_slice(&sum.to_be_bytes());
+
+        let proto = p[9];
+        let total_len = (u16::from_be_bytes([p[2], p[3]]) as usize).min(p.len());
+        let fragmented = u16::from_be_bytes([p[6], p[7]]) & 0x3fff != 0;
+        let mut pseudo = Vec::with_capacity(12);
+        pseudo.extend_from_slice(&p[12..20]);
+        pseudo.extend_from_slice(&[0, proto]);
+        let l4_len = total_len.saturating_sub(ihl);
+        pseudo.extend_from_slice(&(l4_len as u16).to_be_bytes());
+        self.l4_segment(proto, &mut p[ihl..], l4_len, &pseudo, !fragmented);
+    }
+
+    fn ipv6_packet(&mut self, p: &mut [u8]) {
+        if p.len() < 40 {
+            p.iter_mut().for_each(|b| *b = 0);
+            return;
+        }
+        self.ip6(&mut p[8..24]);
+        self.ip6(&mut p[24..40]);
+        let proto = p[6];
+        let l4_len = (u16::from_be_bytes([p[4], p[5]]) as usize).min(p.len() - 40);
+        let mut pseudo = Vec::with_capacity(40);
+        pseudo.extend_from_slice(&p[8..40]);
+        pseudo.extend_from_slice(&(l4_len as u32).to_be_bytes());
+        pseudo.extend_from_slice(&[0, 0, 0, proto]);
+        // extension headers are not walked; their contents are zeroed along
+        // with the payload by l4_segment()
+        self.l4_segment(proto, &mut p[40..], l4_len, &pseudo, true);
+    }
+
+    /// Zero the payload of a transport segment and fix its checksum.
+    /// `len` is the segment length claimed by the IP header.
+    fn l4_segment(&mut self, proto: u8, s: &mut [u8], len: usize, pseudo: &[u8], fix: bool) {
+        let (hdr_len, csum_off) = match proto {
+            PROTO_TCP if s.len() >= 20 => (((s[12] >> 4) as usize * 4).max(20), 16),
+            PROTO_UDP if s.len() >= 8 => (8, 6),
+            PROTO_ICMP | PROTO_ICMPV6 if s.len() >= 8 => (8, 2),
+            _ => (0, usize::MAX),
+        };
+        let hdr_len = hdr_len.min(s.len());
+        s[hdr_len..].iter_mut().for_each(|b| *b = 0);
+        // checksums can only be recomputed over a complete, unfragmented segment
+        if !fix || csum_off == usize::MAX || len > s.len() || len < hdr_len {
+            return;
+        }
+        // leave out any Ethernet padding
+        let s = &mut s[..len];
+        if proto == PROTO_UDP && s[6] == 0 && s[7] == 0 {
+            // checksum not in use
+            return;
+        }
+        s[csum_off] = 0;
+        s[csum_off + 1] = 0;
+        let sum = if proto == PROTO_ICMP {
+            checksum(&[s])
+        } else {
+            checksum(&[pseudo, s])
+        };
+        let sum = if sum == 0 && proto == PROTO_UDP {
+            0xffff
+        } else {
+            sum
+        };
+        s[csum_off..csum_off + 2].copy_from_slice(&sum.to_be_bytes());
+    }
+}
+
+/// The Internet checksum over the concatenation of the given chunks,
+/// each of which must have an even length except the last one.
+fn checksum(chunks: &[&[u8]]) -> u16 {
+    let mut sum: u32 = 0;
+    for chunk in chunks {
+        for pair in chunk.chunks(2) {
+            let word = if pair.len() == 2 {
+                u16::from_be_bytes([pair[0], pair[1]])
+            } else {
+                u16::from_be_bytes([pair[0], 0])
+            };
+            sum += word as u32;
+        }
+    }
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An IPv4 UDP packet from 192.0.2.1 to 198.51.100.2 with a payload.
+    fn udp4() -> Vec<u8> {
+        let mut p = vec![
+            0x45, 0, 0, 32, 0, 1, 0, 0, 64, PROTO_UDP, 0, 0, 192, 0, 2, 1, 198, 51, 100, 2,
+        ];
+        p.extend_from_slice(&[0x30, 0x39, 0, 53, 0, 12, 0x12, 0x34]);
+        p.extend_from_slice(b"abcd");
+        let sum = checksum(&[&p[..20]]);
+        p[10..12].copy_from_slice(&sum.to_be_bytes());
+        p
+    }
+
+    fn ether(ethertypes: &[u16], payload: &[u8]) -> Vec<u8> {
+        let mut f = vec![
+            0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x00, 0x66, 0x77, 0x88, 0x99, 0xaa,
+        ];
+        for (i, ethertype) in ethertypes.iter().enumerate() {
+            f.extend_from_slice(&ethertype.to_be_bytes());
+            if i + 1 < ethertypes.len() {
+                f.extend_from_slice(&[0, 100]);
+            }
+        }
+        f.extend_from_slice(payload);
+        f
+    }
+
+    /// Check an anonymized `udp4()`.
+    fn check_udp4(p: &[u8]) {
+        assert_eq!(&p[12..16], &[10, 0, 0, 1]);
+        assert_eq!(&p[16..20], &[10, 0, 0, 2]);
+        assert_eq!(checksum(&[&p[..20]]), 0);
+        assert_eq!(&p[20..24], &[0x30, 0x39, 0, 53]);
+        assert_eq!(&p[28..], &[0; 4]);
+        let mut pseudo = p[12..20].to_vec();
+        pseudo.extend_from_slice(&[0, PROTO_UDP, 0, 12]);
+        assert_eq!(checksum(&[&pseudo, &p[20..]]), 0);
+    }
+
+    #[test]
+    fn ethernet() {
+        let mut f = ether(&[ETHERTYPE_IPV4], &udp4());
+        Anonymizer::new().frame("ether", &mut f);
+        assert_eq!(f[0], 0x02);
+        assert_eq!(f[6], 0x02);
+        assert_ne!(&f[0..6], &[0x00, 0x11, 0x22, 0x33, 0x44, 0x55]);
+        assert_ne!(&f[0..6], &f[6..12]);
+        check_udp4(&f[14..]);
+    }
+
+    #[test]
+    fn vlan() {
+        let mut f = ether(&[ETHERTYPE_QINQ, ETHERTYPE_VLAN, ETHERTYPE_IPV4], &udp4());
+        Anonymizer::new().frame("ether", &mut f);
+        assert_eq!(f[0], 0x02);
+        check_udp4(&f[22..]);
+    }
+
+    #[test]
+    fn consistent() {
+        let mut a = Anonymizer::new();
+        let mut f = ether(&[ETHERTYPE_IPV4], &udp4());
+        let mut g = f.clone();
+        a.frame("ether", &mut f);
+        a.frame("ether", &mut g);
+        assert_eq!(f, g);
+    }
+
+    #[test]
+    fn ip_start() {
+        let mut p = udp4();
+        Anonymizer::new().frame("ip", &mut p);
+        check_udp4(&p);
+
+        let mut p = vec![0x60, 0, 0, 0, 0, 4, 59, 64];
+        p.extend_from_slice(&[0x20, 0x01, 0x0d, 0xb8].repeat(8));
+        p.extend_from_slice(b"abcd");
+        Anonymizer::new().frame("ipv6", &mut p);
+        assert_eq!(p[8], 0xfd);
+        assert_eq!(p[24], 0xfd);
+        assert_eq!(&p[40..], &[0; 4]);
+    }
+
+    #[test]
+    fn unknown_start() {
+        for start in ["cookedlinux", "usbpcap", "tcp", "hci_hdr"] {
+            let mut f = ether(&[ETHERTYPE_IPV4], &udp4());
+            Anonymizer::new().frame(start, &mut f);
+            assert!(f.iter().all(|b| *b == 0), "{} frame kept bytes", start);
+        }
+        let mut p = udp4();
+        p[0] = 0x15;
+        Anonymizer::new().frame("ip", &mut p);
+        assert!(p.iter().all(|b| *b == 0));
+    }
+}
@@ -0,0 +1,365 @@
+//! Reference implementations oside is compared against.
+//!
+//! The default backend is Scapy in the embedded interpreter. Others are
+//! selected with `--backend`:
+//!
+//! - `scapy`: Scapy in the embedded interpreter
+//! - `python[:INTERPRETER]`: Scapy in an external Python, `python3` by default
+//! - `tshark`: Wireshark's dissectors, via `tshark -T json`
+//! - `cmd:COMMAND`: any command speaking the JSON driver protocol
+//!
+//! The JSON driver protocol is line based: for each frame the command gets
+//! `{"hex": "<frame bytes>", "layer": "<start layer>"}` on stdin, the start
+//! layer being `ether` unless `--start-layer` says otherwise, and answers
+//! with one line holding
+//! either the list of layers, `[{"Name": {"field": value, ...}}, ...]`, or
+//! `{"error": "<message>"}`.
+//!
+//! A command taking longer than `TIMEOUT` over a frame is killed and started
+//! again, the frame failing to dissect. tshark is given the frames of the
+//! next cases in one capture, see `Backend::prefetch`, and is killed the
+//! same way when it hangs.
+
+use crate::compare::{self, Layer};
+use crate::pcap;
+use crate::scapy;
+use pyo3::Python;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+/// How long an external backend may take over a frame.
+pub const TIMEOUT: Duration = Duration::from_secs(30);
+
+pub trait Backend {
+    /// Short name used in reports.
+    fn name(&self) -> &str;
+
+    /// Dissect a frame, starting at the backend's start layer.
+    fn dissect(&mut self, data: &[u8]) -> Result<Vec<Layer>, String>;
+
+    /// How long dissecting the frame takes. The default times `dissect()`,
+    /// including any conversion and transport overhead.
+    fn dissect_time(&mut self, data: &[u8]) -> Result<Duration, String> {
+        let start = Instant::now();
+        self.dissect(data)?;
+        Ok(start.elapsed())
+    }
+
+    /// Frames about to be dissected, for backends with a high cost per
+    /// call to dissect at once; `dissect()` then answers from what was
+    /// kept. The default does nothing.
+    fn prefetch(&mut self, _frames: &[Vec<u8>]) {}
+}
+
+/// Create the backend described by a `--backend` value, dissecting frames
+/// from the `start` layer on (`ether` for Ethernet frames).
+pub fn from_spec<'py>(
+    py: Python<'py>,
+    spec: &str,
+    start: &str,
+) -> Result<Box<dyn Backend + 'py>, String> {
+    let (kind, arg) = match spec.split_once(':') {
+        Some((kind, arg)) => (kind, Some(arg)),
+        None => (spec, None),
+    };
+    match kind {
+        "scapy" => Ok(Box::new(ScapyEmbedded {
+            py,
+            start: start.to_string(),
+        })),
+        "python" => Ok(Box::new(DriverCommand::external_python(
+            arg.unwrap_or("python3"),
+            start,
+        )?)),
+        "tshark" => Ok(Box::new(Tshark::new(arg.unwrap_or("tshark"), start)?)),
+        "cmd" => match arg {
+            Some(cmd) => Ok(Box::new(DriverCommand::shell(cmd, start)?)),
+            None => Err("cmd backend needs a command, e.g. cmd:./dissect.sh".to_string()),
+        },
+        _ => Err(format!(
+            "unknown backend '{}', expected scapy, python, tshark or cmd",
+            kind
+        )),
+    }
+}
+
+/// Scapy in the embedded interpreter.
+pub struct ScapyEmbedded<'py> {
+    py: Python<'py>,
+    start: String,
+}
+
+impl<'py> Backend for ScapyEmbedded<'py> {
+    fn name(&self) -> &str {
+        "scapy"
+    }
+
+    fn dissect(&mut self, data: &[u8]) -> Result<Vec<Layer>, String> {
+        scapy::dissect(self.py, &self.start, data).map(|j| compare::layers(&j))
+    }
+
+    fn dissect_time(&mut self, data: &[u8]) -> Result<Duration, String> {
+        scapy::dissect_time(self.py, &self.start, data)
+    }
+}
+
+/// A long-running command speaking the JSON driver protocol.
+pub struct DriverCommand {
+    name: String,
+    start: String,
+    /// The program and its arguments, to start it again after a timeout.
+    program: String,
+    args: Vec<String>,
+    child: Child,
+    stdin: ChildStdin,
+    /// The lines the command writes, read on a thread of their own so that
+    /// waiting for one can time out; an empty line is the end of output.
+    lines: Receiver<std::io::Result<String>>,
+}
+
+impl DriverCommand {
+    fn spawn(
+        name: &str,
+        program: &str,
+        args: Vec<String>,
+        start: &str,
+    ) -> Result<DriverCommand, String> {
+        let mut child = Command::new(program)
+            .args(&args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("could not start {} backend: {}", name, e))?;
+        let stdin = child.stdin.take().unwrap();
+        let mut stdout = BufReader::new(child.stdout.take().unwrap());
+        let (tx, lines) = mpsc::channel();
+        std::thread::spawn(move || loop {
+            let mut line = String::new();
+            let read = stdout.read_line(&mut line);
+            let done = !matches!(read, Ok(n) if n > 0);
+            if tx.send(read.map(|_| line)).is_err() || done {
+                break;
+            }
+        });
+        Ok(DriverCommand {
+            name: name.to_string(),
+            start: start.to_string(),
+            program: program.to_string(),
+            args,
+            child,
+            stdin,
+            lines,
+        })
+    }
+
+    /// Run a command line through the shell.
+    pub fn shell(cmdline: &str, start: &str) -> Result<DriverCommand, String> {
+        let args = vec!["-c".to_string(), cmdline.to_string()];
+        DriverCommand::spawn("cmd", "sh", args, start)
+    }
+
+    /// Run Scapy in an external Python interpreter.
+    pub fn external_python(python: &str, start: &str) -> Result<DriverCommand, String> {
+        let script = format!(
+            "from scapy.all import *\n{}\n{}",
+            scapy::HELPER,
+            include_str!("scapy_driver.py")
+        );
+        DriverCommand::spawn("python", python, vec!["-c".to_string(), script], start)
+    }
+
+    /// Kill the command, hung over a frame, and start it again.
+    fn restart(&mut self) -> Result<(), String> {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        *self = DriverCommand::spawn(&self.name, &self.program, self.args.clone(), &self.start)?;
+        Ok(())
+    }
+}
+
+impl Backend for DriverCommand {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn dissect(&mut self, data: &[u8]) -> Result<Vec<Layer>, String> {
+        let req = serde_json::json!({ "hex": scapy::hex(data), "layer": self.start });
+        writeln!(self.stdin, "{}", req)
+            .and_then(|_| self.stdin.flush())
+            .map_err(|e| format!("{} backend: {}", self.name, e))?;
+        let line = match self.lines.recv_timeout(TIMEOUT) {
+            Ok(Ok(line)) if !line.is_empty() => line,
+            Ok(Ok(_)) | Err(RecvTimeoutError::Disconnected) => {
+                return Err(format!("{} backend exited", self.name))
+            }
+            Ok(Err(e)) => return Err(format!("{} backend: {}", self.name, e)),
+            Err(RecvTimeoutError::Timeout) => {
+                let timeout = format!(
+                    "{} backend took over {}s, killed",
+                    self.name,
+                    TIMEOUT.as_secs()
+                );
+                return Err(match self.restart() {
+                    Ok(()) => format!("{} and started again", timeout),
+                    Err(e) => format!("{}; {}", timeout, e),
+                });
+            }
+        };
+        let reply: Value = serde_json::from_str(&line)
+            .map_err(|e| format!("{} backend sent bad JSON: {}", self.name, e))?;
+        if let Some(err) = reply.get("error") {
+            return Err(err
+                .as_str()
+                .map(|s| s.to_string())
+                .unwrap_or_else(|| err.to_string()));
+        }
+        Ok(compare::layers(&reply))
+    }
+}
+
+impl Drop for DriverCommand {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Wireshark's dissectors, one `tshark` run per batch of prefetched frames,
+/// or per frame otherwise.
+pub struct Tshark {
+    exe: String,
+    linktype: u32,
+    /// The dissections of prefetched frames not asked for yet.
+    prefetched: HashMap<Vec<u8>, Vec<Layer>>,
+}
+
+impl Tshark {
+    /// tshark only learns the start layer from the link type of the capture
+    /// it reads, so only bare IP packets and the layers of `pcap::LINKTYPES`
+    /// are supported.
+    pub fn new(exe: &str, start: &str) -> Result<Tshark, String> {
+        let linktype = match start {
+            "ip" | "ipv6" => pcap::LINKTYPE_RAW,
+            start => pcap::linktype(start)
+                .ok_or_else(|| format!("tshark backend cannot start at {}", start))?,
+        };
+        Ok(Tshark {
+            exe: exe.to_string(),
+            linktype,
+            prefetched: HashMap::new(),
+        })
+    }
+
+    /// Run tshark over a capture of the frames, returning its JSON, one
+    /// packet per frame. tshark is killed if it takes over `TIMEOUT` per
+    /// frame.
+    fn run(&self, frames: &[&[u8]]) -> Result<Vec<Value>, String> {
+        let mut pcap = pcap::Writer::new(vec![], self.linktype).unwrap();
+        for data in frames {
+            pcap.write_frame(Duration::default(), data).unwrap();
+        }
+        let mut child = Command::new(&self.exe)
+            .args(["-r", "-", "-T", "json"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("could not run {}: {}", self.exe, e))?;
+        let input = pcap.into_inner();
+        let mut stdin = child.stdin.take().unwrap();
+        let mut stdout = child.stdout.take().unwrap();
+        let writer = std::thread::spawn(move || stdin.write_all(&input));
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let mut out = vec![];
+            let _ = tx.send(stdout.read_to_end(&mut out).map(|_| out));
+        });
+        let out = rx.recv_timeout(TIMEOUT * frames.len().max(1) as u32);
+        if out.is_err() {
+            let _ = child.kill();
+        }
+        let _ = child.wait();
+        let _ = writer.join();
+        let out = match out {
+            Ok(out) => out.map_err(|e| format!("{}: {}", self.exe, e))?,
+            Err(_) => return Err(format!("{} took too long, killed", self.exe)),
+        };
+        let json: Value = serde_json::from_slice(&out)
+            .map_err(|e| format!("{} sent bad JSON: {}", self.exe, e))?;
+        match json {
+            Value::Array(packets) if packets.len() == frames.len() => Ok(packets),
+            _ => Err(format!(
+                "{} did not dissect the {} frames given",
+                self.exe,
+                frames.len()
+            )),
+        }
+    }
+}
+
+/// tshark protocol names which differ from the Scapy/oside layer names.
+const TSHARK_NAMES: &[(&str, &str)] = &[("eth", "ether"), ("vlan", "dot1q"), ("data", "raw")];
+
+impl Backend for Tshark {
+    fn name(&self) -> &str {
+        "tshark"
+    }
+
+    fn dissect(&mut self, data: &[u8]) -> Result<Vec<Layer>, String> {
+        if let Some(layers) = self.prefetched.remove(data) {
+            return Ok(layers);
+        }
+        let packets = self.run(&[data])?;
+        Ok(tshark_layers(&packets[0]))
+    }
+
+    fn prefetch(&mut self, frames: &[Vec<u8>]) {
+        self.prefetched.clear();
+        let mut unique: Vec<&[u8]> = frames.iter().map(|f| f.as_slice()).collect();
+        unique.sort_unstable();
+        unique.dedup();
+        // on failure, each frame is dissected on its own, to tell which
+        // one tshark fails on
+        if let Ok(packets) = self.run(&unique) {
+            for (data, packet) in unique.into_iter().zip(&packets) {
+                self.prefetched.insert(data.to_vec(), tshark_layers(packet));
+            }
+        }
+    }
+}
+
+/// Convert the `tshark -T json` output for a frame into layers.
+///
+/// The protocol order is taken from `frame.protocols`, since the layers
+/// object does not preserve it. Field names lose their protocol prefix
+/// (`ip.ttl` becomes `ttl`) and `_tree` subtrees are skipped.
+fn tshark_layers(packet: &Value) -> Vec<Layer> {
+    let layers = &packet["_source"]["layers"];
+    let protocols = layers["frame"]["frame.protocols"].as_str().unwrap_or("");
+    let mut out = vec![];
+    for proto in protocols.split(':') {
+        let fields = match layers.get(proto).and_then(|l| l.as_object()) {
+            // `frame` and the `raw` link layer of bare IP packets are
+            // tshark's own pseudo-layers
+            Some(fields) if proto != "frame" && proto != "raw" => fields,
+            _ => continue,
+        };
+        let prefix = format!("{}.", proto);
+        let fields = fields
+            .iter()
+            .filter(|(k, v)| !v.is_object() && !k.ends_with("_tree"))
+            .map(|(k, v)| (k.strip_prefix(&prefix).unwrap_or(k).to_string(), v.clone()))
+            .collect();
+        let name = TSHARK_NAMES
+            .iter()
+            .find(|(t, _)| *t == proto)
+            .map(|(_, n)| n.to_string())
+            .unwrap_or_else(|| proto.to_string());
+        out.push(Layer { name, fields });
+    }
+    out
+}
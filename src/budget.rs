@@ -0,0 +1,126 @@
+//! Time budgets for `--duration`: runs that have to end by a wall-clock
+//! limit, e.g. a CI job's, finish the case they are in and report instead
+//! of being killed mid-case.
+//!
+//! The cost of a case is measured as the run goes. Once a few cases have
+//! run, a run of a known list of cases (a sweep, mutations) plans how many
+//! of the rest fit and spreads them evenly over the rest, so that a short
+//! budget still samples every part of the list rather than only its start;
+//! open-ended runs (the fuzzers) just stop before a case that would not fit.
+
+use crate::Opts;
+use std::time::{Duration, Instant};
+
+/// Cases measured before planning, unless a twentieth of the budget goes
+/// first.
+const WARMUP_CASES: usize = 20;
+
+/// Parse a duration such as `90s`, `30m`, `2h`, `1h30m` or `45` (seconds).
+pub fn parse(s: &str) -> Result<Duration, String> {
+    let bad = || format!("bad duration '{}', expected e.g. 90s, 30m, 2h or 1h30m", s);
+    let mut total = 0;
+    let mut digits = String::new();
+    for c in s.trim().chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+        let unit = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 3600,
+            'd' => 86400,
+            _ => return Err(bad()),
+        };
+        total += digits.parse::<u64>().map_err(|_| bad())? * unit;
+        digits.clear();
+    }
+    if !digits.is_empty() {
+        total += digits.parse::<u64>().map_err(|_| bad())?;
+    }
+    if total == 0 {
+        return Err(bad());
+    }
+    Ok(Duration::from_secs(total))
+}
+
+pub struct Budget {
+    limit: Duration,
+    started: Instant,
+    /// Cases started.
+    done: usize,
+    planned: bool,
+}
+
+impl Budget {
+    pub fn new(limit: Duration) -> Budget {
+        Budget {
+            limit,
+            started: Instant::now(),
+            done: 0,
+            planned: false,
+        }
+    }
+
+    /// The budget of a run: its own limit in seconds if it has one, or
+    /// else --duration's.
+    pub fn from_opts(opts: &Opts, seconds: Option<u64>) -> Option<Budget> {
+        let limit = match seconds {
+            Some(seconds) => Duration::from_secs(seconds),
+            None => parse(opts.duration.as_ref()?).unwrap_or_else(|e| panic!("{}", e)),
+        };
+        Some(Budget::new(limit))
+    }
+
+    /// The average cost of the cases so far.
+    fn cost(&self) -> Duration {
+        match self.done {
+            0 => Duration::ZERO,
+            done => self.started.elapsed() / done as u32,
+        }
+    }
+
+    /// Whether another case fits in what is left of the budget, at the
+    /// average cost of those before it; counts it if so.
+    pub fn next(&mut self) -> bool {
+        if self.started.elapsed() + self.cost() > self.limit {
+            return false;
+        }
+        self.done += 1;
+        true
+    }
+
+    /// How many of the remaining cases fit, once the warm-up is over, if
+    /// not all of them do. Plans only once.
+    pub fn plan(&mut self, remaining: usize) -> Option<usize> {
+        let warm = self.done >= WARMUP_CASES || self.started.elapsed() >= self.limit / 20;
+        if self.planned || !warm || self.done == 0 {
+            return None;
+        }
+        self.planned = true;
+        let left = self.limit.saturating_sub(self.started.elapsed());
+        let fits = (left.as_secs_f64() / self.cost().as_secs_f64().max(1e-6)) as usize;
+        (fits < remaining).then(|| fits)
+    }
+
+    /// Print how much of the run fit, if not all of it.
+    pub fn report(&self, total: usize) {
+        if self.done >= total {
+            return;
+        }
+        eprintln!(
+            "--duration: ran {} of {} cases in {:.0}s, {:.1} ms each on average; the rest did not fit",
+            self.done,
+            total,
+            self.started.elapsed().as_secs_f64(),
+            self.cost().as_secs_f64() * 1000.0
+        );
+    }
+}
+
+/// `n` of the items, spread evenly over them.
+pub fn spread<T: Copy>(items: &[T], n: usize) -> Vec<T> {
+    (0..n.min(items.len()))
+        .map(|k| items[k * items.len() / n.min(items.len())])
+        .collect()
+}
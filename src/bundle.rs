@@ -0,0 +1,210 @@
+//! `--bundle`: collect what a run produced into one `.tar.gz`, to attach to
+//! a ticket or archive.
+//!
+//! The archive holds, under a directory named after it:
+//!
+//! - `manifest.json`: command line, exit code, time and versions
+//! - `config.json`: the effective options, after --options-override and
+//!   --profile
+//! - `results.jsonl`: the --results-db; under --anonymize without the
+//!   differences' values and oside's JSON, which hold the frames' contents
+//! - `failures/`: the --save-failures corpus, with `failures.pcapng` holding
+//!   the same frames, commented with their differences, when decoding
+//!   starts at Ethernet
+//! - the --pcap-out capture, under its own file name
+//! - `slow/`: the --slow-dir corpus, when --slow-ms or --slow-ratio is given
+//! - `summary.json`: the --summary-json summary
+//! - `known-failures.toml`: the --known-failures file
+//!
+//! Where the run was not given a results database or failures directory,
+//! they are written into a staging directory that is removed afterwards.
+
+use crate::corpus_convert::{self, Format};
+use crate::schema::SchemaVersion;
+use crate::{corpus, known_failures, results, Opts};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use pyo3::Python;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+#[derive(Serialize)]
+struct Manifest {
+    schema_version: SchemaVersion,
+    args: Vec<String>,
+    exit_code: i32,
+    time: u64,
+    versions: BTreeMap<String, String>,
+}
+
+pub struct Bundle {
+    path: String,
+    staging: PathBuf,
+    versions: BTreeMap<String, String>,
+}
+
+impl Bundle {
+    /// Start a bundle if --bundle is given, pointing the results database
+    /// and failures directory into the staging directory unless set.
+    pub fn prepare(opts: &mut Opts) -> Option<Bundle> {
+        let path = opts.bundle.clone()?;
+        let staging =
+            std::env::temp_dir().join(format!("oside-tests-bundle-{}", std::process::id()));
+        std::fs::create_dir_all(&staging)
+            .unwrap_or_else(|e| panic!("{}: {}", staging.display(), e));
+        if opts.results_db.is_none() {
+            opts.results_db = Some(staging.join("results.jsonl").display().to_string());
+        }
+        if opts.save_failures.is_none() {
+            opts.save_failures = Some(staging.join("failures").display().to_string());
+        }
+        let mut versions = BTreeMap::new();
+        versions.insert(
+            "oside-tests".to_string(),
+            env!("CARGO_PKG_VERSION").to_string(),
+        );
+        versions.insert(
+            "oside".to_string(),
+            known_failures::OSIDE_VERSION.to_string(),
+        );
+        Some(Bundle {
+            path,
+            staging,
+            versions,
+        })
+    }
+
+    /// Record the Python and Scapy versions.
+    pub fn versions(&mut self, py: Python) {
+        for (name, code) in [
+            ("python", "__import__('sys').version.split()[0]"),
+            ("scapy", "__import__('scapy').VERSION"),
+        ] {
+            if let Ok(v) = py
+                .eval(code, None, None)
+                .and_then(|v| v.extract::<String>())
+            {
+                self.versions.insert(name.to_string(), v);
+            }
+        }
+    }
+
+    /// Write the archive and remove the staging directory.
+    pub fn write(self, opts: &Opts, exit_code: i32) -> Result<(), String> {
+        let res = self.write_archive(opts, exit_code);
+        let _ = std::fs::remove_dir_all(&self.staging);
+        res
+    }
+
+    fn write_archive(&self, opts: &Opts, exit_code: i32) -> Result<(), String> {
+        let err = |e: std::io::Error| format!("{}: {}", self.path, e);
+        let name = Path::new(&self.path)
+            .file_name()
+            .and_then(|n| n.to_str())
+            .map(|n| n.trim_end_matches(".gz").trim_end_matches(".tgz"))
+            .map(|n| n.trim_end_matches(".tar"))
+            .unwrap_or("bundle")
+            .to_string();
+        let manifest = Manifest {
+            schema_version: SchemaVersion,
+            args: std::env::args().collect(),
+            exit_code,
+            time: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            versions: self.versions.clone(),
+        };
+
+        let file = std::fs::File::create(&self.path).map_err(err)?;
+        let mut tar = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+        let add_data = |tar: &mut tar::Builder<_>, file: &str, data: &[u8]| {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_mtime(manifest.time);
+            header.set_cksum();
+            tar.append_data(&mut header, format!("{}/{}", name, file), data)
+        };
+        add_data(
+            &mut tar,
+            "manifest.json",
+            serde_json::to_string_pretty(&manifest).unwrap().as_bytes(),
+        )
+        .map_err(err)?;
+        add_data(
+            &mut tar,
+            "config.json",
+            serde_json::to_string_pretty(opts).unwrap().as_bytes(),
+        )
+        .map_err(err)?;
+        if let Some(db) = &opts.results_db {
+            if Path::new(db).exists() && opts.anonymize {
+                add_data(&mut tar, "results.jsonl", scrubbed_results(db).as_bytes())
+                    .map_err(err)?;
+            } else if Path::new(db).exists() {
+                tar.append_path_with_name(db, format!("{}/results.jsonl", name))
+                    .map_err(err)?;
+            }
+        }
+        if let Some(dir) = &opts.save_failures {
+            if Path::new(dir).is_dir() {
+                tar.append_dir_all(format!("{}/failures", name), dir)
+                    .map_err(err)?;
+                if opts.start_layer == "ether" {
+                    let cases = corpus::load(dir).map_err(err)?;
+                    let pcap = self.staging.join("failures.pcapng");
+                    corpus_convert::write(&pcap.display().to_string(), Format::Pcap, &cases)?;
+                    tar.append_path_with_name(&pcap, format!("{}/failures.pcapng", name))
+                        .map_err(err)?;
+                }
+            }
+        }
+        if let Some(pcap) = &opts.pcap_out {
+            let file = Path::new(pcap)
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or("pcap-out.pcapng");
+            if Path::new(pcap).exists() {
+                tar.append_path_with_name(pcap, format!("{}/{}", name, file))
+                    .map_err(err)?;
+            }
+        }
+        if (opts.slow_ms.is_some() || opts.slow_ratio.is_some())
+            && Path::new(&opts.slow_dir).is_dir()
+        {
+            tar.append_dir_all(format!("{}/slow", name), &opts.slow_dir)
+                .map_err(err)?;
+        }
+        if let Some(summary) = &opts.summary_json {
+            if Path::new(summary).exists() {
+                tar.append_path_with_name(summary, format!("{}/summary.json", name))
+                    .map_err(err)?;
+            }
+        }
+        if let Some(known) = &opts.known_failures {
+            tar.append_path_with_name(known, format!("{}/known-failures.toml", name))
+                .map_err(err)?;
+        }
+        tar.into_inner().and_then(|gz| gz.finish()).map_err(err)?;
+        Ok(())
+    }
+}
+
+/// The records of a results database without the values of their
+/// differences and oside's JSON, for --anonymize.
+fn scrubbed_results(db: &str) -> String {
+    let mut data = String::new();
+    for mut record in results::load(db) {
+        for diff in &mut record.diffs {
+            diff.reference = serde_json::Value::Null;
+            diff.oside = serde_json::Value::Null;
+        }
+        record.oside_json = None;
+        data += &serde_json::to_string(&record).unwrap();
+        data.push('\n');
+    }
+    data
+}
@@ -0,0 +1,194 @@
+//! The verdict cache: frames that passed, keyed by a hash of their bytes
+//! and of everything else the verdict depends on, so that later runs over
+//! unchanged corpora skip them. `--no-cache` turns it off.
+//!
+//! The cache is a file, `verdicts`, in --cache-dir or else
+//! `$XDG_CACHE_HOME/oside-tests` (`~/.cache/oside-tests`), with one line per
+//! frame that passed: `<environment hash> <bytes hash> <length> <layers>`,
+//! the last oside's layer path such as `ether/ip/udp`, for the results
+//! database to record the cached case under. The environment hash covers the oside, Scapy and Python versions and the
+//! options that change how frames are compared, so upgrading either
+//! library or comparing differently starts afresh. Only passes are cached;
+//! failures are always run again, to be reported in full.
+//!
+//! The cache is left out when an option reports on every case, such as
+//! --print-json or --field-stats, as a cached case is not decoded. Opening
+//! the cache drops duplicate lines and those of all but the last
+//! `ENVIRONMENTS` environments, so the file does not grow without bound.
+
+use crate::runner::{fnv1a, FNV_OFFSET};
+use crate::{known_failures, recording, Opts};
+use pyo3::Python;
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::PathBuf;
+
+/// How many environments' verdicts are kept, the current one included.
+const ENVIRONMENTS: usize = 4;
+
+pub struct Cache {
+    environment: u64,
+    /// The layer paths of the frames that passed, by key.
+    passed: HashMap<String, String>,
+    file: std::fs::File,
+}
+
+/// The cache directory: --cache-dir, or the user's cache directory.
+fn dir(opts: &Opts) -> Option<PathBuf> {
+    if let Some(dir) = &opts.cache_dir {
+        return Some(PathBuf::from(dir));
+    }
+    let base = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".cache")))?;
+    Some(base.join("oside-tests"))
+}
+
+/// A hash of the versions and of the options a verdict depends on.
+fn environment(py: Python, opts: &Opts) -> u64 {
+    let versions = recording::environment(py);
+    let key = serde_json::json!({
+        "oside": known_failures::OSIDE_VERSION,
+        "python": versions.get("python"),
+        "scapy": versions.get("scapy"),
+        "backend": opts.backend,
+        "recording": opts.recording,
+        "start_layer": opts.start_layer,
+        "skip_bytes": opts.skip_bytes,
+        "fcs": opts.fcs,
+        "tolerate_offload": opts.tolerate_offload,
+        "bind_port": opts.bind_port,
+        "filter": opts.filter,
+        "strict": opts.strict,
+        "level": opts.level,
+        "check_build": opts.check_build,
+        "reverse": opts.reverse,
+        "check_oracle": opts.check_oracle,
+        "checksum_fill": opts.checksum_fill,
+        "compare_rejections": opts.compare_rejections,
+        "ignore_field": opts.ignore_field,
+        "normalize_text": opts.normalize_text,
+        "keep_representation": opts.keep_representation,
+        "elide_payloads": opts.elide_payloads,
+        "semantic": opts.semantic,
+        // the contents, so that editing the file starts afresh
+        "known_failures": opts
+            .known_failures
+            .as_ref()
+            .map(|path| std::fs::read(path).map(|data| fnv1a(FNV_OFFSET, &data)).ok()),
+    });
+    fnv1a(FNV_OFFSET, key.to_string().as_bytes())
+}
+
+/// Whether an option reports on every case, which a cached case, not
+/// decoded, would be missing from.
+fn reports_per_case(opts: &Opts) -> bool {
+    opts.print_json
+        || opts.field_stats
+        || opts.class_coverage
+        || opts.alloc_stats
+        || opts.slow_ms.is_some()
+        || opts.slow_ratio.is_some()
+        || opts.compare_summary
+        || opts.store_oside_json
+}
+
+/// The lines of the cache to keep: each once, and only those of the current
+/// environment and of the last others seen.
+fn compact(lines: &[&str], environment: &str) -> Vec<String> {
+    let env = |line: &str| line.split(' ').next().unwrap_or("").to_string();
+    let mut recent: HashSet<String> = HashSet::from([environment.to_string()]);
+    for line in lines.iter().rev() {
+        if recent.len() >= ENVIRONMENTS {
+            break;
+        }
+        recent.insert(env(line));
+    }
+    let mut seen = HashSet::new();
+    lines
+        .iter()
+        .filter(|line| recent.contains(&env(line)) && seen.insert(**line))
+        .map(|line| line.to_string())
+        .collect()
+}
+
+impl Cache {
+    /// Open the cache, unless --no-cache or --verify-determinism is given or
+    /// an option reports on every case; a cache that cannot be opened is
+    /// reported and left out.
+    pub fn open(py: Python, opts: &Opts) -> Option<Cache> {
+        if opts.no_cache || opts.verify_determinism.is_some() || reports_per_case(opts) {
+            return None;
+        }
+        let dir = dir(opts)?;
+        let path = dir.join("verdicts");
+        let environment = environment(py, opts);
+        let data = std::fs::read_to_string(&path).unwrap_or_default();
+        let lines: Vec<&str> = data.lines().filter(|l| !l.is_empty()).collect();
+        let kept = compact(&lines, &format!("{:016x}", environment));
+        let opened = std::fs::create_dir_all(&dir)
+            .and_then(|_| {
+                if kept.len() == lines.len() {
+                    return Ok(());
+                }
+                // written aside and renamed over, as results::prune does
+                let tmp = dir.join("verdicts.tmp");
+                let text: String = kept.iter().map(|l| format!("{}\n", l)).collect();
+                std::fs::write(&tmp, text)?;
+                std::fs::rename(&tmp, &path)
+            })
+            .and_then(|_| {
+                std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&path)
+            });
+        let file = match opened {
+            Ok(file) => file,
+            Err(e) => {
+                eprintln!("warning: no verdict cache: {}: {}", path.display(), e);
+                return None;
+            }
+        };
+        let passed = kept
+            .iter()
+            .map(|line| {
+                let mut fields = line.splitn(4, ' ');
+                let key: Vec<&str> = fields.by_ref().take(3).collect();
+                (key.join(" "), fields.next().unwrap_or("").to_string())
+            })
+            .collect();
+        Some(Cache {
+            environment,
+            passed,
+            file,
+        })
+    }
+
+    fn key(&self, data: &[u8]) -> String {
+        format!(
+            "{:016x} {:016x} {}",
+            self.environment,
+            fnv1a(FNV_OFFSET, data),
+            data.len()
+        )
+    }
+
+    /// The layer path of the frame, if it passed before.
+    pub fn passed(&self, data: &[u8]) -> Option<&str> {
+        self.passed
+            .get(&self.key(data))
+            .map(|layers| layers.as_str())
+    }
+
+    /// Remember that the frame passed, decoded into the layer path.
+    pub fn insert(&mut self, data: &[u8], layers: &str) {
+        let key = self.key(data);
+        if !self.passed.contains_key(&key) {
+            if let Err(e) = writeln!(self.file, "{} {}", key, layers) {
+                eprintln!("warning: could not update the verdict cache: {}", e);
+            }
+            self.passed.insert(key, layers.to_string());
+        }
+    }
+}
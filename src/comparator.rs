@@ -0,0 +1,57 @@
+//! Per-protocol comparison logic: equivalences a field ignore cannot
+//! express, e.g. DNS names differing only in case.
+//!
+//! A `LayerComparator` is asked about each differing field of the layers
+//! it names and may declare the two values equivalent, dropping the
+//! difference. Comparators are registered once with `register`, before the
+//! run, and every `Runner` consults them all; `Runner::add_comparator`
+//! adds one to a single runner.
+
+use crate::compare::{self, Diff};
+use serde_json::Value;
+use std::sync::{Arc, Mutex};
+
+pub trait LayerComparator: Send + Sync {
+    /// The lowercased names of the layers whose fields it compares, or
+    /// `*` for every layer.
+    fn layers(&self) -> &[&str];
+
+    /// Whether the reference's and oside's values of the field are
+    /// equivalent, or `None` to leave the difference to the next
+    /// comparator.
+    fn equivalent(
+        &self,
+        layer: &str,
+        field: &str,
+        reference: &Value,
+        oside: &Value,
+    ) -> Option<bool>;
+}
+
+static REGISTRY: Mutex<Vec<Arc<dyn LayerComparator>>> = Mutex::new(Vec::new());
+
+/// Have every runner created afterwards consult the comparator, after
+/// those registered before it.
+pub fn register(comparator: Arc<dyn LayerComparator>) {
+    REGISTRY.lock().unwrap().push(comparator);
+}
+
+/// The registered comparators, in registration order.
+pub fn registered() -> Vec<Arc<dyn LayerComparator>> {
+    REGISTRY.lock().unwrap().clone()
+}
+
+/// Whether the first comparator with an opinion on the difference's field
+/// takes the two values as equivalent. Differences in the layer list
+/// itself are never equivalent.
+pub fn equivalent(comparators: &[Arc<dyn LayerComparator>], diff: &Diff) -> bool {
+    let (layer, field) = match compare::split_path(&diff.path) {
+        Some((layer, _, field)) => (layer, field),
+        None => return false,
+    };
+    comparators
+        .iter()
+        .filter(|c| c.layers().iter().any(|l| *l == "*" || *l == layer))
+        .find_map(|c| c.equivalent(layer, field, &diff.reference, &diff.oside))
+        .unwrap_or(false)
+}
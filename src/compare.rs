@@ -0,0 +1,375 @@
+//! Comparison of a reference dissection (Scapy's by default) with oside's
+//! dissection of the same bytes.
+//!
+//! Both sides are first brought into a common shape, a list of `Layer`s,
+//! and then compared layer by layer. Fields that only one side knows about
+//! are not compared.
+//!
+//! A field difference is located by the layer's name and the field, e.g.
+//! `ip.ttl`. The second and later layers of the same name carry their
+//! ordinal among them, counting from 0, so that in IP in IP the inner
+//! layer's TTL is `ip[1].ttl`, told apart from the outer `ip.ttl`.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// A decoded layer: its lowercased name and its fields.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Layer {
+    pub name: String,
+    pub fields: Map<String, Value>,
+}
+
+/// A single difference between the two dissections.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Diff {
+    /// Location of the difference, e.g. `ip.ttl`, `ip[1].ttl` or `layers[2]`.
+    pub path: String,
+    /// The value according to the reference backend.
+    pub reference: Value,
+    pub oside: Value,
+}
+
+/// Convert a list of serialized layers into `Layer`s.
+///
+/// Layers may be either externally tagged (`{"IP": {...}}`) or carry their
+/// name in a `layertype` field; anything else is skipped.
+pub fn layers(v: &Value) -> Vec<Layer> {
+    let mut out = vec![];
+    for item in v.as_array().map(|a| a.as_slice()).unwrap_or(&[]) {
+        let obj = match item.as_object() {
+            Some(obj) => obj,
+            None => continue,
+        };
+        if let Some(name) = obj.get("layertype").and_then(|n| n.as_str()) {
+            let mut fields = obj.clone();
+            fields.remove("layertype");
+            out.push(Layer {
+                name: name.to_lowercase(),
+                fields,
+            });
+        } else if obj.len() == 1 {
+            let (name, fields) = obj.iter().next().unwrap();
+            out.push(Layer {
+                name: name.to_lowercase(),
+                fields: fields.as_object().cloned().unwrap_or_default(),
+            });
+        }
+    }
+    out
+}
+
+/// Unwrap oside's `{"Set": value}` field wrapper, if present.
+pub fn scalar(v: &Value) -> &Value {
+    match v.as_object() {
+        Some(obj) if obj.len() == 1 && obj.contains_key("Set") => scalar(&obj["Set"]),
+        _ => v,
+    }
+}
+
+/// Render a field value as a string for loose comparison, so that e.g.
+/// `80` and `"80"` compare equal.
+pub fn value_str(v: &Value) -> String {
+    match scalar(v) {
+        Value::String(s) => s.to_lowercase(),
+        v => v.to_string(),
+    }
+}
+
+/// The name of layer `i` in a difference's path: its name, followed for
+/// the second and later layers of that name by their ordinal, e.g. `ip[1]`.
+pub fn layer_key(layers: &[Layer], i: usize) -> String {
+    let name = &layers[i].name;
+    match layers[..i].iter().filter(|l| &l.name == name).count() {
+        0 => name.clone(),
+        n => format!("{}[{}]", name, n),
+    }
+}
+
+/// Split a field difference's path such as `ip.ttl` or `ip[1].ttl` into
+/// the layer name, its ordinal among the layers of that name and the field.
+pub fn split_path(path: &str) -> Option<(&str, usize, &str)> {
+    let (layer, field) = path.split_once('.')?;
+    match layer.strip_suffix(']').and_then(|l| l.split_once('[')) {
+        Some((name, n)) => Some((name, n.parse().ok()?, field)),
+        None => Some((layer, 0, field)),
+    }
+}
+
+/// The layer named `name` with the given ordinal among those of that name.
+pub fn nth_layer<'a>(layers: &'a [Layer], name: &str, ordinal: usize) -> Option<&'a Layer> {
+    layers.iter().filter(|l| l.name == name).nth(ordinal)
+}
+
+/// Compare the reference layers against oside's.
+pub fn compare(reference: &[Layer], oside: &[Layer]) -> Vec<Diff> {
+    let mut diffs = vec![];
+    if reference.len() != oside.len() {
+        diffs.push(Diff {
+            path: "layers".to_string(),
+            reference: layer_path(reference).into(),
+            oside: layer_path(oside).into(),
+        });
+    }
+    for (i, (s, o)) in reference.iter().zip(oside.iter()).enumerate() {
+        if s.name != o.name {
+            diffs.push(Diff {
+                path: format!("layers[{}]", i),
+                reference: s.name.clone().into(),
+                oside: o.name.clone().into(),
+            });
+            continue;
+        }
+        for (fname, sval) in &s.fields {
+            if let Some(oval) = o.fields.get(fname) {
+                if value_str(sval) != value_str(oval) {
+                    diffs.push(Diff {
+                        path: format!("{}.{}", layer_key(reference, i), fname),
+                        reference: sval.clone(),
+                        oside: oval.clone(),
+                    });
+                }
+            }
+        }
+    }
+    diffs
+}
+
+/// Every field compared by `compare()`, with whether the two sides agreed
+/// on it.
+pub fn field_agreement(reference: &[Layer], oside: &[Layer]) -> Vec<(String, bool)> {
+    let mut out = vec![];
+    for (i, (s, o)) in reference.iter().zip(oside.iter()).enumerate() {
+        if s.name != o.name {
+            continue;
+        }
+        for (fname, sval) in &s.fields {
+            if let Some(oval) = o.fields.get(fname) {
+                out.push((
+                    format!("{}.{}", layer_key(reference, i), fname),
+                    value_str(sval) == value_str(oval),
+                ));
+            }
+        }
+    }
+    out
+}
+
+/// The layer names joined with `/`, e.g. `ether/ip/udp`.
+pub fn layer_path(layers: &[Layer]) -> String {
+    layers
+        .iter()
+        .map(|l| l.name.as_str())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// A one-line summary of a dissection in the spirit of Scapy's
+/// `summary()`: the layer path and the addresses and ports of the innermost
+/// network and transport layers, e.g.
+/// `ether/ip/udp/dns 10.0.0.1:53 > 10.0.0.2:1024`. It is built the same way
+/// from either side, so that two dissections agreeing on it agree on the
+/// protocol path and 5-tuple whatever their other fields.
+pub fn summary(layers: &[Layer]) -> String {
+    let field = |layer: &Layer, name: &str| {
+        layer
+            .fields
+            .get(name)
+            .map(value_str)
+            .unwrap_or_else(|| "?".to_string())
+    };
+    let mut out = layer_path(layers);
+    let network = layers
+        .iter()
+        .rposition(|l| l.name == "ip" || l.name == "ipv6");
+    if let Some(n) = network {
+        let transport = layers[n + 1..]
+            .iter()
+            .find(|l| l.fields.contains_key("sport") && l.fields.contains_key("dport"));
+        let (src, dst) = (field(&layers[n], "src"), field(&layers[n], "dst"));
+        match transport {
+            Some(t) => out.push_str(&format!(
+                " {}:{} > {}:{}",
+                src,
+                field(t, "sport"),
+                dst,
+                field(t, "dport")
+            )),
+            None => out.push_str(&format!(" {} > {}", src, dst)),
+        }
+    }
+    out
+}
+
+/// Comparison tiers, from the loosest to the strictest; each includes the
+/// ones below it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    /// L1: the same layer path.
+    Path = 1,
+    /// L2: the same `summary()`, i.e. also the addresses and ports.
+    Key = 2,
+    /// L3: no differing field, the default.
+    Fields = 3,
+    /// L4: also oside encodes its dissection back into the exact bytes.
+    Bytes = 4,
+}
+
+impl Level {
+    pub const ALL: [Level; 4] = [Level::Path, Level::Key, Level::Fields, Level::Bytes];
+
+    /// A level by number (`2`, `L2`) or name (`key`).
+    pub fn parse(s: &str) -> Result<Level, String> {
+        let s = s.trim().to_lowercase();
+        Level::ALL
+            .into_iter()
+            .find(|l| {
+                let n = (*l as u8).to_string();
+                s == n || s == format!("l{}", n) || s == l.name()
+            })
+            .ok_or_else(|| {
+                format!(
+                    "bad level '{}', expected 1-4 or path, key, fields or bytes",
+                    s
+                )
+            })
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Level::Path => "path",
+            Level::Key => "key",
+            Level::Fields => "fields",
+            Level::Bytes => "bytes",
+        }
+    }
+}
+
+impl std::fmt::Display for Level {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "L{} ({})", *self as u8, self.name())
+    }
+}
+
+/// Whether a value looks like a symbolic name such as `udp` or `echo-request`
+/// rather than a number, address or hex string.
+fn is_symbolic(v: &Value) -> bool {
+    let s = match scalar(v) {
+        Value::String(s) => s,
+        _ => return false,
+    };
+    let mut chars = s.chars();
+    chars.next().map(|c| c.is_ascii_alphabetic() || c == '_') == Some(true)
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        && !s.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn is_numeric(v: &Value) -> bool {
+    match scalar(v) {
+        Value::Number(_) => true,
+        Value::String(s) => s.parse::<i64>().is_ok(),
+        _ => false,
+    }
+}
+
+/// Whether a difference is only one of representation: one side names a
+/// value the other leaves numeric, or both name it differently.
+pub fn is_representation(d: &Diff) -> bool {
+    let (r, o) = (&d.reference, &d.oside);
+    (is_symbolic(r) && is_numeric(o))
+        || (is_numeric(r) && is_symbolic(o))
+        || (is_symbolic(r) && is_symbolic(o))
+}
+
+/// Layers a dissector falls back to for bytes it does not understand.
+pub const FALLBACK_LAYERS: &[&str] = &["raw", "padding", "unknown"];
+
+/// Whether a difference in the dissections of a truncated frame is
+/// explained by the cut: the sides stopping at different layers, or
+/// differing in the last layer either side decoded, which the cut falls
+/// in, or in the bytes either side left undecoded.
+pub fn is_truncation(d: &Diff, oside: &[Layer], refs: &[(String, Vec<Layer>)]) -> bool {
+    let last = |layers: &[Layer]| {
+        layers
+            .iter()
+            .rev()
+            .find(|l| !FALLBACK_LAYERS.contains(&l.name.as_str()))
+            .map(|l| l.name.clone())
+    };
+    let mut cut: Vec<String> = refs.iter().filter_map(|(_, layers)| last(layers)).collect();
+    cut.extend(last(oside));
+    if let Some(i) = d
+        .path
+        .strip_prefix("layers[")
+        .and_then(|i| i.strip_suffix(']'))
+        .and_then(|i| i.parse::<usize>().ok())
+    {
+        // a mismatch before the end of the shorter dissection is not the cut
+        let shortest = refs
+            .iter()
+            .map(|(_, l)| l.len())
+            .fold(oside.len(), usize::min);
+        return i + 1 >= shortest;
+    }
+    match split_path(&d.path) {
+        Some((layer, _, _)) => FALLBACK_LAYERS.contains(&layer) || cut.iter().any(|c| c == layer),
+        None => d.path == "layers",
+    }
+}
+
+/// Describe each fallback layer in a dissection; `who` names the
+/// dissector, e.g. `oside`.
+pub fn undecoded(who: &str, layers: &[Layer]) -> Vec<String> {
+    layers
+        .iter()
+        .enumerate()
+        .filter(|(_, l)| FALLBACK_LAYERS.contains(&l.name.as_str()))
+        .map(|(i, l)| format!("{} left bytes undecoded ({} at layers[{}])", who, l.name, i))
+        .collect()
+}
+
+/// A signature grouping failures that are likely the same bug: the sorted
+/// paths of the differences, or for failures without differences the
+/// message with its numbers and hex strings blanked out.
+pub fn failure_signature(message: &str, diffs: &[Diff]) -> String {
+    if !diffs.is_empty() {
+        let mut paths: Vec<&str> = diffs.iter().map(|d| d.path.as_str()).collect();
+        paths.sort_unstable();
+        paths.dedup();
+        return paths.join(",");
+    }
+    let mut out = String::new();
+    let mut word = String::new();
+    for c in message.chars().chain(std::iter::once(' ')) {
+        if c.is_ascii_alphanumeric() {
+            word.push(c);
+            continue;
+        }
+        if word.starts_with(|c: char| c.is_ascii_digit()) {
+            out.push('N');
+        } else {
+            out.push_str(&word);
+        }
+        word.clear();
+        out.push(c);
+    }
+    out.pop();
+    out
+}
+
+/// Whether oside's value is the reference's with its bytes swapped, for a
+/// 2, 4 or 8 byte field: the mark of an endianness bug.
+pub fn is_byte_swap(d: &Diff) -> bool {
+    let (r, o) = match (
+        value_str(&d.reference).parse::<u64>(),
+        value_str(&d.oside).parse::<u64>(),
+    ) {
+        (Ok(r), Ok(o)) if r != o => (r, o),
+        _ => return false,
+    };
+    [2u32, 4, 8].iter().any(|&width| {
+        let bits = width * 8;
+        let fits = |v: u64| bits == 64 || v >> bits == 0;
+        fits(r) && fits(o) && r.swap_bytes() >> (64 - bits) == o
+    })
+}
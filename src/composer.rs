@@ -0,0 +1,343 @@
+//! Random layer stacks, for `daemon --compose` and `fuzz-layer --compose`.
+//!
+//! Instead of one fixed template per protocol, each case walks a
+//! declarative adjacency table from the first layer, picking each next
+//! layer by weight, until it reaches a layer with nothing above it, the
+//! depth limit, or picks to stop. Each layer has a template whose
+//! placeholders are filled as in `daemon::instantiate`; the fields that
+//! select the next layer are left to Scapy, which sets them from its layer
+//! bindings when stacking. A `Grammar` file adds layers of its own.
+
+use crate::daemon;
+use crate::rng::Rng;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/// The options selecting composed stacks, shared by the subcommands.
+#[derive(Debug, Clone, clap::Args)]
+pub struct ComposeArgs {
+    /// Generate random layer stacks from an adjacency table instead of fixed templates
+    #[clap(long)]
+    pub compose: bool,
+    /// The most layers a composed stack has
+    #[clap(long, default_value = "6")]
+    pub depth: usize,
+    /// Weights of the adjacency table edges, e.g. "ip>gre=2,udp>vxlan=0"
+    #[clap(long)]
+    pub edge_weights: Option<String>,
+    /// YAML grammar adding layers, edges and field value pools to the table; implies
+    /// --compose
+    #[clap(long)]
+    pub grammar: Option<String>,
+}
+
+impl ComposeArgs {
+    /// The composer, if --compose is given.
+    pub fn composer(&self) -> Result<Option<Composer>, String> {
+        if !self.compose && self.grammar.is_none() {
+            return Ok(None);
+        }
+        let grammar = self.grammar.as_deref().map(Grammar::load).transpose()?;
+        Composer::new(self.edge_weights.as_deref(), self.depth, grammar.as_ref()).map(Some)
+    }
+}
+
+/// Templates of each layer the composer knows.
+const LAYERS: &[(&str, &str)] = &[
+    ("ether", "Ether()"),
+    ("dot1q", "Dot1Q(prio={u3}, vlan={u12})"),
+    (
+        "arp",
+        "ARP(op={u16}, psrc='10.0.{u8}.{u8}', pdst='10.0.{u8}.{u8}')",
+    ),
+    ("ip", "IP(tos={u8}, id={u16}, ttl={u8})"),
+    ("ipv6", "IPv6(tc={u8}, fl={u20}, hlim={u8})"),
+    ("gre", "GRE()"),
+    ("icmp", "ICMP(type={u8}, code={u8})"),
+    ("icmpv6", "ICMPv6EchoRequest(id={u16}, seq={u16})"),
+    ("udp", "UDP(sport={u16})"),
+    (
+        "tcp",
+        "TCP(sport={u16}, dport={u16}, seq={u32}, ack={u32}, flags={u8}, window={u16})",
+    ),
+    ("vxlan", "VXLAN(vni={u24})"),
+    (
+        "dns",
+        "DNS(id={u16}, rd={u1}, qd=DNSQR(qname='example.com', qtype={u16}))",
+    ),
+    ("raw", "Raw(load={bytes})"),
+];
+
+/// The layers that may follow each layer, with their default weights. The
+/// special `end` stops the stack there.
+const ADJACENCY: &[(&str, &[(&str, f64)])] = &[
+    (
+        "ether",
+        &[("ip", 4.0), ("ipv6", 3.0), ("arp", 1.0), ("dot1q", 1.0)],
+    ),
+    (
+        "dot1q",
+        &[("ip", 3.0), ("ipv6", 2.0), ("arp", 1.0), ("dot1q", 0.3)],
+    ),
+    (
+        "ip",
+        &[
+            ("tcp", 3.0),
+            ("udp", 3.0),
+            ("icmp", 1.0),
+            ("gre", 0.5),
+            ("ip", 0.3),
+            ("ipv6", 0.2),
+            ("end", 0.2),
+        ],
+    ),
+    (
+        "ipv6",
+        &[
+            ("tcp", 3.0),
+            ("udp", 3.0),
+            ("icmpv6", 1.0),
+            ("gre", 0.3),
+            ("ipv6", 0.2),
+            ("ip", 0.2),
+            ("end", 0.2),
+        ],
+    ),
+    ("gre", &[("ip", 1.0), ("ipv6", 0.5), ("ether", 0.3)]),
+    (
+        "udp",
+        &[("raw", 2.0), ("dns", 1.0), ("vxlan", 0.5), ("end", 0.3)],
+    ),
+    ("tcp", &[("raw", 2.0), ("end", 1.0)]),
+    ("vxlan", &[("ether", 1.0)]),
+    ("icmp", &[("raw", 1.0), ("end", 1.0)]),
+];
+
+/// A user grammar, given with --grammar as YAML, extending or replacing
+/// the built-in layers and adjacency table:
+///
+/// ```yaml
+/// start: ether          # first layer of daemon stacks
+/// builtin: true         # keep the built-in layers and edges
+/// layers:
+///   myproto:
+///     template: "MyProto(kind={kind}, seq={u16})"
+///     next: { raw: 1, end: 1 }
+///   udp:
+///     next: { myproto: 2 }   # adds or reweighs an edge of a built-in layer
+/// pools:
+///   kind: [1, 2, 0xff]       # {kind} takes one of these
+/// ```
+///
+/// Pool values are inserted into templates as written, so strings are
+/// Python expressions, e.g. `"b'abc'"`.
+#[derive(Debug, Deserialize)]
+pub struct Grammar {
+    #[serde(default)]
+    pub start: Option<String>,
+    #[serde(default = "default_builtin")]
+    pub builtin: bool,
+    #[serde(default)]
+    pub layers: BTreeMap<String, GrammarLayer>,
+    #[serde(default)]
+    pub pools: BTreeMap<String, Vec<serde_yaml::Value>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GrammarLayer {
+    #[serde(default)]
+    pub template: Option<String>,
+    #[serde(default)]
+    pub next: BTreeMap<String, f64>,
+}
+
+fn default_builtin() -> bool {
+    true
+}
+
+impl Grammar {
+    pub fn load(path: &str) -> Result<Grammar, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        serde_yaml::from_str(&text).map_err(|e| format!("{}: {}", path, e))
+    }
+}
+
+/// A pool value as it goes into a template.
+fn pool_value(v: &serde_yaml::Value) -> String {
+    match v {
+        serde_yaml::Value::String(s) => s.clone(),
+        serde_yaml::Value::Bool(true) => "True".to_string(),
+        serde_yaml::Value::Bool(false) => "False".to_string(),
+        serde_yaml::Value::Null => "None".to_string(),
+        v => serde_yaml::to_string(v)
+            .unwrap_or_default()
+            .trim_start_matches("---")
+            .trim()
+            .to_string(),
+    }
+}
+
+pub struct Composer {
+    /// Template of each layer.
+    layers: BTreeMap<String, String>,
+    /// (from, to, weight) for every edge of the table.
+    edges: Vec<(String, String, f64)>,
+    /// Values of each pool placeholder.
+    pools: BTreeMap<String, Vec<String>>,
+    start: String,
+    depth: usize,
+}
+
+impl Composer {
+    /// A composer stacking at most `depth` layers, from the built-in table
+    /// extended by `grammar`; `weights` such as `ip>gre=2,udp>vxlan=0`
+    /// overrides the weights of edges of the table.
+    pub fn new(
+        weights: Option<&str>,
+        depth: usize,
+        grammar: Option<&Grammar>,
+    ) -> Result<Composer, String> {
+        let builtin = grammar.map_or(true, |g| g.builtin);
+        let mut layers = BTreeMap::new();
+        let mut edges = vec![];
+        if builtin {
+            for (name, template) in LAYERS {
+                layers.insert(name.to_string(), template.to_string());
+            }
+            for (from, next) in ADJACENCY {
+                for (to, w) in next.iter() {
+                    edges.push((from.to_string(), to.to_string(), *w));
+                }
+            }
+        }
+        let mut pools = BTreeMap::new();
+        if let Some(grammar) = grammar {
+            for (name, layer) in &grammar.layers {
+                if let Some(template) = &layer.template {
+                    layers.insert(name.clone(), template.clone());
+                }
+                for (to, w) in &layer.next {
+                    set_edge(&mut edges, name, to, *w);
+                }
+            }
+            for (name, values) in &grammar.pools {
+                if values.is_empty() {
+                    return Err(format!("pool '{}' has no values", name));
+                }
+                pools.insert(name.clone(), values.iter().map(pool_value).collect());
+            }
+        }
+        for item in weights.unwrap_or("").split(',').filter(|i| !i.is_empty()) {
+            let (edge, weight) = item
+                .split_once('=')
+                .ok_or_else(|| format!("bad weight '{}', expected FROM>TO=WEIGHT", item))?;
+            let (from, to) = edge
+                .split_once('>')
+                .ok_or_else(|| format!("bad weight '{}', expected FROM>TO=WEIGHT", item))?;
+            let weight: f64 = weight
+                .parse()
+                .map_err(|_| format!("bad weight '{}'", item))?;
+            if !edges.iter().any(|(f, t, _)| f == from && t == to) {
+                return Err(format!("no edge {}>{} in the adjacency table", from, to));
+            }
+            set_edge(&mut edges, from, to, weight);
+        }
+        for (from, to, _) in &edges {
+            for layer in [from, to] {
+                if layer != "end" && !layers.contains_key(layer) {
+                    return Err(format!("layer '{}' has no template", layer));
+                }
+            }
+        }
+        let start = grammar
+            .and_then(|g| g.start.clone())
+            .unwrap_or_else(|| "ether".to_string());
+        if !layers.contains_key(&start) {
+            return Err(format!("start layer '{}' has no template", start));
+        }
+        if depth == 0 {
+            return Err("the stack depth must be at least 1".to_string());
+        }
+        Ok(Composer {
+            layers,
+            edges,
+            pools,
+            start,
+            depth,
+        })
+    }
+
+    /// The layer daemon stacks start at.
+    pub fn start(&self) -> &str {
+        &self.start
+    }
+
+    /// Whether stacks can start at the layer.
+    pub fn knows(&self, layer: &str) -> bool {
+        self.layers.contains_key(layer)
+    }
+
+    /// The layer following `from`, or `None` to stop.
+    fn next(&self, from: &str, rng: &mut Rng) -> Option<&str> {
+        let next: Vec<_> = self
+            .edges
+            .iter()
+            .filter(|(f, _, w)| f == from && *w > 0.0)
+            .collect();
+        let total: f64 = next.iter().map(|(_, _, w)| w).sum();
+        let mut x = rng.unit() * total;
+        for (_, to, w) in &next {
+            if x < *w {
+                return Some(to.as_str()).filter(|to| *to != "end");
+            }
+            x -= w;
+        }
+        None
+    }
+
+    /// Fill a template's pool placeholders with a value from the pool, and
+    /// the others as `daemon::instantiate` does.
+    fn instantiate(&self, template: &str, rng: &mut Rng) -> String {
+        let mut out = template.to_string();
+        for (name, values) in &self.pools {
+            let placeholder = format!("{{{}}}", name);
+            while let Some(at) = out.find(&placeholder) {
+                let value = &values[rng.below(values.len() as u64) as usize];
+                out.replace_range(at..at + placeholder.len(), value);
+            }
+        }
+        daemon::instantiate(&out, rng)
+    }
+
+    /// A random stack starting at `first`, one of the layers it `knows()`,
+    /// as its layer path (e.g. `ether/ip/udp/dns`) and a Scapy expression.
+    pub fn compose(&self, first: &str, rng: &mut Rng) -> (String, String) {
+        let mut path = vec![first];
+        while path.len() < self.depth {
+            match self.next(path[path.len() - 1], rng) {
+                Some(layer) => path.push(layer),
+                None => break,
+            }
+        }
+        let expr = path
+            .iter()
+            .map(|layer| {
+                let template = self
+                    .layers
+                    .get(*layer)
+                    .unwrap_or_else(|| panic!("no template for layer {}", layer));
+                self.instantiate(template, rng)
+            })
+            .collect::<Vec<_>>()
+            .join("/");
+        (path.join("/"), expr)
+    }
+}
+
+/// Add the edge, or change its weight.
+fn set_edge(edges: &mut Vec<(String, String, f64)>, from: &str, to: &str, weight: f64) {
+    match edges.iter_mut().find(|(f, t, _)| f == from && t == to) {
+        Some(edge) => edge.2 = weight,
+        None => edges.push((from.to_string(), to.to_string(), weight)),
+    }
+}
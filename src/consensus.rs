@@ -0,0 +1,85 @@
+//! Comparison of oside against several reference backends at once, with
+//! majority voting to single out the implementation that disagrees.
+
+use crate::compare::{self, value_str, Diff, Layer};
+use serde_json::Value;
+
+/// The values all implementations give for one differing path.
+#[derive(Debug, Clone)]
+pub struct Vote {
+    pub path: String,
+    /// Implementation name and its value; `Null` when it has no such field.
+    pub values: Vec<(String, Value)>,
+    /// The single implementation disagreeing with a strict majority, if any.
+    pub outlier: Option<String>,
+}
+
+/// Look up a diff path such as `ip.ttl`, `ip[1].ttl`, `layers` or
+/// `layers[2]` in a list of layers. For a field the layer of that name with
+/// the path's ordinal is used, the one `compare::compare` aligned.
+pub fn lookup(layers: &[Layer], path: &str) -> Value {
+    if path == "layers" {
+        return compare::layer_path(layers).into();
+    }
+    if let Some(idx) = path
+        .strip_prefix("layers[")
+        .and_then(|p| p.strip_suffix(']'))
+        .and_then(|i| i.parse::<usize>().ok())
+    {
+        return layers
+            .get(idx)
+            .map(|l| l.name.clone().into())
+            .unwrap_or(Value::Null);
+    }
+    let (name, ordinal, field) = match compare::split_path(path) {
+        Some(path) => path,
+        None => return Value::Null,
+    };
+    compare::nth_layer(layers, name, ordinal)
+        .and_then(|l| l.fields.get(field))
+        .cloned()
+        .unwrap_or(Value::Null)
+}
+
+/// Compare oside against each of the named reference dissections and vote
+/// on every path where any of them disagrees with oside. Also returns the
+/// plain diffs against each reference, concatenated.
+pub fn vote(oside: &[Layer], refs: &[(String, Vec<Layer>)]) -> (Vec<Vote>, Vec<Diff>) {
+    let mut all_diffs: Vec<Diff> = vec![];
+    let mut paths: Vec<String> = vec![];
+    for (_, layers) in refs {
+        for d in compare::compare(layers, oside) {
+            if !paths.contains(&d.path) {
+                paths.push(d.path.clone());
+            }
+            all_diffs.push(d);
+        }
+    }
+
+    let mut votes = vec![];
+    for path in paths {
+        let mut values = vec![("oside".to_string(), lookup(oside, &path))];
+        for (name, layers) in refs {
+            values.push((name.clone(), lookup(layers, &path)));
+        }
+        let outlier = find_outlier(&values);
+        votes.push(Vote {
+            path,
+            values,
+            outlier,
+        });
+    }
+    (votes, all_diffs)
+}
+
+fn find_outlier(values: &[(String, Value)]) -> Option<String> {
+    let keys: Vec<String> = values.iter().map(|(_, v)| value_str(v)).collect();
+    for key in &keys {
+        let agreeing = keys.iter().filter(|k| *k == key).count();
+        if agreeing * 2 > keys.len() && agreeing + 1 == keys.len() {
+            let idx = keys.iter().position(|k| k != key).unwrap();
+            return Some(values[idx].0.clone());
+        }
+    }
+    None
+}
@@ -0,0 +1,93 @@
+//! On-disk corpus of test cases.
+//!
+//! Each case is stored as `<name>.bin` holding the raw frame, plus a
+//! `<name>.json` sidecar with its `Meta`.
+
+use crate::schema::SchemaVersion;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Meta {
+    #[serde(default)]
+    pub schema_version: SchemaVersion,
+    /// Where the case came from, e.g. `capture.pcap#12`.
+    pub source: String,
+    /// Paths of the fields that differed when the case was saved.
+    #[serde(default)]
+    pub diffs: Vec<String>,
+    /// Free-form note on why the case was saved.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    /// What the embedded interpreter printed while the case ran.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+}
+
+/// Turn a case source such as `dir/capture.pcap#12` into a file name: the
+/// last path component, and for sources with a directory a hash of it, so
+/// that `a/x.pcap#1` and `b/x.pcap#1` from a glob are not saved over each
+/// other.
+pub fn case_name(source: &str) -> String {
+    let (dir, base) = match source.rsplit_once('/') {
+        Some((dir, base)) => (Some(dir), base),
+        None => (None, source),
+    };
+    let mut name: String = base
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if let Some(dir) = dir {
+        name += &format!("-{:08x}", fnv1a32(dir.as_bytes()));
+    }
+    name
+}
+
+/// 32-bit FNV-1a; not `runner::fnv1a`, as the decode harness shares this
+/// file without the runner.
+fn fnv1a32(data: &[u8]) -> u32 {
+    data.iter().fold(0x811c_9dc5, |hash, b| {
+        (hash ^ *b as u32).wrapping_mul(0x0100_0193)
+    })
+}
+
+/// Write a case into the corpus directory, creating it if needed.
+pub fn save(dir: &str, data: &[u8], meta: &Meta) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    let name = case_name(&meta.source);
+    let dir = Path::new(dir);
+    std::fs::write(dir.join(format!("{}.bin", name)), data)?;
+    std::fs::write(
+        dir.join(format!("{}.json", name)),
+        serde_json::to_string_pretty(meta).unwrap(),
+    )
+}
+
+/// Read all cases of a corpus directory, sorted by name.
+pub fn load(dir: &str) -> std::io::Result<Vec<(Meta, Vec<u8>)>> {
+    let mut names: Vec<_> = std::fs::read_dir(dir)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().map(|x| x == "bin") == Some(true))
+        .collect();
+    names.sort();
+    let mut cases = vec![];
+    for bin in names {
+        let data = std::fs::read(&bin)?;
+        let meta = std::fs::read_to_string(bin.with_extension("json"))
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_else(|| Meta {
+                source: bin.display().to_string(),
+                ..Default::default()
+            });
+        cases.push((meta, data));
+    }
+    Ok(cases)
+}
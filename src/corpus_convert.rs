@@ -0,0 +1,225 @@
+//! `corpus convert`: move cases between the formats the various harnesses
+//! read.
+//!
+//! - `corpus`: this tool's corpus directory, `<name>.bin` plus a
+//!   `<name>.json` sidecar with the `Meta`
+//! - `raw`: a directory of bare frame files, one per case, as libFuzzer and
+//!   AFL use
+//! - `pcap`: a capture file of Ethernet frames; written as pcapng, with
+//!   each case's source, differences and note as its packet comment, when
+//!   the file is named `.pcapng`
+//! - `jsonl`: a manifest with one JSON object per case, the `Meta` fields
+//!   plus the frame as `hex`
+//!
+//! Only the corpus and JSONL formats keep the metadata; converting through
+//! the others keeps the frames and a source naming where they came from.
+
+use crate::corpus::{self, Meta};
+use crate::{glob, pcap, scapy};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum Format {
+    Corpus,
+    Raw,
+    Pcap,
+    Jsonl,
+}
+
+/// One line of a JSONL manifest.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Entry {
+    #[serde(flatten)]
+    meta: Meta,
+    hex: String,
+}
+
+/// Guess the format of a path from its extension, or for a directory from
+/// whether it holds `.json` sidecars.
+pub fn guess_format(path: &str) -> Format {
+    let p = Path::new(path);
+    if glob::is_capture(path) {
+        return Format::Pcap;
+    }
+    if p.extension().and_then(|x| x.to_str()) == Some("jsonl") {
+        return Format::Jsonl;
+    }
+    let has_sidecars = std::fs::read_dir(p)
+        .map(|d| {
+            d.filter_map(|e| e.ok())
+                .any(|e| e.path().extension().map(|x| x == "json") == Some(true))
+        })
+        .unwrap_or(false);
+    if p.is_dir() && !has_sidecars {
+        Format::Raw
+    } else {
+        Format::Corpus
+    }
+}
+
+pub(crate) fn unhex(s: &str) -> Result<Vec<u8>, String> {
+    if s.len() % 2 != 0 {
+        return Err("odd number of hex digits".to_string());
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).map_err(|e| e.to_string()))
+        .collect()
+}
+
+pub fn read(path: &str, format: Format) -> Result<Vec<(Meta, Vec<u8>)>, String> {
+    let err = |e: std::io::Error| format!("{}: {}", path, e);
+    match format {
+        Format::Corpus => corpus::load(path).map_err(err),
+        Format::Raw => {
+            let mut files: Vec<_> = std::fs::read_dir(path)
+                .map_err(err)?
+                .filter_map(|e| e.ok())
+                .map(|e| e.path())
+                .filter(|p| p.is_file())
+                .collect();
+            files.sort();
+            files
+                .into_iter()
+                .map(|f| {
+                    let data = std::fs::read(&f).map_err(err)?;
+                    let meta = Meta {
+                        source: f.display().to_string(),
+                        ..Default::default()
+                    };
+                    Ok((meta, data))
+                })
+                .collect()
+        }
+        Format::Pcap => {
+            let (frames, skipped): (Vec<_>, Vec<_>) = pcap::read_file(path)?
+                .into_iter()
+                .partition(|f| f.linktype == pcap::LINKTYPE_ETHERNET);
+            if !skipped.is_empty() {
+                eprintln!(
+                    "{}: {} frames of link types other than Ethernet left out",
+                    path,
+                    skipped.len()
+                );
+            }
+            Ok(frames
+                .into_iter()
+                .map(|f| {
+                    let meta = Meta {
+                        source: format!("{}#{}", path, f.index),
+                        ..Default::default()
+                    };
+                    (meta, f.data)
+                })
+                .collect())
+        }
+        Format::Jsonl => {
+            let data = std::fs::read_to_string(path).map_err(err)?;
+            data.lines()
+                .enumerate()
+                .filter(|(_, l)| !l.trim().is_empty())
+                .map(|(i, l)| {
+                    let entry: Entry = serde_json::from_str(l)
+                        .map_err(|e| format!("{}:{}: {}", path, i + 1, e))?;
+                    let data =
+                        unhex(&entry.hex).map_err(|e| format!("{}:{}: {}", path, i + 1, e))?;
+                    Ok((entry.meta, data))
+                })
+                .collect()
+        }
+    }
+}
+
+/// The packet comment of a case written to pcapng.
+fn packet_comment(meta: &Meta) -> String {
+    let mut comment = format!("oside-tests case {}", meta.source);
+    if !meta.diffs.is_empty() {
+        comment += &format!(": failed\ndiffers: {}", meta.diffs.join(", "));
+    }
+    if let Some(note) = &meta.note {
+        comment += &format!("\n{}", note);
+    }
+    comment
+}
+
+pub fn write(path: &str, format: Format, cases: &[(Meta, Vec<u8>)]) -> Result<(), String> {
+    let err = |e: std::io::Error| format!("{}: {}", path, e);
+    match format {
+        Format::Corpus => {
+            for (meta, data) in cases {
+                corpus::save(path, data, meta).map_err(err)?;
+            }
+        }
+        Format::Raw => {
+            std::fs::create_dir_all(path).map_err(err)?;
+            for (meta, data) in cases {
+                let name = corpus::case_name(&meta.source);
+                std::fs::write(Path::new(path).join(name), data).map_err(err)?;
+            }
+        }
+        Format::Pcap if path.ends_with(".pcapng") => {
+            let file = std::fs::File::create(path).map_err(err)?;
+            let mut w = pcap::NgWriter::new(std::io::BufWriter::new(file), pcap::LINKTYPE_ETHERNET)
+                .map_err(err)?;
+            for (meta, data) in cases {
+                let comment = packet_comment(meta);
+                w.write_frame(Duration::default(), data, Some(&comment))
+                    .map_err(err)?;
+            }
+            w.into_inner().flush().map_err(err)?;
+        }
+        Format::Pcap => {
+            let file = std::fs::File::create(path).map_err(err)?;
+            let mut w = pcap::Writer::new(std::io::BufWriter::new(file), pcap::LINKTYPE_ETHERNET)
+                .map_err(err)?;
+            for (_, data) in cases {
+                w.write_frame(Duration::default(), data).map_err(err)?;
+            }
+            w.into_inner().flush().map_err(err)?;
+        }
+        Format::Jsonl => {
+            let mut out = std::io::BufWriter::new(std::fs::File::create(path).map_err(err)?);
+            for (meta, data) in cases {
+                let entry = Entry {
+                    meta: meta.clone(),
+                    hex: scapy::hex(data),
+                };
+                writeln!(out, "{}", serde_json::to_string(&entry).unwrap()).map_err(err)?;
+            }
+            out.flush().map_err(err)?;
+        }
+    }
+    Ok(())
+}
+
+/// Convert `from` into `to`, guessing formats not given.
+pub fn convert(
+    from: &str,
+    from_format: Option<Format>,
+    to: &str,
+    to_format: Option<Format>,
+) -> i32 {
+    let from_format = from_format.unwrap_or_else(|| guess_format(from));
+    let to_format = to_format.unwrap_or_else(|| guess_format(to));
+    let cases = match read(from, from_format) {
+        Ok(cases) => cases,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 1;
+        }
+    };
+    if let Err(e) = write(to, to_format, &cases) {
+        eprintln!("{}", e);
+        return 1;
+    }
+    eprintln!(
+        "{} cases converted from {:?} to {:?}",
+        cases.len(),
+        from_format,
+        to_format
+    );
+    0
+}
@@ -0,0 +1,82 @@
+//! `corpus minimize`: shrink a corpus without losing what it exercises in
+//! oside.
+//!
+//! A case's signature is oside's layer path for it plus the set of fields
+//! decoded, plus its coverage signature (see `coverage`). Cases whose
+//! signature another case already has are dropped, and trailing bytes are
+//! cut from each case as long as its signature stays the same.
+
+use crate::corpus::{self, Meta};
+use crate::{compare, coverage, decode};
+use std::collections::{BTreeSet, HashSet};
+
+type Signature = (String, BTreeSet<String>);
+
+fn signature(start: &str, data: &[u8]) -> Option<Signature> {
+    let layers = compare::layers(&decode::oside_json(start, data)?);
+    let mut features = coverage::layers_signature(&layers);
+    features.extend(layers.iter().flat_map(|l| {
+        l.fields
+            .keys()
+            .map(move |f| format!("field:{}.{}", l.name, f))
+    }));
+    Some((compare::layer_path(&layers), features))
+}
+
+/// The shortest prefix of `data` with the same signature found by cutting
+/// ever smaller chunks off the end.
+fn truncate(start: &str, data: &[u8], sig: &Signature) -> usize {
+    let mut len = data.len();
+    let mut step = len / 2;
+    while step > 0 {
+        if step <= len && signature(start, &data[..len - step]).as_ref() == Some(sig) {
+            len -= step;
+        } else {
+            step /= 2;
+        }
+    }
+    len
+}
+
+pub fn minimize(from: &str, to: &str, start: &str) -> i32 {
+    let cases = match corpus::load(from) {
+        Ok(cases) => cases,
+        Err(e) => {
+            eprintln!("{}: {}", from, e);
+            return 1;
+        }
+    };
+    let mut seen: HashSet<Signature> = HashSet::new();
+    let mut kept: Vec<(Meta, Vec<u8>)> = vec![];
+    let mut bytes_before = 0;
+    for (meta, data) in cases.iter() {
+        bytes_before += data.len();
+        // Cases oside cannot decode at all are kept whole.
+        let sig = match signature(start, data) {
+            Some(sig) => sig,
+            None => {
+                kept.push((meta.clone(), data.clone()));
+                continue;
+            }
+        };
+        if !seen.insert(sig.clone()) {
+            continue;
+        }
+        let len = truncate(start, data, &sig);
+        kept.push((meta.clone(), data[..len].to_vec()));
+    }
+    for (meta, data) in &kept {
+        if let Err(e) = corpus::save(to, data, meta) {
+            eprintln!("could not save {} into {}: {}", meta.source, to, e);
+            return 1;
+        }
+    }
+    eprintln!(
+        "{} of {} cases kept, {} of {} bytes",
+        kept.len(),
+        cases.len(),
+        kept.iter().map(|(_, d)| d.len()).sum::<usize>(),
+        bytes_before
+    );
+    0
+}
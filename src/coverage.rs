@@ -0,0 +1,109 @@
+//! Lightweight coverage signatures computed from oside's decode of a
+//! packet, standing in for compiler-level coverage in corpus work.
+//!
+//! A signature is a set of features such as `layer:ipv6`, `next:ipv6/udp`,
+//! `option:tcp.mss` or `feature:ipv6-fragment`. Two packets with the same
+//! signature are taken to exercise the same decode paths.
+
+use crate::compare::{self, scalar, value_str, Layer};
+use crate::decode;
+use serde_json::Value;
+use std::collections::BTreeSet;
+
+pub type Signature = BTreeSet<String>;
+
+/// The signature of a frame, or `None` if oside cannot decode it.
+pub fn signature(start: &str, data: &[u8]) -> Option<Signature> {
+    let json = decode::oside_json(start, data)?;
+    Some(layers_signature(&compare::layers(&json)))
+}
+
+/// The signature of decoded layers.
+pub fn layers_signature(layers: &[Layer]) -> Signature {
+    let mut sig = Signature::new();
+    for (i, layer) in layers.iter().enumerate() {
+        sig.insert(format!("layer:{}", layer.name));
+        if let Some(next) = layers.get(i + 1) {
+            sig.insert(format!("next:{}/{}", layer.name, next.name));
+        }
+        for (fname, value) in &layer.fields {
+            if fname.contains("option") || fname == "opts" {
+                for kind in option_kinds(value) {
+                    sig.insert(format!("option:{}.{}", layer.name, kind));
+                }
+            }
+        }
+        for feature in features(layer) {
+            sig.insert(format!("feature:{}", feature));
+        }
+    }
+    sig
+}
+
+/// The kinds of the options in an options field: the name of an
+/// externally tagged option, its `kind`/`type`/`code` field, or the first
+/// element of a `[name, value]` pair as Scapy gives TCP options.
+fn option_kinds(v: &Value) -> Vec<String> {
+    let items = match scalar(v).as_array() {
+        Some(items) => items,
+        None => return vec![],
+    };
+    items
+        .iter()
+        .filter_map(|item| match scalar(item) {
+            Value::Object(obj) if obj.len() == 1 => obj.keys().next().map(|k| k.to_lowercase()),
+            Value::Object(obj) => ["kind", "type", "code", "option"]
+                .iter()
+                .find_map(|k| obj.get(*k))
+                .map(value_str),
+            Value::Array(pair) => pair.first().map(value_str),
+            Value::Null => None,
+            other => Some(value_str(other)),
+        })
+        .collect()
+}
+
+/// Decode paths worth telling apart that the layer names alone do not
+/// show.
+fn features(layer: &Layer) -> Vec<&'static str> {
+    let field = |name: &str| layer.fields.get(name).map(value_str);
+    let mut out = vec![];
+    match layer.name.as_str() {
+        "ip" => {
+            if field("frag").map(|f| f != "0") == Some(true) {
+                out.push("ip-fragment-offset");
+            }
+            let flags = field("flags").unwrap_or_default();
+            if flags.contains("mf") || flags.parse::<u64>().map(|f| f & 1 != 0) == Ok(true) {
+                out.push("ip-more-fragments");
+            }
+            if field("ihl")
+                .and_then(|h| h.parse::<u64>().ok())
+                .map(|h| h > 5)
+                == Some(true)
+            {
+                out.push("ip-options");
+            }
+        }
+        name if name.starts_with("ipv6") && name.contains("frag") => {
+            out.push("ipv6-fragment");
+        }
+        name if name.starts_with("ipv6") && name.contains("ext") => {
+            out.push("ipv6-extension-header");
+        }
+        "tcp" => {
+            if field("dataofs")
+                .and_then(|d| d.parse::<u64>().ok())
+                .map(|d| d > 5)
+                == Some(true)
+            {
+                out.push("tcp-options");
+            }
+        }
+        name if compare::FALLBACK_LAYERS.contains(&name) => {
+            out.push("undecoded-bytes");
+        }
+        _ => {}
+    }
+    out
+}
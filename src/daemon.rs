@@ -0,0 +1,213 @@
+//! `daemon`: generate and compare cases until stopped.
+//!
+//! Cases come from per-protocol templates, Scapy expressions with
+//! placeholders filled with random values. A scheduler rotates between the
+//! protocols by weight, boosting those that failed recently, so that a long
+//! run covers every protocol instead of hammering one template.
+//!
+//! With --compose, cases are random layer stacks instead (see `composer`),
+//! tagged with their layer path.
+
+use crate::backend::Backend;
+use crate::budget::Budget;
+use crate::composer::Composer;
+use crate::rng::{self, Rng};
+use crate::runner::{Case, Input, Runner};
+use crate::{sandbox, scapy, Opts};
+use pyo3::Python;
+use std::collections::VecDeque;
+
+/// Generation templates by protocol. `{uN}` is replaced by a random N-bit
+/// number and `{bytes}` by up to 64 random bytes.
+pub const TEMPLATES: &[(&str, &str)] = &[
+    (
+        "ip",
+        "Ether()/IP(tos={u8}, id={u16}, flags={u3}, ttl={u8}, proto={u8})/Raw(load={bytes})",
+    ),
+    (
+        "udp",
+        "Ether()/IP()/UDP(sport={u16}, dport={u16})/Raw(load={bytes})",
+    ),
+    (
+        "tcp",
+        "Ether()/IP()/TCP(sport={u16}, dport={u16}, seq={u32}, ack={u32}, flags={u8}, window={u16})/Raw(load={bytes})",
+    ),
+    (
+        "icmp",
+        "Ether()/IP()/ICMP(type={u8}, code={u8})/Raw(load={bytes})",
+    ),
+    (
+        "ipv6",
+        "Ether()/IPv6(tc={u8}, fl={u20}, nh={u8}, hlim={u8})/Raw(load={bytes})",
+    ),
+    (
+        "arp",
+        "Ether()/ARP(op={u16}, psrc='10.0.{u8}.{u8}', pdst='10.0.{u8}.{u8}')",
+    ),
+    (
+        "dot1q",
+        "Ether()/Dot1Q(prio={u3}, vlan={u12})/IP()/UDP(dport={u16})/Raw(load={bytes})",
+    ),
+];
+
+/// How many recent outcomes per protocol the scheduler remembers.
+const RECENT: usize = 50;
+
+/// The largest factor recent failures boost a protocol's weight by.
+const MAX_BOOST: f64 = 10.0;
+
+/// Fill a template's placeholders with random values.
+pub fn instantiate(template: &str, rng: &mut Rng) -> String {
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        out.push_str(&rest[..open]);
+        let close = open + rest[open..].find('}').expect("unclosed placeholder");
+        let name = &rest[open + 1..close];
+        if name == "bytes" {
+            let len = rng.below(65) as usize;
+            out.push_str(&format!("bytes.fromhex('{}')", scapy::hex(&rng.bytes(len))));
+        } else {
+            let bits: u32 = name
+                .strip_prefix('u')
+                .and_then(|b| b.parse().ok())
+                .unwrap_or_else(|| panic!("bad placeholder {{{}}}", name));
+            let value = if bits >= 64 {
+                rng.next_u64()
+            } else {
+                rng.below(1 << bits)
+            };
+            out.push_str(&value.to_string());
+        }
+        rest = &rest[close + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+struct Slot {
+    proto: &'static str,
+    template: &'static str,
+    weight: f64,
+    recent: VecDeque<bool>,
+}
+
+/// Picks the protocol of each generated case.
+pub struct Scheduler {
+    slots: Vec<Slot>,
+}
+
+impl Scheduler {
+    /// A scheduler over all templates; `weights` such as `udp=3,arp=0`
+    /// overrides the default weight of 1.
+    pub fn new(weights: Option<&str>) -> Result<Scheduler, String> {
+        let mut slots: Vec<Slot> = TEMPLATES
+            .iter()
+            .map(|&(proto, template)| Slot {
+                proto,
+                template,
+                weight: 1.0,
+                recent: VecDeque::new(),
+            })
+            .collect();
+        for item in weights.unwrap_or("").split(',').filter(|i| !i.is_empty()) {
+            let (proto, weight) = item
+                .split_once('=')
+                .ok_or_else(|| format!("bad weight '{}', expected PROTO=WEIGHT", item))?;
+            let weight: f64 = weight
+                .parse()
+                .map_err(|_| format!("bad weight '{}'", item))?;
+            let slot = slots
+                .iter_mut()
+                .find(|s| s.proto == proto)
+                .ok_or_else(|| format!("no template for protocol '{}'", proto))?;
+            slot.weight = weight;
+        }
+        if slots.iter().all(|s| s.weight <= 0.0) {
+            return Err("all protocol weights are zero".to_string());
+        }
+        Ok(Scheduler { slots })
+    }
+
+    fn effective_weight(slot: &Slot) -> f64 {
+        let failures = slot.recent.iter().filter(|passed| !**passed).count();
+        slot.weight * (1.0 + failures as f64).min(MAX_BOOST)
+    }
+
+    /// Pick the next protocol, returning its index.
+    pub fn pick(&self, rng: &mut Rng) -> usize {
+        let total: f64 = self.slots.iter().map(Scheduler::effective_weight).sum();
+        let mut x = rng.unit() * total;
+        for (i, slot) in self.slots.iter().enumerate() {
+            let w = Scheduler::effective_weight(slot);
+            if x < w {
+                return i;
+            }
+            x -= w;
+        }
+        self.slots.iter().rposition(|s| s.weight > 0.0).unwrap()
+    }
+
+    pub fn proto(&self, idx: usize) -> &'static str {
+        self.slots[idx].proto
+    }
+
+    pub fn template(&self, idx: usize) -> &'static str {
+        self.slots[idx].template
+    }
+
+    /// Remember the outcome of a case of the protocol.
+    pub fn record(&mut self, idx: usize, passed: bool) {
+        let recent = &mut self.slots[idx].recent;
+        recent.push_back(passed);
+        if recent.len() > RECENT {
+            recent.pop_front();
+        }
+    }
+}
+
+pub fn run<'py>(
+    py: Python<'py>,
+    backends: &mut [Box<dyn Backend + 'py>],
+    opts: &Opts,
+    seconds: Option<u64>,
+    seed: Option<u64>,
+    weights: Option<&str>,
+    composer: Option<&Composer>,
+) -> i32 {
+    let seed = seed.unwrap_or_else(rng::time_seed);
+    eprintln!("daemon: seed {}", seed);
+    let mut rng = Rng::new(seed);
+    let mut scheduler = Scheduler::new(weights).unwrap_or_else(|e| panic!("{}", e));
+    let mut budget = Budget::from_opts(opts, seconds);
+
+    let mut runner = Runner::new(py, backends, opts);
+    sandbox::enter(opts);
+    let mut n: u64 = 0;
+    while !runner.aborted() && budget.as_mut().map_or(true, Budget::next) {
+        n += 1;
+        if let Some(composer) = composer {
+            let (path, expr) = composer.compose(composer.start(), &mut rng);
+            runner.case(&Case {
+                source: format!("daemon/compose#{}", n),
+                input: Input::Expr(expr),
+                tag: Some(path),
+                annotations: Default::default(),
+                timestamp: None,
+            });
+            continue;
+        }
+        let idx = scheduler.pick(&mut rng);
+        let proto = scheduler.proto(idx);
+        let case = Case {
+            source: format!("daemon/{}#{}", proto, n),
+            input: Input::Expr(instantiate(scheduler.template(idx), &mut rng)),
+            tag: Some(proto.to_string()),
+            annotations: Default::default(),
+            timestamp: None,
+        };
+        let passed = runner.case(&case);
+        scheduler.record(idx, passed);
+    }
+    runner.finish()
+}
@@ -0,0 +1,146 @@
+//! Decoding with oside.
+
+use crate::alloc_stats;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+/// Layers decoding can start at, selected with `--start-layer`.
+pub const ENTRY_LAYERS: &[&str] = &["ether", "ip", "ipv6", "udp", "tcp", "icmp", "arp", "dns"];
+
+/// oside's JSON tag of a layer it registers, matched case-insensitively.
+fn registered(name: &str) -> Option<&'static str> {
+    static NAMES: OnceLock<Vec<String>> = OnceLock::new();
+    NAMES
+        .get_or_init(oside_layer_names)
+        .iter()
+        .find(|n| n.eq_ignore_ascii_case(name))
+        .map(|n| n.as_str())
+}
+
+/// Whether oside can start decoding at the named layer: one of
+/// `ENTRY_LAYERS`, or any other layer it registers.
+pub fn can_start_at(start: &str) -> bool {
+    ENTRY_LAYERS.contains(&start) || registered(start).is_some()
+}
+
+/// Decode bytes with oside, starting at the named entry layer, or at any
+/// other layer oside registers.
+fn decode(start: &str, data: &[u8]) -> Option<oside::LayerStack> {
+    use oside::protocols::all::*;
+    use oside::*;
+    let res = match start {
+        "ether" => Ether!().decode(data),
+        "ip" => IP!().decode(data),
+        "ipv6" => IPV6!().decode(data),
+        "udp" => UDP!().decode(data),
+        "tcp" => TCP!().decode(data),
+        "icmp" => ICMP!().decode(data),
+        "arp" => ARP!().decode(data),
+        "dns" => DNS!().decode(data),
+        _ => {
+            let tag = registered(start).unwrap_or_else(|| {
+                panic!(
+                    "cannot start decoding at '{}', expected one of {} or another layer oside has",
+                    start,
+                    ENTRY_LAYERS.join(", ")
+                )
+            });
+            // A layer with its default fields decodes the frame.
+            let stack: LayerStack = serde_json::from_value(
+                serde_json::json!({ "layers": [{ tag: {} }], "filled": false }),
+            )
+            .ok()?;
+            stack.layers.first()?.decode(data)
+        }
+    };
+    Some(res?.0)
+}
+
+/// Decode a frame with oside, starting at the `start` layer (`ether` for an
+/// Ethernet frame), returning its layers as JSON.
+pub fn oside_json(start: &str, data: &[u8]) -> Option<serde_json::Value> {
+    let pkt = decode(start, data)?;
+    serde_json::to_value(&pkt.layers).ok()
+}
+
+/// Measure how long oside takes to decode a frame, without serializing the
+/// result.
+pub fn oside_decode_time(start: &str, data: &[u8]) -> Duration {
+    let begin = Instant::now();
+    let pkt = decode(start, data);
+    let elapsed = begin.elapsed();
+    drop(pkt);
+    elapsed
+}
+
+/// Count the allocations oside makes decoding a frame, without serializing
+/// the result.
+pub fn oside_decode_allocs(start: &str, data: &[u8]) -> alloc_stats::Snapshot {
+    let (pkt, allocs) = alloc_stats::measure(|| decode(start, data));
+    drop(pkt);
+    allocs
+}
+
+/// The names of the layers oside registers, as its JSON tags them.
+///
+/// oside has no call listing its protocols, but its layers deserialize from
+/// tagged JSON through a registry, and the error for an unknown tag names
+/// every registered one: "unknown variant `x`, expected one of `Ether`, ...".
+pub fn oside_layer_names() -> Vec<String> {
+    use oside::*;
+    let probe = serde_json::json!({ "layers": [{ "__ot_probe": {} }], "filled": false });
+    let err = match serde_json::from_value::<LayerStack>(probe) {
+        Ok(_) => return vec![],
+        Err(e) => e.to_string(),
+    };
+    let expected = match err.split_once("expected") {
+        Some((_, expected)) => expected,
+        None => return vec![],
+    };
+    expected
+        .split('`')
+        .skip(1)
+        .step_by(2)
+        .map(|name| name.to_string())
+        .collect()
+}
+
+/// Checksum fields, reset to automatic values by `oside_refill`.
+pub const CHECKSUM_FIELDS: &[&str] = &["chksum", "cksum", "checksum"];
+
+/// Decode a frame with oside, reset its checksum fields to automatic values
+/// and encode it again, so that oside computes them.
+pub fn oside_refill(start: &str, data: &[u8]) -> Option<Vec<u8>> {
+    use oside::*;
+    let mut json = oside_json(start, data)?;
+    for layer in json.as_array_mut()? {
+        for fields in layer.as_object_mut()?.values_mut() {
+            if let Some(fields) = fields.as_object_mut() {
+                for name in CHECKSUM_FIELDS {
+                    if let Some(value) = fields.get_mut(*name) {
+                        *value = "Auto".into();
+                    }
+                }
+            }
+        }
+    }
+    let stack: LayerStack =
+        serde_json::from_value(serde_json::json!({ "layers": json, "filled": false })).ok()?;
+    Some(stack.lencode())
+}
+
+/// Build a layer stack given as oside's JSON, e.g. by `reverse::oside_layers()`,
+/// with the fields it leaves out computed by oside.
+pub fn oside_build(layers: serde_json::Value) -> Result<Vec<u8>, String> {
+    use oside::*;
+    let stack: LayerStack =
+        serde_json::from_value(serde_json::json!({ "layers": layers, "filled": false }))
+            .map_err(|e| e.to_string())?;
+    Ok(stack.lencode())
+}
+
+/// Decode a frame with oside and encode the result again.
+pub fn oside_encode(start: &str, data: &[u8]) -> Option<Vec<u8>> {
+    use oside::*;
+    Some(decode(start, data)?.lencode())
+}
@@ -0,0 +1,167 @@
+//! `dispatch`: sweep a dispatch table, e.g. every EtherType, with a fixed
+//! dummy payload and report which next layer the reference and oside pick
+//! for each value where they disagree.
+//!
+//! The port tables cover the well-known ports and the registered ones
+//! dissectors commonly bind, in `REGISTERED_PORTS`; the source port is
+//! `SOURCE_PORT`, which nothing binds.
+
+use crate::backend::Backend;
+use crate::compare::{self, Layer};
+use crate::decode;
+
+/// The dispatch tables that can be swept.
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum Table {
+    /// EtherTypes 0 to 0xffff
+    Ethertype,
+    /// IPv4 protocol numbers 0 to 255
+    IpProto,
+    /// UDP destination ports
+    UdpPort,
+    /// TCP destination ports
+    TcpPort,
+}
+
+/// Registered ports beyond the well-known range that dissectors bind.
+const REGISTERED_PORTS: &[u16] = &[
+    1194, 1701, 1812, 1813, 1900, 2049, 2152, 2123, 3478, 3784, 3785, 4500, 4739, 4784, 4789, 5004,
+    5005, 5060, 5061, 5246, 5247, 5353, 5355, 6081, 6343, 6633, 6653, 8080, 8472, 9995, 10001,
+    47808,
+];
+
+const SOURCE_PORT: u16 = 54321;
+
+const SRC: [u8; 4] = [192, 0, 2, 1];
+const DST: [u8; 4] = [198, 51, 100, 1];
+
+/// The payload following the dispatching header.
+fn payload() -> Vec<u8> {
+    (0..64).collect()
+}
+
+fn ether(ethertype: u16, payload: &[u8]) -> Vec<u8> {
+    let mut frame = vec![0xff; 6];
+    frame.extend([0x02, 0, 0, 0, 0, 0x01]);
+    frame.extend(ethertype.to_be_bytes());
+    frame.extend(payload);
+    frame
+}
+
+/// The Internet checksum of a header.
+fn checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = data
+        .chunks(2)
+        .map(|c| u16::from_be_bytes([c[0], *c.get(1).unwrap_or(&0)]) as u32)
+        .sum();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+fn ipv4(proto: u8, payload: &[u8]) -> Vec<u8> {
+    let len = (20 + payload.len()) as u16;
+    let mut header = vec![0x45, 0];
+    header.extend(len.to_be_bytes());
+    header.extend([0, 1, 0, 0, 64, proto, 0, 0]);
+    header.extend(SRC);
+    header.extend(DST);
+    let sum = checksum(&header);
+    header[10..12].copy_from_slice(&sum.to_be_bytes());
+    header.extend(payload);
+    header
+}
+
+/// A UDP (17) or TCP (6) segment from `SOURCE_PORT` to `port`, with its
+/// checksum over the IPv4 pseudo-header.
+fn transport(proto: u8, port: u16, payload: &[u8]) -> Vec<u8> {
+    let mut segment = SOURCE_PORT.to_be_bytes().to_vec();
+    segment.extend(port.to_be_bytes());
+    if proto == 17 {
+        segment.extend(((8 + payload.len()) as u16).to_be_bytes());
+        segment.extend([0, 0]);
+    } else {
+        // seq 1, ack 0, data offset 5, PSH+ACK, window 8192
+        segment.extend([0, 0, 0, 1, 0, 0, 0, 0, 0x50, 0x18, 0x20, 0, 0, 0, 0, 0]);
+    }
+    segment.extend(payload);
+    let mut pseudo = SRC.to_vec();
+    pseudo.extend(DST);
+    pseudo.extend([0, proto]);
+    pseudo.extend((segment.len() as u16).to_be_bytes());
+    pseudo.extend(&segment);
+    let sum = checksum(&pseudo);
+    let at = if proto == 17 { 6 } else { 16 };
+    segment[at..at + 2].copy_from_slice(&sum.to_be_bytes());
+    segment
+}
+
+fn ports() -> impl Iterator<Item = u16> {
+    (0..1024).chain(REGISTERED_PORTS.iter().copied())
+}
+
+impl Table {
+    /// The values of the table, each with how to print it, the frame
+    /// carrying it, and the index of the layer it dispatches to.
+    fn cases(&self) -> Vec<(String, Vec<u8>, usize)> {
+        match self {
+            Table::Ethertype => (0..=0xffffu16)
+                .map(|t| (format!("0x{:04x}", t), ether(t, &payload()), 1))
+                .collect(),
+            Table::IpProto => (0..=255u8)
+                .map(|p| (p.to_string(), ether(0x0800, &ipv4(p, &payload())), 2))
+                .collect(),
+            Table::UdpPort | Table::TcpPort => {
+                let proto = if *self == Table::UdpPort { 17 } else { 6 };
+                ports()
+                    .map(|port| {
+                        let segment = transport(proto, port, &payload());
+                        (port.to_string(), ether(0x0800, &ipv4(proto, &segment)), 3)
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// The name of the layer at `index`, with fallback layers and a missing
+/// layer all shown as `raw`.
+fn next_layer(layers: &[Layer], index: usize) -> String {
+    match layers.get(index) {
+        Some(l) if !compare::FALLBACK_LAYERS.contains(&l.name.as_str()) => l.name.clone(),
+        _ => "raw".to_string(),
+    }
+}
+
+/// Sweep `table`, printing the values the two sides dispatch differently,
+/// or with `all` every value either side dispatches to something.
+pub fn run(backend: &mut dyn Backend, table: Table, all: bool) -> i32 {
+    let mut values = 0;
+    let mut differ = 0;
+    println!("{:>8}  {:<20}  oside", "value", backend.name());
+    for (value, frame, index) in table.cases() {
+        values += 1;
+        let oside = decode::oside_json("ether", &frame)
+            .map(|j| next_layer(&compare::layers(&j), index))
+            .unwrap_or_else(|| "(failed)".to_string());
+        let reference = match backend.dissect(&frame) {
+            Ok(layers) => next_layer(&layers, index),
+            Err(e) => {
+                eprintln!("{}: {} failed to dissect: {}", value, backend.name(), e);
+                "(failed)".to_string()
+            }
+        };
+        if reference != oside {
+            differ += 1;
+        }
+        if reference != oside || (all && (reference != "raw" || oside != "raw")) {
+            println!("{:>8}  {:<20}  {}", value, reference, oside);
+        }
+    }
+    println!(
+        "{:?}: {} values, {} dispatched differently",
+        table, values, differ
+    );
+    0
+}
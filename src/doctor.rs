@@ -0,0 +1,115 @@
+//! `doctor`: report on the environment the tool runs in, with hints on how
+//! to fix what is missing.
+
+use pyo3::Python;
+
+fn show(what: &str, value: &str) {
+    println!("{:<24}{}", format!("{}:", what), value);
+}
+
+fn hint(text: &str) {
+    println!("{:<24}hint: {}", "", text);
+}
+
+/// Python-side checks followed by `host()`; returns the process exit code.
+pub fn run(py: Python) -> i32 {
+    let mut problems = 0;
+    let eval = |code: &str| -> Result<String, String> {
+        py.eval(code, None, None)
+            .and_then(|v| v.str())
+            .map(|s| s.to_string())
+            .map_err(|e| format!("{:?}", e))
+    };
+
+    let _ = py.run("import sys, sysconfig", None, None);
+    show(
+        "python version",
+        &eval("sys.version.split()[0]").unwrap_or_else(|e| e),
+    );
+    show("python prefix", &eval("sys.prefix").unwrap_or_else(|e| e));
+    show(
+        "python stdlib",
+        &eval("sysconfig.get_paths().get('stdlib')").unwrap_or_else(|e| e),
+    );
+    show("python path", &eval("sys.path").unwrap_or_else(|e| e));
+    if eval("__import__('json').__name__").is_err() {
+        problems += 1;
+        hint("the standard library is not importable; the lib/ directory next to the binary is probably missing");
+    }
+
+    match eval("__import__('scapy').VERSION") {
+        Ok(v) => show("scapy", &v),
+        Err(e) => {
+            problems += 1;
+            show("scapy", &format!("not importable: {}", e));
+            hint("scapy is installed into lib/ by `pyoxidizer build`; rebuild with `make` or copy lib/ along with the binary");
+        }
+    }
+
+    problems += host();
+    if problems > 0 {
+        println!("{} problem(s) found", problems);
+        1
+    } else {
+        println!("no problems found");
+        0
+    }
+}
+
+/// Checks that do not need the interpreter; returns the number of problems.
+pub fn host() -> i32 {
+    let mut problems = 0;
+
+    let mode = if cfg!(feature = "build-mode-standalone") {
+        "standalone (default interpreter config, no embedded resources)"
+    } else if cfg!(feature = "build-mode-pyoxidizer-exe") {
+        "pyoxidizer-exe"
+    } else {
+        "prebuilt-artifacts"
+    };
+    show("build mode", mode);
+    show("python config", env!("DEFAULT_PYTHON_CONFIG_RS"));
+    show("oside version", crate::known_failures::OSIDE_VERSION);
+    if cfg!(feature = "build-mode-standalone") {
+        hint("standalone builds rely on a system Python with scapy; use `make` for a self-contained build");
+    }
+
+    let libpcap = [
+        "/usr/lib/x86_64-linux-gnu/libpcap.so.0.8",
+        "/usr/lib/aarch64-linux-gnu/libpcap.so.0.8",
+        "/usr/lib64/libpcap.so.1",
+        "/usr/lib/libpcap.so.1",
+        "/usr/lib/libpcap.so.0.8",
+        "/usr/lib/libpcap.A.dylib",
+        "/usr/local/lib/libpcap.so",
+    ]
+    .iter()
+    .find(|p| std::path::Path::new(p).exists());
+    match libpcap {
+        Some(p) => show("libpcap", p),
+        None => {
+            show("libpcap", "not found");
+            hint("capture files are read without libpcap, but Scapy's live capture needs it (e.g. `apt install libpcap0.8`)");
+        }
+    }
+
+    match net_raw_capability() {
+        Some(true) => show("CAP_NET_RAW", "yes"),
+        Some(false) => {
+            show("CAP_NET_RAW", "no");
+            hint("live capture on interfaces needs root or `sudo setcap cap_net_raw+ep <binary>`");
+            problems += 1;
+        }
+        None => show("CAP_NET_RAW", "unknown on this platform"),
+    }
+    problems
+}
+
+/// Whether the process has CAP_NET_RAW in its effective capability set.
+fn net_raw_capability() -> Option<bool> {
+    const CAP_NET_RAW: u32 = 13;
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|l| l.starts_with("CapEff:"))?;
+    let caps = u64::from_str_radix(line["CapEff:".len()..].trim(), 16).ok()?;
+    Some(caps & (1 << CAP_NET_RAW) != 0)
+}
@@ -0,0 +1,80 @@
+//! The Ethernet FCS (frame check sequence, a CRC-32) at the end of frames,
+//! for `--fcs`. Captures differ on whether they keep it; when they do and
+//! neither side is told, it shows up as four baffling trailing bytes. With
+//! the FCS taken off before either side decodes the frame, both compare
+//! the same bytes, and the FCS itself is checked here.
+
+/// The length of an Ethernet FCS.
+pub const LEN: usize = 4;
+
+/// Whether frames end in an FCS.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fcs {
+    /// Every frame does.
+    Present,
+    /// None does: the frames are compared as they are.
+    Absent,
+    /// A frame does if its last four bytes are the FCS of the rest.
+    Auto,
+}
+
+impl Fcs {
+    pub fn parse(s: &str) -> Result<Fcs, String> {
+        match s {
+            "present" => Ok(Fcs::Present),
+            "absent" => Ok(Fcs::Absent),
+            "auto" => Ok(Fcs::Auto),
+            _ => Err(format!(
+                "bad --fcs '{}', expected present, absent or auto",
+                s
+            )),
+        }
+    }
+}
+
+/// The CRC-32 of Ethernet (IEEE 802.3), as the FCS carries it.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for b in data {
+        crc ^= *b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// A frame's FCS as found, and as computed over the rest of the frame.
+#[derive(Debug, Clone, Copy)]
+pub struct Check {
+    pub found: u32,
+    pub computed: u32,
+}
+
+impl Check {
+    pub fn valid(&self) -> bool {
+        self.found == self.computed
+    }
+}
+
+/// Split the FCS off an Ethernet frame, if it has one: the frame without
+/// it, and the check of the FCS. Frames too short for a header and an FCS
+/// are left as they are.
+pub fn split(fcs: Fcs, data: &[u8]) -> (&[u8], Option<Check>) {
+    if fcs == Fcs::Absent || data.len() < 14 + LEN {
+        return (data, None);
+    }
+    let (frame, trailer) = data.split_at(data.len() - LEN);
+    let check = Check {
+        found: u32::from_le_bytes(trailer.try_into().unwrap()),
+        computed: crc32(frame),
+    };
+    if fcs == Fcs::Auto && !check.valid() {
+        return (data, None);
+    }
+    (frame, Some(check))
+}
@@ -0,0 +1,217 @@
+//! A small BPF-like frame filter evaluated over oside's decoded layers.
+//!
+//! Grammar:
+//!
+//! ```text
+//! expr    := term ("or" term)*
+//! term    := factor ("and" factor)*
+//! factor  := "not" factor | "(" expr ")" | prim
+//! prim    := [src|dst] port N | [src|dst] host ADDR | PROTO
+//! ```
+//!
+//! `PROTO` matches any layer of that name (`tcp`, `udp`, `ip`, `ip6`, ...),
+//! `port` matches the `sport`/`dport` fields and `host` matches `src`/`dst`
+//! in any layer. `&&`, `||` and `!` are accepted as aliases.
+
+use crate::compare::{value_str, Layer};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+    Not(Box<Filter>),
+    Proto(String),
+    /// Field names to look at, and the value to match.
+    Field(Vec<&'static str>, String),
+}
+
+impl Filter {
+    pub fn parse(s: &str) -> Result<Filter, String> {
+        let tokens = tokenize(s);
+        let mut pos = 0;
+        let f = parse_expr(&tokens, &mut pos)?;
+        if pos != tokens.len() {
+            return Err(format!("unexpected '{}' in filter", tokens[pos]));
+        }
+        Ok(f)
+    }
+
+    pub fn matches(&self, layers: &[Layer]) -> bool {
+        match self {
+            Filter::And(a, b) => a.matches(layers) && b.matches(layers),
+            Filter::Or(a, b) => a.matches(layers) || b.matches(layers),
+            Filter::Not(a) => !a.matches(layers),
+            Filter::Proto(p) => layers.iter().any(|l| &l.name == p),
+            Filter::Field(names, val) => layers.iter().any(|l| {
+                names
+                    .iter()
+                    .any(|n| l.fields.get(*n).map(|v| &value_str(v) == val) == Some(true))
+            }),
+        }
+    }
+}
+
+fn tokenize(s: &str) -> Vec<String> {
+    let mut tokens = vec![];
+    let mut cur = String::new();
+    for c in s.chars() {
+        if c.is_whitespace() || c == '(' || c == ')' || c == '!' {
+            if !cur.is_empty() {
+                tokens.push(std::mem::take(&mut cur));
+            }
+            if !c.is_whitespace() {
+                tokens.push(c.to_string());
+            }
+        } else {
+            cur.push(c);
+        }
+    }
+    if !cur.is_empty() {
+        tokens.push(cur);
+    }
+    tokens
+}
+
+fn parse_expr(t: &[String], pos: &mut usize) -> Result<Filter, String> {
+    let mut f = parse_term(t, pos)?;
+    while *pos < t.len() && (t[*pos] == "or" || t[*pos] == "||") {
+        *pos += 1;
+        f = Filter::Or(Box::new(f), Box::new(parse_term(t, pos)?));
+    }
+    Ok(f)
+}
+
+fn parse_term(t: &[String], pos: &mut usize) -> Result<Filter, String> {
+    let mut f = parse_factor(t, pos)?;
+    while *pos < t.len() && (t[*pos] == "and" || t[*pos] == "&&") {
+        *pos += 1;
+        f = Filter::And(Box::new(f), Box::new(parse_factor(t, pos)?));
+    }
+    Ok(f)
+}
+
+fn parse_factor(t: &[String], pos: &mut usize) -> Result<Filter, String> {
+    let tok = t
+        .get(*pos)
+        .ok_or("unexpected end of filter")?
+        .to_lowercase();
+    *pos += 1;
+    match tok.as_str() {
+        "not" | "!" => Ok(Filter::Not(Box::new(parse_factor(t, pos)?))),
+        "(" => {
+            let f = parse_expr(t, pos)?;
+            if t.get(*pos).map(|s| s.as_str()) != Some(")") {
+                return Err("missing ')' in filter".to_string());
+            }
+            *pos += 1;
+            Ok(f)
+        }
+        "src" | "dst" => {
+            let kind = t.get(*pos).ok_or("expected 'port' or 'host'")?.clone();
+            *pos += 1;
+            let dir = if tok == "src" { 0 } else { 1 };
+            let names = match kind.as_str() {
+                "port" => vec![["sport", "dport"][dir]],
+                "host" => vec![["src", "dst"][dir]],
+                _ => return Err(format!("expected 'port' or 'host' after '{}'", tok)),
+            };
+            Ok(Filter::Field(names, parse_value(t, pos)?))
+        }
+        "port" => Ok(Filter::Field(vec!["sport", "dport"], parse_value(t, pos)?)),
+        "host" => Ok(Filter::Field(vec!["src", "dst"], parse_value(t, pos)?)),
+        "ip6" => Ok(Filter::Proto("ipv6".to_string())),
+        ")" | "and" | "or" | "&&" | "||" => Err(format!("unexpected '{}' in filter", tok)),
+        _ => Ok(Filter::Proto(tok)),
+    }
+}
+
+fn parse_value(t: &[String], pos: &mut usize) -> Result<String, String> {
+    let v = t
+        .get(*pos)
+        .ok_or("expected a value in filter")?
+        .to_lowercase();
+    *pos += 1;
+    Ok(v)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn layers() -> Vec<Layer> {
+        [
+            ("ether", json!({ "src": "00:00:00:00:00:01" })),
+            ("ip", json!({ "src": "10.0.0.1", "dst": "10.0.0.2" })),
+            ("tcp", json!({ "sport": 80, "dport": { "Set": 1234 } })),
+        ]
+        .into_iter()
+        .map(|(name, fields)| Layer {
+            name: name.to_string(),
+            fields: fields.as_object().cloned().unwrap(),
+        })
+        .collect()
+    }
+
+    fn matches(s: &str) -> bool {
+        Filter::parse(s).unwrap().matches(&layers())
+    }
+
+    #[test]
+    fn precedence() {
+        let proto = |p: &str| Box::new(Filter::Proto(p.to_string()));
+        assert_eq!(
+            Filter::parse("tcp or udp and icmp").unwrap(),
+            Filter::Or(
+                proto("tcp"),
+                Box::new(Filter::And(proto("udp"), proto("icmp")))
+            )
+        );
+        assert_eq!(
+            Filter::parse("!(tcp || udp) && ip6").unwrap(),
+            Filter::And(
+                Box::new(Filter::Not(Box::new(Filter::Or(
+                    proto("tcp"),
+                    proto("udp")
+                )))),
+                proto("ipv6")
+            )
+        );
+    }
+
+    #[test]
+    fn primitives() {
+        assert!(matches("tcp"));
+        assert!(matches("TCP and ip"));
+        assert!(!matches("udp"));
+        assert!(!matches("ip6"));
+        assert!(matches("not udp"));
+        assert!(matches("port 80"));
+        assert!(matches("src port 80"));
+        assert!(!matches("dst port 80"));
+        assert!(matches("dst port 1234"));
+        assert!(matches("host 10.0.0.2"));
+        assert!(matches("src host 00:00:00:00:00:01"));
+        assert!(!matches("src host 10.0.0.2"));
+        assert!(matches("udp or (tcp and not port 53)"));
+    }
+
+    #[test]
+    fn errors() {
+        for s in [
+            "",
+            "tcp and",
+            "or tcp",
+            "(tcp",
+            "tcp)",
+            "()",
+            "not",
+            "port",
+            "src",
+            "src proto 6",
+            "dst host",
+        ] {
+            assert!(Filter::parse(s).is_err(), "{:?} parsed", s);
+        }
+    }
+}
@@ -0,0 +1,83 @@
+//! `gap-report`: which protocols in a capture the reference dissector
+//! decodes and oside does not, and the other way round. A directory or
+//! glob pattern scans every capture it names, as for `--pcap`.
+
+use crate::backend::Backend;
+use crate::compare::{self, Layer};
+use crate::runner;
+use crate::{decode, glob, sandbox, Opts};
+use std::collections::{BTreeSet, HashMap};
+
+/// Layer names of a dissection, without the fallback layers.
+fn protocols(layers: &[Layer]) -> BTreeSet<String> {
+    layers
+        .iter()
+        .map(|l| l.name.clone())
+        .filter(|n| !compare::FALLBACK_LAYERS.contains(&n.as_str()))
+        .collect()
+}
+
+/// Print protocol names with their packet counts, most frequent first.
+fn print_counts(title: &str, counts: &HashMap<String, usize>) {
+    println!("{}:", title);
+    if counts.is_empty() {
+        println!("  (none)");
+        return;
+    }
+    let mut counts: Vec<_> = counts.iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+    for (name, count) in counts {
+        println!("  {:>8}  {}", count, name);
+    }
+}
+
+pub fn run(backend: &mut dyn Backend, opts: &Opts, arg: &str) -> i32 {
+    let files = glob::expand(arg).unwrap_or_else(|e| panic!("{}", e));
+    sandbox::enter(opts);
+    let mut reference_only: HashMap<String, usize> = HashMap::new();
+    let mut oside_only: HashMap<String, usize> = HashMap::new();
+    let mut scanned = 0;
+    let frames = files.iter().flat_map(|fname| {
+        let frames = runner::read_frames(opts, fname);
+        frames.into_iter().map(move |frame| (fname, frame))
+    });
+    for (fname, frame) in frames {
+        if !runner::linktype_supported(opts, frame.linktype) {
+            continue;
+        }
+        scanned += 1;
+        let data = runner::entry(opts, &frame.data);
+        let oside = decode::oside_json(&opts.start_layer, data)
+            .map(|j| protocols(&compare::layers(&j)))
+            .unwrap_or_default();
+        let reference = match backend.dissect(data) {
+            Ok(layers) => protocols(&layers),
+            Err(e) => {
+                eprintln!(
+                    "{}#{}: {} failed to dissect: {}",
+                    fname,
+                    frame.index,
+                    backend.name(),
+                    e
+                );
+                continue;
+            }
+        };
+        for name in reference.difference(&oside) {
+            *reference_only.entry(name.clone()).or_default() += 1;
+        }
+        for name in oside.difference(&reference) {
+            *oside_only.entry(name.clone()).or_default() += 1;
+        }
+    }
+    println!("{} frames scanned", scanned);
+    print_counts(
+        &format!("decoded by {} but not by oside", backend.name()),
+        &reference_only,
+    );
+    print_counts(
+        &format!("decoded by oside but not by {}", backend.name()),
+        &oside_only,
+    );
+    0
+}
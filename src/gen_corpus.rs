@@ -0,0 +1,54 @@
+//! `gen-corpus`: a skeleton batch file with default-value expressions for
+//! every protocol oside registers, stacked on Ethernet the common ways, as
+//! a baseline to grow a corpus from.
+
+use crate::{decode, scapy};
+use pyo3::Python;
+use std::fmt::Write;
+
+/// Write the batch file to `out`, or stdout; returns the process exit code.
+pub fn run(py: Python, out: Option<&str>) -> i32 {
+    let names = decode::oside_layer_names();
+    if names.is_empty() {
+        eprintln!("oside did not name its registered layers");
+        return 1;
+    }
+    let mut batch = String::new();
+    let mut exprs = 0;
+    let mut missing = vec![];
+    for name in &names {
+        match scapy::skeletons(py, name) {
+            Ok(skeletons) if !skeletons.is_empty() => {
+                writeln!(batch, "# {}", name).unwrap();
+                for expr in skeletons {
+                    writeln!(batch, "{}", expr).unwrap();
+                    exprs += 1;
+                }
+            }
+            Ok(_) => missing.push(format!("{} (not reachable from Ether in Scapy)", name)),
+            Err(_) => missing.push(format!("{} (no Scapy layer of that name)", name)),
+        }
+    }
+    if !missing.is_empty() {
+        writeln!(batch, "\n# left out:").unwrap();
+        for m in &missing {
+            writeln!(batch, "#   {}", m).unwrap();
+        }
+    }
+    match out {
+        Some(path) => {
+            if let Err(e) = std::fs::write(path, &batch) {
+                eprintln!("{}: {}", path, e);
+                return 1;
+            }
+        }
+        None => print!("{}", batch),
+    }
+    eprintln!(
+        "{} expressions for {} of {} oside layers",
+        exprs,
+        names.len() - missing.len(),
+        names.len()
+    );
+    0
+}
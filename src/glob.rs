@@ -0,0 +1,180 @@
+//! Capture archives for `--pcap`: a glob pattern such as
+//! `captures/**/*.pcap*` or a directory names every capture file it
+//! matches or holds, run in order of their paths.
+//!
+//! Patterns match path components with `*` (any characters), `?` (any one
+//! character) and `[...]` (one of the characters or ranges, `[!...]` none
+//! of them); a `**` component matches any number of directories. Names
+//! starting with `.` are only matched by patterns that start with `.` too.
+
+use crate::pcap;
+use std::path::{Path, PathBuf};
+
+/// Extensions of capture files, looked for when a directory is given.
+pub const CAPTURE_EXTENSIONS: &[&str] = &["pcap", "pcapng", "cap"];
+
+fn is_pattern(s: &str) -> bool {
+    s.contains(['*', '?', '['])
+}
+
+/// Whether a file name looks like a capture file, compressed or not.
+pub fn is_capture(name: &str) -> bool {
+    let name = Path::new(name);
+    let name = match name.extension().and_then(|x| x.to_str()) {
+        Some(x) if pcap::COMPRESSED_EXTENSIONS.contains(&x) => name.with_extension(""),
+        _ => name.to_path_buf(),
+    };
+    name.extension()
+        .and_then(|x| x.to_str())
+        .map_or(false, |x| CAPTURE_EXTENSIONS.contains(&x))
+}
+
+/// Whether the characters of a `[...]` class, without the brackets,
+/// include `c`.
+fn class_matches(class: &[char], c: char) -> bool {
+    let (negated, class) = match class.first() {
+        Some('!') | Some('^') => (true, &class[1..]),
+        _ => (false, class),
+    };
+    let mut found = false;
+    let mut i = 0;
+    while i < class.len() {
+        if i + 2 < class.len() && class[i + 1] == '-' {
+            found |= class[i] <= c && c <= class[i + 2];
+            i += 3;
+        } else {
+            found |= class[i] == c;
+            i += 1;
+        }
+    }
+    found != negated
+}
+
+fn matches_chars(pattern: &[char], name: &[char]) -> bool {
+    match pattern.first() {
+        None => name.is_empty(),
+        Some('*') => (0..=name.len()).any(|skip| matches_chars(&pattern[1..], &name[skip..])),
+        Some('?') => !name.is_empty() && matches_chars(&pattern[1..], &name[1..]),
+        Some('[') => match pattern.iter().skip(2).position(|c| *c == ']') {
+            // a `]` right after the `[` is one of the characters
+            Some(end) => {
+                let end = end + 2;
+                !name.is_empty()
+                    && class_matches(&pattern[1..end], name[0])
+                    && matches_chars(&pattern[end + 1..], &name[1..])
+            }
+            None => name.first() == Some(&'[') && matches_chars(&pattern[1..], &name[1..]),
+        },
+        Some(c) => name.first() == Some(c) && matches_chars(&pattern[1..], &name[1..]),
+    }
+}
+
+/// Whether a path component matches a pattern component.
+pub fn matches(pattern: &str, name: &str) -> bool {
+    if name.starts_with('.') && !pattern.starts_with('.') {
+        return false;
+    }
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    matches_chars(&pattern, &name)
+}
+
+/// The entries of a directory, sorted; unreadable ones are left out.
+fn entries(dir: &Path) -> Vec<PathBuf> {
+    let mut entries: Vec<PathBuf> = std::fs::read_dir(dir)
+        .map(|d| d.filter_map(|e| e.ok()).map(|e| e.path()).collect())
+        .unwrap_or_default();
+    entries.sort();
+    entries
+}
+
+fn name(path: &Path) -> &str {
+    path.file_name().and_then(|n| n.to_str()).unwrap_or("")
+}
+
+/// Add the files beneath `dir` matching the pattern components.
+fn walk(dir: &Path, components: &[&str], out: &mut Vec<PathBuf>) {
+    let (first, rest) = match components.split_first() {
+        Some(split) => split,
+        None => return,
+    };
+    if *first == "**" {
+        // no directory, or one more
+        walk(dir, rest, out);
+        for entry in entries(dir) {
+            if entry.is_dir() && !name(&entry).starts_with('.') {
+                walk(&entry, components, out);
+            }
+        }
+        return;
+    }
+    if !is_pattern(first) {
+        visit(dir.join(first), rest, out);
+        return;
+    }
+    for entry in entries(dir) {
+        if matches(first, name(&entry)) {
+            visit(entry, rest, out);
+        }
+    }
+}
+
+/// Add a path matching a pattern component: the file if it was the last
+/// component, or the files beneath the directory matching the rest.
+fn visit(path: PathBuf, rest: &[&str], out: &mut Vec<PathBuf>) {
+    if rest.is_empty() {
+        if path.is_file() {
+            out.push(path);
+        }
+    } else if path.is_dir() {
+        walk(&path, rest, out);
+    }
+}
+
+/// The capture files beneath a directory, in order of their paths.
+fn captures(dir: &Path, out: &mut Vec<PathBuf>) {
+    for entry in entries(dir) {
+        if name(&entry).starts_with('.') {
+            continue;
+        }
+        if entry.is_dir() {
+            captures(&entry, out);
+        } else if is_capture(name(&entry)) {
+            out.push(entry);
+        }
+    }
+}
+
+/// The files a --pcap argument names: a file as it is, the capture files
+/// beneath a directory, or the files a pattern matches, sorted.
+pub fn expand(arg: &str) -> Result<Vec<String>, String> {
+    let path = Path::new(arg);
+    let mut out = vec![];
+    if path.is_dir() {
+        captures(path, &mut out);
+        if out.is_empty() {
+            return Err(format!("{}: no capture files in the directory", arg));
+        }
+    } else if !is_pattern(arg) {
+        return Ok(vec![arg.to_string()]);
+    } else {
+        let (root, pattern) = match arg.strip_prefix('/') {
+            Some(pattern) => (Path::new("/"), pattern),
+            None => (Path::new("."), arg),
+        };
+        let components: Vec<&str> = pattern.split('/').filter(|c| !c.is_empty()).collect();
+        walk(root, &components, &mut out);
+        out.sort();
+        out.dedup();
+        if out.is_empty() {
+            return Err(format!("{}: no file matches the pattern", arg));
+        }
+    }
+    Ok(out
+        .iter()
+        .map(|p| {
+            let p = p.strip_prefix("./").unwrap_or(p);
+            p.display().to_string()
+        })
+        .collect())
+}
@@ -0,0 +1,136 @@
+//! Intra-value highlighting of differences in the console report, in the
+//! spirit of `git diff --word-diff`: of two differing field values, only the
+//! characters that differ are colored, so that the differing nibbles of a
+//! long hex string stand out.
+//!
+//! Colors are used with `--color always`, or with `--color auto` (the
+//! default) when stdout is a terminal and `NO_COLOR` is not set.
+
+use serde_json::Value;
+use std::io::IsTerminal;
+
+const REFERENCE: &str = "\x1b[1;31m";
+const OSIDE: &str = "\x1b[1;32m";
+const RESET: &str = "\x1b[0m";
+
+/// Above this many cells, the differing middle of two values is found by
+/// their common prefix and suffix rather than a full alignment.
+const MAX_ALIGN: usize = 1 << 22;
+
+/// Whether to color, per a --color of auto, always or never.
+pub fn enabled(color: &str) -> Result<bool, String> {
+    match color {
+        "always" => Ok(true),
+        "never" => Ok(false),
+        "auto" => Ok(std::env::var_os("NO_COLOR").is_none() && std::io::stdout().is_terminal()),
+        color => Err(format!(
+            "bad --color '{}', expected auto, always or never",
+            color
+        )),
+    }
+}
+
+/// For each character of `a` and of `b`, whether it is not part of their
+/// alignment, i.e. differs.
+fn changed(a: &[char], b: &[char]) -> (Vec<bool>, Vec<bool>) {
+    if a.len() == b.len() {
+        let changed: Vec<bool> = a.iter().zip(b).map(|(x, y)| x != y).collect();
+        return (changed.clone(), changed);
+    }
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let (am, bm) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+    let (mut ca, mut cb) = (vec![true; am.len()], vec![true; bm.len()]);
+    if (am.len() + 1) * (bm.len() + 1) <= MAX_ALIGN {
+        // longest common subsequence of the differing middles
+        let w = bm.len() + 1;
+        let mut lcs = vec![0u32; (am.len() + 1) * w];
+        for i in (0..am.len()).rev() {
+            for j in (0..bm.len()).rev() {
+                lcs[i * w + j] = if am[i] == bm[j] {
+                    lcs[(i + 1) * w + j + 1] + 1
+                } else {
+                    lcs[(i + 1) * w + j].max(lcs[i * w + j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < am.len() && j < bm.len() {
+            if am[i] == bm[j] {
+                ca[i] = false;
+                cb[j] = false;
+                i += 1;
+                j += 1;
+            } else if lcs[(i + 1) * w + j] >= lcs[i * w + j + 1] {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+    }
+    let wrap = |changed: Vec<bool>, len: usize| {
+        let mut out = vec![false; prefix];
+        out.extend(changed);
+        out.resize(len, false);
+        out
+    };
+    (wrap(ca, a.len()), wrap(cb, b.len()))
+}
+
+/// Render `chars`, coloring the runs of changed ones.
+fn paint(chars: &[char], changed: &[bool], color: &str) -> String {
+    let mut out = String::new();
+    let mut on = false;
+    for (c, changed) in chars.iter().zip(changed) {
+        if *changed != on {
+            out.push_str(if *changed { color } else { RESET });
+            on = *changed;
+        }
+        out.push(*c);
+    }
+    if on {
+        out.push_str(RESET);
+    }
+    out
+}
+
+/// Render the values of a difference as `name value`, each with the
+/// characters differing from oside's value (oside's: from the first
+/// reference's) highlighted if `color`.
+pub fn values(values: &[(String, Value)], color: bool) -> Vec<String> {
+    let rendered: Vec<String> = values.iter().map(|(_, v)| v.to_string()).collect();
+    if !color {
+        return values
+            .iter()
+            .zip(&rendered)
+            .map(|((name, _), v)| format!("{} {}", name, v))
+            .collect();
+    }
+    let oside = values.iter().position(|(name, _)| name == "oside");
+    let first_reference = values.iter().position(|(name, _)| name != "oside");
+    values
+        .iter()
+        .enumerate()
+        .map(|(i, (name, _))| {
+            let (other, color) = if Some(i) == oside {
+                (first_reference, OSIDE)
+            } else {
+                (oside, REFERENCE)
+            };
+            let this: Vec<char> = rendered[i].chars().collect();
+            let painted = match other {
+                Some(other) => {
+                    let other: Vec<char> = rendered[other].chars().collect();
+                    paint(&this, &changed(&this, &other).0, color)
+                }
+                None => rendered[i].clone(),
+            };
+            format!("{} {}", name, painted)
+        })
+        .collect()
+}
@@ -0,0 +1,197 @@
+//! Root-cause hints for failures: common shapes of differences matched to
+//! the bug classes that usually cause them, printed under the differences
+//! to speed up triage.
+//!
+//! - every differing number byte-swapped: an endianness mismatch;
+//! - a length off by a header size: one side counts the header in it;
+//! - only the last few bytes differing, or taken as padding by one side
+//!   only: FCS (Ethernet CRC) handling;
+//! - only checksums differing: checksum computation or verification;
+//! - one side falling back to raw bytes where the other dissects a layer:
+//!   a missing dispatch binding.
+
+use crate::compare::{self, value_str, Diff, Layer, FALLBACK_LAYERS};
+use crate::normalize;
+
+/// Header sizes of common layers, by layer name.
+const HEADER_SIZES: &[(&str, u64)] = &[
+    ("ether", 14),
+    ("dot1q", 4),
+    ("ip", 20),
+    ("ipv6", 40),
+    ("udp", 8),
+    ("tcp", 20),
+    ("icmp", 8),
+    ("gre", 4),
+    ("vxlan", 8),
+];
+
+const LENGTH_FIELDS: &[&str] = &["len", "length", "tot_len", "plen", "payload_len", "hlen"];
+
+const CHECKSUM_FIELDS: &[&str] = &["chksum", "cksum", "checksum", "crc", "fcs"];
+
+/// The most bytes taken as an FCS or trailer.
+const MAX_TRAILER: usize = 4;
+
+/// The hints for the differences of a failure.
+pub fn hints(diffs: &[Diff], oside: &[Layer], refs: &[(String, Vec<Layer>)]) -> Vec<String> {
+    let mut out = vec![];
+    out.extend(endianness(diffs));
+    out.extend(diffs.iter().filter_map(length_off_by_header));
+    out.extend(trailer(diffs, oside, refs));
+    out.extend(checksums(diffs));
+    out.extend(diffs.iter().filter_map(dispatch));
+    // several references give the same difference several times
+    let mut seen = std::collections::HashSet::new();
+    out.retain(|hint| seen.insert(hint.clone()));
+    out
+}
+
+/// The layer name and field of a field difference.
+fn field(d: &Diff) -> Option<(&str, &str)> {
+    compare::split_path(&d.path).map(|(layer, _, field)| (layer, field))
+}
+
+fn numbers(d: &Diff) -> Option<(u64, u64)> {
+    let r = value_str(&d.reference).parse::<u64>().ok()?;
+    let o = value_str(&d.oside).parse::<u64>().ok()?;
+    (r != o).then(|| (r, o))
+}
+
+fn endianness(diffs: &[Diff]) -> Option<String> {
+    let numeric: Vec<&Diff> = diffs.iter().filter(|d| numbers(d).is_some()).collect();
+    let swapped: Vec<&str> = numeric
+        .iter()
+        .filter(|d| compare::is_byte_swap(d))
+        .map(|d| d.path.as_str())
+        .collect();
+    if swapped.is_empty() {
+        return None;
+    }
+    if swapped.len() == numeric.len() && swapped.len() > 1 {
+        return Some(
+            "every differing number is byte-swapped: an endianness mismatch, one side reading in host order"
+                .to_string(),
+        );
+    }
+    Some(format!(
+        "{} byte-swapped: read in the wrong byte order",
+        swapped.join(", ")
+    ))
+}
+
+fn length_off_by_header(d: &Diff) -> Option<String> {
+    let (layer, name) = field(d)?;
+    if !LENGTH_FIELDS.contains(&name) {
+        return None;
+    }
+    let (r, o) = numbers(d)?;
+    let delta = r.max(o) - r.min(o);
+    let (header, _) = HEADER_SIZES
+        .iter()
+        .find(|(l, size)| *l == layer && *size == delta)
+        .or_else(|| HEADER_SIZES.iter().find(|(_, size)| *size == delta))?;
+    let longer = if o > r { "oside" } else { "the reference" };
+    Some(format!(
+        "{} off by {}, the size of a {} header: {} counts the header in the length, the other side does not",
+        d.path, delta, header, longer
+    ))
+}
+
+/// Whether one byte string is the other with a few bytes more at the end,
+/// or differs from it only in its last few bytes; with how many.
+fn trailing(a: &[u8], b: &[u8]) -> Option<usize> {
+    let (short, long) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+    if long.len() > short.len() {
+        let extra = long.len() - short.len();
+        return (extra <= MAX_TRAILER && long.starts_with(short)).then(|| extra);
+    }
+    let common = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let differing = a.len() - common;
+    (differing > 0 && differing <= MAX_TRAILER).then(|| differing)
+}
+
+/// The bytes of a trailing fallback layer.
+fn trailer_bytes(layers: &[Layer]) -> Option<Vec<u8>> {
+    let last = layers.last()?;
+    if !FALLBACK_LAYERS.contains(&last.name.as_str()) {
+        return None;
+    }
+    last.fields.values().find_map(normalize::field_bytes)
+}
+
+fn trailer(diffs: &[Diff], oside: &[Layer], refs: &[(String, Vec<Layer>)]) -> Option<String> {
+    for d in diffs {
+        if let Some((layer, _)) = field(d) {
+            if !FALLBACK_LAYERS.contains(&layer) {
+                continue;
+            }
+            let bytes = (
+                normalize::field_bytes(&d.reference),
+                normalize::field_bytes(&d.oside),
+            );
+            if let (Some(r), Some(o)) = bytes {
+                if let Some(n) = trailing(&r, &o) {
+                    return Some(format!(
+                        "{} differs only in its last {} bytes: FCS (Ethernet CRC) or trailer handling, see --fcs",
+                        d.path, n
+                    ));
+                }
+            }
+            continue;
+        }
+        if d.path != "layers" {
+            continue;
+        }
+        // one side has an extra short fallback layer at the end
+        for (name, layers) in refs {
+            let (extra, longer) = if layers.len() == oside.len() + 1 {
+                (layers, name.as_str())
+            } else if oside.len() == layers.len() + 1 {
+                (oside, "oside")
+            } else {
+                continue;
+            };
+            let bytes = match trailer_bytes(extra) {
+                Some(bytes) if bytes.len() <= MAX_TRAILER => bytes,
+                _ => continue,
+            };
+            return Some(format!(
+                "{} alone takes the last {} bytes as {}: FCS (Ethernet CRC) or trailer handling, see --fcs",
+                longer,
+                bytes.len(),
+                extra.last().unwrap().name
+            ));
+        }
+    }
+    None
+}
+
+fn checksums(diffs: &[Diff]) -> Option<String> {
+    let all = !diffs.is_empty()
+        && diffs
+            .iter()
+            .all(|d| field(d).map_or(false, |(_, f)| CHECKSUM_FIELDS.contains(&f)));
+    all.then(|| {
+        "only checksums differ: checksum computation, or one side verifying and the other not"
+            .to_string()
+    })
+}
+
+fn dispatch(d: &Diff) -> Option<String> {
+    if !d.path.starts_with("layers[") {
+        return None;
+    }
+    let (r, o) = (value_str(&d.reference), value_str(&d.oside));
+    let (stops, other, layer) = if FALLBACK_LAYERS.contains(&o.as_str()) {
+        ("oside", "the reference", r)
+    } else if FALLBACK_LAYERS.contains(&r.as_str()) {
+        ("the reference", "oside", o)
+    } else {
+        return None;
+    };
+    Some(format!(
+        "{} stops dissecting at {} where {} sees {}: a missing dispatch (port, EtherType or protocol binding)",
+        stops, d.path, other, layer
+    ))
+}
@@ -0,0 +1,184 @@
+//! `schema`: the shape of the JSON oside emits, inferred from the cases
+//! run, as a contract for consumers of that JSON to validate against.
+//!
+//! The shape lists, for each layer, every field path seen in its JSON
+//! (`field`, `field.sub` for objects, `field[]` for array items) with the
+//! JSON types seen there. Shapes written by earlier runs can be compared
+//! with the current one: added layers, fields and types are compatible,
+//! removed ones and fields that lost a type are not. Only shapes inferred
+//! from the same cases compare meaningfully.
+
+use crate::runner::{self, Case, Input};
+use crate::{decode, known_failures, sandbox, scapy, Opts};
+use pyo3::Python;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Shape {
+    pub oside_version: String,
+    /// Field paths and their JSON types, by layer.
+    pub layers: BTreeMap<String, BTreeMap<String, BTreeSet<String>>>,
+}
+
+fn type_name(v: &Value) -> &'static str {
+    match v {
+        Value::Null => "null",
+        Value::Bool(_) => "bool",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+impl Shape {
+    fn add_value(fields: &mut BTreeMap<String, BTreeSet<String>>, path: &str, v: &Value) {
+        fields
+            .entry(path.to_string())
+            .or_default()
+            .insert(type_name(v).to_string());
+        match v {
+            Value::Object(obj) => {
+                for (key, v) in obj {
+                    Shape::add_value(fields, &format!("{}.{}", path, key), v);
+                }
+            }
+            Value::Array(items) => {
+                for v in items {
+                    Shape::add_value(fields, &format!("{}[]", path), v);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Add the layers of one decode, a list of `{"Layer": {fields}}`.
+    pub fn add(&mut self, layers: &Value) {
+        for layer in layers.as_array().map(|a| a.as_slice()).unwrap_or(&[]) {
+            for (name, fields) in layer.as_object().into_iter().flatten() {
+                let shape = self.layers.entry(name.clone()).or_default();
+                for (field, v) in fields.as_object().into_iter().flatten() {
+                    Shape::add_value(shape, field, v);
+                }
+            }
+        }
+    }
+}
+
+/// The changes from an earlier shape to a later one, and whether any of
+/// them breaks consumers of the earlier one.
+pub fn diff(old: &Shape, new: &Shape) -> (Vec<String>, bool) {
+    let mut out = vec![];
+    let mut breaking = false;
+    for (layer, old_fields) in &old.layers {
+        let new_fields = match new.layers.get(layer) {
+            Some(fields) => fields,
+            None => {
+                out.push(format!("- {}", layer));
+                breaking = true;
+                continue;
+            }
+        };
+        for (path, old_types) in old_fields {
+            match new_fields.get(path) {
+                None => {
+                    out.push(format!("- {}.{}", layer, path));
+                    breaking = true;
+                }
+                Some(new_types) if new_types != old_types => {
+                    let join =
+                        |t: &BTreeSet<String>| t.iter().cloned().collect::<Vec<_>>().join("|");
+                    out.push(format!(
+                        "~ {}.{}: {} -> {}",
+                        layer,
+                        path,
+                        join(old_types),
+                        join(new_types)
+                    ));
+                    breaking |= !old_types.is_subset(new_types);
+                }
+                Some(_) => {}
+            }
+        }
+        for path in new_fields.keys().filter(|p| !old_fields.contains_key(*p)) {
+            out.push(format!("+ {}.{}", layer, path));
+        }
+    }
+    for layer in new.layers.keys().filter(|l| !old.layers.contains_key(*l)) {
+        out.push(format!("+ {}", layer));
+    }
+    (out, breaking)
+}
+
+pub fn run(
+    py: Python,
+    opts: &Opts,
+    cases: Vec<Case>,
+    out: Option<&str>,
+    against: Option<&str>,
+) -> i32 {
+    sandbox::enter(opts);
+    let mut shape = Shape {
+        oside_version: known_failures::OSIDE_VERSION.to_string(),
+        ..Default::default()
+    };
+    let mut decoded = 0;
+    for case in &cases {
+        let data = match &case.input {
+            Input::Frame(data) => data.clone(),
+            Input::Expr(expr) => match scapy::build(py, expr) {
+                Ok(data) => data,
+                Err(e) => {
+                    eprintln!("{}: scapy could not build {}: {}", case.label(), expr, e);
+                    continue;
+                }
+            },
+        };
+        if let Some(json) = decode::oside_json(&opts.start_layer, runner::entry(opts, &data)) {
+            shape.add(&json);
+            decoded += 1;
+        }
+    }
+    eprintln!(
+        "schema: {} layers from {} of {} cases decoded",
+        shape.layers.len(),
+        decoded,
+        cases.len()
+    );
+    let text = serde_json::to_string_pretty(&shape).unwrap();
+    match out {
+        Some(path) => {
+            std::fs::write(path, text + "\n").unwrap_or_else(|e| panic!("{}: {}", path, e))
+        }
+        None if against.is_none() => println!("{}", text),
+        None => {}
+    }
+    let path = match against {
+        Some(path) => path,
+        None => return 0,
+    };
+    let data = std::fs::read_to_string(path).unwrap_or_else(|e| panic!("{}: {}", path, e));
+    let old: Shape = serde_json::from_str(&data).unwrap_or_else(|e| panic!("{}: {}", path, e));
+    let (changes, breaking) = diff(&old, &shape);
+    if changes.is_empty() {
+        println!(
+            "the shape is the same as in {} (oside {})",
+            path, old.oside_version
+        );
+        return 0;
+    }
+    println!(
+        "changes since {} (oside {} -> {}):",
+        path, old.oside_version, shape.oside_version
+    );
+    for change in &changes {
+        println!("  {}", change);
+    }
+    if breaking {
+        println!("some changes remove layers, fields or types consumers of the earlier shape may rely on");
+        return 1;
+    }
+    0
+}
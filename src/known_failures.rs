@@ -0,0 +1,149 @@
+//! Known failures, given with `--known-failures`, which are reported but not
+//! counted as failures.
+//!
+//! The file is TOML with one `[[failure]]` table per entry:
+//!
+//! ```toml
+//! [[failure]]
+//! signature = "udp.chksum"
+//! reason = "oside does not verify UDP checksums yet"
+//! expires = "2026-12-31"
+//! oside_before = "0.2.0"
+//! ```
+//!
+//! `signature` is a failure signature as printed with failures (see
+//! `compare::failure_signature`). An entry stops applying after its
+//! `expires` date or once oside reaches the `oside_before` version, and the
+//! failures it covered count again, so the list cannot quietly outlive
+//! the bugs it was written for.
+
+use serde::Deserialize;
+use std::time::SystemTime;
+
+/// The oside version this tool was built against, from Cargo.lock.
+pub const OSIDE_VERSION: &str = env!("OSIDE_VERSION");
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Entry {
+    pub signature: String,
+    #[serde(default)]
+    pub reason: Option<String>,
+    /// Last day the entry applies, as YYYY-MM-DD.
+    #[serde(default)]
+    pub expires: Option<String>,
+    /// The entry applies only to oside versions below this one.
+    #[serde(default)]
+    pub oside_before: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct File {
+    #[serde(default)]
+    failure: Vec<Entry>,
+}
+
+pub enum Match<'a> {
+    /// No entry has the signature.
+    None,
+    Known(&'a Entry),
+    /// An entry has the signature but no longer applies, for the reason given.
+    Lapsed(&'a Entry, String),
+}
+
+pub struct KnownFailures {
+    entries: Vec<Entry>,
+    today: String,
+}
+
+/// Parse a dotted version into its numbers; missing or non-numeric parts
+/// count as 0.
+fn version(v: &str) -> Vec<u64> {
+    v.split(|c: char| c == '.' || c == '-' || c == '+')
+        .map(|p| p.parse().unwrap_or(0))
+        .collect()
+}
+
+/// Whether `a` is an older version than `b`.
+fn older(a: &str, b: &str) -> bool {
+    let (mut a, mut b) = (version(a), version(b));
+    let len = a.len().max(b.len());
+    a.resize(len, 0);
+    b.resize(len, 0);
+    a < b
+}
+
+fn valid_date(s: &str) -> bool {
+    let parts: Vec<&str> = s.split('-').collect();
+    parts.len() == 3
+        && [4, 2, 2]
+            .iter()
+            .zip(&parts)
+            .all(|(len, p)| p.len() == *len && p.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// Today's UTC date as YYYY-MM-DD.
+fn today() -> String {
+    let secs = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    utc_date(secs)
+}
+
+/// The UTC date of a time in seconds since the Unix epoch, as YYYY-MM-DD.
+pub(crate) fn utc_date(secs: u64) -> String {
+    // days since 1970-01-01 to a civil date, after Howard Hinnant's algorithm
+    let z = (secs / 86400) as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+impl KnownFailures {
+    pub fn load(path: &str) -> Result<KnownFailures, String> {
+        let data = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let file: File = toml::from_str(&data).map_err(|e| format!("{}: {}", path, e))?;
+        for entry in &file.failure {
+            if let Some(date) = &entry.expires {
+                if !valid_date(date) {
+                    return Err(format!(
+                        "{}: entry for {}: bad expiry date '{}', expected YYYY-MM-DD",
+                        path, entry.signature, date
+                    ));
+                }
+            }
+        }
+        Ok(KnownFailures {
+            entries: file.failure,
+            today: today(),
+        })
+    }
+
+    /// Look up a failure signature.
+    pub fn check(&self, signature: &str) -> Match<'_> {
+        let entry = match self.entries.iter().find(|e| e.signature == signature) {
+            Some(entry) => entry,
+            None => return Match::None,
+        };
+        if let Some(date) = &entry.expires {
+            if self.today.as_str() > date.as_str() {
+                return Match::Lapsed(entry, format!("expired on {}", date));
+            }
+        }
+        if let Some(bound) = &entry.oside_before {
+            if !older(OSIDE_VERSION, bound) {
+                return Match::Lapsed(
+                    entry,
+                    format!("oside {} is not below {}", OSIDE_VERSION, bound),
+                );
+            }
+        }
+        Match::Known(entry)
+    }
+}
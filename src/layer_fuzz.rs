@@ -0,0 +1,137 @@
+//! `fuzz-layer`: fuzz one oside layer decoder directly, comparing it with
+//! the Scapy class of the same layer instantiated on the same bytes.
+//!
+//! Entering every frame through Ethernet rarely reaches an inner layer's
+//! error paths with bytes that are wrong in interesting ways: the outer
+//! layers have to be valid for it to be handed anything at all. Here a
+//! packet of the layer is built from a template (see `daemon::instantiate`)
+//! and then mutated at the byte level, and decoding starts at the layer.
+
+use crate::budget::Budget;
+use crate::composer::ComposeArgs;
+use crate::daemon;
+use crate::rng::{self, Rng};
+use crate::runner::{self, Case, Input, Runner};
+use crate::{decode, sandbox, scapy, Opts};
+use pyo3::Python;
+
+/// Templates of a single packet of each entry layer, with whatever it
+/// carries as payload.
+pub const LAYER_TEMPLATES: &[(&str, &str)] = &[
+    (
+        "ether",
+        "Ether(type={u16})/Raw(load={bytes})",
+    ),
+    (
+        "ip",
+        "IP(tos={u8}, id={u16}, flags={u3}, ttl={u8}, proto={u8})/Raw(load={bytes})",
+    ),
+    (
+        "ipv6",
+        "IPv6(tc={u8}, fl={u20}, nh={u8}, hlim={u8})/Raw(load={bytes})",
+    ),
+    ("udp", "UDP(sport={u16}, dport={u16})/Raw(load={bytes})"),
+    (
+        "tcp",
+        "TCP(sport={u16}, dport={u16}, seq={u32}, ack={u32}, flags={u8}, window={u16}, options=[('MSS', {u16}), ('WScale', {u4}), ('SAckOK', b'')])/Raw(load={bytes})",
+    ),
+    ("icmp", "ICMP(type={u8}, code={u8})/Raw(load={bytes})"),
+    (
+        "arp",
+        "ARP(op={u16}, psrc='10.0.{u8}.{u8}', pdst='10.0.{u8}.{u8}')",
+    ),
+    (
+        "dns",
+        "DNS(id={u16}, rd={u1}, qd=DNSQR(qname='example.com', qtype={u16}))",
+    ),
+];
+
+/// Mutate a packet at the byte level, returning the mutation's name.
+fn mutate(rng: &mut Rng, data: &mut Vec<u8>) -> &'static str {
+    if data.is_empty() {
+        data.extend(rng.bytes(1 + rng.below(16) as usize));
+        return "extend";
+    }
+    let at = rng.below(data.len() as u64) as usize;
+    match rng.below(5) {
+        0 => {
+            data[at] ^= 1 << rng.below(8);
+            "flip"
+        }
+        1 => {
+            data[at] = rng.next_u64() as u8;
+            "byte"
+        }
+        2 => {
+            data.truncate(at);
+            "truncate"
+        }
+        3 => {
+            let extra = rng.bytes(1 + rng.below(16) as usize);
+            data.extend(extra);
+            "extend"
+        }
+        _ => "none",
+    }
+}
+
+pub fn run(
+    py: Python,
+    opts: &Opts,
+    layer: &str,
+    seconds: Option<u64>,
+    seed: Option<u64>,
+    compose: &ComposeArgs,
+) -> i32 {
+    let composer = compose.composer().unwrap_or_else(|e| panic!("{}", e));
+    if matches!(&composer, Some(c) if !c.knows(layer)) {
+        eprintln!("--compose cannot start a stack at '{}'", layer);
+        return 1;
+    }
+    let template = match LAYER_TEMPLATES.iter().find(|(name, _)| *name == layer) {
+        Some((_, template)) => template,
+        None => {
+            eprintln!(
+                "no template for layer '{}', expected one of {}",
+                layer,
+                decode::ENTRY_LAYERS.join(", ")
+            );
+            return 1;
+        }
+    };
+    let mut opts = opts.clone();
+    opts.start_layer = layer.to_string();
+    let seed = seed.unwrap_or_else(rng::time_seed);
+    eprintln!("fuzz-layer {}: seed {}", layer, seed);
+    let mut rng = Rng::new(seed);
+    let mut budget = Budget::from_opts(&opts, seconds);
+
+    let mut backends = runner::backends(py, &opts);
+    let mut runner = Runner::new(py, &mut backends, &opts);
+    sandbox::enter(&opts);
+    let mut n: u64 = 0;
+    while !runner.aborted() && budget.as_mut().map_or(true, Budget::next) {
+        let expr = match &composer {
+            Some(composer) => composer.compose(layer, &mut rng).1,
+            None => daemon::instantiate(template, &mut rng),
+        };
+        let mut data = match scapy::build(py, &expr) {
+            Ok(data) => data,
+            Err(e) => {
+                eprintln!("could not build {}: {}", expr, e);
+                continue;
+            }
+        };
+        let op = mutate(&mut rng, &mut data);
+        n += 1;
+        let case = Case {
+            source: format!("fuzz-layer/{}@{}#{}", layer, seed, n),
+            input: Input::Frame(data),
+            tag: Some(op.to_string()),
+            annotations: Default::default(),
+            timestamp: None,
+        };
+        runner.case(&case);
+    }
+    runner.finish()
+}
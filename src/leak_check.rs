@@ -0,0 +1,85 @@
+//! `--leak-check`: decode the same frames over and over and check that
+//! memory use levels off.
+//!
+//! The frames are built with Scapy once, up front, and only replayed as
+//! recorded bytes afterwards, so the interpreter sits idle while memory is
+//! measured and any growth is attributable to oside.
+
+use crate::runner::{self, Case, Input};
+use crate::{alloc_stats, decode, sandbox, scapy, Opts};
+use pyo3::Python;
+
+/// Resident set size growth over the measured iterations tolerated before
+/// it is reported as a leak, as allocator noise makes it inexact.
+const RSS_SLACK: usize = 1 << 20;
+
+/// The process's resident set size in bytes, on Linux.
+fn rss() -> Option<usize> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|l| l.starts_with("VmRSS:"))?;
+    let kb: usize = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb * 1024)
+}
+
+pub fn run(py: Python, opts: &Opts, cases: Vec<Case>) -> i32 {
+    sandbox::enter(opts);
+    let mut frames = vec![];
+    for case in &cases {
+        match &case.input {
+            Input::Frame(data) => frames.push(runner::entry(opts, data).to_vec()),
+            Input::Expr(expr) => match scapy::build(py, expr) {
+                Ok(data) => frames.push(runner::entry(opts, &data).to_vec()),
+                Err(e) => eprintln!("{}: scapy could not build {}: {}", case.label(), expr, e),
+            },
+        }
+    }
+    if !alloc_stats::enabled() {
+        eprintln!("warning: --leak-check needs the global-allocator-counting feature for allocator figures; only RSS is checked");
+    }
+    let iterations = opts.leak_iterations.max(2);
+
+    let mut live = vec![];
+    let mut resident = vec![];
+    for i in 0..iterations {
+        for data in &frames {
+            drop(decode::oside_json(&opts.start_layer, data));
+        }
+        live.push(alloc_stats::snapshot().live);
+        resident.push(rss().unwrap_or(0));
+        if opts.verbose > 0 {
+            println!(
+                "iteration {}: {} live bytes, {} bytes resident",
+                i + 1,
+                live[i],
+                resident[i]
+            );
+        }
+    }
+
+    // The first iteration warms up caches and lazily initialized state;
+    // measure from the end of it.
+    let n = (iterations - 1) as f64;
+    let live_growth = live[iterations - 1] as f64 - live[0] as f64;
+    let rss_growth = resident[iterations - 1].saturating_sub(resident[0]);
+    println!(
+        "{} iterations over {} frames: live heap {:+.0} bytes/iteration, RSS {:+} bytes",
+        iterations,
+        frames.len(),
+        live_growth / n,
+        rss_growth as i64
+    );
+    let mut leaking = false;
+    if alloc_stats::enabled() && live_growth > 0.0 && live.windows(2).all(|w| w[1] >= w[0]) {
+        println!("leak: the live heap kept growing across iterations");
+        leaking = true;
+    }
+    if rss_growth > RSS_SLACK {
+        println!("leak: RSS grew by more than {} bytes", RSS_SLACK);
+        leaking = true;
+    }
+    if leaking {
+        1
+    } else {
+        0
+    }
+}
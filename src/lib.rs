@@ -0,0 +1,1099 @@
+//! oside-tests: compares oside's dissections against Scapy's and other
+//! reference implementations.
+//!
+//! The binary is a thin wrapper around `main()`. Crates embedding the
+//! runner take `Opts`, `Runner` and `Normalizer` from here: the options are
+//! parsed from arguments with `Opts::parse_from` or read from YAML or JSON,
+//! and their own normalizers are registered with `Runner::add_normalizer`,
+//! their comparators with `register_comparator` for every runner or
+//! `Runner::add_comparator` for one.
+
+use pyembed::{MainPythonInterpreter, OxidizedPythonInterpreterConfig};
+
+// Include an auto-generated file defining a
+// `fn default_python_config<'a>() -> pyembed::OxidizedPythonInterpreterConfig<'a>`
+// which returns an `OxidizedPythonInterpreterConfig` derived by the PyOxidizer
+// configuration file.
+//
+// If you do not want your application to use this generated file or wish
+// to explicitly instantiate the `OxidizedPythonInterpreterConfig` used to
+// initialize the embedded Python interpreter, simply remove this line and
+// the call to `default_python_config()` below.
+include!(env!("DEFAULT_PYTHON_CONFIG_RS"));
+
+use crate::pymod::PyInit_string_sum;
+use std::ffi::CString;
+
+pub mod alloc_stats;
+pub mod anonymize;
+pub mod backend;
+pub mod budget;
+pub mod bundle;
+pub mod cache;
+pub mod comparator;
+pub mod compare;
+pub mod composer;
+pub mod consensus;
+pub mod corpus;
+pub mod corpus_convert;
+pub mod corpus_minimize;
+pub mod coverage;
+pub mod daemon;
+pub mod decode;
+pub mod dispatch;
+pub mod doctor;
+pub mod fcs;
+pub mod filter;
+pub mod gap_report;
+pub mod gen_corpus;
+pub mod glob;
+pub mod highlight;
+pub mod hints;
+pub mod json_shape;
+pub mod known_failures;
+pub mod layer_fuzz;
+pub mod leak_check;
+pub mod link_detect;
+pub mod nat64;
+pub mod nightly;
+pub mod normalize;
+pub mod notify;
+pub mod offload;
+pub mod package;
+pub mod pcap;
+pub mod plan;
+pub mod profile;
+pub mod pymod;
+pub mod recording;
+pub mod rejection;
+pub mod report;
+pub mod results;
+pub mod reverse;
+pub mod rng;
+pub mod runner;
+pub mod sandbox;
+pub mod scapy;
+pub mod schema;
+pub mod self_test;
+pub mod semantic;
+pub mod sflow;
+pub mod soak;
+pub mod span;
+pub mod stateful;
+pub mod suites;
+pub mod summary;
+pub mod workdir;
+
+pub use comparator::{register as register_comparator, LayerComparator};
+pub use normalize::{Normalizer, Side};
+pub use runner::Runner;
+
+use clap::CommandFactory;
+use clap::FromArgMatches;
+use clap::Parser as ClapParser;
+use oside;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+/// This program does something useful, but its author needs to edit this.
+/// Else it will be just hanging around forever
+#[derive(Debug, Clone, ClapParser, Serialize, Deserialize)]
+#[clap(version = "0.0.1", author = "Andrew Yourtchenko <ayourtch@gmail.com>")]
+pub struct Opts {
+    /// Scapy python string to execute
+    #[clap(short, long, default_value = "Ether()/IP()/UDP()")]
+    scapy_expr: String,
+
+    /// Override options from this yaml/json file
+    #[clap(short, long)]
+    options_override: Option<String>,
+
+    /// Apply a preset of options: quick, thorough, ci, fuzz-overnight, or one from
+    /// ~/.config/oside-tests/profiles.toml
+    #[clap(long)]
+    profile: Option<String>,
+
+    /// Print json corresponding to a given scapy expression
+    #[clap(short, long)]
+    print_json: bool,
+
+    /// Read json from stdin and verify it matches scapy_expr
+    #[clap(long)]
+    verify_json: bool,
+
+    /// Reference dissector to compare oside against: scapy, python[:INTERPRETER], tshark
+    /// or cmd:COMMAND (a command speaking the JSON driver protocol). Give it several times
+    /// to vote on each difference and single out the disagreeing implementation.
+    #[clap(long, default_value = "scapy")]
+    #[serde(default = "default_backend")]
+    backend: Vec<String>,
+
+    /// Compare the reference's and oside's dissection of every frame in this pcap/pcapng
+    /// file, the capture files beneath this directory, or the files matching this glob
+    /// pattern (e.g. 'captures/**/*.pcap*'), in order of their paths; gzip, zstd and xz
+    /// compressed captures are decompressed as they are read
+    #[clap(long)]
+    pcap: Option<String>,
+
+    /// Compare the packet headers sampled by sFlow agents, from this capture of sFlow v5
+    /// datagrams (UDP port 6343), or the captures of a directory or glob pattern as for
+    /// --pcap; differences explained by the headers being cut short are not counted
+    #[clap(long)]
+    sflow: Option<String>,
+
+    /// Compare the reference's and oside's dissection of frames without a link type: hex
+    /// dumps, one frame per line (spaces, colons and 0x prefixes allowed), or else the whole
+    /// file as one binary frame. The layer they start at is guessed unless given with
+    /// --link-type or --start-layer
+    #[clap(long)]
+    raw: Option<String>,
+
+    /// Layer the frames of --raw and --pcap start at, by --start-layer name or pcap link
+    /// type number, overriding the link type of the capture and any guess
+    #[clap(long)]
+    link_type: Option<String>,
+
+    /// Compare the reference's and oside's dissection of each Scapy expression in this file,
+    /// one per line; a line may end in annotations such as "#@ level: 2, expect: fail"
+    /// (expect: fail, scapy-error or oside-error makes the case a negative test)
+    #[clap(long)]
+    batch: Option<String>,
+
+    /// Compare the reference's and oside's dissection of each case of this built-in suite,
+    /// as listed by list-suites; give it several times for several suites. Suites of frames
+    /// starting at another layer than Ethernet set --start-layer
+    #[clap(long)]
+    #[serde(default)]
+    suite: Vec<String>,
+
+    /// Check that Scapy's raw(), bytes() and build() agree on each expression before
+    /// comparing it with oside
+    #[clap(long)]
+    #[serde(default)]
+    check_build: bool,
+
+    /// Also build each expression with oside, from its layers and the fields set in it,
+    /// and compare the bytes with Scapy's; expressions oside has no equivalent of are
+    /// left out
+    #[clap(long)]
+    #[serde(default)]
+    reverse: bool,
+
+    /// Also unset the checksum fields of each frame on both sides, let each compute them
+    /// and compare the bytes built, testing checksum computation rather than copying
+    #[clap(long)]
+    #[serde(default)]
+    checksum_fill: bool,
+
+    /// Check that Scapy renders each frame it dissects back into the same bytes, and leave
+    /// frames it does not out of the comparison rather than blaming oside for them
+    #[clap(long)]
+    #[serde(default)]
+    check_oracle: bool,
+
+    /// Fail cases with undecoded (raw, padding or unknown) layers on either side, or
+    /// differing only in representation, instead of letting them pass
+    #[clap(long)]
+    #[serde(default)]
+    strict: bool,
+
+    /// Comparison tier a case has to pass: 1 or path (same layer path), 2 or key (also the
+    /// same addresses and ports), 3 or fields (no differing field, the default) or 4 or
+    /// bytes (also oside encodes it back into the same bytes); reports the highest tier
+    /// each case passes. A batch entry can set its own with "#@ level: N"
+    #[clap(long)]
+    level: Option<String>,
+
+    /// Also compare one-line summaries of both dissections (layer path, addresses and
+    /// ports), reporting the cases agreeing on them apart from the field comparison
+    #[clap(long)]
+    #[serde(default)]
+    compare_summary: bool,
+
+    /// When oside and the reference all reject a frame, also compare why (truncated, bad
+    /// length, unknown type), failing the case if the reasons differ materially
+    #[clap(long)]
+    #[serde(default)]
+    compare_rejections: bool,
+
+    /// Print, per protocol field, how often the reference and oside agreed on it
+    #[clap(long)]
+    #[serde(default)]
+    field_stats: bool,
+
+    /// Also run structural mutations of each --batch expression: duplicated, dropped and
+    /// swapped layers, and length fields pointing past the end of the frame
+    #[clap(long)]
+    #[serde(default)]
+    mutate: bool,
+
+    /// Also sweep each length field of each --batch expression through zero, under, exact,
+    /// over and past-the-end values, summarizing the results per field
+    #[clap(long)]
+    #[serde(default)]
+    length_sweep: bool,
+
+    /// Sweep one field of each --batch expression (or of --scapy-expr) through a range of
+    /// at most 65536 values, e.g. IP.tos=0..=255 (`..` leaves the end out, so IP.tos=0..255
+    /// stops at 254); an open range ends at the field's largest value
+    #[clap(long)]
+    #[serde(default)]
+    sweep_field: Vec<String>,
+
+    /// Leave this field out of the comparison on both sides, e.g. ip.chksum, *.chksum for
+    /// every layer, or ip[1].ttl for the second IP layer only; give it several times for
+    /// several fields
+    #[clap(long)]
+    #[serde(default)]
+    ignore_field: Vec<String>,
+
+    /// Write the bytes and the first backend's dissection of every case, with the host's
+    /// versions, into this JSON file, to replay elsewhere with --recording
+    #[clap(long)]
+    record_reference: Option<String>,
+
+    /// Use the dissections of a --record-reference file, made on another host, as the
+    /// reference; generated cases decode the recorded bytes, and where the local Scapy
+    /// builds or dissects differently, the difference is reported as environment-dependent
+    #[clap(long)]
+    recording: Option<String>,
+
+    /// Bring string and byte-array fields into one form on both sides before comparing:
+    /// bytes (hex) or text (UTF-8 with control characters escaped)
+    #[clap(long)]
+    normalize_text: Option<String>,
+
+    /// Leave this class of values as each side writes them instead of turning them into
+    /// plain numbers on both sides before comparing: numbers (decimal, hex, octal or binary
+    /// strings), enums (value names such as ip.proto "udp") or flags (flag names such as
+    /// tcp.flags "SA"); give it several times for several classes
+    #[clap(long, value_name = "CLASS")]
+    #[serde(default)]
+    keep_representation: Vec<String>,
+
+    /// Replace byte strings longer than N bytes, on both sides before comparing, with their
+    /// length and hash, to keep reports readable for packets with large payloads
+    #[clap(long, value_name = "N")]
+    elide_payloads: Option<usize>,
+
+    /// Take values that differ only in text as equal: DNS names by case, trailing dot and
+    /// compression pointer, IPv6 addresses by zero compression, MAC addresses by
+    /// separators and case
+    #[clap(long)]
+    #[serde(default)]
+    semantic: bool,
+
+    /// Only run the cases involving these protocols, e.g. "tcp,ipv6": those whose oside
+    /// decode has such a layer or options of it
+    #[clap(long)]
+    changed_protocols: Option<String>,
+
+    /// Only run the cases involving the protocols of these changed oside source files,
+    /// comma-separated, e.g. "src/protocols/tcp.rs"; a mod.rs stands for its directory
+    #[clap(long)]
+    changed_files: Option<String>,
+
+    /// Run cases whose frames passed before again, instead of skipping them by the verdict
+    /// cache, which is keyed by the frame, the oside and Scapy versions and the comparison
+    /// options
+    #[clap(long)]
+    #[serde(default)]
+    no_cache: bool,
+
+    /// Keep the verdict cache in this directory instead of ~/.cache/oside-tests
+    #[clap(long)]
+    cache_dir: Option<String>,
+
+    /// Compare the reference's and oside's dissection of every case in this corpus directory
+    #[clap(long)]
+    corpus: Option<String>,
+
+    /// Only run the case with this ID (or ID prefix), as printed in reports
+    #[clap(long)]
+    case: Option<String>,
+
+    /// Write a JSON summary of the run here at its end: counts, duration, the most
+    /// frequent failure signatures and the files written, for CI scripts
+    #[clap(long)]
+    summary_json: Option<String>,
+
+    /// Append the outcome of every case to this results database (JSON lines)
+    #[clap(long)]
+    results_db: Option<String>,
+
+    /// Store oside's JSON for each case in the --results-db, and report the cases whose
+    /// JSON changed since it was last stored, e.g. by a newer oside, even where they still
+    /// match the reference
+    #[clap(long)]
+    #[serde(default)]
+    store_oside_json: bool,
+
+    /// Report the Scapy classes and fields the run dissected and the oside layers it
+    /// decoded, with the common Scapy classes never dissected (and with -v the fields and
+    /// oside layers never reached)
+    #[clap(long)]
+    #[serde(default)]
+    class_coverage: bool,
+
+    /// Exit with success unless more than N cases fail; with any of the gating options
+    /// the exit code is up to them instead of failing on any failure
+    #[clap(long, value_name = "N")]
+    max_failures: Option<usize>,
+
+    /// Exit with success unless more than N cases fail that did not fail in their last run
+    /// recorded in the --results-db (or were not run before)
+    #[clap(long, value_name = "N")]
+    max_new_failures: Option<usize>,
+
+    /// Exit with success unless fewer than P percent of the compared cases pass, e.g. 95
+    #[clap(long, value_name = "P")]
+    min_pass_rate: Option<f64>,
+
+    /// Stop at the first failure, printing how to reproduce it
+    #[clap(long)]
+    #[serde(default)]
+    fail_fast: bool,
+
+    /// Stop at the Nth failure, printing how to reproduce it
+    #[clap(long, value_name = "N")]
+    abort_after: Option<usize>,
+
+    /// Run the cases in a random order, to reveal state leaking from one case into the next;
+    /// the seed, taken from the clock if not given, is printed to repeat the order
+    #[clap(long, value_name = "SEED")]
+    #[serde(default)]
+    shuffle: Option<Option<u64>>,
+
+    /// Run all the cases K times, in the same order and without the verdict cache, and fail
+    /// unless every run gives the same result for every case, printing the first case that
+    /// differs
+    #[clap(long, value_name = "K")]
+    verify_determinism: Option<usize>,
+
+    /// Fit the run into this much time, e.g. 90s, 30m or 1h30m: once the cost of a case is
+    /// measured, as many of the remaining cases as fit are spread over them, and the run
+    /// stops before a case that would overrun, reporting as usual. Also the default
+    /// --seconds of daemon, fuzz-layer and stateful
+    #[clap(long, value_name = "DURATION")]
+    duration: Option<String>,
+
+    /// What each case starts afresh with: none (cases share the interpreter's globals),
+    /// namespace (the globals are reset before each case) or interpreter (each case runs
+    /// in a new sub-interpreter, importing Scapy again: slow, and needs Scapy importable
+    /// from the filesystem)
+    #[clap(long, default_value = "none")]
+    #[serde(default = "default_isolate")]
+    isolate: String,
+
+    /// Whether Ethernet frames end in an FCS (CRC-32): present (take the last 4 bytes off
+    /// before either side decodes the frame, reporting a wrong one), absent (compare the
+    /// frames as they are) or auto (take them off where they are the frame's CRC-32)
+    #[clap(long, default_value = "absent")]
+    #[serde(default = "default_fcs")]
+    fcs: String,
+
+    /// Fix up the artifacts of hardware offload in frames captured on the sending host, as
+    /// the NIC would have, instead of failing on them: zero IPv4 checksums, TCP and UDP
+    /// checksums holding only the pseudo-header sum, and zero IP lengths of GSO
+    /// super-packets. Such cases are tagged offload
+    #[clap(long)]
+    #[serde(default)]
+    tolerate_offload: bool,
+
+    /// Run the cases that failed or whose protocols changed verdict in the --results-db first
+    #[clap(long)]
+    #[serde(default)]
+    prioritize_failures: bool,
+
+    /// Also report failures in this format: gha (GitHub Actions annotations)
+    #[clap(long)]
+    report: Option<String>,
+
+    /// Compare the payload bytes read from stdin wrapped inside this outer Scapy stack,
+    /// e.g. "Ether()/IP()/UDP(dport=4789)/"
+    #[clap(long)]
+    wrap: Option<String>,
+
+    /// Skip this many bytes at the start of each frame, e.g. an outer encapsulation
+    /// already removed by the capture pipeline
+    #[clap(long, default_value = "0")]
+    #[serde(default)]
+    skip_bytes: usize,
+
+    /// Layer both sides start decoding at: ether, ip, ipv6, udp, tcp, icmp, arp, dns or
+    /// another layer oside has, by name; set from the link type of a --pcap capture of
+    /// Linux cooked (SLL, SLL2), PPP, HDLC-framed PPP, Cisco HDLC, Frame Relay, Bluetooth
+    /// HCI (H4) packets or USB transfers (USBPcap, usbmon), which is left out if a side
+    /// cannot start there. BSD and macOS loopback captures are compared as Ethernet frames.
+    /// Guessed for --raw frames
+    #[clap(long, default_value = "ether")]
+    #[serde(default = "default_start_layer")]
+    start_layer: String,
+
+    /// Dissect the payload of this UDP or TCP port as this layer, e.g. udp:4789=vxlan; give it
+    /// several times for several ports. Applies to the embedded Scapy only: oside has no
+    /// runtime dispatch registration
+    #[clap(long)]
+    #[serde(default)]
+    bind_port: Vec<String>,
+
+    /// Only compare frames matching this BPF-like filter, e.g. "tcp and port 443"
+    #[clap(long)]
+    filter: Option<String>,
+
+    /// Only compare frames with these 1-based indices, e.g. "1000..2000" or "5..=9"
+    #[clap(long)]
+    frames: Option<String>,
+
+    /// Only compare frames captured in this window of seconds since the first frame, e.g. "2.5..10"
+    #[clap(long)]
+    time_range: Option<String>,
+
+    /// Report failures listed in this TOML file as known instead of counting them; entries
+    /// can expire on a date or from an oside version on
+    #[clap(long)]
+    known_failures: Option<String>,
+
+    /// Pack the options, versions, results, failing cases and --pcap-out capture of the run
+    /// into this .tar.gz
+    #[clap(long)]
+    bundle: Option<String>,
+
+    /// Before dissecting any frame, deny network access, running programs and other
+    /// syscalls dissectors have no business making (Linux only), for untrusted captures
+    #[clap(long)]
+    #[serde(default)]
+    sandbox: bool,
+
+    /// Write temporary files, interpreter caches and relative output paths only beneath
+    /// this directory, for running with a read-only root filesystem
+    #[clap(long)]
+    workdir: Option<String>,
+
+    /// Save failing frames as corpus entries into this directory
+    #[clap(long)]
+    save_failures: Option<String>,
+
+    /// Write every case's frame into this pcapng file, at its capture time, with its case
+    /// ID, verdict and differences as the packet comment, to see them inline in Wireshark;
+    /// under --anonymize the frames are anonymized and the differences' values left out
+    #[clap(long)]
+    pcap_out: Option<String>,
+
+    /// POST a JSON description of each failure with a new signature to this URL, e.g.
+    /// to watch a long daemon run
+    #[clap(long)]
+    notify_webhook: Option<String>,
+
+    /// Mail each failure with a new signature through this SMTP relay; only settable
+    /// in the --options-override file, as {server, from, to}
+    #[clap(skip)]
+    #[serde(default)]
+    notify_smtp: Option<notify::Smtp>,
+
+    /// Anonymize frames (addresses remapped, payloads zeroed) before saving them; frames
+    /// starting at a layer other than ether, ip, ipv6 or arp are zeroed whole
+    #[clap(long)]
+    #[serde(default)]
+    anonymize: bool,
+
+    /// Flag frames that oside takes longer than this many milliseconds to decode
+    #[clap(long)]
+    slow_ms: Option<f64>,
+
+    /// Flag frames where one decoder is more than this many times slower than the other
+    #[clap(long)]
+    slow_ratio: Option<f64>,
+
+    /// Report the allocations oside makes decoding each frame (needs the
+    /// global-allocator-counting feature)
+    #[clap(long)]
+    #[serde(default)]
+    alloc_stats: bool,
+
+    /// Benchmark oside decoding of the --pcap frames instead of comparing them
+    #[clap(long)]
+    #[serde(default)]
+    bench: bool,
+
+    /// Number of times each frame is decoded by --bench
+    #[clap(long, default_value = "100")]
+    #[serde(default = "default_bench_iterations")]
+    bench_iterations: usize,
+
+    /// Corpus directory receiving the frames flagged by --slow-ms/--slow-ratio
+    #[clap(long, default_value = "slow")]
+    #[serde(default = "default_slow_dir")]
+    slow_dir: String,
+
+    /// Decode the cases --leak-iterations times and check that memory use levels off
+    #[clap(long)]
+    #[serde(default)]
+    leak_check: bool,
+
+    /// Number of times --leak-check decodes the cases
+    #[clap(long, default_value = "20")]
+    #[serde(default = "default_leak_iterations")]
+    leak_iterations: usize,
+
+    /// Print the JSON Schema of the result, diff and other objects this tool writes and exit
+    #[clap(long)]
+    #[serde(default)]
+    print_schema: bool,
+
+    /// Print a man page for this tool and exit
+    #[clap(long)]
+    #[serde(default)]
+    generate_man: bool,
+
+    /// Highlight the differing characters of differing values: auto (when printing to a
+    /// terminal), always or never
+    #[clap(long, default_value = "auto")]
+    #[serde(default = "default_color")]
+    color: String,
+
+    /// A level of verbosity, and can be used multiple times
+    #[clap(short, long, parse(from_occurrences))]
+    verbose: i32,
+
+    #[clap(subcommand)]
+    #[serde(skip)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Clone, clap::Subcommand)]
+enum CorpusCommand {
+    /// Convert cases between corpus directories, raw files per case, pcap and JSONL manifests
+    Convert {
+        /// Where to read the cases from
+        from: String,
+        /// Where to write the cases to
+        to: String,
+        /// Format of FROM, guessed from the path if not given
+        #[clap(long, value_enum)]
+        from_format: Option<corpus_convert::Format>,
+        /// Format of TO, guessed from the path if not given
+        #[clap(long, value_enum)]
+        to_format: Option<corpus_convert::Format>,
+    },
+    /// Drop cases adding nothing to oside's layer and field coverage, and cut bytes that do
+    /// not change it from the end of the others
+    Minimize {
+        /// The corpus directory to minimize
+        from: String,
+        /// The corpus directory receiving the minimized cases
+        to: String,
+    },
+}
+
+#[derive(Debug, Clone, clap::Subcommand)]
+enum Command {
+    /// Print a shell completion script
+    Completions {
+        #[clap(value_enum)]
+        shell: clap_complete::Shell,
+    },
+    /// Compare the reference's and oside's dissection of the cases of these built-in
+    /// suites, as --suite does
+    Suite {
+        #[clap(required = true)]
+        names: Vec<String>,
+    },
+    /// List the built-in suites with their case counts and descriptions
+    ListSuites,
+    /// Run a plan: built-in suites and batch files, each with the number of cases to run,
+    /// a seed to sample them with and the level to pass
+    Plan {
+        /// The YAML file of the plan
+        file: String,
+    },
+    /// Run a plan as a scheduled job: append to the results database, keep each run's
+    /// failures and summary in a directory of its own, remove those of old runs, and
+    /// print the pass rate over the last runs
+    Nightly {
+        #[clap(flatten)]
+        args: nightly::NightlyArgs,
+    },
+    /// Check that the interpreter, Scapy, oside and the comparison all work on this host
+    SelfTest,
+    /// Report on the Python, Scapy, build and capture environment
+    Doctor,
+    /// Decode the --pcap/--corpus/--batch cases in a loop for a while, reporting oside's
+    /// throughput over time and fully comparing a sample of the decodes
+    Soak {
+        /// How long to run, in seconds
+        #[clap(long, default_value = "60")]
+        seconds: u64,
+        /// Compare one in this many decodes with the reference
+        #[clap(long, default_value = "100")]
+        sample: usize,
+    },
+    /// Generate and compare cases from per-protocol templates until stopped
+    Daemon {
+        /// Stop after this many seconds instead of running until interrupted
+        #[clap(long)]
+        seconds: Option<u64>,
+        /// Seed for the random values, to repeat a run; taken from the clock if not given
+        #[clap(long)]
+        seed: Option<u64>,
+        /// Relative weights of the protocols, e.g. "udp=3,arp=0"; the default weight is 1
+        #[clap(long)]
+        weights: Option<String>,
+        #[clap(flatten)]
+        compose: composer::ComposeArgs,
+    },
+    /// Fuzz one layer's decoder directly with mutated packets of that layer, comparing it
+    /// with the Scapy class of the layer
+    FuzzLayer {
+        /// The layer: ether, ip, ipv6, udp, tcp, icmp, arp or dns
+        layer: String,
+        /// Stop after this many seconds instead of running until interrupted
+        #[clap(long)]
+        seconds: Option<u64>,
+        /// Seed for the random values, to repeat a run; taken from the clock if not given
+        #[clap(long)]
+        seed: Option<u64>,
+        #[clap(flatten)]
+        compose: composer::ComposeArgs,
+    },
+    /// Generate multi-packet scenarios (connection setup, data, teardown, now and then an
+    /// invalid transition) from a state machine and compare every packet, until stopped
+    Stateful {
+        #[clap(flatten)]
+        args: stateful::StatefulArgs,
+    },
+    /// Compare NAT64 and 464XLAT scenarios, IPv4 packets and their IPv6 translations, and
+    /// check the address and port mappings between them on oside's decode
+    Nat64,
+    /// Sweep a dispatch table (EtherTypes, IP protocols, UDP or TCP ports) with a dummy
+    /// payload and print the values for which the reference and oside pick different next
+    /// layers
+    Dispatch {
+        #[clap(value_enum)]
+        table: dispatch::Table,
+        /// Print every value either side dispatches to a layer, not only the differences
+        #[clap(long)]
+        all: bool,
+    },
+    /// Work with corpora
+    Corpus {
+        #[clap(subcommand)]
+        action: CorpusCommand,
+    },
+    /// Infer the shape of the JSON oside emits, the fields of each layer and their JSON
+    /// types, from the --pcap/--corpus/--batch/--suite cases, and compare it with one
+    /// written by an earlier run
+    Schema {
+        /// Write the shape to this file instead of printing it
+        #[clap(long)]
+        out: Option<String>,
+        /// Compare with the shape in this file, failing if layers, fields or types went
+        /// missing
+        #[clap(long)]
+        against: Option<String>,
+    },
+    /// List the protocols in a capture decoded by the reference but not by oside, and the
+    /// other way round, by packet count
+    GapReport {
+        /// The pcap/pcapng file to scan, or a directory or glob pattern as for --pcap
+        pcap: String,
+    },
+    /// Write a batch file with a default-value expression per protocol oside registers,
+    /// stacked on Ether the common ways, as a baseline corpus
+    GenCorpus {
+        /// Write the batch file here instead of to stdout
+        #[clap(long)]
+        out: Option<String>,
+    },
+    /// Put a built binary and its Python resources into a relocatable .tar.gz for a target
+    Package {
+        /// Target triple of the build to package; the one this binary was built for by default
+        #[clap(long)]
+        target: Option<String>,
+        /// Cargo profile of the build to package
+        #[clap(long, default_value = "release")]
+        profile: String,
+        /// Leave out the Python resources, for standalone builds using the machine's Python
+        #[clap(long)]
+        no_python: bool,
+        /// Include this --record-reference file, to replay on machines without Scapy
+        #[clap(long)]
+        recording: Option<String>,
+        /// Write the archive here instead of oside-tests-VERSION-TARGET.tar.gz
+        #[clap(long)]
+        out: Option<String>,
+    },
+}
+
+fn default_backend() -> Vec<String> {
+    vec!["scapy".to_string()]
+}
+
+fn default_start_layer() -> String {
+    "ether".to_string()
+}
+
+fn default_bench_iterations() -> usize {
+    100
+}
+
+fn default_leak_iterations() -> usize {
+    20
+}
+
+fn default_slow_dir() -> String {
+    "slow".to_string()
+}
+
+fn default_isolate() -> String {
+    "none".to_string()
+}
+
+fn default_fcs() -> String {
+    "absent".to_string()
+}
+
+fn default_color() -> String {
+    "auto".to_string()
+}
+
+/// Run `f`, turning a panic into exit code 101 once the panic hook has printed
+/// it, so that the interpreter holding the GIL is still finalized in order.
+fn isolate(f: impl FnOnce() -> i32) -> i32 {
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)).unwrap_or_else(|_| {
+        eprintln!("error: aborted by the panic above");
+        101
+    })
+}
+
+/// What runs with the interpreter up: the mode the options select, returning
+/// the process exit code.
+fn run(
+    py: pyo3::Python,
+    opts: &Opts,
+    plan: Option<&plan::Plan>,
+    bundle: &mut Option<bundle::Bundle>,
+) -> i32 {
+    match &opts.command {
+        Some(Command::SelfTest) => return self_test::run(py),
+        Some(Command::Doctor) => return doctor::run(py),
+        _ => {}
+    }
+    scapy::init(py);
+    if !opts.bind_port.is_empty() {
+        scapy::bind_ports(py, &opts.bind_port).unwrap_or_else(|e| panic!("{}", e));
+        eprintln!(
+            "warning: --bind-port only changes Scapy's dispatch; oside cannot be told \
+             about the ports, so expect their payloads to differ"
+        );
+    }
+    if let Some(bundle) = bundle {
+        bundle.versions(py);
+    }
+    if let Some(Command::GapReport { pcap }) = &opts.command {
+        let mut backend = backend::from_spec(py, &opts.backend[0], &opts.start_layer)
+            .unwrap_or_else(|e| panic!("{}", e));
+        return gap_report::run(backend.as_mut(), opts, pcap);
+    }
+    if let Some(Command::GenCorpus { out }) = &opts.command {
+        return gen_corpus::run(py, out.as_deref());
+    }
+    if let Some(Command::Dispatch { table, all }) = &opts.command {
+        let mut backend =
+            backend::from_spec(py, &opts.backend[0], "ether").unwrap_or_else(|e| panic!("{}", e));
+        return dispatch::run(backend.as_mut(), *table, *all);
+    }
+    if let (Some(fname), true) = (&opts.pcap, opts.bench) {
+        return runner::run_bench(opts, fname);
+    }
+    if let Some(Command::Soak { seconds, sample }) = &opts.command {
+        let cases = runner::collect_cases(py, opts);
+        let mut backends = runner::backends(py, opts);
+        return soak::run(py, &mut backends, opts, cases, *seconds, *sample);
+    }
+    if let Some(Command::Daemon {
+        seconds,
+        seed,
+        weights,
+        compose,
+    }) = &opts.command
+    {
+        let composer = compose.composer().unwrap_or_else(|e| panic!("{}", e));
+        let mut backends = runner::backends(py, opts);
+        return daemon::run(
+            py,
+            &mut backends,
+            opts,
+            *seconds,
+            *seed,
+            weights.as_deref(),
+            composer.as_ref(),
+        );
+    }
+    if let Some(Command::FuzzLayer {
+        layer,
+        seconds,
+        seed,
+        compose,
+    }) = &opts.command
+    {
+        return layer_fuzz::run(py, opts, layer, *seconds, *seed, compose);
+    }
+    if let Some(Command::Stateful { args }) = &opts.command {
+        let mut backends = runner::backends(py, opts);
+        return stateful::run(py, &mut backends, opts, args);
+    }
+    if let Some(Command::Schema { out, against }) = &opts.command {
+        let cases = runner::collect_cases(py, opts);
+        return json_shape::run(py, opts, cases, out.as_deref(), against.as_deref());
+    }
+    if let Some(Command::Nat64) = &opts.command {
+        return nat64::run(py, opts);
+    }
+    if let Some(plan) = plan {
+        let cases = plan.cases(py, opts);
+        if opts.leak_check {
+            return leak_check::run(py, opts, cases);
+        }
+        let mut backends = runner::backends(py, opts);
+        return runner::run(py, &mut backends, opts, cases);
+    }
+    if opts.leak_check {
+        let cases = runner::collect_cases(py, opts);
+        return leak_check::run(py, opts, cases);
+    }
+    if runner::has_cases(opts) {
+        let cases = runner::collect_cases(py, opts);
+        let mut backends = runner::backends(py, opts);
+        return runner::run(py, &mut backends, opts, cases);
+    }
+    sandbox::enter(opts);
+    let x: Vec<u8> = py
+        .eval(&format!("bytes({})", &opts.scapy_expr), None, None)
+        .unwrap()
+        .extract()
+        .unwrap();
+    {
+        use oside::protocols::all::ether;
+        use oside::*;
+        let pkt = Ether!().decode(&x).unwrap().0;
+        let j = serde_json::to_string(&pkt.layers).unwrap();
+        if opts.print_json {
+            println!("{}", j);
+        }
+        if opts.verify_json {
+            use std::io;
+            use std::io::Read;
+
+            let mut input = Vec::new();
+            let stdin = std::io::stdin();
+            let mut handle = stdin.lock();
+            handle.read_to_end(&mut input);
+            let input = String::from_utf8(input).unwrap();
+            if opts.verbose > 0 {
+                eprintln!("Input: {:?}", &input);
+            }
+            let j0: serde_json::Value = serde_json::from_str(&input).unwrap();
+            let j1: serde_json::Value = serde_json::from_str(&j).unwrap();
+            if j0 != j1 {
+                panic!(
+                    "JSON mismatch!\n === expected: {:#?}\n === obtained: {:#?}",
+                    &j0, &j1
+                );
+            }
+        }
+    }
+    // py.run("from scapy.main import interact; interact()", None, None);
+    0
+}
+
+/// The command line tool: parse the options and run what they ask for.
+pub fn main() {
+    let matches = Opts::command().get_matches();
+    let opts = Opts::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+    // allow to load the options, so far there is no good built-in way
+    let opts = if let Some(fname) = &opts.options_override {
+        if let Ok(data) = std::fs::read_to_string(&fname) {
+            let res = serde_json::from_str(&data);
+            let mut loaded: Opts = if res.is_ok() {
+                res.unwrap()
+            } else {
+                serde_yaml::from_str(&data).unwrap()
+            };
+            // the subcommand is not part of the file
+            loaded.command = opts.command;
+            loaded
+        } else {
+            opts
+        }
+    } else {
+        opts
+    };
+
+    let opts = if let Some(name) = &opts.profile {
+        let explicit =
+            |key: &str| matches.value_source(key) == Some(clap::ValueSource::CommandLine);
+        let mut with_profile =
+            profile::apply(&opts, name, explicit).unwrap_or_else(|e| panic!("{}", e));
+        with_profile.command = opts.command.clone();
+        with_profile
+    } else {
+        opts
+    };
+
+    if opts.generate_man {
+        clap_mangen::Man::new(Opts::command())
+            .render(&mut std::io::stdout())
+            .unwrap();
+        return;
+    }
+    if opts.print_schema {
+        print!("{}", schema::SCHEMA);
+        return;
+    }
+    sandbox::check_supported(&opts);
+    if let Some(Command::Completions { shell }) = &opts.command {
+        clap_complete::generate(
+            *shell,
+            &mut Opts::command(),
+            "oside-tests",
+            &mut std::io::stdout(),
+        );
+        return;
+    }
+    if let Some(Command::ListSuites) = &opts.command {
+        std::process::exit(suites::list());
+    }
+    if let Some(Command::Corpus {
+        action:
+            CorpusCommand::Convert {
+                from,
+                to,
+                from_format,
+                to_format,
+            },
+    }) = &opts.command
+    {
+        std::process::exit(corpus_convert::convert(from, *from_format, to, *to_format));
+    }
+    if let Some(Command::Corpus {
+        action: CorpusCommand::Minimize { from, to },
+    }) = &opts.command
+    {
+        // files can still be read and written in the sandbox
+        sandbox::enter(&opts);
+        std::process::exit(corpus_minimize::minimize(from, to, &opts.start_layer));
+    }
+    if let Some(Command::Package {
+        target,
+        profile,
+        no_python,
+        recording,
+        out,
+    }) = &opts.command
+    {
+        std::process::exit(package::run(&package::Package {
+            target: target.as_deref().unwrap_or(package::BUILD_TARGET),
+            profile,
+            no_python: *no_python,
+            recording: recording.as_deref(),
+            out: out.as_deref(),
+        }));
+    }
+
+    let mut opts = opts;
+    if let Some(Command::Suite { names }) = &opts.command {
+        opts.suite.extend(names.clone());
+        opts.command = None;
+    }
+    let plan = match &opts.command {
+        Some(Command::Plan { file })
+        | Some(Command::Nightly {
+            args: nightly::NightlyArgs { plan: file, .. },
+        }) => {
+            let plan = plan::Plan::load(file).unwrap_or_else(|e| panic!("{}", e));
+            plan.apply_start_layer(&mut opts)
+                .unwrap_or_else(|e| panic!("{}", e));
+            Some(plan)
+        }
+        _ => None,
+    };
+    workdir::apply(&mut opts).unwrap_or_else(|e| panic!("{}", e));
+    suites::apply_start_layer(&mut opts).unwrap_or_else(|e| panic!("{}", e));
+    runner::apply_link_type(&mut opts).unwrap_or_else(|e| panic!("{}", e));
+    runner::apply_pcap_start_layer(&mut opts).unwrap_or_else(|e| panic!("{}", e));
+    let nightly = match (opts.command.clone(), &plan) {
+        (Some(Command::Nightly { args }), Some(plan)) => {
+            Some(nightly::Nightly::prepare(&mut opts, &args, plan))
+        }
+        _ => None,
+    };
+    let mut bundle = bundle::Bundle::prepare(&mut opts);
+
+    // The following code runs on its own thread so the MainPythonInterpreter is created,
+    // used and destroyed in an orderly manner, before process exit, on one thread; a panic
+    // while running is caught there, so the interpreter is still finalized, and reported
+    // here.
+    let exit_code = std::thread::scope(|s| {
+        let python = std::thread::Builder::new()
+            .name("python".to_string())
+            .spawn_scoped(s, || {
+                // Load the default Python configuration as derived by the PyOxidizer config
+                // file used at build time.
+                let mut config: OxidizedPythonInterpreterConfig = default_python_config();
+                config.extra_extension_modules = Some(vec![pyembed::ExtensionModule {
+                    name: CString::new("string_sum").unwrap(),
+                    init_func: PyInit_string_sum,
+                }]);
+                if let Some(dir) = &opts.workdir {
+                    workdir::configure(&mut config, dir);
+                }
+
+                // Construct a new Python interpreter using that config, handling any errors
+                // from construction.
+                match MainPythonInterpreter::new(config) {
+                    Ok(interp) => {
+                        // And run it using the default run configuration as specified by the
+                        // configuration.
+                        //
+                        // This will either call `interp.py_runmain()` or
+                        // `interp.run_multiprocessing()`. If `interp.py_runmain()` is called,
+                        // the interpreter is guaranteed to be finalized.
+                        // let dict: pyo3::types::PyDict = Default::default();
+                        interp.with_gil(|py| isolate(|| run(py, &opts, plan.as_ref(), &mut bundle)))
+                        // interp.run()
+                    }
+                    Err(msg) => {
+                        eprintln!("error instantiating embedded Python interpreter: {}", msg);
+                        if let Some(Command::Doctor) = &opts.command {
+                            doctor::host();
+                        }
+                        1
+                    }
+                }
+            })
+            .expect("could not start the Python thread");
+        python.join().unwrap_or_else(|_| {
+            eprintln!("error: the Python thread panicked");
+            101
+        })
+    });
+
+    if let Some(nightly) = nightly {
+        if let Err(e) = nightly.finish(exit_code) {
+            eprintln!("could not record the nightly run: {}", e);
+            std::process::exit(1);
+        }
+    }
+    if let Some(bundle) = bundle {
+        if let Err(e) = bundle.write(&opts, exit_code) {
+            eprintln!("could not write bundle: {}", e);
+            std::process::exit(1);
+        }
+    }
+
+    // And exit the process according to code execution results.
+    std::process::exit(exit_code);
+}
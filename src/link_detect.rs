@@ -0,0 +1,227 @@
+//! Guessing the link type of frames that come without one, as from --raw
+//! dumps and pasted hex, so that both sides start decoding at the right
+//! layer.
+//!
+//! Each candidate layer has a few sanity checks of its header: version
+//! nibbles, EtherTypes, length fields agreeing with the frame, the SLL
+//! packet type and address length. A candidate whose first, defining check
+//! fails is out; the others score the share of their checks passed,
+//! averaged over the frames. The best score is the guess and its
+//! confidence, ties going to the candidate passing more checks.
+//! `--link-type` overrides the guess.
+
+/// EtherTypes seen often enough to count as evidence of one.
+const ETHERTYPES: &[u16] = &[
+    0x0800, 0x0806, 0x86dd, 0x8100, 0x88a8, 0x8847, 0x8848, 0x8863, 0x8864, 0x88cc, 0x88e5, 0x88f7,
+    0x9000,
+];
+
+/// Address families of IPv4 and IPv6 in BSD loopback headers.
+const FAMILIES: &[u32] = &[2, 24, 28, 30];
+
+/// A candidate's checks of a frame: whether each passed, with what it
+/// checks. The first one is the defining one.
+type Checks = fn(&[u8]) -> Vec<(bool, &'static str)>;
+
+fn be16(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn ethertype(data: &[u8], at: usize) -> bool {
+    be16(data, at).map_or(false, |t| ETHERTYPES.contains(&t))
+}
+
+/// Whether an IPv4 or IPv6 version nibble starts at `at`.
+fn ip_version(data: &[u8], at: usize) -> bool {
+    matches!(data.get(at).map(|b| b >> 4), Some(4) | Some(6))
+}
+
+fn ether(data: &[u8]) -> Vec<(bool, &'static str)> {
+    let typ = be16(data, 12);
+    vec![
+        (
+            ethertype(data, 12)
+                || typ.map_or(false, |t| {
+                    (3..=1500).contains(&t) && t as usize + 14 <= data.len()
+                }),
+            "an EtherType or 802.3 length at byte 12",
+        ),
+        (
+            data.len() >= 6 && (data[0] & 1 == 0 || data[..6] == [0xff; 6]),
+            "a unicast or broadcast destination",
+        ),
+        (
+            match typ {
+                Some(0x0800) | Some(0x86dd) => ip_version(data, 14),
+                _ => true,
+            },
+            "an IP version nibble after an IP EtherType",
+        ),
+    ]
+}
+
+fn ipv4(data: &[u8]) -> Vec<(bool, &'static str)> {
+    let ihl = data.first().map_or(0, |b| (b & 0x0f) as usize * 4);
+    vec![
+        (data.first().map(|b| b >> 4) == Some(4), "version 4"),
+        (
+            ihl >= 20 && ihl <= data.len(),
+            "a header length of 20 to 60 bytes",
+        ),
+        (
+            be16(data, 2).map_or(false, |len| len as usize == data.len()),
+            "a total length matching the frame",
+        ),
+        (
+            data.len() >= ihl && ihl >= 20 && checksum(&data[..ihl]) == 0,
+            "a valid header checksum",
+        ),
+    ]
+}
+
+fn ipv6(data: &[u8]) -> Vec<(bool, &'static str)> {
+    vec![
+        (data.first().map(|b| b >> 4) == Some(6), "version 6"),
+        (
+            be16(data, 4).map_or(false, |len| len as usize + 40 == data.len()),
+            "a payload length matching the frame",
+        ),
+        (
+            matches!(
+                data.get(6),
+                Some(0 | 6 | 17 | 43 | 44 | 50 | 51 | 58 | 59 | 60)
+            ),
+            "a common next header",
+        ),
+    ]
+}
+
+fn sll(data: &[u8]) -> Vec<(bool, &'static str)> {
+    vec![
+        (
+            be16(data, 0).map_or(false, |t| t <= 4) && ethertype(data, 14),
+            "a packet type and an EtherType at byte 14",
+        ),
+        (
+            matches!(be16(data, 2), Some(1 | 772 | 776 | 778 | 824 | 65534)),
+            "a known address type",
+        ),
+        (
+            be16(data, 4).map_or(false, |len| len <= 8),
+            "an address length of at most 8",
+        ),
+    ]
+}
+
+fn sll2(data: &[u8]) -> Vec<(bool, &'static str)> {
+    vec![
+        (
+            ethertype(data, 0) && be16(data, 2) == Some(0),
+            "an EtherType and a zero reserved field",
+        ),
+        (
+            data.get(10).map_or(false, |t| *t <= 4),
+            "a packet type of at most 4",
+        ),
+        (
+            data.get(11).map_or(false, |len| *len <= 8),
+            "an address length of at most 8",
+        ),
+        (
+            ip_version(data, 20) || be16(data, 0) != Some(0x0800),
+            "an IP header after the header",
+        ),
+    ]
+}
+
+fn loopback(data: &[u8]) -> Vec<(bool, &'static str)> {
+    let header: Option<[u8; 4]> = data.get(..4).and_then(|h| h.try_into().ok());
+    let family = header.map_or(false, |h| {
+        FAMILIES.contains(&u32::from_le_bytes(h)) || FAMILIES.contains(&u32::from_be_bytes(h))
+    });
+    vec![
+        (family, "an IPv4 or IPv6 address family"),
+        (ip_version(data, 4), "an IP version nibble after it"),
+    ]
+}
+
+/// The candidates, by --start-layer name.
+const CANDIDATES: &[(&str, Checks)] = &[
+    ("ether", ether),
+    ("ip", ipv4),
+    ("ipv6", ipv6),
+    ("cookedlinux", sll),
+    ("cookedlinuxv2", sll2),
+    ("loopback", loopback),
+];
+
+/// The ones' complement sum of a header, 0 if its checksum is right.
+fn checksum(header: &[u8]) -> u16 {
+    let mut sum: u32 = header
+        .chunks(2)
+        .map(|c| u16::from_be_bytes([c[0], *c.get(1).unwrap_or(&0)]) as u32)
+        .sum();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+pub struct Guess {
+    /// The layer, as for --start-layer.
+    pub layer: &'static str,
+    /// The share of the layer's checks passed, averaged over the frames.
+    pub confidence: f64,
+    /// The checks every frame passed.
+    pub evidence: Vec<&'static str>,
+}
+
+/// The likeliest layer the frames start at, if any candidate fits them.
+pub fn detect(frames: &[Vec<u8>]) -> Option<Guess> {
+    if frames.is_empty() {
+        return None;
+    }
+    let mut best: Option<Guess> = None;
+    for (layer, checks) in CANDIDATES {
+        let mut score = 0.0;
+        let mut always: Option<Vec<bool>> = None;
+        let mut names = vec![];
+        for frame in frames {
+            let results = checks(frame);
+            if !results[0].0 {
+                score = 0.0;
+                break;
+            }
+            let passed = results.iter().filter(|(ok, _)| *ok).count();
+            score += passed as f64 / results.len() as f64;
+            let oks: Vec<bool> = results.iter().map(|(ok, _)| *ok).collect();
+            always = Some(match always {
+                Some(always) => always.iter().zip(&oks).map(|(a, b)| *a && *b).collect(),
+                None => oks,
+            });
+            names = results.iter().map(|(_, name)| *name).collect();
+        }
+        let confidence = score / frames.len() as f64;
+        let evidence: Vec<&str> = names
+            .iter()
+            .zip(always.unwrap_or_default())
+            .filter(|(_, ok)| *ok)
+            .map(|(name, _)| *name)
+            .collect();
+        let better = match &best {
+            Some(best) => {
+                confidence > best.confidence
+                    || (confidence == best.confidence && evidence.len() > best.evidence.len())
+            }
+            None => confidence > 0.0,
+        };
+        if better {
+            best = Some(Guess {
+                layer,
+                confidence,
+                evidence,
+            });
+        }
+    }
+    best
+}
@@ -0,0 +1,186 @@
+//! `nat64`: NAT64 and 464XLAT translation scenarios.
+//!
+//! Each scenario is an IPv4 packet and its IPv6-translated sibling. Both are
+//! compared with the reference like any case, and then the mappings the
+//! scenario declares between them (embedded addresses, preserved ports and
+//! so on) are checked on oside's decode of the two.
+
+use crate::compare::{self, value_str, Layer};
+use crate::runner::{self, Case, Input, Runner};
+use crate::{decode, sandbox, scapy, Opts};
+use pyo3::Python;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+/// A relation between a field of the IPv4 packet and one of the IPv6
+/// packet, each given as `layer.field`.
+pub enum Mapping {
+    /// The fields have the same value.
+    Same(&'static str, &'static str),
+    /// The IPv6 address is the IPv4 one embedded in the /96 prefix given
+    /// (RFC 6052).
+    Embedded(&'static str, &'static str, &'static str),
+    /// The IPv4 value is the IPv6 one plus the offset, e.g. -1 for a TTL
+    /// decremented by the translator.
+    Offset(&'static str, &'static str, i64),
+}
+
+pub struct Scenario {
+    pub name: &'static str,
+    pub v4: &'static str,
+    pub v6: &'static str,
+    pub mappings: &'static [Mapping],
+}
+
+pub const SCENARIOS: &[Scenario] = &[
+    Scenario {
+        name: "nat64-udp",
+        v4: "Ether()/IP(src='192.0.2.100', dst='198.51.100.7', ttl=63)/UDP(sport=40000, dport=53)/Raw(load=b'query')",
+        v6: "Ether()/IPv6(src='2001:db8::10', dst='64:ff9b::c633:6407', hlim=64)/UDP(sport=40000, dport=53)/Raw(load=b'query')",
+        mappings: &[
+            Mapping::Embedded("ip.dst", "ipv6.dst", "64:ff9b::"),
+            Mapping::Offset("ip.ttl", "ipv6.hlim", -1),
+            Mapping::Same("udp.sport", "udp.sport"),
+            Mapping::Same("udp.dport", "udp.dport"),
+            Mapping::Same("raw.load", "raw.load"),
+        ],
+    },
+    Scenario {
+        name: "nat64-tcp",
+        v4: "Ether()/IP(src='192.0.2.100', dst='203.0.113.5', ttl=63)/TCP(sport=61000, dport=443, seq=1000, flags='S', window=65535)",
+        v6: "Ether()/IPv6(src='2001:db8::10', dst='64:ff9b::cb00:7105', hlim=64)/TCP(sport=50000, dport=443, seq=1000, flags='S', window=65535)",
+        mappings: &[
+            Mapping::Embedded("ip.dst", "ipv6.dst", "64:ff9b::"),
+            Mapping::Offset("ip.ttl", "ipv6.hlim", -1),
+            Mapping::Same("tcp.dport", "tcp.dport"),
+            Mapping::Same("tcp.seq", "tcp.seq"),
+            Mapping::Same("tcp.flags", "tcp.flags"),
+            Mapping::Same("tcp.window", "tcp.window"),
+        ],
+    },
+    Scenario {
+        name: "nat64-network-specific-prefix",
+        v4: "Ether()/IP(src='192.0.2.100', dst='198.51.100.7', ttl=63)/UDP(sport=40001, dport=123)",
+        v6: "Ether()/IPv6(src='2001:db8::10', dst='2001:db8:64::c633:6407', hlim=64)/UDP(sport=40001, dport=123)",
+        mappings: &[
+            Mapping::Embedded("ip.dst", "ipv6.dst", "2001:db8:64::"),
+            Mapping::Same("udp.dport", "udp.dport"),
+        ],
+    },
+    Scenario {
+        name: "464xlat-clat",
+        v4: "Ether()/IP(src='192.168.1.2', dst='203.0.113.5', ttl=64)/UDP(sport=5353, dport=53)/Raw(load=b'xlat')",
+        v6: "Ether()/IPv6(src='2001:db8:1:4646::c0a8:102', dst='64:ff9b::cb00:7105', hlim=64)/UDP(sport=5353, dport=53)/Raw(load=b'xlat')",
+        mappings: &[
+            Mapping::Embedded("ip.src", "ipv6.src", "2001:db8:1:4646::"),
+            Mapping::Embedded("ip.dst", "ipv6.dst", "64:ff9b::"),
+            Mapping::Offset("ip.ttl", "ipv6.hlim", 0),
+            Mapping::Same("udp.sport", "udp.sport"),
+            Mapping::Same("udp.dport", "udp.dport"),
+            Mapping::Same("raw.load", "raw.load"),
+        ],
+    },
+];
+
+/// The value of `layer.field` in the first layer of that name.
+fn lookup(layers: &[Layer], path: &str) -> Result<String, String> {
+    let (layer, field) = path.split_once('.').unwrap();
+    layers
+        .iter()
+        .find(|l| l.name == layer)
+        .and_then(|l| l.fields.get(field))
+        .map(value_str)
+        .ok_or_else(|| format!("no {} in oside's decode", path))
+}
+
+/// Check a mapping, describing how it does not hold.
+fn check(mapping: &Mapping, v4: &[Layer], v6: &[Layer]) -> Result<(), String> {
+    match mapping {
+        Mapping::Same(p4, p6) => {
+            let (a, b) = (lookup(v4, p4)?, lookup(v6, p6)?);
+            if a != b {
+                return Err(format!("{} is {} but {} is {}", p4, a, p6, b));
+            }
+        }
+        Mapping::Embedded(p4, p6, prefix) => {
+            let (a, b) = (lookup(v4, p4)?, lookup(v6, p6)?);
+            let addr4: Ipv4Addr = a
+                .parse()
+                .map_err(|_| format!("{} {} is not an address", p4, a))?;
+            let addr6: Ipv6Addr = b
+                .parse()
+                .map_err(|_| format!("{} {} is not an address", p6, b))?;
+            let prefix: Ipv6Addr = prefix.parse().unwrap();
+            let mut expected = prefix.octets();
+            expected[12..].copy_from_slice(&addr4.octets());
+            if addr6.octets() != expected {
+                return Err(format!(
+                    "{} is {} but {} embedded in {}/96 is {}",
+                    p6,
+                    addr6,
+                    p4,
+                    prefix,
+                    Ipv6Addr::from(expected)
+                ));
+            }
+        }
+        Mapping::Offset(p4, p6, offset) => {
+            let (a, b) = (lookup(v4, p4)?, lookup(v6, p6)?);
+            let (x, y): (i64, i64) = match (a.parse(), b.parse()) {
+                (Ok(x), Ok(y)) => (x, y),
+                _ => return Err(format!("{} {} or {} {} is not a number", p4, a, p6, b)),
+            };
+            if x != y + offset {
+                return Err(format!(
+                    "{} is {} but {} is {}, expected offset {}",
+                    p4, x, p6, y, offset
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// oside's decode of a scenario packet.
+fn oside_layers(py: Python, expr: &str) -> Result<Vec<Layer>, String> {
+    let data = scapy::build(py, expr)?;
+    decode::oside_json("ether", &data)
+        .map(|json| compare::layers(&json))
+        .ok_or_else(|| "oside failed to decode".to_string())
+}
+
+pub fn run(py: Python, opts: &Opts) -> i32 {
+    let mut opts = opts.clone();
+    opts.start_layer = "ether".to_string();
+    let mut backends = runner::backends(py, &opts);
+    let mut runner = Runner::new(py, &mut backends, &opts);
+    sandbox::enter(&opts);
+    let mut broken = 0;
+    for scenario in SCENARIOS {
+        for (side, expr) in [("v4", scenario.v4), ("v6", scenario.v6)] {
+            runner.case(&Case {
+                source: format!("nat64/{}#{}", scenario.name, side),
+                input: Input::Expr(expr.to_string()),
+                tag: Some(scenario.name.to_string()),
+                annotations: Default::default(),
+                timestamp: None,
+            });
+        }
+        let (v4, v6) = match (oside_layers(py, scenario.v4), oside_layers(py, scenario.v6)) {
+            (Ok(v4), Ok(v6)) => (v4, v6),
+            // already reported as a failing case
+            _ => continue,
+        };
+        for mapping in scenario.mappings {
+            if let Err(e) = check(mapping, &v4, &v6) {
+                println!("{}: mapping does not hold: {}", scenario.name, e);
+                broken += 1;
+            }
+        }
+    }
+    let code = runner.finish();
+    if broken > 0 {
+        eprintln!("{} scenario mappings do not hold", broken);
+        return 1;
+    }
+    code
+}
@@ -0,0 +1,261 @@
+//! `nightly <plan>`: run a plan as a scheduled job, e.g. from cron, keeping
+//! its history.
+//!
+//! Everything goes beneath a directory, `nightly` by default:
+//!
+//! - `results.jsonl`: the --results-db every run appends to, unless given;
+//! - `runs.jsonl`: one line per run, its plan, time, case counts and exit
+//!   code, from which the trend is computed;
+//! - a directory per run, named after its UTC start time, holding the
+//!   --save-failures corpus and the --summary-json summary.
+//!
+//! After the run, the directories of runs beyond the newest --keep-runs, or
+//! older than --keep-days, are removed, and with --keep-days the results
+//! database records older than that too. The run history itself is kept;
+//! it is small, and the trend, the pass rate over the last --trend runs of
+//! the plan, is printed from it.
+
+use crate::known_failures;
+use crate::plan::Plan;
+use crate::results;
+use crate::schema::SchemaVersion;
+use crate::Opts;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+#[derive(Debug, Clone, clap::Args)]
+pub struct NightlyArgs {
+    /// The YAML file of the plan to run
+    pub plan: String,
+    /// The directory the results database, run history and runs go beneath
+    #[clap(long, default_value = "nightly")]
+    dir: String,
+    /// Keep the directories of this many of the newest runs
+    #[clap(long, default_value = "30")]
+    keep_runs: usize,
+    /// Also remove run directories and results database records older than this many
+    /// days
+    #[clap(long)]
+    keep_days: Option<u64>,
+    /// Print the pass rate over this many of the plan's last runs
+    #[clap(long, default_value = "14")]
+    trend: usize,
+}
+
+/// One line of `runs.jsonl`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Run {
+    #[serde(default)]
+    pub schema_version: SchemaVersion,
+    /// The plan's name.
+    pub plan: String,
+    /// Seconds since the Unix epoch, when the run started.
+    pub time: u64,
+    /// The run's directory, beneath the nightly directory.
+    pub dir: String,
+    pub cases: usize,
+    pub passed: usize,
+    pub exit_code: i32,
+}
+
+impl Run {
+    fn pass_rate(&self) -> f64 {
+        if self.cases == 0 {
+            return 100.0;
+        }
+        self.passed as f64 * 100.0 / self.cases as f64
+    }
+}
+
+/// A nightly run under way.
+pub struct Nightly {
+    args: NightlyArgs,
+    dir: PathBuf,
+    plan: String,
+    time: u64,
+    run_dir: String,
+    /// The results database, and how many records it had before the run.
+    results_db: String,
+    records_before: usize,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// A time as `YYYY-MM-DD HH:MM` UTC.
+fn utc(secs: u64) -> String {
+    format!(
+        "{} {:02}:{:02}",
+        known_failures::utc_date(secs),
+        secs % 86400 / 3600,
+        secs % 3600 / 60
+    )
+}
+
+fn load_runs(path: &Path) -> Vec<Run> {
+    let data = match std::fs::read_to_string(path) {
+        Ok(data) => data,
+        Err(_) => return vec![],
+    };
+    data.lines()
+        .filter(|l| !l.trim().is_empty())
+        .filter_map(|l| match serde_json::from_str(l) {
+            Ok(run) => Some(run),
+            Err(e) => {
+                eprintln!("{}: skipping bad run: {}", path.display(), e);
+                None
+            }
+        })
+        .collect()
+}
+
+impl Nightly {
+    /// Make the run's directory and point the results database, failures
+    /// corpus and summary there, unless given.
+    pub fn prepare(opts: &mut Opts, args: &NightlyArgs, plan: &Plan) -> Nightly {
+        let mut dir = PathBuf::from(&args.dir);
+        if let (Some(workdir), true) = (&opts.workdir, dir.is_relative()) {
+            dir = Path::new(workdir).join(dir);
+        }
+        let time = now();
+        let run_dir = format!(
+            "{}T{:02}{:02}{:02}Z",
+            known_failures::utc_date(time),
+            time % 86400 / 3600,
+            time % 3600 / 60,
+            time % 60
+        );
+        let path = dir.join(&run_dir);
+        std::fs::create_dir_all(&path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+        let beneath = |name: &str| path.join(name).display().to_string();
+        let results_db = opts
+            .results_db
+            .get_or_insert_with(|| dir.join("results.jsonl").display().to_string())
+            .clone();
+        opts.save_failures
+            .get_or_insert_with(|| beneath("failures"));
+        opts.summary_json
+            .get_or_insert_with(|| beneath("summary.json"));
+        eprintln!("nightly {}: writing into {}", plan.name, path.display());
+        Nightly {
+            args: args.clone(),
+            records_before: results::load(&results_db).len(),
+            dir,
+            plan: plan.name.clone(),
+            time,
+            run_dir,
+            results_db,
+        }
+    }
+
+    /// Record the run in the history, prune old runs and print the trend.
+    pub fn finish(self, exit_code: i32) -> Result<(), String> {
+        let records = results::load(&self.results_db);
+        let ours = records.get(self.records_before..).unwrap_or(&[]);
+        let run = Run {
+            schema_version: SchemaVersion,
+            plan: self.plan.clone(),
+            time: self.time,
+            dir: self.run_dir.clone(),
+            cases: ours.len(),
+            passed: ours.iter().filter(|r| r.passed).count(),
+            exit_code,
+        };
+        let history = self.dir.join("runs.jsonl");
+        let err = |e: std::io::Error| format!("{}: {}", history.display(), e);
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&history)
+            .map_err(err)?;
+        writeln!(file, "{}", serde_json::to_string(&run).unwrap()).map_err(err)?;
+        let runs = load_runs(&history);
+        self.prune(&runs)?;
+        self.print_trend(&runs);
+        Ok(())
+    }
+
+    fn prune(&self, runs: &[Run]) -> Result<(), String> {
+        let cutoff = self
+            .args
+            .keep_days
+            .map(|days| self.time.saturating_sub(days * 86400));
+        let mut removed = 0;
+        for (i, run) in runs.iter().rev().enumerate() {
+            let old = i >= self.args.keep_runs || cutoff.map_or(false, |c| run.time < c);
+            let path = self.dir.join(&run.dir);
+            // the directory of this run is kept whatever the policy
+            if !old || run.dir == self.run_dir || run.dir.is_empty() || !path.is_dir() {
+                continue;
+            }
+            std::fs::remove_dir_all(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+            removed += 1;
+        }
+        if removed > 0 {
+            eprintln!("nightly: removed the directories of {} old runs", removed);
+        }
+        if let Some(cutoff) = cutoff {
+            let pruned = results::prune(&self.results_db, cutoff)
+                .map_err(|e| format!("{}: {}", self.results_db, e))?;
+            if pruned > 0 {
+                eprintln!(
+                    "nightly: removed {} results older than {} days from {}",
+                    pruned,
+                    self.args.keep_days.unwrap_or(0),
+                    self.results_db
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn print_trend(&self, runs: &[Run]) {
+        let ours: Vec<&Run> = runs.iter().filter(|r| r.plan == self.plan).collect();
+        let last = &ours[ours.len().saturating_sub(self.args.trend.max(1))..];
+        let current = match last.last() {
+            Some(run) => run,
+            None => return,
+        };
+        println!(
+            "nightly {}: {} of {} cases passed ({:.1}%), exit code {}",
+            self.plan,
+            current.passed,
+            current.cases,
+            current.pass_rate(),
+            current.exit_code
+        );
+        if last.len() < 2 {
+            return;
+        }
+        println!("pass rate over the last {} runs:", last.len());
+        for run in last {
+            println!(
+                "  {}  {:5.1}%  {}/{}",
+                utc(run.time),
+                run.pass_rate(),
+                run.passed,
+                run.cases
+            );
+        }
+        let previous = last[last.len() - 2].pass_rate();
+        let change = current.pass_rate() - previous;
+        let mean = last.iter().map(|r| r.pass_rate()).sum::<f64>() / last.len() as f64;
+        println!(
+            "  {} on the previous run, {:.1}% on average",
+            if change.abs() < 0.05 {
+                "unchanged".to_string()
+            } else if change > 0.0 {
+                format!("up {:.1} points", change)
+            } else {
+                format!("down {:.1} points", -change)
+            },
+            mean
+        );
+    }
+}
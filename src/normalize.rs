@@ -0,0 +1,362 @@
+//! Normalizers rewrite the layers of both sides of a comparison before
+//! they are compared, e.g. to strip fields one implementation fills
+//! differently by design.
+//!
+//! Numbers written as strings, enum names and flag names are brought into
+//! plain numbers unless `--keep-representation` says otherwise.
+//! `--ignore-field` gives a static list; anything conditional can implement
+//! `Normalizer` and be registered with `Runner::add_normalizer`.
+
+use crate::compare::{self, Layer};
+use crate::runner;
+use serde_json::Value;
+
+/// Which dissection a normalizer is looking at.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Side<'a> {
+    Oside,
+    /// A reference backend, by name.
+    Reference(&'a str),
+}
+
+pub trait Normalizer {
+    /// Rewrite the layers of one side in place.
+    fn normalize(&self, side: Side, layers: &mut Vec<Layer>);
+}
+
+/// Drops a field from both sides, for `--ignore-field LAYER.FIELD`; a
+/// layer of `*` matches every layer. `LAYER[N].FIELD` drops it from only
+/// one of the layers of that name, by its ordinal as in difference paths,
+/// e.g. `ip[1].ttl` for the inner IP layer of IP in IP.
+pub struct IgnoreField {
+    layer: String,
+    ordinal: Option<usize>,
+    field: String,
+}
+
+impl IgnoreField {
+    pub fn parse(spec: &str) -> Result<IgnoreField, String> {
+        let bad = || {
+            format!(
+                "bad --ignore-field {:?}, expected LAYER.FIELD or LAYER[N].FIELD",
+                spec
+            )
+        };
+        let (layer, field) = spec.split_once('.').ok_or_else(bad)?;
+        let (layer, ordinal) = match layer.strip_suffix(']').and_then(|l| l.split_once('[')) {
+            Some((name, n)) => (name, Some(n.parse::<usize>().map_err(|_| bad())?)),
+            None => (layer, None),
+        };
+        if layer.is_empty() || field.is_empty() || (layer == "*" && ordinal.is_some()) {
+            return Err(bad());
+        }
+        Ok(IgnoreField {
+            layer: layer.to_lowercase(),
+            ordinal,
+            field: field.to_string(),
+        })
+    }
+}
+
+impl Normalizer for IgnoreField {
+    fn normalize(&self, _side: Side, layers: &mut Vec<Layer>) {
+        let mut seen = 0;
+        for layer in layers.iter_mut() {
+            if self.layer != "*" && self.layer != layer.name {
+                continue;
+            }
+            if self.ordinal.map_or(true, |n| n == seen) {
+                layer.fields.remove(&self.field);
+            }
+            seen += 1;
+        }
+    }
+}
+
+/// The canonical forms of `--normalize-text`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextForm {
+    /// Lowercase hex of the bytes.
+    Bytes,
+    /// The bytes as UTF-8, invalid sequences replaced and control
+    /// characters written as `\xNN`.
+    Text,
+}
+
+impl TextForm {
+    pub fn parse(s: &str) -> Result<TextForm, String> {
+        match s {
+            "bytes" => Ok(TextForm::Bytes),
+            "text" => Ok(TextForm::Text),
+            _ => Err(format!(
+                "unknown --normalize-text form '{}', expected bytes or text",
+                s
+            )),
+        }
+    }
+}
+
+/// Brings every string or byte-array field value into one form, for
+/// `--normalize-text`, so that e.g. Scapy's hex and oside's list of byte
+/// values compare equal.
+pub struct TextEncoding(pub TextForm);
+
+/// The bytes a field value stands for: a non-empty array of byte values,
+/// a hex string (not made of decimal digits only, which are more likely a
+/// number), or else the UTF-8 of a string.
+pub(crate) fn field_bytes(v: &Value) -> Option<Vec<u8>> {
+    match compare::scalar(v) {
+        Value::Array(items) if !items.is_empty() => items
+            .iter()
+            .map(|i| i.as_u64().filter(|b| *b <= 255).map(|b| b as u8))
+            .collect(),
+        Value::String(s) => {
+            let hex = s.len() >= 2
+                && s.len() % 2 == 0
+                && s.bytes().all(|b| b.is_ascii_hexdigit())
+                && !s.bytes().all(|b| b.is_ascii_digit());
+            if hex {
+                (0..s.len())
+                    .step_by(2)
+                    .map(|i| u8::from_str_radix(&s[i..i + 2], 16).ok())
+                    .collect()
+            } else {
+                Some(s.as_bytes().to_vec())
+            }
+        }
+        _ => None,
+    }
+}
+
+impl Normalizer for TextEncoding {
+    fn normalize(&self, _side: Side, layers: &mut Vec<Layer>) {
+        for layer in layers.iter_mut() {
+            for value in layer.fields.values_mut() {
+                let bytes = match field_bytes(value) {
+                    Some(bytes) => bytes,
+                    None => continue,
+                };
+                *value = Value::String(match self.0 {
+                    TextForm::Bytes => bytes.iter().map(|b| format!("{:02x}", b)).collect(),
+                    TextForm::Text => String::from_utf8_lossy(&bytes)
+                        .chars()
+                        .map(|c| {
+                            if c.is_control() {
+                                format!("\\x{:02x}", c as u32)
+                            } else {
+                                c.to_string()
+                            }
+                        })
+                        .collect(),
+                });
+            }
+        }
+    }
+}
+
+/// Replaces byte strings longer than the limit with their length and hash,
+/// for `--elide-payloads`, so that large payloads neither dominate the
+/// output nor hide the differences that matter. Both sides get the same
+/// placeholder for the same bytes, however they represent them.
+pub struct ElidePayloads(pub usize);
+
+impl ElidePayloads {
+    fn elide(&self, value: &mut Value) {
+        if let Some(bytes) = field_bytes(value) {
+            if bytes.len() > self.0 {
+                let hash = runner::fnv1a(runner::FNV_OFFSET, &bytes);
+                *value = Value::String(format!("<{} bytes, fnv {:016x}>", bytes.len(), hash));
+            }
+            return;
+        }
+        match value {
+            Value::Array(items) => items.iter_mut().for_each(|v| self.elide(v)),
+            Value::Object(obj) => obj.values_mut().for_each(|v| self.elide(v)),
+            _ => {}
+        }
+    }
+}
+
+impl Normalizer for ElidePayloads {
+    fn normalize(&self, _side: Side, layers: &mut Vec<Layer>) {
+        for layer in layers.iter_mut() {
+            layer.fields.values_mut().for_each(|v| self.elide(v));
+        }
+    }
+}
+
+/// The kinds of values `Canonical` brings into one form, each left alone
+/// with `--keep-representation`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ValueClass {
+    /// Numbers written as strings, in decimal, hex (`0x`), octal (`0o`)
+    /// or binary (`0b`).
+    Numbers,
+    /// Names of the values of the fields in `ENUMS`.
+    Enums,
+    /// Flag names of the fields in `FLAGS`, e.g. Scapy's `SA` or `MF+DF`.
+    Flags,
+}
+
+impl ValueClass {
+    pub fn parse(s: &str) -> Result<ValueClass, String> {
+        match s {
+            "numbers" => Ok(ValueClass::Numbers),
+            "enums" => Ok(ValueClass::Enums),
+            "flags" => Ok(ValueClass::Flags),
+            _ => Err(format!(
+                "unknown --keep-representation class '{}', expected numbers, enums or flags",
+                s
+            )),
+        }
+    }
+}
+
+/// The names of the values of common enumerated fields, by layer and
+/// field. Names match ignoring case, `-` and `_`.
+const ENUMS: &[(&str, &str, &[(&str, u64)])] = &[
+    (
+        "ether",
+        "type",
+        &[
+            ("ipv4", 0x0800),
+            ("arp", 0x0806),
+            ("ipv6", 0x86dd),
+            ("vlan", 0x8100),
+            ("n_802_1q", 0x8100),
+            ("mpls", 0x8847),
+            ("lldp", 0x88cc),
+        ],
+    ),
+    ("ip", "proto", IP_PROTOCOLS),
+    ("ipv6", "nh", IP_PROTOCOLS),
+    (
+        "icmp",
+        "type",
+        &[
+            ("echo-reply", 0),
+            ("dest-unreach", 3),
+            ("redirect", 5),
+            ("echo-request", 8),
+            ("time-exceeded", 11),
+            ("parameter-problem", 12),
+        ],
+    ),
+    ("arp", "op", &[("who-has", 1), ("is-at", 2)]),
+];
+
+const IP_PROTOCOLS: &[(&str, u64)] = &[
+    ("hopopt", 0),
+    ("icmp", 1),
+    ("igmp", 2),
+    ("tcp", 6),
+    ("udp", 17),
+    ("routing", 43),
+    ("fragment", 44),
+    ("gre", 47),
+    ("esp", 50),
+    ("ah", 51),
+    ("icmpv6", 58),
+    ("ipv6-icmp", 58),
+    ("no-next-header", 59),
+    ("sctp", 132),
+];
+
+/// The names of the bits of flag fields, lowest first, by layer and field.
+const FLAGS: &[(&str, &str, &[&str])] = &[
+    ("tcp", "flags", &["F", "S", "R", "P", "A", "U", "E", "C"]),
+    ("ip", "flags", &["MF", "DF", "evil"]),
+];
+
+/// Brings numbers written as strings, enum names and flag names into
+/// plain numbers on both sides, so that differences only in how a value is
+/// written do not count as differences.
+pub struct Canonical {
+    classes: Vec<ValueClass>,
+}
+
+impl Canonical {
+    /// Canonicalize every class but the kept ones.
+    pub fn new(keep: &[ValueClass]) -> Canonical {
+        Canonical {
+            classes: [ValueClass::Numbers, ValueClass::Enums, ValueClass::Flags]
+                .into_iter()
+                .filter(|c| !keep.contains(c))
+                .collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.classes.is_empty()
+    }
+
+    fn canonical(&self, layer: &str, field: &str, s: &str) -> Option<u64> {
+        let mut out = None;
+        for class in &self.classes {
+            out = out.or_else(|| match class {
+                ValueClass::Numbers => number(s),
+                ValueClass::Enums => enum_value(layer, field, s),
+                ValueClass::Flags => flags_value(layer, field, s),
+            });
+        }
+        out
+    }
+}
+
+fn number(s: &str) -> Option<u64> {
+    let lower = s.to_lowercase();
+    let (digits, radix) = match lower.get(..2) {
+        Some("0x") => (&lower[2..], 16),
+        Some("0o") => (&lower[2..], 8),
+        Some("0b") => (&lower[2..], 2),
+        _ => (lower.as_str(), 10),
+    };
+    u64::from_str_radix(digits, radix).ok()
+}
+
+fn enum_value(layer: &str, field: &str, s: &str) -> Option<u64> {
+    let key = |name: &str| -> String {
+        name.chars()
+            .filter(|c| *c != '-' && *c != '_')
+            .collect::<String>()
+            .to_lowercase()
+    };
+    let (_, _, names) = ENUMS.iter().find(|(l, f, _)| *l == layer && *f == field)?;
+    names
+        .iter()
+        .find(|(name, _)| key(name) == key(s))
+        .map(|(_, value)| *value)
+}
+
+/// The bits of flag names joined by `+`, or of single-letter flag names
+/// written together.
+fn flags_value(layer: &str, field: &str, s: &str) -> Option<u64> {
+    let (_, _, names) = FLAGS.iter().find(|(l, f, _)| *l == layer && *f == field)?;
+    let bit = |name: &str| names.iter().position(|n| *n == name).map(|i| 1u64 << i);
+    let mut value = 0;
+    for token in s.split('+').filter(|t| !t.is_empty()) {
+        value |= match bit(token) {
+            Some(bit) => bit,
+            None => token
+                .chars()
+                .try_fold(0, |bits, c| Some(bits | bit(&c.to_string())?))?,
+        };
+    }
+    Some(value)
+}
+
+impl Normalizer for Canonical {
+    fn normalize(&self, _side: Side, layers: &mut Vec<Layer>) {
+        for layer in layers.iter_mut() {
+            for (field, value) in layer.fields.iter_mut() {
+                let canonical = match compare::scalar(value) {
+                    Value::String(s) => self.canonical(&layer.name, field, s),
+                    _ => None,
+                };
+                if let Some(n) = canonical {
+                    *value = Value::from(n);
+                }
+            }
+        }
+    }
+}
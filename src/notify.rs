@@ -0,0 +1,148 @@
+//! Notifications of new failures, for runs nobody watches: a JSON POST to
+//! `--notify-webhook` and/or a mail through the `notify_smtp` relay given
+//! in the options file.
+//!
+//! Only the first failure with each signature (see
+//! `compare::failure_signature`) in a run is notified.
+
+use crate::schema::SchemaVersion;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::time::{Duration, SystemTime};
+
+/// Mail settings. The relay is spoken to in plain SMTP, without TLS or
+/// authentication, so it should be a local or otherwise trusted one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Smtp {
+    /// The relay as host:port.
+    pub server: String,
+    pub from: String,
+    pub to: Vec<String>,
+}
+
+/// What is posted to the webhook.
+#[derive(Debug, Clone, Serialize)]
+pub struct Event {
+    pub schema_version: SchemaVersion,
+    pub signature: String,
+    pub case: String,
+    pub source: String,
+    pub message: String,
+    pub time: u64,
+}
+
+pub struct Notifier {
+    webhook: Option<String>,
+    smtp: Option<Smtp>,
+    seen: HashSet<String>,
+}
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+impl Notifier {
+    /// A notifier, or `None` if neither a webhook nor a relay is set.
+    pub fn new(webhook: Option<&str>, smtp: Option<&Smtp>) -> Option<Notifier> {
+        if webhook.is_none() && smtp.is_none() {
+            return None;
+        }
+        Some(Notifier {
+            webhook: webhook.map(str::to_string),
+            smtp: smtp.cloned(),
+            seen: HashSet::new(),
+        })
+    }
+
+    /// Notify a failure if its signature was not seen before in this run.
+    /// Delivery problems are only warned about.
+    pub fn failure(&mut self, case: &str, source: &str, message: &str, signature: &str) {
+        if !self.seen.insert(signature.to_string()) {
+            return;
+        }
+        let event = Event {
+            schema_version: SchemaVersion,
+            signature: signature.to_string(),
+            case: case.to_string(),
+            source: source.to_string(),
+            message: message.to_string(),
+            time: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+        };
+        if let Some(url) = &self.webhook {
+            if let Err(e) = post(url, &event) {
+                eprintln!("warning: could not notify {}: {}", url, e);
+            }
+        }
+        if let Some(smtp) = &self.smtp {
+            if let Err(e) = mail(smtp, &event) {
+                eprintln!("warning: could not mail through {}: {}", smtp.server, e);
+            }
+        }
+    }
+}
+
+fn post(url: &str, event: &Event) -> Result<(), String> {
+    let resp = minreq::post(url)
+        .with_timeout(TIMEOUT.as_secs())
+        .with_json(event)
+        .map_err(|e| e.to_string())?
+        .send()
+        .map_err(|e| e.to_string())?;
+    if !(200..300).contains(&resp.status_code) {
+        return Err(format!("HTTP {} {}", resp.status_code, resp.reason_phrase));
+    }
+    Ok(())
+}
+
+fn mail(smtp: &Smtp, event: &Event) -> Result<(), String> {
+    let stream = TcpStream::connect(&smtp.server).map_err(|e| e.to_string())?;
+    stream
+        .set_read_timeout(Some(TIMEOUT))
+        .map_err(|e| e.to_string())?;
+    let mut reader = BufReader::new(stream.try_clone().map_err(|e| e.to_string())?);
+    let mut writer = stream;
+    // Read a possibly multi-line reply and check its code class.
+    let mut expect = |class: char| -> Result<(), String> {
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).map_err(|e| e.to_string())?;
+            if !line.starts_with(class) {
+                return Err(format!("unexpected reply: {}", line.trim_end()));
+            }
+            if line.as_bytes().get(3) != Some(&b'-') {
+                return Ok(());
+            }
+        }
+    };
+    let mut send = |line: &str| writer.write_all(format!("{}\r\n", line).as_bytes());
+    let body = format!(
+        "From: {}\r\nTo: {}\r\nSubject: oside-tests: new failure {}\r\n\r\n{}\r\n\r\ncase {} ({})\r\nsignature: {}\r\n",
+        smtp.from,
+        smtp.to.join(", "),
+        event.case,
+        // dot-stuff lines starting with '.'
+        event.message.replace("\n.", "\n..").replace('\n', "\r\n"),
+        event.case,
+        event.source,
+        event.signature
+    );
+
+    expect('2')?;
+    send("HELO oside-tests").map_err(|e| e.to_string())?;
+    expect('2')?;
+    send(&format!("MAIL FROM:<{}>", smtp.from)).map_err(|e| e.to_string())?;
+    expect('2')?;
+    for to in &smtp.to {
+        send(&format!("RCPT TO:<{}>", to)).map_err(|e| e.to_string())?;
+        expect('2')?;
+    }
+    send("DATA").map_err(|e| e.to_string())?;
+    expect('3')?;
+    send(&format!("{}.", body)).map_err(|e| e.to_string())?;
+    expect('2')?;
+    send("QUIT").map_err(|e| e.to_string())?;
+    Ok(())
+}
@@ -0,0 +1,142 @@
+//! Capture artifacts of hardware offload, for `--tolerate-offload`.
+//! Packets captured on the host sending them are taken before the NIC
+//! finishes them:
+//!
+//! - with checksum offload, the IPv4 header checksum is left zero and the
+//!   TCP or UDP checksum holds only the pseudo-header sum;
+//! - with segmentation offload (TSO/GSO), one super-packet stands for the
+//!   segments it becomes, larger than the MTU and sometimes with a zero IP
+//!   length.
+//!
+//! Such frames are fixed up as the NIC would have before either side
+//! decodes them, and the cases tagged `offload`.
+
+/// The largest IP packet of a 1500-byte MTU.
+const MTU: usize = 1500;
+
+/// The ones' complement sum of 16-bit words, folded but not complemented.
+fn sum(data: &[u8], initial: u32) -> u16 {
+    let mut sum = initial;
+    for c in data.chunks(2) {
+        sum += u16::from_be_bytes([c[0], *c.get(1).unwrap_or(&0)]) as u32;
+    }
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    sum as u16
+}
+
+fn be16(data: &[u8], at: usize) -> u16 {
+    u16::from_be_bytes([data[at], data[at + 1]])
+}
+
+fn set16(data: &mut [u8], at: usize, v: u16) {
+    data[at..at + 2].copy_from_slice(&v.to_be_bytes());
+}
+
+/// Where the IP header starts, and its version, for frames starting at
+/// the layer.
+fn ip_start(start: &str, data: &[u8]) -> Option<(usize, u8)> {
+    let mut at = match start {
+        "ether" => 12,
+        "ip" | "ipv6" => return Some((0, data.first()? >> 4)),
+        _ => return None,
+    };
+    loop {
+        match be16(data.get(..at + 2)?, at) {
+            0x8100 | 0x88a8 => at += 4,
+            0x0800 => return Some((at + 2, 4)),
+            0x86dd => return Some((at + 2, 6)),
+            _ => return None,
+        }
+    }
+}
+
+/// Fix the TCP or UDP checksum at `l4` if it is the pseudo-header sum
+/// alone, or zero for TCP; what was fixed.
+fn fix_l4(data: &mut [u8], l4: usize, proto: u8, pseudo: u32) -> Option<&'static str> {
+    let (at, fixed) = match proto {
+        6 if data.len() >= l4 + 20 => (l4 + 16, "partial TCP checksum"),
+        17 if data.len() >= l4 + 8 => (l4 + 6, "partial UDP checksum"),
+        _ => return None,
+    };
+    let found = be16(data, at);
+    if proto == 17 && found == 0 {
+        // no checksum, which UDP over IPv4 allows
+        return None;
+    }
+    let len = (data.len() - l4) as u32;
+    let pseudo = pseudo + proto as u32 + len;
+    let partial = sum(&[], pseudo);
+    if found != 0 && found != partial && found != !partial {
+        return None;
+    }
+    set16(data, at, 0);
+    let full = !sum(&data[l4..], pseudo);
+    if full == found {
+        return None;
+    }
+    set16(
+        data,
+        at,
+        if proto == 17 && full == 0 {
+            0xffff
+        } else {
+            full
+        },
+    );
+    Some(fixed)
+}
+
+/// Fix the offload artifacts of a frame starting at the layer, returning
+/// what was found.
+pub fn normalize(start: &str, data: &mut [u8]) -> Vec<&'static str> {
+    let mut found = vec![];
+    let (ip, version) = match ip_start(start, data) {
+        Some(start) => start,
+        None => return found,
+    };
+    let len = data.len() - ip.min(data.len());
+    if len > MTU {
+        found.push("GSO super-packet");
+    }
+    match version {
+        4 if len >= 20 => {
+            let ihl = (data[ip] & 0x0f) as usize * 4;
+            if ihl < 20 || ihl > len {
+                return found;
+            }
+            if be16(data, ip + 2) == 0 && len <= 0xffff {
+                set16(data, ip + 2, len as u16);
+                found.push("zero IP total length");
+            }
+            let total = (be16(data, ip + 2) as usize).clamp(ihl, len);
+            if be16(data, ip + 10) == 0 {
+                let checksum = !sum(&data[ip..ip + ihl], 0);
+                if checksum != 0 {
+                    set16(data, ip + 10, checksum);
+                    found.push("zero IP checksum");
+                }
+            }
+            let fragment = be16(data, ip + 6) & 0x3fff != 0;
+            if !fragment {
+                let pseudo = sum(&data[ip + 12..ip + 20], 0) as u32;
+                let end = ip + total;
+                let proto = data[ip + 9];
+                found.extend(fix_l4(&mut data[..end], ip + ihl, proto, pseudo));
+            }
+        }
+        6 if len >= 40 => {
+            if be16(data, ip + 4) == 0 && len - 40 <= 0xffff && len > 40 {
+                set16(data, ip + 4, (len - 40) as u16);
+                found.push("zero IPv6 payload length");
+            }
+            let end = ip + 40 + (be16(data, ip + 4) as usize).min(len - 40);
+            let pseudo = sum(&data[ip + 8..ip + 40], 0) as u32;
+            let proto = data[ip + 6];
+            found.extend(fix_l4(&mut data[..end], ip + 40, proto, pseudo));
+        }
+        _ => {}
+    }
+    found
+}
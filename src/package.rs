@@ -0,0 +1,131 @@
+//! `package`: put a built binary and the Python resources it needs into a
+//! relocatable `.tar.gz`, to copy to test machines.
+//!
+//! The binary is looked up where cargo puts it, `target/<triple>/<profile>/`
+//! or, for the host's own target, `target/<profile>/`; the resources in the
+//! `lib/` directory next to it (where `make` moves them) or in PyOxidizer's
+//! `build/<triple>/<profile>/install/lib`. The archive holds, under a
+//! directory named after the version and target:
+//!
+//! - the binary, with `lib/` next to it where it looks for it
+//! - `recording.json`: the --recording, if given, for replaying recorded
+//!   reference output on machines without Scapy
+//!
+//! Builds without embedded resources (build-mode-standalone) use the
+//! machine's own Python; package them with --no-python.
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::path::{Path, PathBuf};
+
+/// The target this binary was built for, the default package target.
+pub const BUILD_TARGET: &str = env!("BUILD_TARGET");
+
+pub struct Package<'a> {
+    pub target: &'a str,
+    pub profile: &'a str,
+    pub no_python: bool,
+    pub recording: Option<&'a str>,
+    pub out: Option<&'a str>,
+}
+
+fn binary_name(target: &str) -> String {
+    if target.contains("windows") {
+        "oside-tests.exe".to_string()
+    } else {
+        "oside-tests".to_string()
+    }
+}
+
+/// The first of the paths that exists.
+fn first_existing(paths: Vec<PathBuf>) -> Option<PathBuf> {
+    paths.into_iter().find(|p| p.exists())
+}
+
+impl<'a> Package<'a> {
+    fn binary(&self) -> Result<PathBuf, String> {
+        let name = binary_name(self.target);
+        let mut paths = vec![Path::new("target")
+            .join(self.target)
+            .join(self.profile)
+            .join(&name)];
+        if self.target == BUILD_TARGET {
+            paths.push(Path::new("target").join(self.profile).join(&name));
+        }
+        first_existing(paths.clone()).ok_or_else(|| {
+            format!(
+                "no {} binary for {}; looked for {}; build it with `cargo build --target {}`",
+                self.profile,
+                self.target,
+                paths
+                    .iter()
+                    .map(|p| p.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(" and "),
+                self.target
+            )
+        })
+    }
+
+    fn resources(&self, binary: &Path) -> Result<PathBuf, String> {
+        let paths = vec![
+            binary.with_file_name("lib"),
+            Path::new("build")
+                .join(self.target)
+                .join(self.profile)
+                .join("install")
+                .join("lib"),
+        ];
+        first_existing(paths).ok_or_else(|| {
+            format!(
+                "no Python resources (lib/) for {}; build them with `pyoxidizer build --target-triple {}`, or give --no-python for a standalone build",
+                self.target, self.target
+            )
+        })
+    }
+
+    /// Write the archive, returning its path.
+    pub fn write(&self) -> Result<String, String> {
+        let binary = self.binary()?;
+        let resources = if self.no_python {
+            None
+        } else {
+            Some(self.resources(&binary)?)
+        };
+        let name = format!("oside-tests-{}-{}", env!("CARGO_PKG_VERSION"), self.target);
+        let path = self
+            .out
+            .map(|o| o.to_string())
+            .unwrap_or_else(|| format!("{}.tar.gz", name));
+        let err = |e: std::io::Error| format!("{}: {}", path, e);
+
+        let file = std::fs::File::create(&path).map_err(err)?;
+        let mut tar = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+        tar.append_path_with_name(&binary, format!("{}/{}", name, binary_name(self.target)))
+            .map_err(err)?;
+        if let Some(lib) = &resources {
+            tar.append_dir_all(format!("{}/lib", name), lib)
+                .map_err(err)?;
+        }
+        if let Some(recording) = self.recording {
+            tar.append_path_with_name(recording, format!("{}/recording.json", name))
+                .map_err(|e| format!("{}: {}", recording, e))?;
+        }
+        tar.into_inner().and_then(|gz| gz.finish()).map_err(err)?;
+        Ok(path)
+    }
+}
+
+/// Run the `package` subcommand, returning the process exit code.
+pub fn run(package: &Package) -> i32 {
+    match package.write() {
+        Ok(path) => {
+            println!("wrote {}", path);
+            0
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        }
+    }
+}
@@ -0,0 +1,632 @@
+//! Minimal reader and writer for the pcap and pcapng capture file formats.
+//!
+//! Only what is needed to pull the frames out of a capture is implemented:
+//! classic pcap in either byte order with micro- or nanosecond timestamps,
+//! and the section header, interface description, enhanced and simple packet
+//! blocks of pcapng. Everything else is skipped. Frames are written as
+//! classic pcap, or as pcapng where they carry packet comments.
+//!
+//! Captures compressed with gzip, zstd or xz, as archived captures usually
+//! are, are decompressed as they are read, whatever they are named.
+
+use std::io::Read;
+use std::time::Duration;
+
+/// Link type of BSD loopback: a 4-byte address family in the byte order of
+/// the capturing host (DLT_NULL), as on macOS lo0.
+pub const LINKTYPE_NULL: u32 = 0;
+
+/// Link type of Ethernet frames (DLT_EN10MB).
+pub const LINKTYPE_ETHERNET: u32 = 1;
+
+/// Link type of PPP packets, starting at the protocol field.
+pub const LINKTYPE_PPP: u32 = 9;
+
+/// Link type of PPP in HDLC-like framing, as on serial lines (RFC 1662).
+pub const LINKTYPE_PPP_HDLC: u32 = 50;
+
+/// Link type of bare IPv4 or IPv6 packets (DLT_RAW).
+pub const LINKTYPE_RAW: u32 = 101;
+
+/// Link type of BSD loopback with the address family in network byte
+/// order (DLT_LOOP), as on OpenBSD.
+pub const LINKTYPE_LOOP: u32 = 108;
+
+/// Link type of Cisco HDLC frames.
+pub const LINKTYPE_C_HDLC: u32 = 104;
+
+/// Link type of Frame Relay frames, starting at the Q.922 address.
+pub const LINKTYPE_FRELAY: u32 = 107;
+
+/// Link type of Linux cooked captures, v1 (`tcpdump -i any` before 4.99).
+pub const LINKTYPE_LINUX_SLL: u32 = 113;
+
+/// Link type of Linux cooked captures, v2, adding the interface index.
+pub const LINKTYPE_LINUX_SLL2: u32 = 276;
+
+/// Link type of Bluetooth HCI packets with the UART (H4) packet type byte.
+pub const LINKTYPE_BLUETOOTH_HCI_H4: u32 = 187;
+
+/// Link type of H4 packets after a 4-byte direction header.
+pub const LINKTYPE_BLUETOOTH_HCI_H4_WITH_PHDR: u32 = 201;
+
+/// Link type of Linux usbmon captures, with the 48-byte header.
+pub const LINKTYPE_USB_LINUX: u32 = 189;
+
+/// Link type of Linux usbmon captures, with the 64-byte memory-mapped
+/// header.
+pub const LINKTYPE_USB_LINUX_MMAPPED: u32 = 220;
+
+/// Link type of USBPcap captures on Windows.
+pub const LINKTYPE_USBPCAP: u32 = 249;
+
+/// The link types whose frames can be compared, and the layer, as for
+/// --start-layer, their frames start at. Not every layer is known to both
+/// sides; captures starting at one either lacks are left out.
+pub const LINKTYPES: &[(u32, &str)] = &[
+    (LINKTYPE_ETHERNET, "ether"),
+    (LINKTYPE_PPP, "ppp"),
+    (LINKTYPE_PPP_HDLC, "hdlc"),
+    (LINKTYPE_C_HDLC, "chdlc"),
+    (LINKTYPE_FRELAY, "frame_relay"),
+    (LINKTYPE_LINUX_SLL, "cookedlinux"),
+    (LINKTYPE_LINUX_SLL2, "cookedlinuxv2"),
+    (LINKTYPE_BLUETOOTH_HCI_H4, "hci_hdr"),
+    (LINKTYPE_BLUETOOTH_HCI_H4_WITH_PHDR, "hci_phdr_hdr"),
+    (LINKTYPE_USB_LINUX, "usb_linux"),
+    (LINKTYPE_USB_LINUX_MMAPPED, "usb_linux_mmapped"),
+    (LINKTYPE_USBPCAP, "usbpcap"),
+];
+
+/// The layer frames of the link type start at.
+pub fn start_layer(linktype: u32) -> Option<&'static str> {
+    LINKTYPES
+        .iter()
+        .find(|(l, _)| *l == linktype)
+        .map(|(_, start)| *start)
+}
+
+/// The link type of frames starting at the layer.
+pub fn linktype(start: &str) -> Option<u32> {
+    LINKTYPES
+        .iter()
+        .find(|(_, s)| *s == start)
+        .map(|(linktype, _)| *linktype)
+}
+
+/// The loopback address families of IPv4 and of IPv6, which differs
+/// between the BSDs (NetBSD and OpenBSD, FreeBSD, macOS).
+const AF_INET: u32 = 2;
+const AF_INET6: &[u32] = &[24, 28, 30];
+
+/// A loopback frame as an Ethernet frame: its address family header
+/// replaced by an Ethernet header with zero addresses and the EtherType of
+/// the family. `None` for families other than IPv4 and IPv6.
+pub fn loopback_to_ether(linktype: u32, data: &[u8]) -> Option<Vec<u8>> {
+    let header: [u8; 4] = data.get(..4)?.try_into().ok()?;
+    let known = |family: u32| family == AF_INET || AF_INET6.contains(&family);
+    let family = match linktype {
+        LINKTYPE_LOOP => u32::from_be_bytes(header),
+        // the capturing host's byte order is not recorded, so take the one
+        // giving a known family
+        LINKTYPE_NULL if known(u32::from_le_bytes(header)) => u32::from_le_bytes(header),
+        LINKTYPE_NULL => u32::from_be_bytes(header),
+        _ => return None,
+    };
+    let ethertype: u16 = if family == AF_INET {
+        0x0800
+    } else if AF_INET6.contains(&family) {
+        0x86dd
+    } else {
+        return None;
+    };
+    let mut out = vec![0; 12];
+    out.extend_from_slice(&ethertype.to_be_bytes());
+    out.extend_from_slice(&data[4..]);
+    Some(out)
+}
+
+const PCAP_MAGIC_USEC: u32 = 0xa1b2c3d4;
+const PCAP_MAGIC_NSEC: u32 = 0xa1b23c4d;
+const PCAPNG_SHB: u32 = 0x0a0d0d0a;
+const PCAPNG_BYTE_ORDER_MAGIC: u32 = 0x1a2b3c4d;
+const PCAPNG_IDB: u32 = 0x00000001;
+const PCAPNG_SPB: u32 = 0x00000003;
+const PCAPNG_EPB: u32 = 0x00000006;
+
+/// A single frame read from a capture file.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    /// 1-based position of the frame in the file, as shown by Wireshark.
+    pub index: usize,
+    /// Capture timestamp, relative to the Unix epoch.
+    pub timestamp: Duration,
+    /// Link type of the interface the frame was captured on.
+    pub linktype: u32,
+    /// Length of the frame on the wire, which may exceed `data.len()`.
+    pub orig_len: usize,
+    pub data: Vec<u8>,
+}
+
+#[derive(Clone, Copy)]
+struct Endian(bool);
+
+impl Endian {
+    fn u16(&self, b: &[u8]) -> u16 {
+        let v = [b[0], b[1]];
+        if self.0 {
+            u16::from_be_bytes(v)
+        } else {
+            u16::from_le_bytes(v)
+        }
+    }
+    fn u32(&self, b: &[u8]) -> u32 {
+        let v = [b[0], b[1], b[2], b[3]];
+        if self.0 {
+            u32::from_be_bytes(v)
+        } else {
+            u32::from_le_bytes(v)
+        }
+    }
+}
+
+/// The compression formats captures are read in, by their magic.
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+const XZ_MAGIC: &[u8] = &[0xfd, b'7', b'z', b'X', b'Z', 0x00];
+
+/// The file extensions of those compression formats.
+pub const COMPRESSED_EXTENSIONS: &[&str] = &["gz", "zst", "xz"];
+
+/// The contents of a file, decompressed if compressed.
+fn decompress(data: Vec<u8>) -> std::io::Result<Vec<u8>> {
+    let mut out = vec![];
+    if data.starts_with(GZIP_MAGIC) {
+        // concatenated gzip members, as `cat a.gz b.gz` makes, are one file
+        flate2::read::MultiGzDecoder::new(&data[..]).read_to_end(&mut out)?;
+    } else if data.starts_with(ZSTD_MAGIC) {
+        zstd::stream::read::Decoder::new(&data[..])?.read_to_end(&mut out)?;
+    } else if data.starts_with(XZ_MAGIC) {
+        xz2::read::XzDecoder::new_multi_decoder(&data[..]).read_to_end(&mut out)?;
+    } else {
+        return Ok(data);
+    }
+    Ok(out)
+}
+
+/// Read all frames from a pcap or pcapng file, compressed or not.
+pub fn read_file(path: &str) -> Result<Vec<Frame>, String> {
+    let data = std::fs::read(path)
+        .and_then(decompress)
+        .map_err(|e| format!("{}: {}", path, e))?;
+    parse(&data).map_err(|e| format!("{}: {}", path, e))
+}
+
+/// Parse the contents of a pcap or pcapng file.
+pub fn parse(data: &[u8]) -> Result<Vec<Frame>, String> {
+    if data.len() < 4 {
+        return Err("file too short".to_string());
+    }
+    let magic_le = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+    let magic_be = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+    if magic_le == PCAPNG_SHB {
+        parse_pcapng(data)
+    } else if magic_le == PCAP_MAGIC_USEC || magic_le == PCAP_MAGIC_NSEC {
+        parse_pcap(data, Endian(false), magic_le == PCAP_MAGIC_NSEC)
+    } else if magic_be == PCAP_MAGIC_USEC || magic_be == PCAP_MAGIC_NSEC {
+        parse_pcap(data, Endian(true), magic_be == PCAP_MAGIC_NSEC)
+    } else {
+        Err(format!("unknown capture file magic {:08x}", magic_be))
+    }
+}
+
+fn parse_pcap(data: &[u8], e: Endian, nsec: bool) -> Result<Vec<Frame>, String> {
+    if data.len() < 24 {
+        return Err("truncated pcap file header".to_string());
+    }
+    let linktype = e.u32(&data[20..24]) & 0x0fff_ffff;
+    let mut frames = vec![];
+    let mut pos = 24;
+    while pos + 16 <= data.len() {
+        let hdr = &data[pos..pos + 16];
+        let secs = e.u32(&hdr[0..4]) as u64;
+        let frac = e.u32(&hdr[4..8]);
+        let incl_len = e.u32(&hdr[8..12]) as usize;
+        let orig_len = e.u32(&hdr[12..16]) as usize;
+        pos += 16;
+        if pos + incl_len > data.len() {
+            return Err(format!("truncated frame {}", frames.len() + 1));
+        }
+        let nanos = if nsec {
+            frac
+        } else {
+            frac.saturating_mul(1000)
+        };
+        frames.push(Frame {
+            index: frames.len() + 1,
+            timestamp: Duration::new(secs, 0) + Duration::from_nanos(nanos as u64),
+            linktype,
+            orig_len,
+            data: data[pos..pos + incl_len].to_vec(),
+        });
+        pos += incl_len;
+    }
+    Ok(frames)
+}
+
+/// Per-interface state from a pcapng interface description block.
+struct Interface {
+    linktype: u32,
+    /// Timestamp units per second.
+    ts_resolution: u64,
+}
+
+fn parse_pcapng(data: &[u8]) -> Result<Vec<Frame>, String> {
+    let mut frames = vec![];
+    let mut interfaces: Vec<Interface> = vec![];
+    let mut e = Endian(false);
+    let mut pos = 0;
+    while pos + 12 <= data.len() {
+        let block_type = e.u32(&data[pos..pos + 4]);
+        if block_type == PCAPNG_SHB {
+            let bom = &data[pos + 8..pos + 12];
+            e = if u32::from_be_bytes([bom[0], bom[1], bom[2], bom[3]]) == PCAPNG_BYTE_ORDER_MAGIC {
+                Endian(true)
+            } else {
+                Endian(false)
+            };
+            interfaces.clear();
+        }
+        let block_len = e.u32(&data[pos + 4..pos + 8]) as usize;
+        if block_len < 12 || pos + block_len > data.len() {
+            return Err(format!("bad pcapng block length {} at {}", block_len, pos));
+        }
+        let body = &data[pos + 8..pos + block_len - 4];
+        match block_type {
+            PCAPNG_IDB if body.len() >= 8 => {
+                interfaces.push(Interface {
+                    linktype: e.u16(&body[0..2]) as u32,
+                    ts_resolution: idb_ts_resolution(&body[8..], e),
+                });
+            }
+            PCAPNG_EPB if body.len() >= 20 => {
+                let if_id = e.u32(&body[0..4]) as usize;
+                let ts = ((e.u32(&body[4..8]) as u64) << 32) | e.u32(&body[8..12]) as u64;
+                let cap_len = e.u32(&body[12..16]) as usize;
+                let orig_len = e.u32(&body[16..20]) as usize;
+                if 20 + cap_len > body.len() {
+                    return Err(format!("truncated frame {}", frames.len() + 1));
+                }
+                let iface = interfaces
+                    .get(if_id)
+                    .ok_or_else(|| format!("frame refers to unknown interface {}", if_id))?;
+                let res = iface.ts_resolution;
+                // in u128, as resolutions finer than about 2^-34 s overflow
+                // the nanoseconds in u64
+                let nanos = (ts % res) as u128 * 1_000_000_000 / res as u128;
+                frames.push(Frame {
+                    index: frames.len() + 1,
+                    timestamp: Duration::new(ts / res, 0) + Duration::from_nanos(nanos as u64),
+                    linktype: iface.linktype,
+                    orig_len,
+                    data: body[20..20 + cap_len].to_vec(),
+                });
+            }
+            PCAPNG_SPB if body.len() >= 4 => {
+                let orig_len = e.u32(&body[0..4]) as usize;
+                let cap_len = orig_len.min(body.len() - 4);
+                let linktype = interfaces
+                    .first()
+                    .map(|i| i.linktype)
+                    .ok_or("simple packet block before any interface description")?;
+                frames.push(Frame {
+                    index: frames.len() + 1,
+                    timestamp: Duration::default(),
+                    linktype,
+                    orig_len,
+                    data: body[4..4 + cap_len].to_vec(),
+                });
+            }
+            _ => {}
+        }
+        pos += block_len;
+    }
+    Ok(frames)
+}
+
+/// Extract the timestamp resolution from the if_tsresol option, if any.
+fn idb_ts_resolution(mut opts: &[u8], e: Endian) -> u64 {
+    while opts.len() >= 4 {
+        let code = e.u16(&opts[0..2]);
+        let len = e.u16(&opts[2..4]) as usize;
+        if code == 0 || opts.len() < 4 + len {
+            break;
+        }
+        if code == 9 && len >= 1 {
+            let v = opts[4];
+            let exp = (v & 0x7f) as u32;
+            return if v & 0x80 != 0 {
+                2u64.saturating_pow(exp)
+            } else {
+                10u64.saturating_pow(exp)
+            };
+        }
+        opts = opts.get(4 + ((len + 3) & !3)..).unwrap_or(&[]);
+    }
+    1_000_000
+}
+
+/// Writer of classic pcap files with microsecond timestamps.
+pub struct Writer<W: std::io::Write> {
+    out: W,
+}
+
+impl<W: std::io::Write> Writer<W> {
+    /// Write the file header; all frames share `linktype`.
+    pub fn new(mut out: W, linktype: u32) -> std::io::Result<Writer<W>> {
+        let mut hdr = Vec::with_capacity(24);
+        hdr.extend_from_slice(&PCAP_MAGIC_USEC.to_le_bytes());
+        hdr.extend_from_slice(&2u16.to_le_bytes());
+        hdr.extend_from_slice(&4u16.to_le_bytes());
+        hdr.extend_from_slice(&0i32.to_le_bytes());
+        hdr.extend_from_slice(&0u32.to_le_bytes());
+        hdr.extend_from_slice(&65535u32.to_le_bytes());
+        hdr.extend_from_slice(&linktype.to_le_bytes());
+        out.write_all(&hdr)?;
+        Ok(Writer { out })
+    }
+
+    pub fn write_frame(&mut self, timestamp: Duration, data: &[u8]) -> std::io::Result<()> {
+        let mut hdr = Vec::with_capacity(16);
+        hdr.extend_from_slice(&(timestamp.as_secs() as u32).to_le_bytes());
+        hdr.extend_from_slice(&timestamp.subsec_micros().to_le_bytes());
+        hdr.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        hdr.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        self.out.write_all(&hdr)?;
+        self.out.write_all(data)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+/// Writer of pcapng files with one interface and microsecond timestamps,
+/// whose frames can carry a comment that Wireshark shows with them.
+pub struct NgWriter<W: std::io::Write> {
+    out: W,
+}
+
+fn pcapng_block(block_type: u32, body: &[u8]) -> Vec<u8> {
+    let len = (12 + body.len()) as u32;
+    let mut block = Vec::with_capacity(len as usize);
+    block.extend_from_slice(&block_type.to_le_bytes());
+    block.extend_from_slice(&len.to_le_bytes());
+    block.extend_from_slice(body);
+    block.extend_from_slice(&len.to_le_bytes());
+    block
+}
+
+/// The pcapng opt_comment option code.
+const PCAPNG_OPT_COMMENT: u16 = 1;
+
+impl<W: std::io::Write> NgWriter<W> {
+    /// Write the section header and the interface description; all frames
+    /// share `linktype`.
+    pub fn new(mut out: W, linktype: u32) -> std::io::Result<NgWriter<W>> {
+        let mut shb = vec![];
+        shb.extend_from_slice(&PCAPNG_BYTE_ORDER_MAGIC.to_le_bytes());
+        shb.extend_from_slice(&1u16.to_le_bytes());
+        shb.extend_from_slice(&0u16.to_le_bytes());
+        // section length not given
+        shb.extend_from_slice(&(-1i64).to_le_bytes());
+        out.write_all(&pcapng_block(PCAPNG_SHB, &shb))?;
+        let mut idb = vec![];
+        idb.extend_from_slice(&(linktype as u16).to_le_bytes());
+        idb.extend_from_slice(&0u16.to_le_bytes());
+        idb.extend_from_slice(&0u32.to_le_bytes());
+        out.write_all(&pcapng_block(PCAPNG_IDB, &idb))?;
+        Ok(NgWriter { out })
+    }
+
+    /// Write a frame as an enhanced packet block, with the comment if any.
+    pub fn write_frame(
+        &mut self,
+        timestamp: Duration,
+        data: &[u8],
+        comment: Option<&str>,
+    ) -> std::io::Result<()> {
+        let ts = timestamp.as_micros() as u64;
+        let mut epb = Vec::with_capacity(20 + data.len() + 4);
+        epb.extend_from_slice(&0u32.to_le_bytes());
+        epb.extend_from_slice(&((ts >> 32) as u32).to_le_bytes());
+        epb.extend_from_slice(&(ts as u32).to_le_bytes());
+        epb.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        epb.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        epb.extend_from_slice(data);
+        epb.resize((epb.len() + 3) & !3, 0);
+        if let Some(comment) = comment {
+            // an option's length is 16 bits; longer comments are cut short
+            let mut end = comment.len().min(0xfff0);
+            while !comment.is_char_boundary(end) {
+                end -= 1;
+            }
+            epb.extend_from_slice(&PCAPNG_OPT_COMMENT.to_le_bytes());
+            epb.extend_from_slice(&(end as u16).to_le_bytes());
+            epb.extend_from_slice(&comment.as_bytes()[..end]);
+            epb.resize((epb.len() + 3) & !3, 0);
+            // opt_endofopt
+            epb.extend_from_slice(&[0; 4]);
+        }
+        self.out.write_all(&pcapng_block(PCAPNG_EPB, &epb))
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A little-endian pcapng section with one interface whose if_tsresol
+    /// option is `tsresol`, followed by `blocks`.
+    fn pcapng(tsresol: Option<u8>, blocks: &[Vec<u8>]) -> Vec<u8> {
+        let mut shb = vec![];
+        shb.extend_from_slice(&PCAPNG_BYTE_ORDER_MAGIC.to_le_bytes());
+        shb.extend_from_slice(&1u16.to_le_bytes());
+        shb.extend_from_slice(&0u16.to_le_bytes());
+        shb.extend_from_slice(&(-1i64).to_le_bytes());
+        let mut idb = vec![];
+        idb.extend_from_slice(&(LINKTYPE_ETHERNET as u16).to_le_bytes());
+        idb.extend_from_slice(&0u16.to_le_bytes());
+        idb.extend_from_slice(&0u32.to_le_bytes());
+        if let Some(v) = tsresol {
+            idb.extend_from_slice(&9u16.to_le_bytes());
+            idb.extend_from_slice(&1u16.to_le_bytes());
+            idb.extend_from_slice(&[v, 0, 0, 0]);
+            idb.extend_from_slice(&[0; 4]);
+        }
+        let mut out = pcapng_block(PCAPNG_SHB, &shb);
+        out.extend(pcapng_block(PCAPNG_IDB, &idb));
+        for block in blocks {
+            out.extend_from_slice(block);
+        }
+        out
+    }
+
+    /// An enhanced packet block on interface 0.
+    fn epb(ts: u64, cap_len: u32, data: &[u8]) -> Vec<u8> {
+        let mut body = vec![];
+        body.extend_from_slice(&0u32.to_le_bytes());
+        body.extend_from_slice(&((ts >> 32) as u32).to_le_bytes());
+        body.extend_from_slice(&(ts as u32).to_le_bytes());
+        body.extend_from_slice(&cap_len.to_le_bytes());
+        body.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        body.extend_from_slice(data);
+        body.resize((body.len() + 3) & !3, 0);
+        pcapng_block(PCAPNG_EPB, &body)
+    }
+
+    #[test]
+    fn pcap_round_trip() {
+        let mut w = Writer::new(vec![], LINKTYPE_RAW).unwrap();
+        w.write_frame(Duration::new(7, 250_000_000), &[1, 2, 3])
+            .unwrap();
+        w.write_frame(Duration::new(8, 0), &[]).unwrap();
+        let frames = parse(&w.into_inner()).unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].index, 1);
+        assert_eq!(frames[0].linktype, LINKTYPE_RAW);
+        assert_eq!(frames[0].timestamp, Duration::new(7, 250_000_000));
+        assert_eq!(frames[0].data, vec![1, 2, 3]);
+        assert_eq!(frames[1].data, Vec::<u8>::new());
+    }
+
+    #[test]
+    fn pcap_big_endian_nanoseconds() {
+        let mut data = vec![];
+        data.extend_from_slice(&PCAP_MAGIC_NSEC.to_be_bytes());
+        data.extend_from_slice(&[0; 16]);
+        data.extend_from_slice(&LINKTYPE_ETHERNET.to_be_bytes());
+        for v in [5u32, 123_456_789, 2, 60] {
+            data.extend_from_slice(&v.to_be_bytes());
+        }
+        data.extend_from_slice(&[0xaa, 0xbb]);
+        let frames = parse(&data).unwrap();
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].timestamp, Duration::new(5, 123_456_789));
+        assert_eq!(frames[0].orig_len, 60);
+        assert_eq!(frames[0].data, vec![0xaa, 0xbb]);
+    }
+
+    #[test]
+    fn truncated_files() {
+        assert!(parse(&[]).is_err());
+        assert!(parse(&[0xd4, 0xc3]).is_err());
+        assert!(parse(&[0; 24]).is_err());
+        assert!(parse(&PCAP_MAGIC_USEC.to_le_bytes()).is_err());
+
+        let mut w = Writer::new(vec![], LINKTYPE_ETHERNET).unwrap();
+        w.write_frame(Duration::default(), &[0; 20]).unwrap();
+        let data = w.into_inner();
+        // a partial record header is ignored, a partial frame is not
+        assert_eq!(parse(&data[..24 + 10]).unwrap().len(), 0);
+        assert!(parse(&data[..data.len() - 1]).is_err());
+
+        let data = pcapng(None, &[epb(0, 4, &[1, 2, 3, 4])]);
+        assert!(parse(&data[..data.len() - 4]).is_err());
+    }
+
+    #[test]
+    fn bad_pcapng_lengths() {
+        let mut block = epb(0, 4, &[1, 2, 3, 4]);
+        // a block length shorter than the block header and trailer
+        block[4..8].copy_from_slice(&8u32.to_le_bytes());
+        assert!(parse(&pcapng(None, &[block])).is_err());
+
+        let mut block = epb(0, 4, &[1, 2, 3, 4]);
+        block[4..8].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(parse(&pcapng(None, &[block])).is_err());
+
+        // a captured length beyond the end of the block
+        assert!(parse(&pcapng(None, &[epb(0, 100, &[1, 2, 3, 4])])).is_err());
+        assert!(parse(&pcapng(None, &[epb(0, u32::MAX, &[1, 2, 3, 4])])).is_err());
+
+        let mut block = epb(0, 4, &[1, 2, 3, 4]);
+        block[8..12].copy_from_slice(&1u32.to_le_bytes());
+        assert!(parse(&pcapng(None, &[block])).is_err());
+    }
+
+    #[test]
+    fn simple_packet_blocks() {
+        let mut spb = vec![];
+        spb.extend_from_slice(&6u32.to_le_bytes());
+        spb.extend_from_slice(&[1, 2, 3, 4, 5, 6]);
+        spb.resize(12, 0);
+        let spb = pcapng_block(PCAPNG_SPB, &spb);
+        let frames = parse(&pcapng(None, &[spb.clone()])).unwrap();
+        assert_eq!(frames[0].linktype, LINKTYPE_ETHERNET);
+        assert_eq!(frames[0].data, vec![1, 2, 3, 4, 5, 6]);
+
+        // without an interface description there is no link type
+        let shb = &pcapng(None, &[])[..28];
+        let mut data = shb.to_vec();
+        data.extend_from_slice(&spb);
+        assert!(parse(&data).is_err());
+    }
+
+    #[test]
+    fn pcapng_comments_round_trip() {
+        let mut w = NgWriter::new(vec![], LINKTYPE_ETHERNET).unwrap();
+        w.write_frame(Duration::new(3, 500_000), &[9; 5], Some("a comment"))
+            .unwrap();
+        w.write_frame(Duration::new(4, 0), &[8; 3], None).unwrap();
+        let frames = parse(&w.into_inner()).unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].timestamp, Duration::new(3, 500_000));
+        assert_eq!(frames[0].data, vec![9; 5]);
+        assert_eq!(frames[1].data, vec![8; 3]);
+    }
+
+    #[test]
+    fn timestamp_resolutions() {
+        let ts = |tsresol, ts| parse(&pcapng(tsresol, &[epb(ts, 0, &[])])).unwrap()[0].timestamp;
+        assert_eq!(ts(None, 1_500_000), Duration::new(1, 500_000_000));
+        assert_eq!(ts(Some(9), 2_000_000_001), Duration::new(2, 1));
+        // 2^-40 s and 10^-11 s, whose nanoseconds overflow u64
+        assert_eq!(
+            ts(Some(0x80 | 40), (3 << 40) | (1 << 39)),
+            Duration::new(3, 500_000_000)
+        );
+        assert_eq!(
+            ts(Some(11), 12_345_678_901_234),
+            Duration::new(123, 456_789_012)
+        );
+        // resolutions beyond u64 saturate rather than panic
+        assert_eq!(ts(Some(127), u64::MAX), Duration::new(1, 0));
+        assert_eq!(ts(Some(0x80 | 127), 5), Duration::default());
+        assert_eq!(ts(Some(0), 42), Duration::new(42, 0));
+    }
+}
@@ -0,0 +1,198 @@
+//! `plan <file>`: a named run composed of built-in suites and batch files,
+//! with parameters per entry, as one versioned definition of a recurring
+//! run such as a nightly one.
+//!
+//! ```yaml
+//! name: nightly
+//! level: path              # the tier cases have to pass, unless an entry says
+//! entries:
+//!   - suite: ipv4-options
+//!   - suite: strings
+//!     count: 40            # a sample of 40 of its cases, spread over them
+//!   - suite: jumbo
+//!     count: 10
+//!     seed: 7              # a random sample instead, repeated by the seed
+//!   - batch: regressions.txt
+//!     level: fields
+//! ```
+//!
+//! Entries are run in order. Cases annotated with a level of their own keep
+//! it. Every suite of a plan has to start at the same layer; batch files
+//! start at --start-layer, which that layer becomes.
+
+use crate::compare::Level;
+use crate::rng::Rng;
+use crate::runner::{self, Case};
+use crate::{budget, scapy, suites, Opts};
+use pyo3::Python;
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Plan {
+    pub name: String,
+    /// The tier the cases have to pass, unless an entry says otherwise.
+    #[serde(default)]
+    pub level: Option<String>,
+    pub entries: Vec<Entry>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Entry {
+    /// A built-in suite, by name.
+    #[serde(default)]
+    pub suite: Option<String>,
+    /// A batch file of Scapy expressions, relative to the plan's directory.
+    #[serde(default)]
+    pub batch: Option<String>,
+    /// How many of the cases to run, if not all of them.
+    #[serde(default)]
+    pub count: Option<usize>,
+    /// With `count`, pick the cases at random with this seed instead of
+    /// spreading them over the entry.
+    #[serde(default)]
+    pub seed: Option<u64>,
+    #[serde(default)]
+    pub level: Option<String>,
+}
+
+impl Entry {
+    fn describe(&self) -> String {
+        match (&self.suite, &self.batch) {
+            (Some(suite), _) => format!("suite {}", suite),
+            (None, Some(batch)) => format!("batch {}", batch),
+            (None, None) => "entry".to_string(),
+        }
+    }
+}
+
+impl Plan {
+    pub fn load(path: &str) -> Result<Plan, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let mut plan: Plan = serde_yaml::from_str(&text).map_err(|e| format!("{}: {}", path, e))?;
+        let dir = std::path::Path::new(path)
+            .parent()
+            .unwrap_or_else(|| std::path::Path::new(""));
+        for (i, entry) in plan.entries.iter_mut().enumerate() {
+            let bad = |e: String| format!("{}: entry {}: {}", path, i + 1, e);
+            if entry.suite.is_some() == entry.batch.is_some() {
+                return Err(bad("expected one of suite or batch".to_string()));
+            }
+            if let Some(suite) = &entry.suite {
+                suites::find(suite).map_err(bad)?;
+            }
+            if let Some(level) = &entry.level {
+                Level::parse(level).map_err(bad)?;
+            }
+            if entry.seed.is_some() && entry.count.is_none() {
+                return Err(bad("seed without count".to_string()));
+            }
+            if let Some(batch) = &mut entry.batch {
+                *batch = dir.join(&*batch).display().to_string();
+            }
+        }
+        if let Some(level) = &plan.level {
+            Level::parse(level).map_err(|e| format!("{}: {}", path, e))?;
+        }
+        Ok(plan)
+    }
+
+    /// Set --start-layer to the layer the plan's suites start at, as
+    /// `suites::apply_start_layer` does for --suite.
+    pub fn apply_start_layer(&self, opts: &mut Opts) -> Result<(), String> {
+        let mut start = None;
+        for suite in self.entries.iter().filter_map(|e| e.suite.as_ref()) {
+            let suite = suites::find(suite)?;
+            match start {
+                Some(start) if start != suite.start => {
+                    return Err(format!(
+                        "plan {}: suite '{}' starts at the {} layer, the other suites at {}",
+                        self.name, suite.name, suite.start, start
+                    ))
+                }
+                _ => start = Some(suite.start),
+            }
+        }
+        match start {
+            Some(start) if start != opts.start_layer => {
+                let batches = self.entries.iter().any(|e| e.batch.is_some());
+                if opts.start_layer != "ether" || (batches && start != "ether") {
+                    return Err(format!(
+                        "plan {}: the suites start at the {} layer, not --start-layer {}",
+                        self.name, start, opts.start_layer
+                    ));
+                }
+                opts.start_layer = start.to_string();
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The cases of the plan, entry by entry.
+    pub fn cases(&self, py: Python, opts: &Opts) -> Vec<Case> {
+        let default_level = self.level.as_ref().map(|l| Level::parse(l).unwrap());
+        let mut out = vec![];
+        for entry in &self.entries {
+            let mut cases = match (&entry.suite, &entry.batch) {
+                (Some(name), _) => {
+                    let suite = suites::find(name).unwrap_or_else(|e| panic!("{}", e));
+                    for module in suite.contrib {
+                        scapy::load_contrib(py, module).unwrap_or_else(|e| panic!("{}", e));
+                    }
+                    if let Some(why) = runner::missing_start_layer(py, suite.start) {
+                        eprintln!("plan {}: suite {} left out: {}", self.name, name, why);
+                        continue;
+                    }
+                    suites::cases(name).unwrap_or_else(|e| panic!("{}", e))
+                }
+                (None, Some(batch)) => runner::batch_cases(batch),
+                (None, None) => continue,
+            };
+            let total = cases.len();
+            if let Some(count) = entry.count.filter(|count| *count < total) {
+                match entry.seed {
+                    Some(seed) => {
+                        Rng::new(seed).shuffle(&mut cases);
+                        cases.truncate(count);
+                    }
+                    None => {
+                        let picked = budget::spread(&(0..total).collect::<Vec<_>>(), count);
+                        cases = cases
+                            .into_iter()
+                            .enumerate()
+                            .filter(|(i, _)| picked.contains(i))
+                            .map(|(_, case)| case)
+                            .collect();
+                    }
+                }
+            }
+            let level = entry
+                .level
+                .as_ref()
+                .map(|l| Level::parse(l).unwrap())
+                .or(default_level);
+            for case in &mut cases {
+                case.annotations.level = case.annotations.level.or(level);
+            }
+            if opts.verbose > 0 {
+                eprintln!(
+                    "plan {}: {}: {} of {} cases",
+                    self.name,
+                    entry.describe(),
+                    cases.len(),
+                    total
+                );
+            }
+            out.extend(cases);
+        }
+        eprintln!(
+            "plan {}: {} cases from {} entries",
+            self.name,
+            out.len(),
+            self.entries.len()
+        );
+        out
+    }
+}
@@ -0,0 +1,75 @@
+//! Option presets selected with `--profile`.
+//!
+//! A profile is a TOML table of option values. The built-in profiles are
+//! embedded from `profiles.toml`; the user's
+//! `~/.config/oside-tests/profiles.toml` can add profiles or override keys
+//! of the built-in ones.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+const BUILTIN: &str = include_str!("profiles.toml");
+
+fn user_profiles_path() -> Option<std::path::PathBuf> {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .map(std::path::PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| std::path::Path::new(&h).join(".config")))?;
+    Some(base.join("oside-tests").join("profiles.toml"))
+}
+
+/// All known profiles, built-in ones merged with the user's.
+pub fn profiles() -> toml::value::Table {
+    let mut all: toml::value::Table = toml::from_str(BUILTIN).unwrap();
+    if let Some(path) = user_profiles_path() {
+        if let Ok(data) = std::fs::read_to_string(&path) {
+            let user: toml::value::Table =
+                toml::from_str(&data).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+            for (name, table) in user {
+                match (all.get_mut(&name), table) {
+                    (Some(toml::Value::Table(builtin)), toml::Value::Table(table)) => {
+                        builtin.extend(table)
+                    }
+                    (_, table) => {
+                        all.insert(name, table);
+                    }
+                }
+            }
+        }
+    }
+    all
+}
+
+/// Apply the named profile to `opts`, skipping options for which
+/// `explicit(name)` says they were given on the command line.
+pub fn apply<T: Serialize + DeserializeOwned>(
+    opts: &T,
+    name: &str,
+    explicit: impl Fn(&str) -> bool,
+) -> Result<T, String> {
+    let all = profiles();
+    let profile = match all.get(name) {
+        Some(toml::Value::Table(t)) => t,
+        _ => {
+            let names: Vec<&str> = all.keys().map(|k| k.as_str()).collect();
+            return Err(format!(
+                "unknown profile '{}', known profiles: {}",
+                name,
+                names.join(", ")
+            ));
+        }
+    };
+    let mut value = serde_json::to_value(opts).map_err(|e| e.to_string())?;
+    let obj = value.as_object_mut().unwrap();
+    for (key, v) in profile {
+        if !obj.contains_key(key) {
+            return Err(format!("profile '{}' sets unknown option '{}'", name, key));
+        }
+        if !explicit(key) {
+            obj.insert(
+                key.clone(),
+                serde_json::to_value(v).map_err(|e| e.to_string())?,
+            );
+        }
+    }
+    serde_json::from_value(value).map_err(|e| format!("profile '{}': {}", name, e))
+}
@@ -0,0 +1,141 @@
+//! Recorded reference output: `--record-reference` writes, for every case,
+//! the bytes the reference built and its dissection of them, together with
+//! the environment they were produced in; `--recording` replays such a file
+//! on another host as the reference for the local oside.
+//!
+//! When replaying, generated cases are decoded from the recorded bytes,
+//! not from what Scapy builds locally. Where the local Scapy builds or
+//! dissects a case differently from the recording, the difference is
+//! reported as environment-dependent rather than counted as a failure:
+//! it says something about the two hosts, not about oside.
+
+use crate::backend::Backend;
+use crate::compare::Layer;
+use crate::scapy;
+use crate::schema::SchemaVersion;
+use pyo3::Python;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    /// The case ID, see `Case::id`.
+    pub id: String,
+    pub source: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expr: Option<String>,
+    /// The frame, as hex.
+    pub hex: String,
+    /// The reference's dissection.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layers: Option<Vec<Layer>>,
+    /// Why the reference could not build or dissect the case.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Recording {
+    #[serde(default)]
+    pub schema_version: SchemaVersion,
+    /// Where the recording was made: os, arch, python and scapy versions,
+    /// and the reference backend.
+    pub environment: BTreeMap<String, String>,
+    pub entries: Vec<Entry>,
+}
+
+impl Recording {
+    pub fn new(py: Python, reference: &str) -> Recording {
+        let mut environment = environment(py);
+        environment.insert("reference".to_string(), reference.to_string());
+        Recording {
+            schema_version: SchemaVersion,
+            environment,
+            entries: vec![],
+        }
+    }
+
+    pub fn load(path: &str) -> Result<Recording, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        serde_json::from_str(&text).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let text = serde_json::to_string_pretty(self).unwrap();
+        std::fs::write(path, text).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    /// The entries by case ID.
+    pub fn by_id(&self) -> HashMap<String, Entry> {
+        self.entries
+            .iter()
+            .map(|e| (e.id.clone(), e.clone()))
+            .collect()
+    }
+
+    /// The environment, as `key=value` pairs on one line.
+    pub fn host(&self) -> String {
+        self.environment
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// The environment of this process: os, arch, and the Python and Scapy
+/// versions of the embedded interpreter.
+pub fn environment(py: Python) -> BTreeMap<String, String> {
+    let mut env = BTreeMap::new();
+    env.insert("os".to_string(), std::env::consts::OS.to_string());
+    env.insert("arch".to_string(), std::env::consts::ARCH.to_string());
+    for (name, code) in [
+        ("python", "__import__('sys').version.split()[0]"),
+        ("scapy", "__import__('scapy').VERSION"),
+    ] {
+        if let Ok(v) = py
+            .eval(code, None, None)
+            .and_then(|v| v.extract::<String>())
+        {
+            env.insert(name.to_string(), v);
+        }
+    }
+    env
+}
+
+/// The recorded dissections as a backend, looking frames up by their bytes
+/// after the first `skip` (see --skip-bytes).
+pub struct Recorded {
+    by_hex: HashMap<String, Result<Vec<Layer>, String>>,
+}
+
+impl Recorded {
+    pub fn new(recording: &Recording, skip: usize) -> Recorded {
+        let by_hex = recording
+            .entries
+            .iter()
+            .map(|e| {
+                let result = match (&e.layers, &e.error) {
+                    (Some(layers), _) => Ok(layers.clone()),
+                    (None, Some(error)) => Err(error.clone()),
+                    (None, None) => Err("no dissection recorded".to_string()),
+                };
+                (e.hex.get(skip * 2..).unwrap_or("").to_string(), result)
+            })
+            .collect();
+        Recorded { by_hex }
+    }
+}
+
+impl Backend for Recorded {
+    fn name(&self) -> &str {
+        "recorded"
+    }
+
+    fn dissect(&mut self, data: &[u8]) -> Result<Vec<Layer>, String> {
+        self.by_hex
+            .get(&scapy::hex(data))
+            .cloned()
+            .unwrap_or_else(|| Err("frame not in the recording".to_string()))
+    }
+}
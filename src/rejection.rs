@@ -0,0 +1,101 @@
+//! `--compare-rejections`: when oside and every reference reject a frame,
+//! whether they reject it for the same reason.
+//!
+//! The sides report errors in their own words, or in oside's case not at
+//! all, so each rejection is first mapped to a `Rejection` category: by the
+//! `PATTERNS` table from the error message, or, failing that, by probing
+//! whether the side accepts the frame once zero bytes are appended to it.
+//! Agreeing to reject is then only agreement if the categories are
+//! compatible: a validator built on oside has to reject for the right
+//! reason too.
+
+/// Why a frame was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    /// The frame ends before a layer does.
+    Truncated,
+    /// A length field disagrees with the frame.
+    BadLength,
+    /// A type, version or option value the dissector does not know.
+    UnknownType,
+    /// Rejected for a reason not stated, but not truncation: the frame is
+    /// rejected even with bytes appended to it.
+    Malformed,
+}
+
+impl Rejection {
+    pub fn name(self) -> &'static str {
+        match self {
+            Rejection::Truncated => "truncated",
+            Rejection::BadLength => "bad-length",
+            Rejection::UnknownType => "unknown-type",
+            Rejection::Malformed => "malformed",
+        }
+    }
+
+    /// Whether two rejections do not differ materially: the same category,
+    /// or an unstated reason and one that is not truncation.
+    pub fn compatible(self, other: Rejection) -> bool {
+        use Rejection::*;
+        match (self, other) {
+            (a, b) if a == b => true,
+            (Malformed, BadLength | UnknownType) | (BadLength | UnknownType, Malformed) => true,
+            _ => false,
+        }
+    }
+}
+
+impl std::fmt::Display for Rejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Lowercase fragments of error messages, and the category they indicate;
+/// the first matching one wins.
+const PATTERNS: &[(&str, Rejection)] = &[
+    ("unpack requires a buffer", Rejection::Truncated),
+    ("not enough data", Rejection::Truncated),
+    ("not enough bytes", Rejection::Truncated),
+    ("truncated", Rejection::Truncated),
+    ("too short", Rejection::Truncated),
+    ("unexpected end", Rejection::Truncated),
+    ("incomplete", Rejection::Truncated),
+    ("indexerror", Rejection::Truncated),
+    ("index out of range", Rejection::Truncated),
+    ("bad length", Rejection::BadLength),
+    ("invalid length", Rejection::BadLength),
+    ("length mismatch", Rejection::BadLength),
+    ("too long", Rejection::BadLength),
+    ("unknown", Rejection::UnknownType),
+    ("unsupported", Rejection::UnknownType),
+    ("not supported", Rejection::UnknownType),
+    ("unrecognized", Rejection::UnknownType),
+    ("invalid type", Rejection::UnknownType),
+    ("invalid version", Rejection::UnknownType),
+    ("keyerror", Rejection::UnknownType),
+];
+
+/// How many zero bytes are appended to a frame to probe for truncation.
+const PROBE_PADDING: usize = 2048;
+
+/// The category an error message indicates, if any.
+pub fn classify(message: &str) -> Option<Rejection> {
+    let message = message.to_lowercase();
+    PATTERNS
+        .iter()
+        .find(|(pattern, _)| message.contains(pattern))
+        .map(|(_, rejection)| *rejection)
+}
+
+/// The category of a rejection without a recognized message, by whether
+/// `accepts` the frame with zero bytes appended.
+pub fn probe(data: &[u8], accepts: impl FnOnce(&[u8]) -> bool) -> Rejection {
+    let mut padded = data.to_vec();
+    padded.resize(data.len() + PROBE_PADDING, 0);
+    if accepts(&padded) {
+        Rejection::Truncated
+    } else {
+        Rejection::Malformed
+    }
+}
@@ -0,0 +1,64 @@
+//! Machine-readable failure reports selected with `--report`.
+
+/// The `--report` formats.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Report {
+    /// GitHub Actions workflow commands, shown inline on pull requests.
+    Gha,
+}
+
+impl Report {
+    pub fn parse(s: &str) -> Result<Report, String> {
+        match s {
+            "gha" => Ok(Report::Gha),
+            _ => Err(format!("unknown report format '{}', expected gha", s)),
+        }
+    }
+
+    /// Report a failing case.
+    pub fn failure(&self, source: &str, message: &str) {
+        match self {
+            Report::Gha => println!("{}", gha_error(source, message)),
+        }
+    }
+}
+
+/// Split a case source into its file and, for batch files, line; for
+/// capture frames (`file#N`) the frame is kept for the message. Batch
+/// sources may carry a suffix such as ` swap-layers@1` after the line.
+fn location(source: &str) -> (&str, Option<&str>, Option<&str>) {
+    if let Some((file, rest)) = source.rsplit_once(':') {
+        let line = rest.split(' ').next().unwrap_or("");
+        if !line.is_empty() && line.bytes().all(|b| b.is_ascii_digit()) {
+            return (file, Some(line), None);
+        }
+    }
+    if let Some((file, frame)) = source.rsplit_once('#') {
+        return (file, None, Some(frame));
+    }
+    (source, None, None)
+}
+
+fn gha_escape_data(s: &str) -> String {
+    s.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+fn gha_escape_property(s: &str) -> String {
+    gha_escape_data(s).replace(':', "%3A").replace(',', "%2C")
+}
+
+/// An `::error` workflow command for a failing case.
+pub fn gha_error(source: &str, message: &str) -> String {
+    let (file, line, frame) = location(source);
+    let mut props = format!("file={}", gha_escape_property(file));
+    if let Some(line) = line {
+        props.push_str(&format!(",line={}", line));
+    }
+    let message = match frame {
+        Some(frame) => format!("frame {}: {}", frame, message),
+        None => message.to_string(),
+    };
+    format!("::error {}::{}", props, gha_escape_data(&message))
+}
@@ -0,0 +1,155 @@
+//! The results database: a JSON-lines file with one record per case run,
+//! appended to by every run given `--results-db`.
+
+use crate::compare::Diff;
+use crate::schema::SchemaVersion;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Record {
+    #[serde(default)]
+    pub schema_version: SchemaVersion,
+    /// The case source, e.g. `capture.pcap#12` or `batch.txt:3`.
+    pub source: String,
+    pub passed: bool,
+    /// Seconds since the Unix epoch.
+    pub time: u64,
+    /// oside's layer path for the case, e.g. `ether/ip/udp`.
+    #[serde(default)]
+    pub layers: String,
+    /// The differences found, if any.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub diffs: Vec<Diff>,
+    /// The highest comparison tier passed (1-4, 0 for none), for cases
+    /// compared by tiers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub level: Option<u8>,
+    /// The oside version and its JSON for the case, with
+    /// `--store-oside-json`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub oside_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub oside_json: Option<Value>,
+}
+
+/// Read all records; a missing file is an empty database.
+pub fn load(path: &str) -> Vec<Record> {
+    let data = match std::fs::read_to_string(path) {
+        Ok(data) => data,
+        Err(_) => return vec![],
+    };
+    data.lines()
+        .filter(|l| !l.trim().is_empty())
+        .filter_map(|l| match serde_json::from_str(l) {
+            Ok(r) => Some(r),
+            Err(e) => {
+                eprintln!("{}: skipping bad record: {}", path, e);
+                None
+            }
+        })
+        .collect()
+}
+
+pub struct Db {
+    file: std::fs::File,
+}
+
+impl Db {
+    pub fn open(path: &str) -> std::io::Result<Db> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        Ok(Db { file })
+    }
+
+    pub fn append(&mut self, record: &Record) -> std::io::Result<()> {
+        writeln!(self.file, "{}", serde_json::to_string(record).unwrap())
+    }
+}
+
+/// Remove the records older than `before`, in seconds since the Unix
+/// epoch, returning how many were removed.
+pub fn prune(path: &str, before: u64) -> std::io::Result<usize> {
+    let records = load(path);
+    let kept: Vec<&Record> = records.iter().filter(|r| r.time >= before).collect();
+    let removed = records.len() - kept.len();
+    if removed == 0 {
+        return Ok(0);
+    }
+    let mut data = String::new();
+    for record in kept {
+        data += &serde_json::to_string(record).unwrap();
+        data.push('\n');
+    }
+    // written aside and renamed over, so that an interrupted prune leaves
+    // the database whole
+    let tmp = format!("{}.tmp", path);
+    std::fs::write(&tmp, data)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(removed)
+}
+
+/// The verdict of the latest run of each case source.
+pub fn last_verdicts(records: &[Record]) -> HashMap<String, bool> {
+    records
+        .iter()
+        .map(|r| (r.source.clone(), r.passed))
+        .collect()
+}
+
+/// The oside version and JSON of the latest stored decode of each case
+/// source.
+pub fn last_oside_json(records: &[Record]) -> HashMap<String, (String, Value)> {
+    records
+        .iter()
+        .filter_map(|r| {
+            let stored = (r.oside_version.clone()?, r.oside_json.clone()?);
+            Some((r.source.clone(), stored))
+        })
+        .collect()
+}
+
+/// Priority of each case source for `--prioritize-failures`; higher runs
+/// first.
+///
+/// A case scores its historical failure rate, plus 1 if it failed the last
+/// time it ran, plus 0.5 if its layers include a protocol whose cases
+/// recently changed verdict between their last two runs.
+pub fn priorities(records: &[Record]) -> HashMap<String, f64> {
+    let mut by_source: HashMap<&str, Vec<&Record>> = HashMap::new();
+    for r in records {
+        by_source.entry(r.source.as_str()).or_default().push(r);
+    }
+
+    let mut changed_protocols: HashSet<&str> = HashSet::new();
+    for runs in by_source.values() {
+        if runs.len() >= 2 && runs[runs.len() - 1].passed != runs[runs.len() - 2].passed {
+            changed_protocols.extend(runs[runs.len() - 1].layers.split('/'));
+        }
+    }
+    changed_protocols.remove("");
+
+    by_source
+        .iter()
+        .map(|(source, runs)| {
+            let failures = runs.iter().filter(|r| !r.passed).count();
+            let last = runs[runs.len() - 1];
+            let mut score = failures as f64 / runs.len() as f64;
+            if !last.passed {
+                score += 1.0;
+            }
+            if last
+                .layers
+                .split('/')
+                .any(|p| changed_protocols.contains(p))
+            {
+                score += 0.5;
+            }
+            (source.to_string(), score)
+        })
+        .collect()
+}
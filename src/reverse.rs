@@ -0,0 +1,55 @@
+//! `--reverse`: the other direction, oside building what Scapy builds.
+//!
+//! The layers of a Scapy expression and the fields explicitly set in each
+//! (`pkt.fields`) are read from Scapy and turned into the equivalent oside
+//! layer stack, which oside builds; the bytes have to be Scapy's. The
+//! fields the expression leaves unset are left to each side's defaults and
+//! computation (lengths, checksums, next-layer types), so these are tested
+//! as well, for any expression and without hand-written mappings.
+//!
+//! Layers are matched to oside's by name, case-insensitively, and fields
+//! by name. Layers whose set fields have no oside equivalent, such as
+//! nested packets and option lists, cannot be converted; such expressions
+//! are left out rather than failed.
+
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+/// A layer of a Scapy expression.
+#[derive(Debug, Deserialize)]
+pub struct SetLayer {
+    /// The Scapy class, e.g. `IPv6`.
+    pub layer: String,
+    /// The fields set in the expression, with values as oside takes them.
+    pub fields: Map<String, Value>,
+    /// The fields set to values oside has no equivalent of.
+    pub unsupported: Vec<String>,
+}
+
+/// The oside layer stack for the Scapy layers, as the JSON oside
+/// deserializes its layers from; `names` are oside's layer names.
+pub fn oside_layers(layers: &[SetLayer], names: &[String]) -> Result<Value, String> {
+    let mut out = vec![];
+    for layer in layers {
+        if !layer.unsupported.is_empty() {
+            return Err(format!(
+                "{} fields {} have no oside equivalent",
+                layer.layer,
+                layer.unsupported.join(", ")
+            ));
+        }
+        let name = names
+            .iter()
+            .find(|n| n.eq_ignore_ascii_case(&layer.layer))
+            .ok_or_else(|| format!("oside has no layer {}", layer.layer))?;
+        let fields: Map<String, Value> = layer
+            .fields
+            .iter()
+            .map(|(field, value)| (field.clone(), serde_json::json!({ "Set": value })))
+            .collect();
+        let mut tagged = Map::new();
+        tagged.insert(name.clone(), Value::Object(fields));
+        out.push(Value::Object(tagged));
+    }
+    Ok(Value::Array(out))
+}
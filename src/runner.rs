@@ -0,0 +1,2580 @@
+//! Running cases through oside and the reference backends.
+
+use crate::anonymize::Anonymizer;
+use crate::backend::Backend;
+use crate::budget::{self, Budget};
+use crate::cache::Cache;
+use crate::comparator::{self, LayerComparator};
+use crate::compare::Level;
+use crate::fcs::{self, Fcs};
+use crate::filter::Filter;
+use crate::known_failures::{self, KnownFailures};
+use crate::normalize::{
+    Canonical, ElidePayloads, IgnoreField, Normalizer, Side, TextEncoding, TextForm, ValueClass,
+};
+use crate::notify::Notifier;
+use crate::recording::{self, Recorded, Recording};
+use crate::rejection::{self, Rejection};
+use crate::report::Report;
+use crate::rng::{self, Rng};
+use crate::scapy::Isolate;
+use crate::schema::SchemaVersion;
+use crate::span::Span;
+use crate::summary::{self, Summary};
+use crate::{
+    alloc_stats, backend, compare, consensus, corpus, corpus_convert, coverage, decode, glob,
+    highlight, hints, link_detect, offload, pcap, results, reverse, sandbox, scapy, semantic,
+    sflow, suites, Opts,
+};
+use pyo3::Python;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+/// What a case feeds to the decoders.
+#[derive(Debug, Clone)]
+pub enum Input {
+    /// Raw frame bytes, e.g. from a capture file.
+    Frame(Vec<u8>),
+    /// A Scapy expression, built into bytes when the case runs.
+    Expr(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Case {
+    /// Where the case came from, e.g. `capture.pcap#12` or `batch.txt:3`.
+    pub source: String,
+    pub input: Input,
+    /// Groups generated cases for the per-tag summary, e.g. `ip.len`.
+    pub tag: Option<String>,
+    pub annotations: Annotations,
+    /// When the frame was captured, for cases read from a capture.
+    pub timestamp: Option<Duration>,
+}
+
+/// Per-case settings, given in batch files after `#@` at the end of the
+/// entry's line, as comma-separated `key: value` pairs:
+///
+/// ```text
+/// Ether()/IP()/UDP()/VXLAN()  #@ level: 2
+/// Ether()/IP(ihl=2)           #@ expect: oside-error
+/// Ether()/IP()/TCP()          #@ orig-len: 1514
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Annotations {
+    /// The comparison tier the case has to pass, instead of --level.
+    pub level: Option<Level>,
+    /// How the case is expected to fail, for negative tests.
+    pub expect: Option<Expect>,
+    /// The length of the frame the case's bytes were cut from, if longer:
+    /// differences the cut explains are not counted.
+    pub orig_len: Option<usize>,
+}
+
+/// The failure a negative test passes on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Expect {
+    /// Any failure: differences, or either side failing.
+    Fail,
+    /// Scapy (the reference) failing to build or dissect the case.
+    ScapyError,
+    /// oside failing to decode the case.
+    OsideError,
+}
+
+impl Expect {
+    pub fn parse(s: &str) -> Result<Expect, String> {
+        match s.trim() {
+            "fail" => Ok(Expect::Fail),
+            "scapy-error" => Ok(Expect::ScapyError),
+            "oside-error" => Ok(Expect::OsideError),
+            s => Err(format!(
+                "bad expectation '{}', expected fail, scapy-error or oside-error",
+                s
+            )),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Expect::Fail => "fail",
+            Expect::ScapyError => "scapy-error",
+            Expect::OsideError => "oside-error",
+        }
+    }
+}
+
+impl Annotations {
+    pub fn parse(text: &str) -> Result<Annotations, String> {
+        let mut annotations = Annotations::default();
+        for item in text.split(',').map(str::trim).filter(|i| !i.is_empty()) {
+            let (key, value) = item
+                .split_once(':')
+                .ok_or_else(|| format!("bad annotation '{}', expected KEY: VALUE", item))?;
+            match key.trim() {
+                "level" => annotations.level = Some(Level::parse(value)?),
+                "expect" => annotations.expect = Some(Expect::parse(value)?),
+                "orig-len" => {
+                    let len = value.trim().parse().map_err(|_| {
+                        format!(
+                            "bad orig-len '{}', expected a length in bytes",
+                            value.trim()
+                        )
+                    })?;
+                    annotations.orig_len = Some(len);
+                }
+                key => return Err(format!("unknown annotation '{}'", key)),
+            }
+        }
+        Ok(annotations)
+    }
+}
+
+impl Case {
+    /// A stable ID for the case: a hash of its source and, for generated
+    /// cases, the expression it is built from. Captured frames keep their
+    /// ID when saved into and replayed from a corpus.
+    pub fn id(&self) -> String {
+        let mut hash = fnv1a(FNV_OFFSET, self.source.as_bytes());
+        if let Input::Expr(expr) = &self.input {
+            hash = fnv1a(hash, &[0]);
+            hash = fnv1a(hash, expr.as_bytes());
+        }
+        format!("{:016x}", hash)
+    }
+
+    /// The source and ID, as shown in reports.
+    pub fn label(&self) -> String {
+        format!("{} [{}]", self.source, self.id())
+    }
+}
+
+pub(crate) const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+
+pub(crate) fn fnv1a(mut hash: u64, data: &[u8]) -> u64 {
+    for b in data {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+/// The index of the first byte where two byte strings differ, or the
+/// length of the shorter one if it is a prefix of the other.
+fn first_difference(a: &[u8], b: &[u8]) -> usize {
+    a.iter()
+        .zip(b)
+        .position(|(x, y)| x != y)
+        .unwrap_or_else(|| a.len().min(b.len()))
+}
+
+/// Read a capture file, keeping only the frames selected by --frames and
+/// --time-range.
+pub fn read_frames(opts: &Opts, fname: &str) -> Vec<pcap::Frame> {
+    let frame_span = opts
+        .frames
+        .as_ref()
+        .map(|s| Span::<usize>::parse(s).unwrap_or_else(|e| panic!("bad --frames {:?}: {}", s, e)));
+    let time_span = opts.time_range.as_ref().map(|s| {
+        Span::<f64>::parse(s).unwrap_or_else(|e| panic!("bad --time-range {:?}: {}", s, e))
+    });
+    let frames = pcap::read_file(fname).unwrap_or_else(|e| panic!("{}", e));
+    let first_ts = frames.first().map(|f| f.timestamp).unwrap_or_default();
+    frames
+        .into_iter()
+        .filter(|frame| {
+            let rel = frame.timestamp.saturating_sub(first_ts).as_secs_f64();
+            frame_span.map(|s| s.contains(frame.index)) != Some(false)
+                && time_span.map(|s| s.contains(rel)) != Some(false)
+        })
+        .collect()
+}
+
+/// Whether frames of the link type can be compared: Ethernet frames, which
+/// --skip-bytes and --start-layer can enter further in, frames starting at
+/// --start-layer, or any frames --link-type says start there.
+pub fn linktype_supported(opts: &Opts, linktype: u32) -> bool {
+    linktype == pcap::LINKTYPE_ETHERNET
+        || pcap::start_layer(linktype) == Some(&opts.start_layer)
+        || opts.link_type.is_some()
+}
+
+/// Set --start-layer to the layer the frames of the --pcap capture start
+/// at, if it is left at Ethernet and their link type is another known one.
+/// Of several captures, the first decides.
+pub fn apply_pcap_start_layer(opts: &mut Opts) -> Result<(), String> {
+    let arg = match &opts.pcap {
+        Some(arg)
+            if opts.start_layer == "ether" && opts.skip_bytes == 0 && opts.link_type.is_none() =>
+        {
+            arg
+        }
+        _ => return Ok(()),
+    };
+    let fname = &glob::expand(arg)?[0];
+    let linktype = match pcap::read_file(fname)?.first() {
+        Some(frame) => frame.linktype,
+        None => return Ok(()),
+    };
+    if let Some(start) = pcap::start_layer(linktype) {
+        if start != opts.start_layer {
+            eprintln!("{}: link type {}, starting at {}", fname, linktype, start);
+            opts.start_layer = start.to_string();
+        }
+    }
+    Ok(())
+}
+
+/// Set --start-layer from --link-type, a layer name or a pcap link type
+/// number, or else to the layer the --raw frames look like they start at,
+/// if it is left at Ethernet.
+pub fn apply_link_type(opts: &mut Opts) -> Result<(), String> {
+    if let Some(link_type) = &opts.link_type {
+        opts.start_layer = match link_type.parse::<u32>() {
+            Ok(linktype) => pcap::start_layer(linktype)
+                .ok_or_else(|| format!("--link-type: unknown link type {}", linktype))?
+                .to_string(),
+            Err(_) => link_type.clone(),
+        };
+        return Ok(());
+    }
+    let fname = match &opts.raw {
+        Some(fname) if opts.start_layer == "ether" && opts.skip_bytes == 0 => fname,
+        _ => return Ok(()),
+    };
+    match link_detect::detect(&raw_frames(fname)?) {
+        Some(guess) => {
+            eprintln!(
+                "{}: looks like {} (confidence {:.0}%: {}); --link-type overrides",
+                fname,
+                guess.layer,
+                guess.confidence * 100.0,
+                guess.evidence.join(", ")
+            );
+            opts.start_layer = guess.layer.to_string();
+        }
+        None => eprintln!(
+            "{}: link type not recognized, starting at {}; --link-type overrides",
+            fname, opts.start_layer
+        ),
+    }
+    Ok(())
+}
+
+/// The frames of a --raw file: one hex dump per line, or if it is not
+/// text made of those, the whole file as one frame.
+pub fn raw_frames(fname: &str) -> Result<Vec<Vec<u8>>, String> {
+    let data = std::fs::read(fname).map_err(|e| format!("{}: {}", fname, e))?;
+    let hex = std::str::from_utf8(&data).ok().and_then(|text| {
+        text.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                let digits: String = line
+                    .split(|c: char| c.is_whitespace() || c == ':')
+                    .map(|word| word.trim_start_matches("0x"))
+                    .collect();
+                corpus_convert::unhex(&digits).ok()
+            })
+            .collect::<Option<Vec<_>>>()
+    });
+    Ok(match hex {
+        Some(frames) if !frames.is_empty() => frames,
+        _ => vec![data],
+    })
+}
+
+/// The cases for the frames of a --raw file.
+pub fn raw_cases(fname: &str) -> Vec<Case> {
+    raw_frames(fname)
+        .unwrap_or_else(|e| panic!("{}", e))
+        .into_iter()
+        .enumerate()
+        .map(|(i, data)| Case {
+            source: format!("{}#{}", fname, i + 1),
+            input: Input::Frame(data),
+            tag: None,
+            annotations: Default::default(),
+            timestamp: None,
+        })
+        .collect()
+}
+
+/// Why frames starting at the layer cannot be compared, if a side cannot
+/// start decoding there.
+pub fn missing_start_layer(py: Python, start: &str) -> Option<String> {
+    let missing: Vec<&str> = [
+        ("oside", decode::can_start_at(start)),
+        ("Scapy", scapy::has_layer(py, start)),
+    ]
+    .iter()
+    .filter(|(_, has)| !has)
+    .map(|(side, _)| *side)
+    .collect();
+    if missing.is_empty() {
+        return None;
+    }
+    Some(format!(
+        "{} {} no {} layer to start decoding at",
+        missing.join(" and "),
+        if missing.len() > 1 { "have" } else { "has" },
+        start
+    ))
+}
+
+/// The cases for the selected frames of a capture file. Loopback frames
+/// are compared as Ethernet frames, their address family header replaced
+/// by an Ethernet header.
+pub fn pcap_cases(opts: &Opts, fname: &str) -> Vec<Case> {
+    let skip = |frame: &pcap::Frame, why: &str| {
+        if opts.verbose > 0 {
+            eprintln!("{}#{}: skipping {}", fname, frame.index, why);
+        }
+    };
+    read_frames(opts, fname)
+        .into_iter()
+        .filter_map(|frame| {
+            let data = match frame.linktype {
+                pcap::LINKTYPE_NULL | pcap::LINKTYPE_LOOP
+                    if opts.start_layer == "ether" && opts.link_type.is_none() =>
+                {
+                    match pcap::loopback_to_ether(frame.linktype, &frame.data) {
+                        Some(data) => data,
+                        None => {
+                            skip(&frame, "loopback frame of an address family other than IP");
+                            return None;
+                        }
+                    }
+                }
+                linktype if linktype_supported(opts, linktype) => frame.data,
+                linktype => {
+                    skip(&frame, &format!("unsupported link type {}", linktype));
+                    return None;
+                }
+            };
+            Some(Case {
+                source: format!("{}#{}", fname, frame.index),
+                input: Input::Frame(data),
+                tag: None,
+                annotations: Default::default(),
+                timestamp: Some(frame.timestamp),
+            })
+        })
+        .collect()
+}
+
+/// The cases of a batch file: one Scapy expression per line, with empty
+/// lines and lines starting with `#` ignored.
+pub fn batch_cases(fname: &str) -> Vec<Case> {
+    let data = std::fs::read_to_string(fname).unwrap_or_else(|e| panic!("{}: {}", fname, e));
+    data.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(|(i, line)| {
+            let (expr, annotations) = match line.rsplit_once("#@") {
+                Some((expr, annotations)) => (
+                    expr,
+                    Annotations::parse(annotations)
+                        .unwrap_or_else(|e| panic!("{}:{}: {}", fname, i + 1, e)),
+                ),
+                None => (line, Annotations::default()),
+            };
+            Case {
+                source: format!("{}:{}", fname, i + 1),
+                input: Input::Expr(expr.trim().to_string()),
+                tag: None,
+                annotations,
+                timestamp: None,
+            }
+        })
+        .collect()
+}
+
+/// The structural mutations (duplicated, dropped and swapped layers,
+/// overlong length fields) of each expression case, for --mutate.
+pub fn mutated_cases(py: Python, cases: &[Case]) -> Vec<Case> {
+    let mut out = vec![];
+    for case in cases {
+        let expr = match &case.input {
+            Input::Expr(expr) => expr,
+            Input::Frame(_) => continue,
+        };
+        let mutations = match scapy::mutations(py, expr) {
+            Ok(mutations) => mutations,
+            Err(e) => {
+                eprintln!("{}: could not mutate {}: {}", case.label(), expr, e);
+                continue;
+            }
+        };
+        for (op, index) in mutations {
+            out.push(Case {
+                source: format!("{} {}@{}", case.source, op, index),
+                input: Input::Expr(scapy::mutate_expr(expr, &op, index)),
+                tag: Some(op),
+                annotations: Default::default(),
+                timestamp: None,
+            });
+        }
+    }
+    out
+}
+
+/// For each length-bearing field of each expression case, cases with the
+/// field set to zero, one under, exactly, one over and well past its
+/// correct value, for --length-sweep. The cases are tagged with the field.
+pub fn length_sweep_cases(py: Python, cases: &[Case]) -> Vec<Case> {
+    let mut out = vec![];
+    for case in cases {
+        let expr = match &case.input {
+            Input::Expr(expr) => expr,
+            Input::Frame(_) => continue,
+        };
+        let fields = match scapy::length_fields(py, expr) {
+            Ok(fields) => fields,
+            Err(e) => {
+                eprintln!(
+                    "{}: could not find length fields of {}: {}",
+                    case.label(),
+                    expr,
+                    e
+                );
+                continue;
+            }
+        };
+        for f in fields {
+            let tag = format!("{}.{}", f.layer, f.field);
+            out.push(Case {
+                source: format!("{} {}={}", case.source, tag, f.label),
+                input: Input::Expr(scapy::set_field_expr(
+                    expr,
+                    f.layer_index,
+                    &f.field,
+                    f.value,
+                )),
+                tag: Some(tag),
+                annotations: Default::default(),
+                timestamp: None,
+            });
+        }
+    }
+    out
+}
+
+/// The most values --sweep-field sweeps a field through, as every value
+/// becomes a case before any runs.
+const SWEEP_LIMIT: u64 = 65536;
+
+/// For --sweep-field `LAYER.FIELD=RANGE`: for each expression case, cases
+/// with the field set to every value of the range, which ends at the
+/// field's largest value if left open. The cases are tagged with the field.
+pub fn sweep_field_cases(py: Python, cases: &[Case], spec: &str) -> Vec<Case> {
+    let bad = |e: &str| -> ! { panic!("bad --sweep-field {:?}: {}", spec, e) };
+    let (path, range) = spec
+        .split_once('=')
+        .unwrap_or_else(|| bad("expected LAYER.FIELD=RANGE"));
+    let (layer, field) = path
+        .split_once('.')
+        .unwrap_or_else(|| bad("expected LAYER.FIELD=RANGE"));
+    let span = Span::<u64>::parse(range).unwrap_or_else(|e| bad(&e));
+    let tag = format!("{}.{}", layer.to_lowercase(), field);
+
+    let mut out = vec![];
+    for case in cases {
+        let expr = match &case.input {
+            Input::Expr(expr) => expr,
+            Input::Frame(_) => continue,
+        };
+        let (index, max) = match scapy::field_info(py, expr, layer, field) {
+            Ok(info) => info,
+            Err(e) => {
+                eprintln!("{}: cannot sweep {}: {}", case.label(), path, e);
+                continue;
+            }
+        };
+        let start = span.start.unwrap_or(0);
+        let end = match span.end {
+            Some(end) if span.inclusive => end.min(max),
+            Some(end) if end > 0 => (end - 1).min(max),
+            Some(_) => continue,
+            None => max,
+        };
+        if end >= start && end - start >= SWEEP_LIMIT {
+            eprintln!(
+                "{}: cannot sweep {} through {} values, more than the {} a sweep is limited to",
+                case.label(),
+                path,
+                end as u128 - start as u128 + 1,
+                SWEEP_LIMIT
+            );
+            continue;
+        }
+        for value in start..=end {
+            out.push(Case {
+                source: format!("{} {}={}", case.source, tag, value),
+                input: Input::Expr(scapy::set_field_expr(expr, index, field, value)),
+                tag: Some(tag.clone()),
+                annotations: Default::default(),
+                timestamp: None,
+            });
+        }
+    }
+    out
+}
+
+/// The case for --wrap: the payload bytes read from stdin, inside the outer
+/// Scapy stack `wrap` such as `Ether()/IP()/UDP(dport=4789)/`.
+pub fn wrapped_stdin_case(wrap: &str) -> Case {
+    use std::io::Read;
+    let mut payload = vec![];
+    std::io::stdin()
+        .read_to_end(&mut payload)
+        .unwrap_or_else(|e| panic!("reading stdin: {}", e));
+    let wrap = wrap.trim().trim_end_matches('/');
+    Case {
+        source: "stdin".to_string(),
+        input: Input::Expr(format!(
+            "{}/Raw(bytes.fromhex('{}'))",
+            wrap,
+            scapy::hex(&payload)
+        )),
+        tag: None,
+        annotations: Default::default(),
+        timestamp: None,
+    }
+}
+
+/// The cases of a corpus directory, under the sources they were saved with.
+pub fn corpus_cases(dir: &str) -> Vec<Case> {
+    corpus::load(dir)
+        .unwrap_or_else(|e| panic!("{}: {}", dir, e))
+        .into_iter()
+        .map(|(meta, data)| Case {
+            source: meta.source,
+            input: Input::Frame(data),
+            tag: None,
+            annotations: Default::default(),
+            timestamp: None,
+        })
+        .collect()
+}
+
+/// Keep only the case selected by --case, matching a full ID or a prefix
+/// of one.
+pub fn select_case(cases: &mut Vec<Case>, id: &str) {
+    cases.retain(|c| c.id().starts_with(id));
+    if cases.is_empty() {
+        eprintln!("warning: no case with ID {}", id);
+    }
+}
+
+/// The protocols named by --changed-protocols and, by their file names, by
+/// --changed-files: `src/protocols/tcp.rs` is `tcp`, and a `mod.rs` stands
+/// for its directory. `None` when neither is given.
+pub fn changed_protocols(opts: &Opts) -> Option<BTreeSet<String>> {
+    if opts.changed_protocols.is_none() && opts.changed_files.is_none() {
+        return None;
+    }
+    let mut protocols = BTreeSet::new();
+    for list in [&opts.changed_protocols, &opts.changed_files]
+        .into_iter()
+        .flatten()
+    {
+        for item in list.split(',').map(str::trim).filter(|i| !i.is_empty()) {
+            let path = Path::new(item);
+            let name = match path.file_stem().and_then(|s| s.to_str()) {
+                Some("mod") | Some("lib") => path
+                    .parent()
+                    .and_then(|p| p.file_name())
+                    .and_then(|s| s.to_str()),
+                stem => stem,
+            };
+            if let Some(name) = name {
+                protocols.insert(name.to_lowercase());
+            }
+        }
+    }
+    Some(protocols)
+}
+
+/// Keep only the cases whose coverage signature involves one of the
+/// changed protocols, as a layer or by its options. Cases oside cannot
+/// decode, or Scapy cannot build, are kept, since there is no telling.
+pub fn select_changed(py: Python, opts: &Opts, cases: &mut Vec<Case>, changed: &BTreeSet<String>) {
+    let total = cases.len();
+    cases.retain(|case| {
+        let data = match &case.input {
+            Input::Frame(data) => data.clone(),
+            Input::Expr(expr) => match scapy::build(py, expr) {
+                Ok(data) => data,
+                Err(_) => return true,
+            },
+        };
+        let signature = match coverage::signature(&opts.start_layer, entry(opts, &data)) {
+            Some(signature) => signature,
+            None => return true,
+        };
+        signature.iter().any(|feature| {
+            let protocol = match feature.split_once(':') {
+                Some(("layer", name)) => name,
+                Some(("option", option)) => option.split('.').next().unwrap_or(""),
+                _ => return false,
+            };
+            changed.contains(protocol)
+        })
+    });
+    eprintln!(
+        "{} of {} cases involve the changed protocols ({})",
+        cases.len(),
+        total,
+        changed.iter().cloned().collect::<Vec<_>>().join(",")
+    );
+}
+
+pub fn parse_filter(opts: &Opts) -> Option<Filter> {
+    opts.filter
+        .as_ref()
+        .map(|f| Filter::parse(f).unwrap_or_else(|e| panic!("bad filter {:?}: {}", f, e)))
+}
+
+/// Whether the options name any cases to run.
+pub fn has_cases(opts: &Opts) -> bool {
+    opts.pcap.is_some()
+        || opts.sflow.is_some()
+        || opts.raw.is_some()
+        || opts.batch.is_some()
+        || opts.corpus.is_some()
+        || !opts.sweep_field.is_empty()
+        || opts.wrap.is_some()
+        || !opts.suite.is_empty()
+}
+
+/// All the cases named by the options, generated ones included.
+pub fn collect_cases(py: Python, opts: &Opts) -> Vec<Case> {
+    let mut cases = vec![];
+    if let Some(arg) = &opts.pcap {
+        match missing_start_layer(py, &opts.start_layer) {
+            Some(why) => eprintln!("{} left out: {}", arg, why),
+            None => {
+                let files = glob::expand(arg).unwrap_or_else(|e| panic!("--pcap {}", e));
+                if files.len() > 1 {
+                    eprintln!("--pcap {}: {} capture files", arg, files.len());
+                }
+                for fname in &files {
+                    cases.extend(pcap_cases(opts, fname));
+                }
+            }
+        }
+    }
+    if let Some(arg) = &opts.sflow {
+        if opts.start_layer != "ether" {
+            eprintln!(
+                "{} left out: sampled headers are compared from Ethernet, not {}",
+                arg, opts.start_layer
+            );
+        } else {
+            for fname in glob::expand(arg).unwrap_or_else(|e| panic!("--sflow {}", e)) {
+                let frames = read_frames(opts, &fname);
+                cases.extend(sflow::cases(frames, &fname, opts.verbose > 0));
+            }
+        }
+    }
+    if let Some(fname) = &opts.raw {
+        match missing_start_layer(py, &opts.start_layer) {
+            Some(why) => eprintln!("{} left out: {}", fname, why),
+            None => cases.extend(raw_cases(fname)),
+        }
+    }
+    if let Some(fname) = &opts.batch {
+        cases.extend(batch_cases(fname));
+    }
+    if let Some(dir) = &opts.corpus {
+        cases.extend(corpus_cases(dir));
+    }
+    if let Some(wrap) = &opts.wrap {
+        cases.push(wrapped_stdin_case(wrap));
+    }
+    for name in &opts.suite {
+        let suite = suites::find(name).unwrap_or_else(|e| panic!("{}", e));
+        for module in suite.contrib {
+            scapy::load_contrib(py, module).unwrap_or_else(|e| panic!("{}", e));
+        }
+        if let Some(why) = missing_start_layer(py, suite.start) {
+            eprintln!("suite {} left out: {}", name, why);
+            continue;
+        }
+        cases.extend(suites::cases(name).unwrap_or_else(|e| panic!("{}", e)));
+    }
+    if opts.batch.is_none() && opts.wrap.is_none() && !opts.sweep_field.is_empty() {
+        cases.push(Case {
+            source: "scapy-expr".to_string(),
+            input: Input::Expr(opts.scapy_expr.clone()),
+            tag: None,
+            annotations: Default::default(),
+            timestamp: None,
+        });
+    }
+    let mut generated = vec![];
+    if opts.mutate {
+        generated.extend(mutated_cases(py, &cases));
+    }
+    if opts.length_sweep {
+        generated.extend(length_sweep_cases(py, &cases));
+    }
+    for spec in &opts.sweep_field {
+        generated.extend(sweep_field_cases(py, &cases, spec));
+    }
+    cases.extend(generated);
+    cases
+}
+
+/// The reference backends selected with --backend.
+pub fn backends<'py>(py: Python<'py>, opts: &Opts) -> Vec<Box<dyn Backend + 'py>> {
+    if let Some(path) = &opts.recording {
+        let recording = Recording::load(path).unwrap_or_else(|e| panic!("{}", e));
+        return vec![Box::new(Recorded::new(&recording, opts.skip_bytes))];
+    }
+    opts.backend
+        .iter()
+        .map(|spec| {
+            backend::from_spec(py, spec, &opts.start_layer).unwrap_or_else(|e| panic!("{}", e))
+        })
+        .collect()
+}
+
+/// The part of a frame to decode: what follows the --skip-bytes prefix.
+pub fn entry<'d>(opts: &Opts, data: &'d [u8]) -> &'d [u8] {
+    data.get(opts.skip_bytes..).unwrap_or(&[])
+}
+
+/// Benchmark oside's decoding of the frames of the --pcap captures.
+///
+/// oside only offers an owning decode path, so there is no borrowing one to
+/// measure it against; instead each frame is decoded --bench-iterations
+/// times and every decode is checked to produce the same JSON as the first.
+pub fn run_bench(opts: &Opts, arg: &str) -> i32 {
+    let filter = parse_filter(opts);
+    let frames: Vec<(String, pcap::Frame)> = glob::expand(arg)
+        .unwrap_or_else(|e| panic!("--pcap {}", e))
+        .into_iter()
+        .flat_map(|fname| {
+            let frames = read_frames(opts, &fname);
+            frames.into_iter().map(move |frame| (fname.clone(), frame))
+        })
+        .collect();
+    sandbox::enter(opts);
+    let mut decoded = 0;
+    let mut bytes = 0;
+    let mut inconsistent = 0;
+    let mut filtered = 0;
+    let mut elapsed = Duration::default();
+    for (fname, frame) in &frames {
+        if !linktype_supported(opts, frame.linktype) {
+            continue;
+        }
+        let data = entry(opts, &frame.data);
+        let first = decode::oside_json(&opts.start_layer, data);
+        // frames oside fails to decode are timed whatever the filter
+        if let (Some(filter), Some(json)) = (&filter, &first) {
+            if !filter.matches(&compare::layers(json)) {
+                filtered += 1;
+                continue;
+            }
+        }
+        for _ in 0..opts.bench_iterations {
+            elapsed += decode::oside_decode_time(&opts.start_layer, data);
+        }
+        decoded += opts.bench_iterations;
+        bytes += opts.bench_iterations * data.len();
+        if decode::oside_json(&opts.start_layer, data) != first {
+            println!(
+                "{}#{}: repeated decode produced different JSON",
+                fname, frame.index
+            );
+            inconsistent += 1;
+        }
+    }
+    let secs = elapsed.as_secs_f64();
+    println!(
+        "{} decodes of {} bytes in {:.3}s: {:.0} frames/s, {:.1} MB/s, {} inconsistent frames",
+        decoded,
+        bytes,
+        secs,
+        decoded as f64 / secs,
+        bytes as f64 / secs / 1e6,
+        inconsistent
+    );
+    if filtered > 0 {
+        println!("{} frames left out by --filter", filtered);
+    }
+    if inconsistent > 0 {
+        1
+    } else {
+        0
+    }
+}
+
+/// Reorder cases so that those with the highest `results::priorities()`
+/// run first, keeping the original order among equals.
+pub fn prioritize(cases: &mut [Case], records: &[results::Record]) {
+    let prio = results::priorities(records);
+    let score = |c: &Case| prio.get(&c.source).copied().unwrap_or(0.0);
+    cases.sort_by(|a, b| score(b).partial_cmp(&score(a)).unwrap());
+}
+
+/// Quote a command line argument for the shell, if it needs it.
+fn shell_quote(arg: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "-_./=:,@%+".contains(c);
+    if !arg.is_empty() && arg.chars().all(plain) {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+/// Print the --field-stats table, least agreed-on fields first.
+fn print_field_stats(stats: &BTreeMap<String, (usize, usize)>) {
+    let mut rows: Vec<_> = stats
+        .iter()
+        .map(|(path, (agreed, total))| (path, *agreed, *total, *agreed as f64 / *total as f64))
+        .collect();
+    rows.sort_by(|a, b| a.3.partial_cmp(&b.3).unwrap());
+    println!(
+        "{:<30} {:>8} {:>8} {:>7}",
+        "field", "agreed", "compared", "agree%"
+    );
+    for (path, agreed, total, ratio) in rows {
+        println!(
+            "{:<30} {:>8} {:>8} {:>6.1}%",
+            path,
+            agreed,
+            total,
+            ratio * 100.0
+        );
+    }
+}
+
+/// Print which Scapy classes and fields the run dissected, and which of
+/// oside's layers it decoded, with the common classes and the oside layers
+/// it never reached.
+fn print_class_coverage(py: Python, opts: &Opts, oside_seen: &BTreeSet<String>) {
+    let coverage = match scapy::class_coverage(py) {
+        Ok(coverage) => coverage,
+        Err(e) => {
+            eprintln!("could not get Scapy's class coverage: {}", e);
+            return;
+        }
+    };
+    let fields: usize = coverage.seen.values().map(|c| c.fields.len()).sum();
+    let all: usize = coverage.seen.values().map(|c| c.all.len()).sum();
+    println!(
+        "Scapy class coverage: {} classes dissected, {} of their {} fields",
+        coverage.seen.len(),
+        fields,
+        all
+    );
+    for (name, class) in &coverage.seen {
+        let unexercised: Vec<&str> = class
+            .all
+            .iter()
+            .filter(|f| !class.fields.contains(f))
+            .map(|f| f.as_str())
+            .collect();
+        if opts.verbose > 0 && !unexercised.is_empty() {
+            println!("  {}: never dissected {}", name, unexercised.join(", "));
+        }
+    }
+    if !coverage.missing.is_empty() {
+        println!(
+            "  common classes never dissected: {}",
+            coverage.missing.join(", ")
+        );
+    }
+    let registered = decode::oside_layer_names();
+    let unseen: Vec<&str> = registered
+        .iter()
+        .filter(|name| !oside_seen.contains(&name.to_lowercase()))
+        .map(|name| name.as_str())
+        .collect();
+    println!(
+        "oside layer coverage: {} of {} layers decoded",
+        registered.len() - unseen.len(),
+        registered.len()
+    );
+    if opts.verbose > 0 && !unseen.is_empty() {
+        println!("  never decoded: {}", unseen.join(", "));
+    }
+}
+
+/// The most differences listed in a --pcap-out packet comment.
+const COMMENT_DIFFS: usize = 8;
+
+/// How many cases' frames the backends are given ahead, see
+/// `Backend::prefetch`.
+const PREFETCH: usize = 256;
+
+/// The --pcap-out writer, unless frames starting at the --start-layer have
+/// no link type.
+fn open_pcap_out(opts: &Opts, path: &str) -> Option<pcap::NgWriter<BufWriter<File>>> {
+    let linktype = match opts.start_layer.as_str() {
+        "ip" | "ipv6" => pcap::LINKTYPE_RAW,
+        start => match pcap::linktype(start) {
+            Some(linktype) => linktype,
+            None => {
+                eprintln!(
+                    "warning: --pcap-out cannot write frames starting at {}, which has no link type",
+                    start
+                );
+                return None;
+            }
+        },
+    };
+    let file = File::create(path).unwrap_or_else(|e| panic!("{}: {}", path, e));
+    let writer = pcap::NgWriter::new(BufWriter::new(file), linktype)
+        .unwrap_or_else(|e| panic!("{}: {}", path, e));
+    Some(writer)
+}
+
+/// The --pcap-out comment of a case's frame: its ID and source, verdict and
+/// differences, without their values if `anonymized`.
+fn packet_comment(case: &Case, passed: bool, diffs: &[compare::Diff], anonymized: bool) -> String {
+    let mut comment = format!(
+        "oside-tests case {} ({}): {}",
+        case.id(),
+        case.source,
+        if passed { "passed" } else { "failed" }
+    );
+    for d in diffs.iter().take(COMMENT_DIFFS) {
+        if anonymized {
+            comment += &format!("\n{}", d.path);
+            continue;
+        }
+        comment += &format!(
+            "\n{}: reference {}, oside {}",
+            d.path,
+            compare::value_str(&d.reference),
+            compare::value_str(&d.oside)
+        );
+    }
+    if diffs.len() > COMMENT_DIFFS {
+        comment += &format!("\n... and {} more", diffs.len() - COMMENT_DIFFS);
+    }
+    comment
+}
+
+/// Runs cases one by one, keeping the tallies for the summary.
+pub struct Runner<'a, 'py> {
+    py: Python<'py>,
+    opts: &'a Opts,
+    backends: &'a mut [Box<dyn Backend + 'py>],
+    filter: Option<Filter>,
+    normalizers: Vec<Box<dyn Normalizer>>,
+    comparators: Vec<Arc<dyn LayerComparator>>,
+    anonymizer: Option<Anonymizer>,
+    /// Whether to highlight the differing characters of values.
+    color: bool,
+    isolate: Isolate,
+    fcs: Fcs,
+    results: Option<results::Db>,
+    /// The latest stored oside version and JSON of each case, and the
+    /// current case's JSON, for --store-oside-json.
+    stored_json: HashMap<String, (String, serde_json::Value)>,
+    current_json: Option<serde_json::Value>,
+    /// Cases whose oside JSON changed since it was stored, and those of
+    /// them still passing.
+    oside_changed: usize,
+    oside_changed_passing: usize,
+    /// Cases run and failed per tag.
+    tags: BTreeMap<String, (usize, usize)>,
+    /// Cases agreeing on and comparing each field, for --field-stats.
+    field_stats: BTreeMap<String, (usize, usize)>,
+    /// What the interpreter printed during the current case.
+    output: String,
+    /// Representation divergences as (path, reference, oside), with the
+    /// number of cases showing each.
+    representation: BTreeMap<(String, String, String), usize>,
+    report: Option<Report>,
+    notifier: Option<Notifier>,
+    known_failures: Option<KnownFailures>,
+    cache: Option<Cache>,
+    /// The frame of the current case, to cache if it passes.
+    current: Option<Vec<u8>>,
+    /// What --pcap-out is writing, and the frame of the current case.
+    pcap_out: Option<pcap::NgWriter<BufWriter<File>>>,
+    frame: Option<Vec<u8>>,
+    /// The comparison tier the cases have to pass, from --level.
+    level: Option<Level>,
+    /// The tier the current case passed, 0 for none, if it was compared by
+    /// tiers.
+    case_level: Option<u8>,
+    /// Cases compared by tiers, by the highest tier passed.
+    levels: BTreeMap<Option<Level>, usize>,
+    /// What the current case is annotated to fail with, if anything.
+    expect: Option<Expect>,
+    /// How the current case failed so far: the first failure's message,
+    /// and whether Scapy or oside reported an error.
+    failure: Option<String>,
+    scapy_error: bool,
+    oside_error: bool,
+    /// Cases failing as their annotation expects.
+    expected: usize,
+    /// What --record-reference is writing.
+    recorder: Option<Recording>,
+    /// The --recording entries by case ID, and where they were recorded.
+    recording: Option<(HashMap<String, recording::Entry>, Recording)>,
+    total_allocs: alloc_stats::Snapshot,
+    started: Instant,
+    /// Failures counted, by signature.
+    signatures: BTreeMap<String, usize>,
+    /// The verdict of each case's last run in the --results-db, for
+    /// --max-new-failures.
+    last_verdicts: Option<HashMap<String, bool>>,
+    /// Failing cases that did not fail in their last recorded run.
+    new_failures: usize,
+    /// Whether --fail-fast or --abort-after asks to stop.
+    aborted: bool,
+    run: usize,
+    compared: usize,
+    failed: usize,
+    /// Cases left out by --check-oracle.
+    inconsistent: usize,
+    /// Cases left out by --filter.
+    filtered: usize,
+    /// Field differences layer comparators took as equivalent.
+    equivalent: usize,
+    /// Differences of truncated frames explained by the cut.
+    truncation: usize,
+    /// oside's layers seen, for --class-coverage.
+    oside_seen: BTreeSet<String>,
+    /// Frames an FCS was taken off, and those whose FCS was wrong.
+    fcs_split: usize,
+    fcs_bad: usize,
+    /// Cases with offload artifacts fixed by --tolerate-offload, and
+    /// whether the current case is one.
+    offload: usize,
+    case_offload: bool,
+    /// Failures suppressed by --known-failures.
+    known: usize,
+    /// Differences between the local Scapy and the --recording.
+    environment: usize,
+    /// Cases skipped for having passed before.
+    cached: usize,
+    /// Cases agreeing on the --compare-summary one-liner, and those of them
+    /// failing the field comparison.
+    summary_agreed: usize,
+    summary_only: usize,
+    /// Cases every side rejected, for --compare-rejections, and how many of
+    /// them for materially different reasons.
+    rejected: usize,
+    rejections_differ: usize,
+    /// oside's layer names, for --reverse.
+    oside_layer_names: Vec<String>,
+    /// Expressions oside built for --reverse, and those it could not.
+    reverse_built: usize,
+    reverse_skipped: usize,
+    slow: usize,
+    /// The result of every case, in order, for --verify-determinism.
+    stream: Option<Vec<String>>,
+}
+
+/// Compare the reference's and oside's dissection of each case, returning
+/// the process exit code.
+pub fn run<'py>(
+    py: Python<'py>,
+    backends: &mut [Box<dyn Backend + 'py>],
+    opts: &Opts,
+    mut cases: Vec<Case>,
+) -> i32 {
+    // before --changed-protocols has oside decode the frames to select them
+    sandbox::enter(opts);
+    if let Some(id) = &opts.case {
+        select_case(&mut cases, id);
+    }
+    if let Some(changed) = changed_protocols(opts) {
+        select_changed(py, opts, &mut cases, &changed);
+    }
+    if let Some(seed) = opts.shuffle {
+        let seed = seed.unwrap_or_else(rng::time_seed);
+        eprintln!(
+            "shuffling the cases with seed {} (--shuffle {} repeats the order)",
+            seed, seed
+        );
+        Rng::new(seed).shuffle(&mut cases);
+    }
+    if opts.prioritize_failures {
+        match &opts.results_db {
+            Some(path) => prioritize(&mut cases, &results::load(path)),
+            None => eprintln!("warning: --prioritize-failures needs --results-db"),
+        }
+    }
+    let runs = opts.verify_determinism.unwrap_or(1).max(1);
+    let mut first: Option<Vec<String>> = None;
+    let mut code = 0;
+    for n in 1..=runs {
+        if runs > 1 {
+            eprintln!("determinism run {} of {}", n, runs);
+        }
+        let mut runner = Runner::new(py, backends, opts);
+        let mut budget = Budget::from_opts(opts, None);
+        let mut order: Vec<&Case> = cases.iter().collect();
+        let mut i = 0;
+        let mut prefetched = 0;
+        while i < order.len() && !runner.aborted() {
+            if let Some(budget) = &mut budget {
+                if let Some(fits) = budget.plan(order.len() - i) {
+                    eprintln!(
+                        "--duration: {} of the remaining {} cases fit, spread over them",
+                        fits,
+                        order.len() - i
+                    );
+                    let rest = order.split_off(i);
+                    order.extend(budget::spread(&rest, fits));
+                    prefetched = i;
+                    continue;
+                }
+                if !budget.next() {
+                    break;
+                }
+            }
+            if i >= prefetched {
+                prefetched = (i + PREFETCH).min(order.len());
+                runner.prefetch(&order[i..prefetched]);
+            }
+            runner.case(order[i]);
+            i += 1;
+        }
+        if let Some(budget) = &budget {
+            budget.report(cases.len());
+        }
+        let stream = runner.stream.take();
+        code = code.max(runner.finish());
+        match (&first, stream) {
+            (None, stream) => first = stream,
+            (Some(first), Some(stream)) => {
+                if let Some(divergence) = divergence(first, &stream) {
+                    eprintln!("run {} diverges from run 1 {}", n, divergence);
+                    return 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    if runs > 1 {
+        eprintln!("all {} runs gave identical results", runs);
+    }
+    code
+}
+
+/// Where a run's results first differ from the first run's, if anywhere.
+fn divergence(first: &[String], other: &[String]) -> Option<String> {
+    let at = first.iter().zip(other).position(|(a, b)| a != b);
+    match at {
+        Some(i) => Some(format!(
+            "at its case {}:\n  run 1: {}\n  now:   {}",
+            i + 1,
+            first[i],
+            other[i]
+        )),
+        None if first.len() != other.len() => Some(format!(
+            "after {} cases: run 1 has {} results, this one {}",
+            first.len().min(other.len()),
+            first.len(),
+            other.len()
+        )),
+        None => None,
+    }
+}
+
+impl<'a, 'py> Runner<'a, 'py> {
+    pub fn new(
+        py: Python<'py>,
+        backends: &'a mut [Box<dyn Backend + 'py>],
+        opts: &'a Opts,
+    ) -> Runner<'a, 'py> {
+        if opts.alloc_stats && !alloc_stats::enabled() {
+            eprintln!("warning: --alloc-stats needs the global-allocator-counting feature; counts will be zero");
+        }
+        if opts.store_oside_json && opts.results_db.is_none() {
+            eprintln!("warning: --store-oside-json needs --results-db");
+        }
+        let results = opts
+            .results_db
+            .as_ref()
+            .map(|path| results::Db::open(path).unwrap_or_else(|e| panic!("{}: {}", path, e)));
+        scapy::capture_output(py);
+        let mut runner = Runner {
+            py,
+            opts,
+            backends,
+            filter: parse_filter(opts),
+            normalizers: vec![],
+            comparators: comparator::registered(),
+            anonymizer: if opts.anonymize {
+                Some(Anonymizer::new())
+            } else {
+                None
+            },
+            results,
+            stored_json: match (&opts.results_db, opts.store_oside_json) {
+                (Some(path), true) => results::last_oside_json(&results::load(path)),
+                _ => HashMap::new(),
+            },
+            current_json: None,
+            oside_changed: 0,
+            oside_changed_passing: 0,
+            color: highlight::enabled(&opts.color).unwrap_or_else(|e| panic!("{}", e)),
+            isolate: Isolate::parse(&opts.isolate).unwrap_or_else(|e| panic!("{}", e)),
+            fcs: Fcs::parse(&opts.fcs).unwrap_or_else(|e| panic!("{}", e)),
+            tags: BTreeMap::new(),
+            representation: BTreeMap::new(),
+            field_stats: BTreeMap::new(),
+            output: String::new(),
+            report: opts
+                .report
+                .as_ref()
+                .map(|r| Report::parse(r).unwrap_or_else(|e| panic!("{}", e))),
+            notifier: Notifier::new(opts.notify_webhook.as_deref(), opts.notify_smtp.as_ref()),
+            known_failures: opts
+                .known_failures
+                .as_ref()
+                .map(|path| KnownFailures::load(path).unwrap_or_else(|e| panic!("{}", e))),
+            cache: Cache::open(py, opts),
+            current: None,
+            pcap_out: opts
+                .pcap_out
+                .as_ref()
+                .and_then(|path| open_pcap_out(opts, path)),
+            frame: None,
+            level: opts
+                .level
+                .as_ref()
+                .map(|l| Level::parse(l).unwrap_or_else(|e| panic!("{}", e))),
+            case_level: None,
+            levels: BTreeMap::new(),
+            expect: None,
+            failure: None,
+            scapy_error: false,
+            oside_error: false,
+            expected: 0,
+            recorder: opts
+                .record_reference
+                .as_ref()
+                .map(|_| Recording::new(py, &opts.backend[0])),
+            recording: opts.recording.as_ref().map(|path| {
+                let recording = Recording::load(path).unwrap_or_else(|e| panic!("{}", e));
+                (recording.by_id(), recording)
+            }),
+            total_allocs: alloc_stats::Snapshot::default(),
+            started: Instant::now(),
+            signatures: BTreeMap::new(),
+            last_verdicts: opts.max_new_failures.and_then(|_| match &opts.results_db {
+                Some(path) => Some(results::last_verdicts(&results::load(path))),
+                None => {
+                    eprintln!("warning: --max-new-failures needs --results-db; every failure counts as new");
+                    None
+                }
+            }),
+            new_failures: 0,
+            aborted: false,
+            run: 0,
+            compared: 0,
+            failed: 0,
+            inconsistent: 0,
+            filtered: 0,
+            equivalent: 0,
+            truncation: 0,
+            oside_seen: BTreeSet::new(),
+            fcs_split: 0,
+            fcs_bad: 0,
+            offload: 0,
+            case_offload: false,
+            known: 0,
+            environment: 0,
+            cached: 0,
+            summary_agreed: 0,
+            summary_only: 0,
+            rejected: 0,
+            rejections_differ: 0,
+            oside_layer_names: if opts.reverse {
+                decode::oside_layer_names()
+            } else {
+                vec![]
+            },
+            reverse_built: 0,
+            reverse_skipped: 0,
+            slow: 0,
+            stream: opts.verify_determinism.map(|_| vec![]),
+        };
+        let keep: Vec<ValueClass> = opts
+            .keep_representation
+            .iter()
+            .map(|class| ValueClass::parse(class).unwrap_or_else(|e| panic!("{}", e)))
+            .collect();
+        let canonical = Canonical::new(&keep);
+        if !canonical.is_empty() {
+            runner.add_normalizer(Box::new(canonical));
+        }
+        for spec in &opts.ignore_field {
+            let normalizer = IgnoreField::parse(spec).unwrap_or_else(|e| panic!("{}", e));
+            runner.add_normalizer(Box::new(normalizer));
+        }
+        if let Some(form) = &opts.normalize_text {
+            let form = TextForm::parse(form).unwrap_or_else(|e| panic!("{}", e));
+            runner.add_normalizer(Box::new(TextEncoding(form)));
+        }
+        if let Some(limit) = opts.elide_payloads {
+            runner.add_normalizer(Box::new(ElidePayloads(limit)));
+        }
+        if opts.semantic {
+            for comparator in semantic::comparators() {
+                runner.add_comparator(comparator);
+            }
+        }
+        if opts.class_coverage {
+            scapy::start_class_coverage(py);
+        }
+        if runner.isolate == Isolate::Namespace {
+            scapy::snapshot_namespace(py);
+        }
+        runner
+    }
+
+    /// Have `normalizer` rewrite both sides of every later comparison,
+    /// after the normalizers added before it.
+    pub fn add_normalizer(&mut self, normalizer: Box<dyn Normalizer>) {
+        self.normalizers.push(normalizer);
+    }
+
+    /// Have `comparator` judge the differences of every later comparison,
+    /// after the registered ones and those added before it.
+    pub fn add_comparator(&mut self, comparator: Arc<dyn LayerComparator>) {
+        self.comparators.push(comparator);
+    }
+
+    /// Print the summaries, returning the process exit code.
+    pub fn finish(mut self) -> i32 {
+        let opts = self.opts;
+        scapy::restore_output(self.py);
+        if let Some(out) = self.pcap_out.take() {
+            if let Err(e) = out.into_inner().flush() {
+                eprintln!("could not write --pcap-out: {}", e);
+            }
+        }
+        eprintln!(
+            "{} of {} cases compared, {} failed, {} slow",
+            self.compared, self.run, self.failed, self.slow
+        );
+        if self.inconsistent > 0 {
+            eprintln!(
+                "{} cases left out where scapy does not round-trip",
+                self.inconsistent
+            );
+        }
+        if self.filtered > 0 {
+            eprintln!("{} cases left out by --filter", self.filtered);
+        }
+        if self.offload > 0 {
+            eprintln!(
+                "{} cases with offload artifacts fixed up, tagged offload",
+                self.offload
+            );
+        }
+        if self.fcs_split > 0 {
+            eprintln!(
+                "{} frames compared without their FCS, {} of them with a bad one",
+                self.fcs_split, self.fcs_bad
+            );
+        }
+        if self.oside_changed > 0 {
+            eprintln!(
+                "{} cases whose oside output changed since it was stored, {} of them still matching the reference",
+                self.oside_changed, self.oside_changed_passing
+            );
+        }
+        if self.equivalent > 0 {
+            eprintln!(
+                "{} field differences taken as equivalent by layer comparators",
+                self.equivalent
+            );
+        }
+        if self.truncation > 0 {
+            eprintln!(
+                "{} differences of truncated frames explained by the cut, not counted",
+                self.truncation
+            );
+        }
+        if self.known > 0 {
+            eprintln!("{} known failures not counted", self.known);
+        }
+        if self.expected > 0 {
+            eprintln!("{} cases failed as expected", self.expected);
+        }
+        if self.cached > 0 {
+            eprintln!(
+                "{} cases passed before and were not run again (--no-cache runs them)",
+                self.cached
+            );
+        }
+        if opts.compare_summary {
+            eprintln!(
+                "{} of {} cases agree on the summary (layer path, addresses and ports), {} of them only on that",
+                self.summary_agreed, self.compared, self.summary_only
+            );
+        }
+        if opts.reverse {
+            eprintln!(
+                "{} expressions built by oside from their fields, {} left out{}",
+                self.reverse_built,
+                self.reverse_skipped,
+                if self.reverse_skipped > 0 && opts.verbose == 0 {
+                    " (-v lists them)"
+                } else {
+                    ""
+                }
+            );
+        }
+        if opts.compare_rejections && self.rejected > 0 {
+            eprintln!(
+                "{} cases rejected by every side, {} of them for materially different reasons",
+                self.rejected, self.rejections_differ
+            );
+        }
+        if self.environment > 0 {
+            eprintln!(
+                "{} environment-dependent differences from the recording not counted",
+                self.environment
+            );
+        }
+        if let (Some(recorder), Some(path)) = (&self.recorder, &opts.record_reference) {
+            match recorder.save(path) {
+                Ok(()) => eprintln!("recorded {} cases into {}", recorder.entries.len(), path),
+                Err(e) => eprintln!("could not write the recording: {}", e),
+            }
+        }
+        if !self.representation.is_empty() {
+            println!("representation divergences (not counted as failures):");
+            for ((path, reference, oside), count) in &self.representation {
+                println!(
+                    "  {}: reference {}, oside {} ({} cases)",
+                    path, reference, oside, count
+                );
+            }
+        }
+        if !self.levels.is_empty() {
+            eprintln!("cases by the highest comparison tier passed:");
+            for (level, count) in &self.levels {
+                let level = level.map_or("none".to_string(), |l| l.to_string());
+                eprintln!("  {}: {}", level, count);
+            }
+        }
+        if opts.field_stats {
+            print_field_stats(&self.field_stats);
+        }
+        if opts.class_coverage {
+            print_class_coverage(self.py, opts, &self.oside_seen);
+        }
+        for (tag, (run, failed)) in &self.tags {
+            eprintln!("  {}: {} cases, {} failed", tag, run, failed);
+        }
+        if opts.alloc_stats {
+            eprintln!(
+                "oside decode made {} allocations, {} bytes in total",
+                self.total_allocs.allocs, self.total_allocs.bytes
+            );
+        }
+        let exit_code = self.gate();
+        if let Some(path) = &opts.summary_json {
+            if let Err(e) = self.summary(exit_code).write(path) {
+                eprintln!("could not write the summary: {}", e);
+            }
+        }
+        exit_code
+    }
+
+    /// The exit code: 1 if any case failed, or with gating options, if any
+    /// of their thresholds is crossed.
+    fn gate(&self) -> i32 {
+        let opts = self.opts;
+        if opts.max_failures.is_none()
+            && opts.max_new_failures.is_none()
+            && opts.min_pass_rate.is_none()
+        {
+            return if self.failed > 0 { 1 } else { 0 };
+        }
+        let mut crossed = vec![];
+        if let Some(max) = opts.max_failures.filter(|max| self.failed > *max) {
+            crossed.push(format!("{} failures, at most {} allowed", self.failed, max));
+        }
+        if let Some(max) = opts.max_new_failures.filter(|max| self.new_failures > *max) {
+            crossed.push(format!(
+                "{} new failures, at most {} allowed",
+                self.new_failures, max
+            ));
+        }
+        let pass_rate = if self.compared == 0 {
+            100.0
+        } else {
+            (self.compared - self.failed) as f64 * 100.0 / self.compared as f64
+        };
+        if let Some(min) = opts.min_pass_rate.filter(|min| pass_rate < *min) {
+            crossed.push(format!(
+                "{:.2}% of cases passed, at least {}% required",
+                pass_rate, min
+            ));
+        }
+        for reason in &crossed {
+            eprintln!("gate failed: {}", reason);
+        }
+        if crossed.is_empty() {
+            if self.failed > 0 {
+                eprintln!(
+                    "gate passed with {} failures ({} new)",
+                    self.failed, self.new_failures
+                );
+            }
+            0
+        } else {
+            1
+        }
+    }
+
+    /// The --summary-json summary of the run.
+    fn summary(&self, exit_code: i32) -> Summary {
+        let opts = self.opts;
+        let mut reports = BTreeMap::new();
+        let written = [
+            ("results_db", opts.results_db.as_ref()),
+            ("save_failures", opts.save_failures.as_ref()),
+            ("pcap_out", opts.pcap_out.as_ref()),
+            ("record_reference", opts.record_reference.as_ref()),
+            ("bundle", opts.bundle.as_ref()),
+            ("slow_dir", Some(&opts.slow_dir).filter(|_| self.slow > 0)),
+        ];
+        for (option, path) in written {
+            if let Some(path) = path {
+                reports.insert(option.to_string(), path.clone());
+            }
+        }
+        Summary {
+            schema_version: SchemaVersion,
+            exit_code,
+            duration_secs: self.started.elapsed().as_secs_f64(),
+            counts: summary::Counts {
+                run: self.run,
+                compared: self.compared,
+                failed: self.failed,
+                new_failures: self.new_failures,
+                known: self.known,
+                expected: self.expected,
+                cached: self.cached,
+                inconsistent: self.inconsistent,
+                filtered: self.filtered,
+                environment: self.environment,
+                slow: self.slow,
+            },
+            signatures: summary::top_signatures(&self.signatures),
+            reports,
+        }
+    }
+
+    /// Write a case into a corpus directory, anonymizing it first if requested.
+    fn save_case(&mut self, dir: &str, data: &[u8], meta: &corpus::Meta) {
+        let mut data = data.to_vec();
+        if let Some(anonymizer) = &mut self.anonymizer {
+            anonymizer.frame(&self.opts.start_layer, &mut data);
+        }
+        if let Err(e) = corpus::save(dir, &data, meta) {
+            eprintln!("could not save {} into {}: {}", meta.source, dir, e);
+        }
+    }
+
+    /// Count a failing case, report it and save it into the --save-failures
+    /// corpus, if one was given.
+    fn fail(&mut self, case: &Case, message: &str, data: &[u8], diffs: &[compare::Diff]) {
+        if self.expect.is_some() {
+            // Whether it counts is up to record(), against the expectation.
+            self.failure.get_or_insert_with(|| message.to_string());
+            return;
+        }
+        let opts = self.opts;
+        let signature = compare::failure_signature(message, diffs);
+        if let Some(known) = &self.known_failures {
+            match known.check(&signature) {
+                known_failures::Match::Known(entry) => {
+                    println!(
+                        "{}: known failure: {}",
+                        case.label(),
+                        entry.reason.as_deref().unwrap_or(&signature)
+                    );
+                    self.known += 1;
+                    return;
+                }
+                known_failures::Match::Lapsed(entry, why) => {
+                    println!(
+                        "{}: known-failures entry for {} no longer applies: {}",
+                        case.label(),
+                        entry.signature,
+                        why
+                    );
+                }
+                known_failures::Match::None => {
+                    println!("{}: signature: {}", case.label(), signature);
+                }
+            }
+        }
+        self.failed += 1;
+        *self.signatures.entry(signature.clone()).or_default() += 1;
+        let last = self
+            .last_verdicts
+            .as_ref()
+            .and_then(|verdicts| verdicts.get(&case.source));
+        if last != Some(&false) {
+            self.new_failures += 1;
+        }
+        if let Some(report) = &self.report {
+            report.failure(&case.source, &format!("[{}] {}", case.id(), message));
+        }
+        let abort_after = if opts.fail_fast {
+            Some(1)
+        } else {
+            opts.abort_after
+        };
+        if abort_after.map_or(false, |n| self.failed >= n) {
+            self.aborted = true;
+            self.print_reproducer(case);
+        }
+        if let Some(notifier) = &mut self.notifier {
+            notifier.failure(&case.id(), &case.source, message, &signature);
+        }
+        let output = self.case_output();
+        if !output.is_empty() {
+            println!("{}: interpreter output:", case.label());
+            for line in output.lines() {
+                println!("  | {}", line);
+            }
+        }
+        if let Some(dir) = &opts.save_failures {
+            let meta = corpus::Meta {
+                source: case.source.clone(),
+                diffs: diffs.iter().map(|d| d.path.clone()).collect(),
+                note: None,
+                output: Some(output).filter(|o| !o.is_empty()),
+                ..Default::default()
+            };
+            self.save_case(dir, data, &meta);
+        }
+    }
+
+    fn record(
+        &mut self,
+        case: &Case,
+        passed: bool,
+        layers: &[compare::Layer],
+        diffs: &[compare::Diff],
+    ) {
+        let passed = match self.expect.take() {
+            Some(expect) => self.check_expectation(case, expect, passed),
+            None => passed,
+        };
+        if let (true, Some(cache), Some(data)) = (passed, &mut self.cache, &self.current) {
+            cache.insert(data, &compare::layer_path(layers));
+        }
+        if let Some(stream) = &mut self.stream {
+            let result = serde_json::json!({
+                "source": case.source,
+                "passed": passed,
+                "layers": compare::layer_path(layers),
+                "diffs": diffs,
+                "level": self.case_level,
+            });
+            stream.push(result.to_string());
+        }
+        if let (Some(out), Some(mut frame)) = (&mut self.pcap_out, self.frame.take()) {
+            if let Some(anonymizer) = &mut self.anonymizer {
+                anonymizer.frame(&self.opts.start_layer, &mut frame);
+            }
+            let comment = packet_comment(case, passed, diffs, self.anonymizer.is_some());
+            // generated cases are stamped with when they ran
+            let timestamp = case.timestamp.unwrap_or_else(|| {
+                SystemTime::now()
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .unwrap_or_default()
+            });
+            if let Err(e) = out.write_frame(timestamp, &frame, Some(&comment)) {
+                eprintln!("could not write {} into --pcap-out: {}", case.label(), e);
+            }
+        }
+        let offload = std::mem::take(&mut self.case_offload).then(|| "offload".to_string());
+        for tag in case.tag.iter().chain(&offload) {
+            let counts = self.tags.entry(tag.clone()).or_default();
+            counts.0 += 1;
+            if !passed {
+                counts.1 += 1;
+            }
+        }
+        let oside_json = self.current_json.take();
+        if let (Some(json), Some((version, stored))) =
+            (&oside_json, self.stored_json.get(&case.source))
+        {
+            if json != stored {
+                self.oside_changed += 1;
+                if passed {
+                    self.oside_changed_passing += 1;
+                }
+                println!(
+                    "{}: oside output changed since oside {}{}",
+                    case.label(),
+                    version,
+                    if passed {
+                        ", still matching the reference"
+                    } else {
+                        ""
+                    }
+                );
+            }
+        }
+        if let Some(db) = &mut self.results {
+            let record = results::Record {
+                schema_version: SchemaVersion,
+                source: case.source.clone(),
+                passed,
+                time: SystemTime::now()
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or(0),
+                layers: compare::layer_path(layers),
+                diffs: diffs.to_vec(),
+                level: self.case_level,
+                oside_version: oside_json
+                    .as_ref()
+                    .map(|_| known_failures::OSIDE_VERSION.to_string()),
+                oside_json,
+            };
+            if let Err(e) = db.append(&record) {
+                eprintln!("could not record result of {}: {}", case.label(), e);
+            }
+        }
+    }
+
+    /// Whether --fail-fast or --abort-after asks to stop the run.
+    pub fn aborted(&self) -> bool {
+        self.aborted
+    }
+
+    /// Give the backends the frames of the next cases, as `run_case` will
+    /// hand them over, to dissect at once where that is cheaper.
+    pub fn prefetch(&mut self, cases: &[&Case]) {
+        let frames: Vec<Vec<u8>> = cases
+            .iter()
+            .filter_map(|case| match &case.input {
+                Input::Frame(data) => {
+                    let mut data = entry(self.opts, data).to_vec();
+                    if self.opts.start_layer == "ether" {
+                        data = fcs::split(self.fcs, &data).0.to_vec();
+                    }
+                    if self.opts.tolerate_offload {
+                        offload::normalize(&self.opts.start_layer, &mut data);
+                    }
+                    Some(data)
+                }
+                Input::Expr(_) => None,
+            })
+            .collect();
+        for backend in self.backends.iter_mut() {
+            backend.prefetch(&frames);
+        }
+    }
+
+    /// Print how to run the failing case again alone.
+    fn print_reproducer(&mut self, case: &Case) {
+        println!(
+            "stopping after {} failure(s); to reproduce {}:",
+            self.failed,
+            case.label()
+        );
+        if self.opts.command.is_none() && has_cases(self.opts) {
+            let mut args = vec![];
+            let mut skip = false;
+            for arg in std::env::args() {
+                if std::mem::take(&mut skip) {
+                    continue;
+                }
+                if arg == "--case" {
+                    skip = true;
+                } else if !arg.starts_with("--case=") {
+                    args.push(shell_quote(&arg));
+                }
+            }
+            println!("  {} --case {}", args.join(" "), case.id());
+        }
+        match &case.input {
+            Input::Expr(expr) => println!("  expression: {}", expr),
+            Input::Frame(frame) => {
+                let mut frame = frame.clone();
+                if let Some(anonymizer) = &mut self.anonymizer {
+                    // nothing is known of what the --skip-bytes prefix holds
+                    let skip = self.opts.skip_bytes.min(frame.len());
+                    frame[..skip].iter_mut().for_each(|b| *b = 0);
+                    anonymizer.frame(&self.opts.start_layer, &mut frame[skip..]);
+                }
+                println!("  frame: {}", scapy::hex(&frame));
+            }
+        }
+    }
+
+    /// Whether a case annotated to fail with `expect` did, given whether the
+    /// comparison passed; an unmet expectation is reported as a failure.
+    fn check_expectation(&mut self, case: &Case, expect: Expect, passed: bool) -> bool {
+        let met = match expect {
+            // every side rejecting the frame passes as agreement, but is
+            // still either side failing
+            Expect::Fail => !passed || self.scapy_error || self.oside_error,
+            Expect::ScapyError => self.scapy_error,
+            Expect::OsideError => self.oside_error,
+        };
+        let failure = self.failure.take();
+        if met {
+            println!(
+                "{}: fails as expected ({}){}",
+                case.label(),
+                expect.name(),
+                failure.map(|f| format!(": {}", f)).unwrap_or_default()
+            );
+            self.expected += 1;
+            return true;
+        }
+        let message = match failure {
+            Some(failure) => format!("expected {}, but: {}", expect.name(), failure),
+            None => format!("expected {}, but passed", expect.name()),
+        };
+        println!("{}: {}", case.label(), message);
+        self.fail(case, &message, &[], &[]);
+        false
+    }
+
+    /// Describe how Scapy fails to render a frame back into its bytes, if it
+    /// does, for --check-oracle.
+    fn check_oracle(&self, data: &[u8]) -> Option<String> {
+        let rendered = match scapy::roundtrip(self.py, &self.opts.start_layer, data) {
+            Ok(rendered) => rendered,
+            Err(e) => return Some(format!("scapy could not render it back: {}", e)),
+        };
+        if rendered == data {
+            return None;
+        }
+        Some(format!(
+            "scapy does not round-trip it: {} bytes rendered from {}, first difference at byte {}",
+            rendered.len(),
+            data.len(),
+            first_difference(&rendered, data)
+        ))
+    }
+
+    /// Unset the checksums of a frame on both sides and compare the bytes
+    /// each builds it into, for --checksum-fill.
+    fn check_checksum_fill(&self, data: &[u8]) -> Option<String> {
+        let start = &self.opts.start_layer;
+        let scapy = match scapy::refill(self.py, start, data) {
+            Ok(bytes) => bytes,
+            Err(e) => return Some(format!("scapy could not refill checksums: {}", e)),
+        };
+        let oside = match decode::oside_refill(start, data) {
+            Some(bytes) => bytes,
+            None => return Some("oside could not refill checksums".to_string()),
+        };
+        if scapy == oside {
+            return None;
+        }
+        Some(format!(
+            "computed checksums differ at byte {}: scapy {}, oside {}",
+            first_difference(&scapy, &oside),
+            scapy::hex(&scapy),
+            scapy::hex(&oside)
+        ))
+    }
+
+    /// Time both decoders on a frame and report whether oside is suspiciously
+    /// slow, or suspiciously fast compared to the reference, per
+    /// --slow-ms/--slow-ratio.
+    fn check_timing(&mut self, data: &[u8]) -> Option<String> {
+        let backend = self.backends[0].as_mut();
+        let oside_time = decode::oside_decode_time(&self.opts.start_layer, data);
+        let ref_time = backend.dissect_time(data).ok()?;
+        let oside_ms = oside_time.as_secs_f64() * 1000.0;
+        let ref_ms = ref_time.as_secs_f64() * 1000.0;
+        let over_threshold = self.opts.slow_ms.map(|ms| oside_ms > ms) == Some(true);
+        let over_ratio = self
+            .opts
+            .slow_ratio
+            .map(|r| oside_ms > ref_ms * r || ref_ms > oside_ms * r)
+            == Some(true);
+        if over_threshold || over_ratio {
+            Some(format!(
+                "oside {:.3}ms, {} {:.3}ms",
+                oside_ms,
+                backend.name(),
+                ref_ms
+            ))
+        } else {
+            None
+        }
+    }
+
+    /// Check that Scapy renders an expression to the same bytes with
+    /// `raw()`, `bytes()` and `build()`, returning the problem if not.
+    fn check_build(&mut self, expr: &str) -> Option<String> {
+        let variants = match scapy::build_variants(self.py, expr) {
+            Ok(variants) => variants,
+            Err(e) => return Some(format!("scapy could not render {}: {}", expr, e)),
+        };
+        let (first_name, first) = &variants[0];
+        let differing: Vec<String> = variants[1..]
+            .iter()
+            .filter(|(_, data)| data != first)
+            .map(|(name, data)| format!("{} gives {}", name, scapy::hex(data)))
+            .collect();
+        if differing.is_empty() {
+            None
+        } else {
+            Some(format!(
+                "scapy renders inconsistently: {} gives {}, {}",
+                first_name,
+                scapy::hex(first),
+                differing.join(", ")
+            ))
+        }
+    }
+
+    /// Build the expression with oside from its layers and the fields set in
+    /// it, for --reverse, returning the failure message if the bytes differ
+    /// from Scapy's `data`.
+    fn check_reverse(&mut self, label: &str, expr: &str, data: &[u8]) -> Option<String> {
+        let built = scapy::set_fields(self.py, expr)
+            .and_then(|layers| reverse::oside_layers(&layers, &self.oside_layer_names))
+            .and_then(|layers| {
+                decode::oside_build(layers).map_err(|e| format!("oside does not take it: {}", e))
+            });
+        let built = match built {
+            Ok(built) => built,
+            Err(e) => {
+                self.reverse_skipped += 1;
+                if self.opts.verbose > 0 {
+                    println!("{}: left out of --reverse, {}", label, e);
+                }
+                return None;
+            }
+        };
+        self.reverse_built += 1;
+        if built == data {
+            return None;
+        }
+        Some(format!(
+            "oside builds it from its fields differently from byte {} on: {}",
+            first_difference(&built, data),
+            scapy::hex(&built)
+        ))
+    }
+
+    /// Add the case, as built and dissected by the first backend, to the
+    /// --record-reference recording.
+    fn record_reference(&mut self, case: &Case) {
+        let (expr, built) = match &case.input {
+            Input::Frame(data) => (None, Ok(data.clone())),
+            Input::Expr(expr) => (Some(expr.clone()), scapy::build(self.py, expr)),
+        };
+        let mut recorded = recording::Entry {
+            id: case.id(),
+            source: case.source.clone(),
+            expr,
+            hex: String::new(),
+            layers: None,
+            error: None,
+        };
+        match built {
+            Ok(data) => {
+                recorded.hex = scapy::hex(&data);
+                match self.backends[0].dissect(entry(self.opts, &data)) {
+                    Ok(layers) => recorded.layers = Some(layers),
+                    Err(e) => recorded.error = Some(e),
+                }
+            }
+            Err(e) => recorded.error = Some(format!("scapy could not build: {}", e)),
+        }
+        self.recorder.as_mut().unwrap().entries.push(recorded);
+    }
+
+    /// With a --recording, the bytes recorded for a generated case instead
+    /// of the ones built here, noting where the two differ.
+    fn replay_build(
+        &mut self,
+        case: &Case,
+        built: Result<Vec<u8>, String>,
+    ) -> Result<Vec<u8>, String> {
+        let (entries, recording) = match &self.recording {
+            Some(recording) => recording,
+            None => return built,
+        };
+        let recorded = match entries.get(&case.id()) {
+            Some(entry) if !entry.hex.is_empty() => {
+                corpus_convert::unhex(&entry.hex).unwrap_or_else(|e| panic!("{}", e))
+            }
+            _ => return built,
+        };
+        let local = match &built {
+            Ok(data) => scapy::hex(data),
+            Err(e) => format!("an error ({})", e),
+        };
+        if built.as_ref().ok() != Some(&recorded) {
+            println!(
+                "{}: environment-dependent: scapy builds {} here, {} on {}",
+                case.label(),
+                local,
+                scapy::hex(&recorded),
+                recording.host()
+            );
+            self.environment += 1;
+        }
+        Ok(recorded)
+    }
+
+    /// With a --recording made with Scapy, note where the local Scapy
+    /// dissects the frame differently from the recorded dissection.
+    fn check_environment(
+        &mut self,
+        case: &Case,
+        data: &[u8],
+        refs: &[(String, Vec<compare::Layer>)],
+    ) {
+        let (_, recording) = self.recording.as_ref().unwrap();
+        if recording.environment.get("reference").map(|r| r.as_str()) != Some("scapy") {
+            return;
+        }
+        let recorded = match refs.first() {
+            Some((_, layers)) => layers,
+            None => return,
+        };
+        let local = match scapy::dissect(self.py, &self.opts.start_layer, data) {
+            Ok(json) => compare::layers(&json),
+            Err(e) => {
+                println!(
+                    "{}: environment-dependent: scapy fails to dissect here: {}",
+                    case.label(),
+                    e
+                );
+                self.environment += 1;
+                return;
+            }
+        };
+        let diffs = compare::compare(recorded, &local);
+        if !diffs.is_empty() {
+            let paths: Vec<&str> = diffs.iter().map(|d| d.path.as_str()).collect();
+            println!(
+                "{}: environment-dependent: scapy dissects {} differently here than on {}",
+                case.label(),
+                paths.join(", "),
+                recording.host()
+            );
+            self.environment += 1;
+        }
+    }
+
+    /// Compare the one-line summaries of the dissections, for
+    /// --compare-summary, returning whether they all agree; a disagreement
+    /// is reported but not counted as a failure.
+    fn check_summary(
+        &mut self,
+        label: &str,
+        oside: &[compare::Layer],
+        refs: &[(String, Vec<compare::Layer>)],
+    ) -> bool {
+        let summary = compare::summary(oside);
+        let differing: Vec<String> = refs
+            .iter()
+            .map(|(name, layers)| (name, compare::summary(layers)))
+            .filter(|(_, s)| *s != summary)
+            .map(|(name, s)| format!("{} {}", name, s))
+            .collect();
+        if differing.is_empty() {
+            self.summary_agreed += 1;
+            return true;
+        }
+        println!(
+            "{}: summary differs: {}, oside {}",
+            label,
+            differing.join(", "),
+            summary
+        );
+        false
+    }
+
+    /// The reason each reference rejects a frame, by name, if all do. The
+    /// reasons are only categorized for --compare-rejections.
+    fn rejections(&mut self, data: &[u8]) -> Option<Vec<(String, Option<Rejection>)>> {
+        let mut out = vec![];
+        for backend in self.backends.iter_mut() {
+            let message = match backend.dissect(data) {
+                Ok(_) => return None,
+                Err(e) => e,
+            };
+            let rejection = self.opts.compare_rejections.then(|| {
+                rejection::classify(&message)
+                    .unwrap_or_else(|| rejection::probe(data, |d| backend.dissect(d).is_ok()))
+            });
+            out.push((backend.name().to_string(), rejection));
+        }
+        Some(out)
+    }
+
+    /// Compare the reasons the references reject a frame with oside's,
+    /// returning the failure message if they differ materially.
+    fn check_rejections(
+        &mut self,
+        label: &str,
+        data: &[u8],
+        rejections: &[(String, Option<Rejection>)],
+    ) -> Option<String> {
+        let start = &self.opts.start_layer;
+        // oside does not say why it rejects a frame.
+        let oside = rejection::probe(data, |d| decode::oside_json(start, d).is_some());
+        self.rejected += 1;
+        let reasons: Vec<String> = rejections
+            .iter()
+            .filter_map(|(name, r)| r.map(|r| (name, r)))
+            .map(|(name, r)| format!("{} {}", name, r))
+            .collect();
+        println!(
+            "{}: rejected as oside {}, {}",
+            label,
+            oside,
+            reasons.join(", ")
+        );
+        let differ = rejections
+            .iter()
+            .any(|(_, r)| r.map_or(false, |r| !r.compatible(oside)));
+        if !differ {
+            return None;
+        }
+        self.rejections_differ += 1;
+        Some(format!(
+            "rejection reasons differ: oside {}, {}",
+            oside,
+            reasons.join(", ")
+        ))
+    }
+
+    /// What the interpreter printed during the current case so far.
+    fn case_output(&mut self) -> String {
+        self.output.push_str(&scapy::take_output(self.py));
+        self.output.clone()
+    }
+
+    /// The highest comparison tier the case passes, for --level; `None` if
+    /// the sides disagree even on the layer path.
+    fn level_passed(
+        &self,
+        data: &[u8],
+        oside: &[compare::Layer],
+        refs: &[(String, Vec<compare::Layer>)],
+        fields_passed: bool,
+    ) -> Option<Level> {
+        let agree = |f: fn(&[compare::Layer]) -> String| {
+            let theirs = f(oside);
+            refs.iter().all(|(_, layers)| f(layers) == theirs)
+        };
+        if !agree(compare::layer_path) {
+            return None;
+        }
+        if !agree(compare::summary) {
+            return Some(Level::Path);
+        }
+        if !fields_passed {
+            return Some(Level::Key);
+        }
+        if decode::oside_encode(&self.opts.start_layer, data).as_deref() != Some(data) {
+            return Some(Level::Fields);
+        }
+        Some(Level::Bytes)
+    }
+
+    /// Run one case, returning whether it passed.
+    pub fn case(&mut self, case: &Case) -> bool {
+        let failed = self.failed;
+        self.run += 1;
+        scapy::take_output(self.py);
+        self.output.clear();
+        self.expect = case.annotations.expect;
+        self.failure = None;
+        self.scapy_error = false;
+        self.oside_error = false;
+        if self.recorder.is_some() {
+            self.record_reference(case);
+        }
+        match self.isolate {
+            Isolate::None => self.run_case(case),
+            Isolate::Namespace => {
+                scapy::reset_namespace(self.py);
+                self.run_case(case);
+            }
+            Isolate::Interpreter => self.run_case_isolated(case),
+        }
+        let output = self.case_output();
+        if self.opts.verbose > 0 && !output.is_empty() {
+            eprint!("{}", output);
+        }
+        self.failed == failed
+    }
+
+    /// Run a case in a sub-interpreter of its own, for --isolate interpreter.
+    fn run_case_isolated(&mut self, case: &Case) {
+        let py = self.py;
+        let sub = scapy::SubInterpreter::enter(py, &self.opts.bind_port)
+            .unwrap_or_else(|e| panic!("--isolate interpreter: {}", e));
+        {
+            // SAFETY: the pool, and with it every object the case creates,
+            // is dropped before the sub-interpreter.
+            let _pool = unsafe { py.new_pool() };
+            scapy::capture_output(py);
+            self.run_case(case);
+            self.case_output();
+        }
+        drop(sub);
+    }
+
+    fn run_case(&mut self, case: &Case) {
+        let label = case.label();
+        self.current = None;
+        self.frame = None;
+        self.current_json = None;
+        self.case_level = None;
+        self.case_offload = false;
+        let data = match &case.input {
+            Input::Frame(data) => data.clone(),
+            Input::Expr(expr) => match self.replay_build(case, scapy::build(self.py, expr)) {
+                Ok(data) => data,
+                Err(e) => {
+                    let message = format!("scapy could not build {}: {}", expr, e);
+                    println!("{}: {}", label, message);
+                    self.scapy_error = true;
+                    self.compared += 1;
+                    self.fail(case, &message, &[], &[]);
+                    self.record(case, false, &[], &[]);
+                    return;
+                }
+            },
+        };
+        if let (Input::Expr(expr), true) = (&case.input, self.opts.check_build) {
+            if let Some(message) = self.check_build(expr) {
+                println!("{}: {}", label, message);
+                self.compared += 1;
+                self.fail(case, &message, &data, &[]);
+                self.record(case, false, &[], &[]);
+                return;
+            }
+        }
+        if let (Input::Expr(expr), true) = (&case.input, self.opts.reverse) {
+            if let Some(message) = self.check_reverse(&label, expr, &data) {
+                println!("{}: {}", label, message);
+                self.compared += 1;
+                self.fail(case, &message, &data, &[]);
+                self.record(case, false, &[], &[]);
+                return;
+            }
+        }
+        let truncated = case
+            .annotations
+            .orig_len
+            .map_or(false, |len| len > data.len());
+        let mut data = entry(self.opts, &data).to_vec();
+        if self.opts.start_layer == "ether" {
+            let (frame, check) = fcs::split(self.fcs, &data);
+            if let Some(check) = check {
+                self.fcs_split += 1;
+                if !check.valid() {
+                    self.fcs_bad += 1;
+                    println!(
+                        "{}: bad FCS {:08x}, the CRC-32 of the frame is {:08x}",
+                        label, check.found, check.computed
+                    );
+                }
+                data = frame.to_vec();
+            }
+        }
+        if self.opts.tolerate_offload {
+            let fixed = offload::normalize(&self.opts.start_layer, &mut data);
+            if !fixed.is_empty() {
+                println!("{}: offload artifacts: {}", label, fixed.join(", "));
+                self.offload += 1;
+                self.case_offload = true;
+            }
+        }
+        if self.pcap_out.is_some() {
+            self.frame = Some(data.clone());
+        }
+        // The cache does not know what a case's annotations require.
+        let cacheable = case.annotations.level.is_none()
+            && case.annotations.expect.is_none()
+            && case.annotations.orig_len.is_none();
+        let cached = match &self.cache {
+            Some(cache) if cacheable => cache.passed(&data).map(|path| path.to_string()),
+            _ => None,
+        };
+        if let Some(path) = cached {
+            self.cached += 1;
+            // the layers as they were decoded, by name, for the record
+            let layers: Vec<compare::Layer> = path
+                .split('/')
+                .filter(|name| !name.is_empty())
+                .map(|name| compare::Layer {
+                    name: name.to_string(),
+                    fields: Default::default(),
+                })
+                .collect();
+            self.record(case, true, &layers, &[]);
+            return;
+        }
+        if cacheable {
+            self.current = Some(data.clone());
+        }
+        let oside_json = decode::oside_json(&self.opts.start_layer, &data);
+        let mut oside_layers = oside_json.as_ref().map(compare::layers).unwrap_or_default();
+        if let Some(filter) = &self.filter {
+            // Frames oside cannot decode are matched against the reference's
+            // layers instead, and run if it cannot dissect them either: they
+            // are the failures this tool is after.
+            let matched = match &oside_json {
+                Some(_) => filter.matches(&oside_layers),
+                None => match self.backends.first_mut().map(|b| b.dissect(&data)) {
+                    Some(Ok(layers)) => filter.matches(&layers),
+                    _ => true,
+                },
+            };
+            if !matched {
+                self.filtered += 1;
+                return;
+            }
+        }
+        if self.opts.store_oside_json {
+            self.current_json = oside_json.clone();
+        }
+        if self.opts.class_coverage {
+            self.oside_seen
+                .extend(oside_layers.iter().map(|l| l.name.clone()));
+        }
+        if self.opts.print_json {
+            println!("{}", oside_json.clone().unwrap_or_default());
+        }
+        // Left out before being counted: they have no record, so counting
+        // them would skew the pass rate against the results database.
+        if self.opts.check_oracle && oside_json.is_some() {
+            if let Some(note) = self.check_oracle(&data) {
+                println!("{}: left out, {}", label, note);
+                self.inconsistent += 1;
+                return;
+            }
+        }
+        self.compared += 1;
+        if oside_json.is_none() {
+            self.oside_error = true;
+            // Degenerate input every side rejects is agreement, not a failure.
+            if let Some(rejections) = self.rejections(&data) {
+                self.scapy_error = true;
+                println!("{}: oside and the reference all fail to decode", label);
+                if self.opts.compare_rejections {
+                    if let Some(message) = self.check_rejections(&label, &data, &rejections) {
+                        self.fail(case, &message, &data, &[]);
+                        self.record(case, false, &[], &[]);
+                        return;
+                    }
+                }
+                self.record(case, true, &[], &[]);
+                return;
+            }
+            println!("{}: oside failed to decode", label);
+            self.fail(case, "oside failed to decode", &data, &[]);
+            self.record(case, false, &[], &[]);
+            return;
+        }
+        if self.opts.checksum_fill {
+            if let Some(message) = self.check_checksum_fill(&data) {
+                println!("{}: {}", label, message);
+                self.fail(case, &message, &data, &[]);
+                self.record(case, false, &oside_layers, &[]);
+                return;
+            }
+        }
+        let mut refs = vec![];
+        let mut errors = vec![];
+        for backend in self.backends.iter_mut() {
+            match backend.dissect(&data) {
+                Ok(layers) => refs.push((backend.name().to_string(), layers)),
+                Err(e) => errors.push(format!("{} failed to dissect: {}", backend.name(), e)),
+            }
+        }
+        if !errors.is_empty() {
+            self.scapy_error = true;
+            for error in &errors {
+                println!("{}: {}", label, error);
+            }
+            self.fail(case, &errors.join("\n"), &data, &[]);
+            self.record(case, false, &oside_layers, &[]);
+            return;
+        }
+        if self.recording.is_some() {
+            self.check_environment(case, &data, &refs);
+        }
+        for normalizer in &self.normalizers {
+            normalizer.normalize(Side::Oside, &mut oside_layers);
+            for (name, layers) in refs.iter_mut() {
+                normalizer.normalize(Side::Reference(name.as_str()), layers);
+            }
+        }
+        if self.opts.alloc_stats {
+            let allocs = decode::oside_decode_allocs(&self.opts.start_layer, &data);
+            println!(
+                "{}: oside decode made {} allocations, {} bytes",
+                label, allocs.allocs, allocs.bytes
+            );
+            self.total_allocs.allocs += allocs.allocs;
+            self.total_allocs.bytes += allocs.bytes;
+        }
+        if self.opts.slow_ms.is_some() || self.opts.slow_ratio.is_some() {
+            if let Some(note) = self.check_timing(&data) {
+                println!("{}: slow decode ({})", label, note);
+                self.slow += 1;
+                let meta = corpus::Meta {
+                    source: case.source.clone(),
+                    diffs: vec![],
+                    note: Some(note),
+                    output: Some(self.case_output()).filter(|o| !o.is_empty()),
+                    ..Default::default()
+                };
+                let opts = self.opts;
+                self.save_case(&opts.slow_dir, &data, &meta);
+            }
+        }
+        if self.opts.field_stats {
+            for (_, layers) in &refs {
+                for (path, agreed) in compare::field_agreement(layers, &oside_layers) {
+                    let stats = self.field_stats.entry(path).or_default();
+                    stats.1 += 1;
+                    if agreed {
+                        stats.0 += 1;
+                    }
+                }
+            }
+        }
+        let summary_agreed =
+            self.opts.compare_summary && self.check_summary(&label, &oside_layers, &refs);
+        let (votes, mut diffs) = consensus::vote(&oside_layers, &refs);
+        let before = diffs.len();
+        diffs.retain(|d| !comparator::equivalent(&self.comparators, d));
+        self.equivalent += before - diffs.len();
+        if truncated {
+            let before = diffs.len();
+            diffs.retain(|d| !compare::is_truncation(d, &oside_layers, &refs));
+            if diffs.len() < before {
+                println!(
+                    "{}: {} difference(s) explained by the frame being truncated",
+                    label,
+                    before - diffs.len()
+                );
+            }
+            self.truncation += before - diffs.len();
+        }
+        let (representation, diffs): (Vec<_>, Vec<_>) = diffs
+            .into_iter()
+            .partition(|d| !self.opts.strict && compare::is_representation(d));
+        for d in &representation {
+            let key = (d.path.clone(), d.reference.to_string(), d.oside.to_string());
+            *self.representation.entry(key).or_default() += 1;
+        }
+        let votes: Vec<_> = votes
+            .into_iter()
+            .filter(|v| diffs.iter().any(|d| d.path == v.path))
+            .collect();
+        let mut warnings = vec![];
+        if self.opts.strict {
+            warnings.extend(compare::undecoded("oside", &oside_layers));
+            for (name, layers) in &refs {
+                warnings.extend(compare::undecoded(name, layers));
+            }
+        }
+        let fields_passed = diffs.is_empty() && warnings.is_empty();
+        let required = case.annotations.level.or(self.level);
+        let level = required.map(|_| self.level_passed(&data, &oside_layers, &refs, fields_passed));
+        let passed = match (required, level) {
+            (Some(required), Some(level)) => level >= Some(required),
+            _ => fields_passed,
+        };
+        if !diffs.is_empty() {
+            let swapped = |path: &str| {
+                diffs
+                    .iter()
+                    .any(|d| d.path == path && compare::is_byte_swap(d))
+            };
+            let mark = |path: &str| {
+                if swapped(path) {
+                    format!("{} (byte-swapped)", path)
+                } else {
+                    path.to_string()
+                }
+            };
+            println!("{}: {} difference(s)", label, votes.len());
+            for vote in &votes {
+                let values = highlight::values(&vote.values, self.color);
+                match &vote.outlier {
+                    Some(outlier) if refs.len() > 1 => println!(
+                        "  {}: {} (outlier: {})",
+                        mark(&vote.path),
+                        values.join(", "),
+                        outlier
+                    ),
+                    _ => println!("  {}: {}", mark(&vote.path), values.join(", ")),
+                }
+            }
+            for hint in hints::hints(&diffs, &oside_layers, &refs) {
+                println!("  hint: {}", hint);
+            }
+            let message = votes
+                .iter()
+                .map(|v| mark(&v.path))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let message = format!("{} difference(s): {}", votes.len(), message);
+            if !passed {
+                self.fail(case, &message, &data, &diffs);
+            }
+        }
+        for warning in &warnings {
+            println!("{}: strict: {}", label, warning);
+        }
+        if diffs.is_empty() && !warnings.is_empty() && !passed {
+            self.fail(case, &warnings.join("; "), &data, &[]);
+        }
+        if let (Some(required), Some(level)) = (required, level) {
+            let reached = level.map_or("no level".to_string(), |l| l.to_string());
+            if fields_passed && !passed {
+                let message = format!(
+                    "passes {}, {} required: oside does not encode it back into the same bytes",
+                    reached, required
+                );
+                println!("{}: {}", label, message);
+                self.fail(case, &message, &data, &[]);
+            } else if passed && !fields_passed {
+                println!(
+                    "{}: passes {}, {} required; the above is not counted",
+                    label, reached, required
+                );
+            } else if self.opts.verbose > 0 {
+                println!("{}: passes {}", label, reached);
+            }
+            *self.levels.entry(level).or_default() += 1;
+            self.case_level = Some(level.map_or(0, |l| l as u8));
+        }
+        if summary_agreed && !fields_passed {
+            self.summary_only += 1;
+        }
+        self.record(case, passed, &oside_layers, &diffs);
+    }
+}
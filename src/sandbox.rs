@@ -0,0 +1,247 @@
+//! `--sandbox`: confine the process before it dissects untrusted frames.
+//!
+//! Captures and corpora may be attacker-controlled, and the embedded
+//! interpreter behind Scapy is a large attack surface. With --sandbox a
+//! seccomp filter is installed once the inputs are read and the backends
+//! started, just before the first frame is dissected. From then on:
+//!
+//! - sockets other than Unix domain ones cannot be created, so there is no
+//!   network access
+//! - no programs can be executed
+//! - syscalls for debugging other processes, loading kernel modules or BPF
+//!   programs, mounting, keyrings and io_uring fail
+//! - no namespaces can be created or joined: `unshare` and `setns` fail,
+//!   as does `clone` with any of the `CLONE_NEW*` flags
+//! - on x86_64, any syscall through the x32 ABI kills the process, as its
+//!   numbers would get past the checks above
+//!
+//! Denied syscalls fail with EPERM rather than killing the process, so a
+//! Python exception or a failed case reports what was attempted. `clone3`,
+//! whose flags are out of the filter's reach, fails with ENOSYS instead, for
+//! libc to fall back to `clone`. Files can
+//! still be read and written, for the results database and saved failures;
+//! combine with --workdir and a read-only mount to confine those too.
+//! The `python` and `cmd` backends are started before the filter and keep
+//! working; the `tshark` backend, which runs tshark on each batch of
+//! frames, and failure notifications cannot be combined with --sandbox.
+//!
+//! Every mode that decodes frames enters the sandbox; the commands that do
+//! not, or need what it denies, refuse --sandbox rather than ignore it.
+//!
+//! Only Linux on x86_64 and aarch64 is supported; elsewhere --sandbox
+//! refuses to run rather than run unconfined.
+
+use crate::{Command, CorpusCommand, Opts};
+
+/// With --sandbox, exit if the command does not run sandboxed.
+pub fn check_supported(opts: &Opts) {
+    if !opts.sandbox {
+        return;
+    }
+    let command = match &opts.command {
+        Some(Command::SelfTest) => "self-test",
+        Some(Command::Doctor) => "doctor",
+        Some(Command::Dispatch { .. }) => "dispatch",
+        Some(Command::GenCorpus { .. }) => "gen-corpus",
+        Some(Command::Package { .. }) => "package",
+        Some(Command::Corpus {
+            action: CorpusCommand::Convert { .. },
+        }) => "corpus convert",
+        _ => return,
+    };
+    eprintln!("--sandbox is not supported by the {} command", command);
+    std::process::exit(2);
+}
+
+/// With --sandbox, install the filter, exiting if that fails or the options
+/// need the network.
+pub fn enter(opts: &Opts) {
+    if !opts.sandbox {
+        return;
+    }
+    if opts.notify_webhook.is_some() || opts.notify_smtp.is_some() {
+        eprintln!("--sandbox blocks the network access failure notifications need");
+        std::process::exit(2);
+    }
+    if opts.backend.iter().any(|b| b.starts_with("tshark")) {
+        eprintln!(
+            "--sandbox cannot run tshark, which the tshark backend starts for each batch of frames"
+        );
+        std::process::exit(2);
+    }
+    if let Err(e) = apply() {
+        eprintln!("could not enter the sandbox: {}", e);
+        std::process::exit(2);
+    }
+}
+
+/// Install the filter, for this thread and any others of the process.
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+pub fn apply() -> Result<(), String> {
+    linux::apply()
+}
+
+#[cfg(not(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+)))]
+pub fn apply() -> Result<(), String> {
+    Err("--sandbox is only supported on Linux on x86_64 and aarch64".to_string())
+}
+
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+mod linux {
+    use libc::{c_long, sock_filter, sock_fprog};
+
+    #[cfg(target_arch = "x86_64")]
+    const AUDIT_ARCH: u32 = 0xc000_003e;
+    #[cfg(target_arch = "aarch64")]
+    const AUDIT_ARCH: u32 = 0xc000_00b7;
+
+    const BPF_LD_W_ABS: u16 = 0x20;
+    const BPF_JEQ_K: u16 = 0x15;
+    const BPF_JGE_K: u16 = 0x35;
+    const BPF_JSET_K: u16 = 0x45;
+    const BPF_RET_K: u16 = 0x06;
+
+    const SECCOMP_RET_KILL_PROCESS: u32 = 0x8000_0000;
+    const SECCOMP_RET_ERRNO: u32 = 0x0005_0000;
+    const SECCOMP_RET_ALLOW: u32 = 0x7fff_0000;
+    const SECCOMP_SET_MODE_FILTER: u32 = 1;
+    const SECCOMP_FILTER_FLAG_TSYNC: u32 = 1;
+
+    /// Offsets into `struct seccomp_data`: the syscall number, the audit
+    /// architecture and the low half of the first argument.
+    const NR: u32 = 0;
+    const ARCH: u32 = 4;
+    const ARG0: u32 = 16;
+
+    /// Set in the numbers of x32 ABI syscalls, which share x86_64's audit
+    /// architecture.
+    const X32_SYSCALL_BIT: u32 = 0x4000_0000;
+
+    /// The `clone` flags creating a namespace.
+    const CLONE_NEW: u32 = (libc::CLONE_NEWNS
+        | libc::CLONE_NEWCGROUP
+        | libc::CLONE_NEWUTS
+        | libc::CLONE_NEWIPC
+        | libc::CLONE_NEWUSER
+        | libc::CLONE_NEWPID
+        | libc::CLONE_NEWNET) as u32;
+
+    const DENIED: &[c_long] = &[
+        libc::SYS_execve,
+        libc::SYS_execveat,
+        libc::SYS_ptrace,
+        libc::SYS_process_vm_readv,
+        libc::SYS_process_vm_writev,
+        libc::SYS_mount,
+        libc::SYS_umount2,
+        libc::SYS_unshare,
+        libc::SYS_setns,
+        libc::SYS_init_module,
+        libc::SYS_finit_module,
+        libc::SYS_delete_module,
+        libc::SYS_kexec_load,
+        libc::SYS_bpf,
+        libc::SYS_perf_event_open,
+        libc::SYS_userfaultfd,
+        libc::SYS_keyctl,
+        libc::SYS_add_key,
+        libc::SYS_request_key,
+        libc::SYS_io_uring_setup,
+        libc::SYS_io_uring_enter,
+        libc::SYS_io_uring_register,
+    ];
+
+    fn stmt(code: u16, k: u32) -> sock_filter {
+        sock_filter {
+            code,
+            jt: 0,
+            jf: 0,
+            k,
+        }
+    }
+
+    fn jeq(k: u32, jt: u8, jf: u8) -> sock_filter {
+        sock_filter {
+            code: BPF_JEQ_K,
+            jt,
+            jf,
+            k,
+        }
+    }
+
+    fn program() -> Vec<sock_filter> {
+        let deny = stmt(BPF_RET_K, SECCOMP_RET_ERRNO | libc::EPERM as u32);
+        let mut prog = vec![
+            stmt(BPF_LD_W_ABS, ARCH),
+            jeq(AUDIT_ARCH, 1, 0),
+            stmt(BPF_RET_K, SECCOMP_RET_KILL_PROCESS),
+            stmt(BPF_LD_W_ABS, NR),
+            sock_filter {
+                code: BPF_JGE_K,
+                jt: 0,
+                jf: 1,
+                k: X32_SYSCALL_BIT,
+            },
+            stmt(BPF_RET_K, SECCOMP_RET_KILL_PROCESS),
+        ];
+        for nr in DENIED {
+            prog.push(jeq(*nr as u32, 0, 1));
+            prog.push(deny);
+        }
+        prog.push(jeq(libc::SYS_clone3 as u32, 0, 1));
+        prog.push(stmt(BPF_RET_K, SECCOMP_RET_ERRNO | libc::ENOSYS as u32));
+        // clone(2) is allowed without the flags creating namespaces, which
+        // are the first argument on both architectures
+        prog.push(jeq(libc::SYS_clone as u32, 0, 4));
+        prog.push(stmt(BPF_LD_W_ABS, ARG0));
+        prog.push(sock_filter {
+            code: BPF_JSET_K,
+            jt: 0,
+            jf: 1,
+            k: CLONE_NEW,
+        });
+        prog.push(deny);
+        prog.push(stmt(BPF_RET_K, SECCOMP_RET_ALLOW));
+        // socket(2) is allowed for AF_UNIX only
+        prog.push(jeq(libc::SYS_socket as u32, 0, 3));
+        prog.push(stmt(BPF_LD_W_ABS, ARG0));
+        prog.push(jeq(libc::AF_UNIX as u32, 1, 0));
+        prog.push(deny);
+        prog.push(stmt(BPF_RET_K, SECCOMP_RET_ALLOW));
+        prog
+    }
+
+    pub fn apply() -> Result<(), String> {
+        let mut filter = program();
+        let prog = sock_fprog {
+            len: filter.len() as u16,
+            filter: filter.as_mut_ptr(),
+        };
+        let err = |what: &str| format!("{}: {}", what, std::io::Error::last_os_error());
+        // SAFETY: plain syscalls; the kernel copies the filter before returning.
+        unsafe {
+            if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 {
+                return Err(err("prctl(PR_SET_NO_NEW_PRIVS)"));
+            }
+            if libc::syscall(
+                libc::SYS_seccomp,
+                SECCOMP_SET_MODE_FILTER,
+                SECCOMP_FILTER_FLAG_TSYNC,
+                &prog as *const sock_fprog,
+            ) != 0
+            {
+                return Err(err("seccomp"));
+            }
+        }
+        Ok(())
+    }
+}
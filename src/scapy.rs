@@ -0,0 +1,393 @@
+//! Interaction with Scapy running in the embedded interpreter.
+
+use crate::reverse;
+use pyo3::Python;
+use pyo3_ffi::{
+    PyThreadState, PyThreadState_Get, PyThreadState_Swap, Py_EndInterpreter, Py_NewInterpreter,
+};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Python helper functions, installed into `__main__` by `init()`.
+pub const HELPER: &str = include_str!("scapy_helper.py");
+
+/// A Python string literal of `s`, for pasting user-supplied names into
+/// Python source: a JSON string is also a valid Python one.
+pub fn py_str(s: &str) -> String {
+    serde_json::to_string(s).unwrap()
+}
+
+/// Import Scapy and install the helper functions.
+pub fn init(py: Python) {
+    if let Err(e) = try_init(py) {
+        panic!("{}", e);
+    }
+}
+
+/// Layers --start-layer can name that Scapy has in a contrib module, with
+/// the module.
+const CONTRIB_LAYERS: &[(&str, &str)] = &[("chdlc", "chdlc"), ("isotpheader", "isotp")];
+
+/// Whether Scapy has a layer of the name, as for --start-layer, loading
+/// the contrib module it is in if need be.
+pub fn has_layer(py: Python, layer: &str) -> bool {
+    if let Some((_, module)) = CONTRIB_LAYERS.iter().find(|(l, _)| *l == layer) {
+        if load_contrib(py, module).is_err() {
+            return false;
+        }
+    }
+    py.eval(&format!("_ot_has_layer({})", py_str(layer)), None, None)
+        .and_then(|v| v.extract())
+        .unwrap_or(false)
+}
+
+/// Load a Scapy contrib module, e.g. `isotp`.
+pub fn load_contrib(py: Python, module: &str) -> Result<(), String> {
+    py.run(&format!("load_contrib({})", py_str(module)), None, None)
+        .map_err(|e| format!("could not load Scapy's {} module: {:?}", module, e))
+}
+
+/// Like `init()`, but returning the error instead of panicking.
+pub fn try_init(py: Python) -> Result<(), String> {
+    py.run("import scapy; from scapy.all import *", None, None)
+        .map_err(|e| format!("python error: {:?}", e))?;
+    py.run(HELPER, None, None)
+        .map_err(|e| format!("python error in helper: {:?}", e))
+}
+
+/// Apply `--bind-port` specs such as `udp:4789=vxlan` to Scapy's dispatch.
+pub fn bind_ports(py: Python, specs: &[String]) -> Result<(), String> {
+    for spec in specs {
+        let bad = || format!("bad --bind-port {:?}, expected udp|tcp:PORT=LAYER", spec);
+        let (proto, rest) = spec.split_once(':').ok_or_else(bad)?;
+        let (port, layer) = rest.split_once('=').ok_or_else(bad)?;
+        let port: u16 = port.parse().map_err(|_| bad())?;
+        if proto != "udp" && proto != "tcp" {
+            return Err(bad());
+        }
+        py.run(
+            &format!(
+                "_ot_bind_port('{}', {}, {})",
+                proto,
+                port,
+                py_str(&layer.to_lowercase())
+            ),
+            None,
+            None,
+        )
+        .map_err(|e| format!("--bind-port {}: {:?}", spec, e))?;
+    }
+    Ok(())
+}
+
+/// How much of the interpreter's state each case starts afresh with, per
+/// `--isolate`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Isolate {
+    /// Cases share `__main__`: what one defines, the next sees.
+    None,
+    /// `__main__` is reset to how it was before the first case. State kept
+    /// elsewhere, such as Scapy's `conf` and layer bindings, is shared.
+    Namespace,
+    /// Every case runs in a new sub-interpreter importing Scapy afresh:
+    /// slow, but nothing is shared.
+    Interpreter,
+}
+
+impl Isolate {
+    pub fn parse(s: &str) -> Result<Isolate, String> {
+        match s {
+            "none" => Ok(Isolate::None),
+            "namespace" => Ok(Isolate::Namespace),
+            "interpreter" => Ok(Isolate::Interpreter),
+            _ => Err(format!(
+                "bad --isolate '{}', expected none, namespace or interpreter",
+                s
+            )),
+        }
+    }
+}
+
+/// Remember `__main__` as it is, for `reset_namespace()`.
+pub fn snapshot_namespace(py: Python) {
+    py.run("_ot_snapshot_namespace()", None, None).unwrap();
+}
+
+/// Reset `__main__` to how `snapshot_namespace()` found it.
+pub fn reset_namespace(py: Python) {
+    py.run("_ot_reset_namespace()", None, None).unwrap();
+}
+
+/// A sub-interpreter with Scapy and the helpers loaded, current until
+/// dropped. Objects created while it is current must be released before
+/// it is dropped, e.g. by a `GILPool` of their own.
+pub struct SubInterpreter {
+    main: *mut PyThreadState,
+    sub: *mut PyThreadState,
+}
+
+impl SubInterpreter {
+    /// Create the sub-interpreter and make it current, applying the
+    /// `--bind-port` specs to its Scapy.
+    pub fn enter(py: Python, bind_port: &[String]) -> Result<SubInterpreter, String> {
+        // SAFETY: the GIL is held, as `py` attests; the main thread state
+        // is made current again when the sub-interpreter is dropped.
+        let (main, sub) = unsafe {
+            let main = PyThreadState_Get();
+            let sub = Py_NewInterpreter();
+            if sub.is_null() {
+                PyThreadState_Swap(main);
+                return Err("could not create a sub-interpreter".to_string());
+            }
+            (main, sub)
+        };
+        let interp = SubInterpreter { main, sub };
+        try_init(py).map_err(|e| format!("in a sub-interpreter: {}", e))?;
+        bind_ports(py, bind_port)?;
+        Ok(interp)
+    }
+}
+
+impl Drop for SubInterpreter {
+    fn drop(&mut self) {
+        // SAFETY: `sub` is the current thread state, as made by `enter()`.
+        unsafe {
+            PyThreadState_Swap(self.sub);
+            Py_EndInterpreter(self.sub);
+            PyThreadState_Swap(self.main);
+        }
+    }
+}
+
+/// Redirect the interpreter's `sys.stdout` and `sys.stderr` into a buffer
+/// read with `take_output()`, so that Scapy's prints do not mix with ours.
+pub fn capture_output(py: Python) {
+    py.run(
+        "import io, sys\n\
+         _ot_saved_output = (sys.stdout, sys.stderr)\n\
+         sys.stdout = sys.stderr = _ot_output = io.StringIO()",
+        None,
+        None,
+    )
+    .unwrap();
+}
+
+/// Undo `capture_output()`.
+pub fn restore_output(py: Python) {
+    py.run("sys.stdout, sys.stderr = _ot_saved_output", None, None)
+        .unwrap();
+}
+
+/// Return and clear what the interpreter printed since the last call.
+pub fn take_output(py: Python) -> String {
+    py.eval(
+        "(_ot_output.getvalue(), _ot_output.seek(0), _ot_output.truncate())[0]",
+        None,
+        None,
+    )
+    .and_then(|v| v.extract())
+    .unwrap_or_default()
+}
+
+/// Build the bytes of a Scapy expression such as `Ether()/IP()/UDP()`.
+pub fn build(py: Python, expr: &str) -> Result<Vec<u8>, String> {
+    py.eval(&format!("bytes({})", expr), None, None)
+        .and_then(|v| v.extract())
+        .map_err(|e| format!("{:?}", e))
+}
+
+/// The bytes of a Scapy expression as rendered by `raw()`, `bytes()` and
+/// `build()`, each with the name of the rendering.
+pub fn build_variants(py: Python, expr: &str) -> Result<Vec<(String, Vec<u8>)>, String> {
+    py.eval(&format!("_ot_build_variants({})", expr), None, None)
+        .and_then(|v| v.extract())
+        .map_err(|e| format!("{:?}", e))
+}
+
+/// The layers of a Scapy expression with the fields explicitly set in each.
+pub fn set_fields(py: Python, expr: &str) -> Result<Vec<reverse::SetLayer>, String> {
+    let json: String = py
+        .eval(&format!("_ot_set_fields({})", expr), None, None)
+        .and_then(|v| v.extract())
+        .map_err(|e| format!("{:?}", e))?;
+    serde_json::from_str(&json).map_err(|e| e.to_string())
+}
+
+/// Dissect a frame with Scapy, starting at the `start` layer (`ether` for
+/// an Ethernet frame), returning the layers as JSON in the same shape as
+/// oside's: a list of `{"LayerName": {"field": value, ...}}`.
+pub fn dissect(py: Python, start: &str, data: &[u8]) -> Result<serde_json::Value, String> {
+    let json: String = py
+        .eval(
+            &format!("_ot_dissect('{}', {})", hex(data), py_str(start)),
+            None,
+            None,
+        )
+        .and_then(|v| v.extract())
+        .map_err(|e| format!("{:?}", e))?;
+    serde_json::from_str(&json).map_err(|e| e.to_string())
+}
+
+/// Have every later Scapy dissection note its classes and their fields,
+/// for `class_coverage()`.
+pub fn start_class_coverage(py: Python) {
+    py.run("_ot_seen = {}", None, None)
+        .unwrap_or_else(|e| panic!("python error: {:?}", e));
+}
+
+/// The Scapy classes dissected since `start_class_coverage()`.
+#[derive(Debug, Deserialize)]
+pub struct ClassCoverage {
+    /// The fields each class dissected, and all its fields.
+    pub seen: BTreeMap<String, ClassFields>,
+    /// Common classes Scapy has that were never dissected.
+    pub missing: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ClassFields {
+    pub fields: Vec<String>,
+    pub all: Vec<String>,
+}
+
+pub fn class_coverage(py: Python) -> Result<ClassCoverage, String> {
+    let json: String = py
+        .eval("_ot_class_coverage()", None, None)
+        .and_then(|v| v.extract())
+        .map_err(|e| format!("{:?}", e))?;
+    serde_json::from_str(&json).map_err(|e| e.to_string())
+}
+
+/// Dissect a frame with Scapy and render it back into bytes, which for a
+/// self-consistent dissector are the frame again.
+pub fn roundtrip(py: Python, start: &str, data: &[u8]) -> Result<Vec<u8>, String> {
+    py.eval(
+        &format!("_ot_roundtrip('{}', {})", hex(data), py_str(start)),
+        None,
+        None,
+    )
+    .and_then(|v| v.extract())
+    .map_err(|e| format!("{:?}", e))
+}
+
+/// Dissect a frame with Scapy, unset its checksum fields and build it
+/// again, so that Scapy computes them.
+pub fn refill(py: Python, start: &str, data: &[u8]) -> Result<Vec<u8>, String> {
+    py.eval(
+        &format!("_ot_refill('{}', {})", hex(data), py_str(start)),
+        None,
+        None,
+    )
+    .and_then(|v| v.extract())
+    .map_err(|e| format!("{:?}", e))
+}
+
+/// Measure how long Scapy takes to dissect a frame, without converting
+/// the result.
+pub fn dissect_time(py: Python, start: &str, data: &[u8]) -> Result<Duration, String> {
+    let secs: f64 = py
+        .eval(
+            &format!("_ot_dissect_time('{}', {})", hex(data), py_str(start)),
+            None,
+            None,
+        )
+        .and_then(|v| v.extract())
+        .map_err(|e| format!("{:?}", e))?;
+    Ok(Duration::from_secs_f64(secs))
+}
+
+pub fn hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// The structural mutations applicable to a Scapy expression, as
+/// (operator, layer index) pairs for `mutate_expr()`.
+pub fn mutations(py: Python, expr: &str) -> Result<Vec<(String, usize)>, String> {
+    py.eval(&format!("_ot_mutations({})", expr), None, None)
+        .and_then(|v| v.extract())
+        .map_err(|e| format!("{:?}", e))
+}
+
+/// Default-value expressions stacking the named layer (matched
+/// case-insensitively) on Ether in the common ways, following Scapy's
+/// layer bindings.
+pub fn skeletons(py: Python, layer: &str) -> Result<Vec<String>, String> {
+    py.eval(
+        &format!("_ot_skeletons({})", py_str(&layer.to_lowercase())),
+        None,
+        None,
+    )
+    .and_then(|v| v.extract())
+    .map_err(|e| format!("{:?}", e))
+}
+
+/// A Scapy expression building the given structural mutation of `expr`.
+pub fn mutate_expr(expr: &str, op: &str, index: usize) -> String {
+    format!("_ot_mutate({}, '{}', {})", expr, op, index)
+}
+
+/// One value a length field is swept through by `--length-sweep`.
+#[derive(Debug, Clone)]
+pub struct LengthValue {
+    pub layer_index: usize,
+    /// Lowercased layer name, e.g. `ip`.
+    pub layer: String,
+    pub field: String,
+    /// `zero`, `under`, `exact`, `over` or `past-end`.
+    pub label: String,
+    pub value: u64,
+}
+
+/// The length-bearing fields of a Scapy expression, each with the values it
+/// is swept through.
+pub fn length_fields(py: Python, expr: &str) -> Result<Vec<LengthValue>, String> {
+    let fields: Vec<(usize, String, String, String, u64)> = py
+        .eval(&format!("_ot_length_fields({})", expr), None, None)
+        .and_then(|v| v.extract())
+        .map_err(|e| format!("{:?}", e))?;
+    Ok(fields
+        .into_iter()
+        .map(|(layer_index, layer, field, label, value)| LengthValue {
+            layer_index,
+            layer,
+            field,
+            label,
+            value,
+        })
+        .collect())
+}
+
+/// A Scapy expression building `expr` with one field of layer `index` set
+/// to `value`, all other computed fields keeping their built values.
+pub fn set_field_expr(expr: &str, index: usize, field: &str, value: u64) -> String {
+    format!(
+        "_ot_set_field({}, {}, {}, {})",
+        expr,
+        index,
+        py_str(field),
+        value
+    )
+}
+
+/// The index of the first layer of `expr` named `layer` (in any case) that
+/// has `field`, and the largest value that field can hold.
+pub fn field_info(
+    py: Python,
+    expr: &str,
+    layer: &str,
+    field: &str,
+) -> Result<(usize, u64), String> {
+    py.eval(
+        &format!(
+            "_ot_field_info({}, {}, {})",
+            expr,
+            py_str(layer),
+            py_str(field)
+        ),
+        None,
+        None,
+    )
+    .and_then(|v| v.extract())
+    .map_err(|e| format!("{:?}", e))
+}
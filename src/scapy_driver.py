@@ -0,0 +1,18 @@
+# Driver loop speaking the JSON driver protocol, for running Scapy in an
+# external Python interpreter. It is appended to scapy_helper.py and run
+# with `python -c`.
+#
+# Each request is one line of JSON, {"hex": "<frame bytes>", "layer": "ether"};
+# each reply is one line of JSON, either the list of layers or
+# {"error": "<message>"}.
+
+import sys
+
+for _ot_line in sys.stdin:
+    try:
+        _ot_req = json.loads(_ot_line)
+        _ot_reply = _ot_dissect(_ot_req["hex"], _ot_req.get("layer", "ether"))
+    except Exception as e:
+        _ot_reply = json.dumps({"error": repr(e)})
+    sys.stdout.write(_ot_reply + "\n")
+    sys.stdout.flush()
@@ -0,0 +1,338 @@
+# Helper functions installed into the embedded interpreter's __main__
+# namespace after Scapy is imported. They are called from Rust via eval()
+# and exchange data as hex strings and JSON text.
+
+import json
+import time
+
+
+def _ot_value(v):
+    if isinstance(v, bytes):
+        return v.hex()
+    if v is None or isinstance(v, (bool, int, float, str)):
+        return v
+    if isinstance(v, (list, tuple)):
+        return [_ot_value(x) for x in v]
+    if isinstance(v, Packet):
+        return _ot_layers(v)
+    return str(v)
+
+
+def _ot_layers(pkt):
+    out = []
+    while pkt is not None and not isinstance(pkt, NoPayload):
+        fields = {}
+        for f in pkt.fields_desc:
+            fields[f.name] = _ot_value(pkt.getfieldval(f.name))
+        out.append({pkt.__class__.__name__: fields})
+        pkt = pkt.payload
+    return out
+
+
+def _ot_entry(layer):
+    # The Scapy class for an entry layer name such as "ether" or "ipv6".
+    for cls in conf.layers:
+        if cls.__name__.lower() == layer:
+            return cls
+    raise ValueError("no Scapy layer named " + layer)
+
+
+def _ot_has_layer(layer):
+    try:
+        _ot_entry(layer)
+        return True
+    except ValueError:
+        return False
+
+
+def _ot_bind_port(proto, port, layer):
+    # Dissect the payload of the transport layer on the port, either way,
+    # as the named layer.
+    lower = {"udp": UDP, "tcp": TCP}[proto]
+    upper = _ot_entry(layer)
+    bind_layers(lower, upper, dport=port)
+    bind_bottom_up(lower, upper, sport=port)
+
+
+def _ot_snapshot_namespace():
+    # Remember the namespace as it is, for _ot_reset_namespace().
+    _ot_reset_namespace.snapshot = dict(globals())
+
+
+def _ot_reset_namespace():
+    # Drop the names defined since the snapshot and restore the others.
+    g = globals()
+    snapshot = _ot_reset_namespace.snapshot
+    for name in [name for name in g if name not in snapshot]:
+        del g[name]
+    g.update(snapshot)
+
+
+def _ot_path(start, goal, avoid=()):
+    # The shortest chain of layer classes from start to goal following
+    # Scapy's payload bindings, not passing through the avoided classes.
+    prev = {start: None}
+    queue = [start]
+    while queue:
+        cls = queue.pop(0)
+        if cls is goal:
+            path = []
+            while cls is not None:
+                path.append(cls)
+                cls = prev[cls]
+            return path[::-1]
+        for _, upper in cls.payload_guess:
+            if upper not in prev and upper not in avoid:
+                prev[upper] = cls
+                queue.append(upper)
+    return None
+
+
+def _ot_skeletons(layer):
+    # Default-value expressions reaching the named layer from Ether: the
+    # shortest stacking, the shortest ones avoiding IPv4 and IPv6 in turn,
+    # and the first one VLAN-tagged.
+    goal = _ot_entry(layer)
+    out = []
+    for avoid in ((), (IP,), (IPv6,)):
+        path = _ot_path(Ether, goal, avoid)
+        if path:
+            expr = "/".join(c.__name__ + "()" for c in path)
+            if expr not in out:
+                out.append(expr)
+    if out and out[0].startswith("Ether()/"):
+        out.append("Ether()/Dot1Q()/" + out[0][len("Ether()/"):])
+    return out
+
+
+def _ot_dissect(hexdata, layer="ether"):
+    pkt = _ot_entry(layer)(bytes.fromhex(hexdata))
+    if _ot_seen is not None:
+        _ot_record_classes(pkt)
+    return json.dumps(_ot_layers(pkt))
+
+
+# The Scapy classes dissected so far and the fields each dissected, for
+# --class-coverage; None when it is off.
+_ot_seen = None
+
+# Classes a run over common traffic is expected to exercise.
+_OT_COMMON_CLASSES = [
+    "Ether", "Dot1Q", "ARP", "IP", "IPv6", "IPv6ExtHdrFragment",
+    "IPv6ExtHdrHopByHop", "IPv6ExtHdrRouting", "TCP", "UDP", "ICMP",
+    "ICMPv6EchoRequest", "ICMPv6EchoReply", "ICMPv6ND_NS", "ICMPv6ND_NA",
+    "ICMPv6ND_RA", "DNS", "DNSQR", "DNSRR", "DHCP", "BOOTP", "NTPHeader",
+    "GRE", "VXLAN", "ESP", "AH", "SNMP", "Raw", "Padding",
+]
+
+
+def _ot_record_classes(pkt):
+    while pkt is not None and not isinstance(pkt, NoPayload):
+        _ot_seen.setdefault(pkt.__class__.__name__, set()).update(pkt.fields)
+        for v in pkt.fields.values():
+            for item in v if isinstance(v, list) else [v]:
+                if isinstance(item, Packet):
+                    _ot_record_classes(item)
+        pkt = pkt.payload
+
+
+def _ot_class_coverage():
+    seen = {}
+    for name, fields in _ot_seen.items():
+        cls = next((c for c in conf.layers if c.__name__ == name), None)
+        all_fields = [f.name for f in cls.fields_desc] if cls else sorted(fields)
+        seen[name] = {"fields": sorted(fields), "all": all_fields}
+    known = {c.__name__ for c in conf.layers}
+    missing = [c for c in _OT_COMMON_CLASSES if c in known and c not in _ot_seen]
+    return json.dumps({"seen": seen, "missing": missing})
+
+
+def _ot_roundtrip(hexdata, layer="ether"):
+    # The bytes Scapy renders a frame back into after dissecting it.
+    return bytes(_ot_entry(layer)(bytes.fromhex(hexdata)))
+
+
+# Checksum fields, reset to None by --checksum-fill so that Scapy computes
+# them when building.
+_OT_CHECKSUM_FIELDS = ("chksum", "cksum", "checksum")
+
+
+def _ot_refill(hexdata, layer="ether"):
+    pkt = _ot_entry(layer)(bytes.fromhex(hexdata))
+    p = pkt
+    while isinstance(p, Packet) and not isinstance(p, NoPayload):
+        for f in p.fields_desc:
+            if f.name in _OT_CHECKSUM_FIELDS:
+                setattr(p, f.name, None)
+        p = p.payload
+    return bytes(pkt)
+
+
+def _ot_dissect_time(hexdata, layer="ether"):
+    data = bytes.fromhex(hexdata)
+    cls = _ot_entry(layer)
+    start = time.perf_counter()
+    cls(data)
+    return time.perf_counter() - start
+
+
+# Structural mutations of a built packet, applied to batch expressions by
+# --mutate. Each is an (operator, layer index) pair.
+
+_OT_LENGTH_FIELDS = ("len", "plen", "length", "ihl", "dataofs")
+
+
+def _ot_is_length(name):
+    return name in _OT_LENGTH_FIELDS or name.endswith("len")
+
+
+def _ot_field_max(f):
+    if isinstance(f, BitField):
+        return (1 << f.size) - 1
+    if hasattr(f, "sz"):
+        return (1 << (8 * f.sz)) - 1
+    return 65535
+
+
+def _ot_set_field(pkt, i, name, value):
+    # Dissect the built packet, as its own first layer, so every computed
+    # field is concrete, then change just the one field of layer i.
+    built = type(pkt)(bytes(pkt))
+    layer = built
+    for _ in range(i):
+        layer = layer.payload
+    layer.setfieldval(name, value)
+    return bytes(built)
+
+
+def _ot_split(pkt):
+    layers = []
+    while pkt is not None and not isinstance(pkt, NoPayload):
+        layer = pkt.copy()
+        layer.remove_payload()
+        layers.append(layer)
+        pkt = pkt.payload
+    return layers
+
+
+def _ot_join(layers):
+    pkt = layers[0].copy()
+    for layer in layers[1:]:
+        pkt = pkt / layer.copy()
+    return pkt
+
+
+def _ot_mutations(pkt):
+    layers = _ot_split(pkt)
+    out = []
+    for i, layer in enumerate(layers):
+        out.append(("dup-layer", i))
+        if len(layers) > 1:
+            out.append(("drop-layer", i))
+        if i + 1 < len(layers):
+            out.append(("swap-layers", i))
+        if any(_ot_is_length(f.name) for f in layer.fields_desc):
+            out.append(("overlong-length", i))
+    return out
+
+
+def _ot_mutate(pkt, op, i):
+    layers = _ot_split(pkt)
+    if op == "dup-layer":
+        layers.insert(i, layers[i])
+    elif op == "drop-layer":
+        del layers[i]
+    elif op == "swap-layers":
+        layers[i], layers[i + 1] = layers[i + 1], layers[i]
+    elif op == "overlong-length":
+        f = [f for f in layers[i].fields_desc if _ot_is_length(f.name)][0]
+        value = _ot_length_values(pkt, i, f)["past-end"]
+        return _ot_set_field(pkt, i, f.name, value)
+    else:
+        raise ValueError("unknown mutation " + op)
+    return bytes(_ot_join(layers))
+
+
+# Length field sweeps for --length-sweep: each length-bearing field of the
+# built packet, and the values it is swept through.
+
+
+def _ot_length_values(pkt, i, f):
+    layer = type(pkt)(bytes(pkt))
+    for _ in range(i):
+        layer = layer.payload
+    exact = layer.getfieldval(f.name) or 0
+    top = _ot_field_max(f)
+    return {
+        "zero": 0,
+        "under": max(exact - 1, 0),
+        "exact": exact,
+        "over": min(exact + 1, top),
+        "past-end": min(exact + 64, top),
+    }
+
+
+def _ot_length_fields(pkt):
+    out = []
+    for i, layer in enumerate(_ot_split(pkt)):
+        for f in layer.fields_desc:
+            if _ot_is_length(f.name):
+                for label, value in _ot_length_values(pkt, i, f).items():
+                    out.append((i, layer.__class__.__name__.lower(), f.name, label, value))
+    return out
+
+
+def _ot_field_info(pkt, layer_name, field):
+    # Index of the first layer named layer_name (case-insensitively) with
+    # the field, and the largest value the field can hold.
+    for i, layer in enumerate(_ot_split(pkt)):
+        if layer.__class__.__name__.lower() != layer_name.lower():
+            continue
+        for f in layer.fields_desc:
+            if f.name == field:
+                return (i, _ot_field_max(f))
+    raise ValueError("no field %s.%s" % (layer_name, field))
+
+
+def _ot_build_variants(pkt):
+    # The packet rendered each way Scapy offers; mutated expressions are
+    # already bytes and render the same every way.
+    if not isinstance(pkt, Packet):
+        return [("raw", bytes(pkt)), ("bytes", bytes(pkt)), ("build", bytes(pkt))]
+    return [("raw", raw(pkt)), ("bytes", bytes(pkt)), ("build", pkt.build())]
+
+
+def _ot_builder_value(v):
+    # A field value as oside's builder takes it; ValueError for values it
+    # has no equivalent of, such as nested packets and option lists.
+    if isinstance(v, bytes):
+        return list(v)
+    if isinstance(v, bool):
+        return int(v)
+    if isinstance(v, (int, str)):
+        return v
+    if not isinstance(v, (list, tuple, Packet)) and hasattr(v, "__int__"):
+        return int(v)  # e.g. FlagValue
+    raise ValueError(type(v).__name__)
+
+
+def _ot_set_fields(pkt):
+    # The layers of a packet with the fields explicitly set in each, for
+    # building it again with oside.
+    if not isinstance(pkt, Packet):
+        raise ValueError("not a packet but " + type(pkt).__name__)
+    out = []
+    while pkt is not None and not isinstance(pkt, NoPayload):
+        fields, unsupported = {}, []
+        for name, v in pkt.fields.items():
+            if v is None:
+                continue
+            try:
+                fields[name] = _ot_builder_value(v)
+            except ValueError:
+                unsupported.append(name)
+        out.append(
+            {"layer": pkt.__class__.__name__, "fields": fields, "unsupported": unsupported}
+        )
+        pkt = pkt.payload
+    return json.dumps(out)
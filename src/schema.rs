@@ -0,0 +1,37 @@
+//! The JSON Schema of the objects this tool writes: results database
+//! records and their differences, corpus sidecars, JSONL corpus manifests,
+//! failure notifications, bundle manifests, recorded reference output, run
+//! summaries and the run history of `nightly`.
+//! `--print-schema` prints it.
+//!
+//! Every such object carries a `schema_version`. Within a version, fields
+//! are only ever added, never removed, renamed or changed in meaning, so
+//! consumers should ignore fields they do not know. Anything else bumps
+//! the version.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// The version of the schema the objects written follow.
+pub const VERSION: u32 = 1;
+
+/// The schema itself.
+pub const SCHEMA: &str = include_str!("schema.json");
+
+/// The `schema_version` field: always written as `VERSION`, and accepting
+/// any version when read, including none for objects written before the
+/// field existed.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SchemaVersion;
+
+impl Serialize for SchemaVersion {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_u32(VERSION)
+    }
+}
+
+impl<'de> Deserialize<'de> for SchemaVersion {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        u32::deserialize(d)?;
+        Ok(SchemaVersion)
+    }
+}
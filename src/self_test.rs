@@ -0,0 +1,162 @@
+//! `self-test`: check that every part of the pipeline works on this host.
+
+use crate::compare;
+use crate::decode;
+use crate::scapy;
+use pyo3::Python;
+
+/// Expressions both Scapy and oside are expected to dissect identically.
+const KNOWN_GOOD: &[&str] = &[
+    "Ether()/IP()/UDP()",
+    "Ether()/IP()/TCP()",
+    "Ether()/IP()/ICMP()",
+    "Ether()/IPv6()/UDP()",
+];
+
+/// Pairs of expressions whose dissections must be reported as different,
+/// with a path expected among the differences.
+const KNOWN_MISMATCH: &[(&str, &str, &str)] = &[
+    (
+        "Ether()/IP(ttl=1)/UDP()",
+        "Ether()/IP(ttl=2)/UDP()",
+        "ip.ttl",
+    ),
+    (
+        "Ether()/IP()/UDP(dport=53)",
+        "Ether()/IP()/UDP(dport=54)",
+        "udp.dport",
+    ),
+    ("Ether()/IP()/UDP()", "Ether()/IP()/TCP()", "layers[2]"),
+];
+
+struct Check {
+    failures: usize,
+}
+
+impl Check {
+    fn report(&mut self, what: &str, res: Result<(), String>, hint: &str) -> bool {
+        match res {
+            Ok(()) => {
+                println!("ok    {}", what);
+                true
+            }
+            Err(e) => {
+                println!("FAIL  {}: {}", what, e);
+                println!("      hint: {}", hint);
+                self.failures += 1;
+                false
+            }
+        }
+    }
+}
+
+/// Run the self-test, returning the process exit code.
+pub fn run(py: Python) -> i32 {
+    let mut c = Check { failures: 0 };
+
+    let interp = py
+        .eval("1 + 1", None, None)
+        .and_then(|v| v.extract::<i32>())
+        .map_err(|e| format!("{:?}", e))
+        .and_then(|v| {
+            if v == 2 {
+                Ok(())
+            } else {
+                Err(format!("1 + 1 = {}", v))
+            }
+        });
+    if !c.report(
+        "embedded interpreter evaluates code",
+        interp,
+        "the binary may have been built without its Python resources; rebuild with `make`",
+    ) {
+        return 1;
+    }
+
+    if !c.report(
+        "scapy imports",
+        scapy::try_init(py),
+        "scapy is installed next to the binary in lib/ by pyoxidizer; check that lib/ was copied along",
+    ) {
+        return 1;
+    }
+
+    let oside = scapy::build(py, KNOWN_GOOD[0]).and_then(|data| {
+        decode::oside_json("ether", &data)
+            .map(|_| ())
+            .ok_or_else(|| "oside could not decode a default UDP frame".to_string())
+    });
+    c.report(
+        "oside decodes a frame",
+        oside,
+        "the linked oside revision may be broken; check the oside entry in Cargo.lock",
+    );
+
+    for expr in KNOWN_GOOD {
+        let res = layers_of(py, expr, expr).and_then(|(s, o)| {
+            let diffs = compare::compare(&s, &o);
+            match diffs.first() {
+                None => Ok(()),
+                Some(d) => Err(format!(
+                    "{} difference(s), first at {}: scapy {} oside {}",
+                    diffs.len(),
+                    d.path,
+                    d.reference,
+                    d.oside
+                )),
+            }
+        });
+        c.report(
+            &format!("{} matches", expr),
+            res,
+            "Scapy and oside disagree on a basic packet; compare their versions",
+        );
+    }
+
+    for (a, b, path) in KNOWN_MISMATCH {
+        let res = layers_of(py, a, b).and_then(|(s, o)| {
+            let diffs = compare::compare(&s, &o);
+            if diffs.iter().any(|d| d.path == *path) {
+                Ok(())
+            } else if diffs.is_empty() {
+                Err("no differences reported".to_string())
+            } else {
+                let paths: Vec<&str> = diffs.iter().map(|d| d.path.as_str()).collect();
+                Err(format!(
+                    "differences at {}, expected {}",
+                    paths.join(", "),
+                    path
+                ))
+            }
+        });
+        c.report(
+            &format!("{} vs {} is reported at {}", a, b, path),
+            res,
+            "the comparison engine is not detecting differences",
+        );
+    }
+
+    if c.failures > 0 {
+        println!("{} check(s) failed", c.failures);
+        1
+    } else {
+        println!("all checks passed");
+        0
+    }
+}
+
+/// Scapy's dissection of the bytes of `scapy_expr` and oside's decode of the
+/// bytes of `oside_expr`.
+fn layers_of(
+    py: Python,
+    scapy_expr: &str,
+    oside_expr: &str,
+) -> Result<(Vec<compare::Layer>, Vec<compare::Layer>), String> {
+    let scapy_data = scapy::build(py, scapy_expr)?;
+    let oside_data = scapy::build(py, oside_expr)?;
+    let s = compare::layers(&scapy::dissect(py, "ether", &scapy_data)?);
+    let o = compare::layers(
+        &decode::oside_json("ether", &oside_data).ok_or("oside could not decode the frame")?,
+    );
+    Ok((s, o))
+}
@@ -0,0 +1,233 @@
+//! The layer comparators of `--semantic`: values that differ in text but
+//! mean the same, which is where most false positives come from.
+//!
+//! - DNS names compare case-insensitively, with or without the trailing
+//!   dot, as text or in wire format, and a name ending in a compression
+//!   pointer matches an expanded name starting with its labels, since the
+//!   pointer stands for the rest.
+//! - IPv6 addresses compare as addresses, whatever their zero compression.
+//! - MAC addresses compare as their six bytes, whatever the separators
+//!   (`:`, `-`, Cisco-style `.` or none) and case.
+
+use crate::comparator::LayerComparator;
+use crate::compare::{scalar, value_str};
+use serde_json::Value;
+use std::net::Ipv6Addr;
+use std::sync::Arc;
+
+/// The comparators `--semantic` adds.
+pub fn comparators() -> Vec<Arc<dyn LayerComparator>> {
+    vec![
+        Arc::new(Ipv6Addresses),
+        Arc::new(MacAddresses),
+        Arc::new(DnsNames),
+    ]
+}
+
+/// The DNS layers, as Scapy names them.
+const DNS_LAYERS: &[&str] = &[
+    "dns",
+    "dnsqr",
+    "dnsrr",
+    "dnsrrsoa",
+    "dnsrrmx",
+    "dnsrrsrv",
+    "dnsrrnsec",
+    "dnsrrrsig",
+];
+
+/// The DNS fields holding a name.
+const NAME_FIELDS: &[&str] = &[
+    "qname",
+    "rrname",
+    "rdata",
+    "mname",
+    "rname",
+    "exchange",
+    "target",
+    "nextname",
+    "signersname",
+];
+
+/// The DNS fields holding questions or records, compared record by record.
+const SECTION_FIELDS: &[&str] = &["qd", "an", "ns", "ar"];
+
+pub struct DnsNames;
+
+/// A DNS name: its lowercased labels, and whether a compression pointer
+/// follows them.
+#[derive(Debug, PartialEq)]
+struct Name {
+    labels: Vec<String>,
+    pointer: bool,
+}
+
+/// The name of wire-format bytes: length-prefixed labels up to a zero
+/// length or a pointer, which must end the bytes.
+fn wire_name(data: &[u8]) -> Option<Name> {
+    let mut labels = vec![];
+    let mut at = 0;
+    loop {
+        let len = *data.get(at)? as usize;
+        if len == 0 {
+            return (at + 1 == data.len()).then(|| Name {
+                labels,
+                pointer: false,
+            });
+        }
+        if len & 0xc0 == 0xc0 {
+            return (at + 2 == data.len()).then(|| Name {
+                labels,
+                pointer: true,
+            });
+        }
+        if len > 63 {
+            return None;
+        }
+        let label = data.get(at + 1..at + 1 + len)?;
+        labels.push(String::from_utf8_lossy(label).to_lowercase());
+        at += 1 + len;
+    }
+}
+
+fn text_name(s: &str) -> Name {
+    let s = s.strip_suffix('.').unwrap_or(s);
+    Name {
+        labels: if s.is_empty() {
+            vec![]
+        } else {
+            s.split('.').map(|l| l.to_lowercase()).collect()
+        },
+        pointer: false,
+    }
+}
+
+/// The name a field value stands for: a hex string of wire-format or
+/// printable bytes, as Scapy renders bytes, or else text.
+fn dns_name(v: &Value) -> Option<Name> {
+    let s = scalar(v).as_str()?;
+    let hex = s.len() >= 2
+        && s.len() % 2 == 0
+        && s.bytes().all(|b| b.is_ascii_hexdigit())
+        && !s.bytes().all(|b| b.is_ascii_digit());
+    if hex {
+        let bytes: Vec<u8> = (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect();
+        if let Some(name) = wire_name(&bytes) {
+            return Some(name);
+        }
+        if bytes.iter().all(|b| b.is_ascii_graphic()) {
+            return Some(text_name(&String::from_utf8_lossy(&bytes)));
+        }
+    }
+    Some(text_name(s))
+}
+
+fn same_name(r: &Name, o: &Name) -> bool {
+    match (r.pointer, o.pointer) {
+        (true, false) => o.labels.starts_with(&r.labels) && o.labels.len() > r.labels.len(),
+        (false, true) => r.labels.starts_with(&o.labels) && r.labels.len() > o.labels.len(),
+        _ => r == o,
+    }
+}
+
+/// Whether two questions or records, or lists of them, agree once their
+/// names are compared as names.
+fn same_records(r: &Value, o: &Value) -> bool {
+    match (scalar(r), scalar(o)) {
+        (Value::Array(r), Value::Array(o)) => {
+            r.len() == o.len() && r.iter().zip(o).all(|(r, o)| same_records(r, o))
+        }
+        (Value::Object(r), Value::Object(o)) => {
+            r.len() == o.len()
+                && r.iter().all(|(key, rv)| {
+                    o.get(key).map_or(false, |ov| {
+                        if NAME_FIELDS.contains(&key.as_str()) {
+                            same_field_name(rv, ov)
+                        } else {
+                            same_records(rv, ov)
+                        }
+                    })
+                })
+        }
+        (r, o) => value_str(r) == value_str(o),
+    }
+}
+
+fn same_field_name(r: &Value, o: &Value) -> bool {
+    match (dns_name(r), dns_name(o)) {
+        (Some(r), Some(o)) => same_name(&r, &o),
+        _ => same_records(r, o),
+    }
+}
+
+impl LayerComparator for DnsNames {
+    fn layers(&self) -> &[&str] {
+        DNS_LAYERS
+    }
+
+    fn equivalent(&self, _layer: &str, field: &str, r: &Value, o: &Value) -> Option<bool> {
+        if NAME_FIELDS.contains(&field) {
+            Some(same_field_name(r, o))
+        } else if SECTION_FIELDS.contains(&field) {
+            Some(same_records(r, o))
+        } else {
+            None
+        }
+    }
+}
+
+pub struct Ipv6Addresses;
+
+impl LayerComparator for Ipv6Addresses {
+    fn layers(&self) -> &[&str] {
+        &["*"]
+    }
+
+    fn equivalent(&self, _layer: &str, _field: &str, r: &Value, o: &Value) -> Option<bool> {
+        let addr = |v: &Value| scalar(v).as_str()?.parse::<Ipv6Addr>().ok();
+        Some(addr(r)? == addr(o)?)
+    }
+}
+
+pub struct MacAddresses;
+
+/// The bytes of a MAC address written as six pairs of hex digits with
+/// `:` or `-` between them, three groups of four with `.` between them,
+/// or twelve digits together.
+fn mac(v: &Value) -> Option<Vec<u8>> {
+    let s = scalar(v).as_str()?;
+    let groups: Vec<&str> = s
+        .split(|c: char| c == ':' || c == '-' || c == '.')
+        .collect();
+    let well_formed = match groups.len() {
+        6 => groups.iter().all(|g| g.len() == 2),
+        3 => groups.iter().all(|g| g.len() == 4) && s.contains('.'),
+        1 => s.len() == 12,
+        _ => false,
+    };
+    if !well_formed
+        || !groups
+            .iter()
+            .all(|g| g.bytes().all(|b| b.is_ascii_hexdigit()))
+    {
+        return None;
+    }
+    let digits: String = groups.concat();
+    (0..12)
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).ok())
+        .collect()
+}
+
+impl LayerComparator for MacAddresses {
+    fn layers(&self) -> &[&str] {
+        &["*"]
+    }
+
+    fn equivalent(&self, _layer: &str, _field: &str, r: &Value, o: &Value) -> Option<bool> {
+        Some(mac(r)? == mac(o)?)
+    }
+}
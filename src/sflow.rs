@@ -0,0 +1,405 @@
+//! `--sflow`: the packet headers sampled by sFlow agents, read from a
+//! capture of the sFlow v5 datagrams they send to a collector (UDP port
+//! 6343). Flow collectors see headers from all over a network, a plentiful
+//! source of real-world cases.
+//!
+//! Each raw packet header record of a flow sample or expanded flow sample
+//! becomes a case. Agents send only the first bytes of the frame, commonly
+//! 128, so most cases are truncated; they carry the length of the frame
+//! they were cut from and are compared truncation-aware. IPv4 and IPv6
+//! headers are given an Ethernet header with zero addresses, as loopback
+//! frames are.
+
+use crate::pcap::{self, Frame};
+use crate::runner::{Annotations, Case, Input};
+
+/// The UDP port sFlow agents send to.
+pub const PORT: u16 = 6343;
+
+/// sFlow header protocols of the raw packet header record.
+const HEADER_ETHERNET: u32 = 1;
+const HEADER_IPV4: u32 = 11;
+const HEADER_IPV6: u32 = 12;
+
+/// Sample formats (enterprise 0).
+const FLOW_SAMPLE: u32 = 1;
+const EXPANDED_FLOW_SAMPLE: u32 = 3;
+
+/// Flow record format of a raw packet header.
+const RAW_PACKET_HEADER: u32 = 1;
+
+/// A packet header sampled by an agent.
+#[derive(Debug, Clone)]
+pub struct Sample {
+    /// The address of the agent that sampled it.
+    pub agent: String,
+    /// The header protocol, e.g. 1 for Ethernet.
+    pub protocol: u32,
+    /// The length of the frame on the wire, less the bytes stripped from
+    /// it, such as the FCS.
+    pub frame_length: usize,
+    pub header: Vec<u8>,
+}
+
+/// A reader of the big-endian (XDR) fields of a datagram.
+struct Xdr<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Xdr<'a> {
+    fn u32(&mut self) -> Result<u32, String> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let b = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or_else(|| format!("truncated at byte {}", self.pos))?;
+        self.pos += len;
+        Ok(b)
+    }
+
+    /// Opaque data: a length, then the bytes padded to 32 bits.
+    fn opaque(&mut self) -> Result<&'a [u8], String> {
+        let len = self.u32()? as usize;
+        let b = self.bytes(len)?;
+        self.pos += (4 - len % 4) % 4;
+        Ok(b)
+    }
+
+    fn skip(&mut self, words: usize) -> Result<(), String> {
+        self.bytes(words * 4).map(|_| ())
+    }
+}
+
+/// The flow records of a flow sample: their format and data.
+fn records<'a>(sample: &mut Xdr<'a>, expanded: bool) -> Result<Vec<(u32, &'a [u8])>, String> {
+    // sequence number, source ID (two words expanded), sampling rate,
+    // sample pool, drops, input and output interfaces (two words each
+    // expanded)
+    sample.skip(if expanded { 10 } else { 7 })?;
+    let count = sample.u32()?;
+    (0..count)
+        .map(|_| {
+            let format = sample.u32()?;
+            Ok((format, sample.opaque()?))
+        })
+        .collect()
+}
+
+/// The packet headers sampled in an sFlow v5 datagram.
+pub fn parse(datagram: &[u8]) -> Result<Vec<Sample>, String> {
+    let mut x = Xdr {
+        data: datagram,
+        pos: 0,
+    };
+    let version = x.u32()?;
+    if version != 5 {
+        return Err(format!("sFlow version {}, only 5 is read", version));
+    }
+    let agent = match x.u32()? {
+        1 => {
+            let a = x.bytes(4)?;
+            std::net::Ipv4Addr::new(a[0], a[1], a[2], a[3]).to_string()
+        }
+        2 => {
+            let a: [u8; 16] = x.bytes(16)?.try_into().unwrap();
+            std::net::Ipv6Addr::from(a).to_string()
+        }
+        t => return Err(format!("unknown agent address type {}", t)),
+    };
+    // sub-agent ID, sequence number, uptime
+    x.skip(3)?;
+    let count = x.u32()?;
+    let mut out = vec![];
+    for _ in 0..count {
+        let format = x.u32()?;
+        let data = x.opaque()?;
+        let expanded = match format {
+            FLOW_SAMPLE => false,
+            EXPANDED_FLOW_SAMPLE => true,
+            // counter samples, and other enterprises' formats
+            _ => continue,
+        };
+        let mut sample = Xdr { data, pos: 0 };
+        for (format, data) in records(&mut sample, expanded)? {
+            if format != RAW_PACKET_HEADER {
+                continue;
+            }
+            let mut record = Xdr { data, pos: 0 };
+            let protocol = record.u32()?;
+            let frame_length = record.u32()? as usize;
+            let stripped = record.u32()? as usize;
+            let header = record.opaque()?.to_vec();
+            out.push(Sample {
+                agent: agent.clone(),
+                protocol,
+                frame_length: frame_length.saturating_sub(stripped),
+                header,
+            });
+        }
+    }
+    Ok(out)
+}
+
+/// The UDP payload of a frame, if it is sent to or from the sFlow port.
+fn datagram(frame: &Frame) -> Option<Vec<u8>> {
+    let data = match frame.linktype {
+        pcap::LINKTYPE_ETHERNET => frame.data.clone(),
+        pcap::LINKTYPE_RAW => {
+            let ethertype: u16 = match frame.data.first()? >> 4 {
+                4 => 0x0800,
+                6 => 0x86dd,
+                _ => return None,
+            };
+            let mut data = vec![0; 12];
+            data.extend_from_slice(&ethertype.to_be_bytes());
+            data.extend_from_slice(&frame.data);
+            data
+        }
+        linktype => pcap::loopback_to_ether(linktype, &frame.data)?,
+    };
+    let be16 = |at: usize| Some(u16::from_be_bytes(data.get(at..at + 2)?.try_into().ok()?));
+    let mut at = 12;
+    while matches!(be16(at)?, 0x8100 | 0x88a8) {
+        at += 4;
+    }
+    let ip = at + 2;
+    let udp = match be16(at)? {
+        0x0800 => {
+            let ihl = (*data.get(ip)? & 0x0f) as usize * 4;
+            // fragments are not put back together
+            let fragment = be16(ip + 6)? & 0x3fff != 0;
+            if ihl < 20 || *data.get(ip + 9)? != 17 || fragment {
+                return None;
+            }
+            ip + ihl
+        }
+        0x86dd if *data.get(ip + 6)? == 17 => ip + 40,
+        _ => return None,
+    };
+    if be16(udp)? != PORT && be16(udp + 2)? != PORT {
+        return None;
+    }
+    data.get(udp + 8..).map(|payload| payload.to_vec())
+}
+
+/// A sampled header as a frame starting at Ethernet.
+fn ether_frame(sample: &Sample) -> Option<(Vec<u8>, usize)> {
+    let ethertype: u16 = match sample.protocol {
+        HEADER_ETHERNET => return Some((sample.header.clone(), sample.frame_length)),
+        HEADER_IPV4 => 0x0800,
+        HEADER_IPV6 => 0x86dd,
+        _ => return None,
+    };
+    let mut data = vec![0; 12];
+    data.extend_from_slice(&ethertype.to_be_bytes());
+    data.extend_from_slice(&sample.header);
+    Some((data, sample.frame_length + 14))
+}
+
+/// The cases of the headers sampled in a capture of sFlow datagrams, from
+/// the frames selected by --frames and --time-range.
+pub fn cases(frames: Vec<Frame>, fname: &str, verbose: bool) -> Vec<Case> {
+    let mut cases = vec![];
+    let mut datagrams = 0;
+    let mut skipped = 0;
+    for frame in frames {
+        let data = match datagram(&frame) {
+            Some(data) => data,
+            None => continue,
+        };
+        datagrams += 1;
+        let samples = match parse(&data) {
+            Ok(samples) => samples,
+            Err(e) => {
+                eprintln!("{}#{}: bad sFlow datagram: {}", fname, frame.index, e);
+                continue;
+            }
+        };
+        for (n, sample) in samples.iter().enumerate() {
+            let (data, frame_length) = match ether_frame(sample) {
+                Some(frame) => frame,
+                None => {
+                    skipped += 1;
+                    if verbose {
+                        eprintln!(
+                            "{}#{}.{}: skipping header protocol {} sampled by {}",
+                            fname,
+                            frame.index,
+                            n + 1,
+                            sample.protocol,
+                            sample.agent
+                        );
+                    }
+                    continue;
+                }
+            };
+            cases.push(Case {
+                source: format!("{}#{}.{}", fname, frame.index, n + 1),
+                annotations: Annotations {
+                    orig_len: Some(frame_length).filter(|len| *len > data.len()),
+                    ..Default::default()
+                },
+                input: Input::Frame(data),
+                tag: None,
+                timestamp: Some(frame.timestamp),
+            });
+        }
+    }
+    eprintln!(
+        "{}: {} sampled headers from {} sFlow datagrams{}",
+        fname,
+        cases.len(),
+        datagrams,
+        if skipped > 0 {
+            format!(", {} of other header protocols left out", skipped)
+        } else {
+            String::new()
+        }
+    );
+    cases
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(out: &mut Vec<u8>, words: &[u32]) {
+        for w in words {
+            out.extend_from_slice(&w.to_be_bytes());
+        }
+    }
+
+    fn opaque(out: &mut Vec<u8>, data: &[u8]) {
+        words(out, &[data.len() as u32]);
+        out.extend_from_slice(data);
+        out.resize((out.len() + 3) & !3, 0);
+    }
+
+    /// A raw packet header record of the header, cut from a frame of
+    /// `frame_length` bytes with 4 stripped.
+    fn raw_header(protocol: u32, frame_length: u32, header: &[u8]) -> Vec<u8> {
+        let mut out = vec![];
+        words(&mut out, &[protocol, frame_length, 4]);
+        opaque(&mut out, header);
+        out
+    }
+
+    /// A flow sample, expanded or not, of the records.
+    fn flow_sample(expanded: bool, records: &[(u32, Vec<u8>)]) -> Vec<u8> {
+        let mut out = vec![0; if expanded { 40 } else { 28 }];
+        words(&mut out, &[records.len() as u32]);
+        for (format, data) in records {
+            words(&mut out, &[*format]);
+            opaque(&mut out, data);
+        }
+        out
+    }
+
+    /// A datagram from 192.0.2.1 of the samples.
+    fn datagram(samples: &[(u32, Vec<u8>)]) -> Vec<u8> {
+        let mut out = vec![];
+        words(&mut out, &[5, 1]);
+        out.extend_from_slice(&[192, 0, 2, 1]);
+        words(&mut out, &[0, 1, 1000, samples.len() as u32]);
+        for (format, data) in samples {
+            words(&mut out, &[*format]);
+            opaque(&mut out, data);
+        }
+        out
+    }
+
+    fn example() -> Vec<u8> {
+        datagram(&[
+            (
+                FLOW_SAMPLE,
+                flow_sample(
+                    false,
+                    &[
+                        // an extended switch record, not a header
+                        (1001, vec![0; 16]),
+                        (
+                            RAW_PACKET_HEADER,
+                            raw_header(HEADER_ETHERNET, 1518, &[1; 6]),
+                        ),
+                    ],
+                ),
+            ),
+            // a counter sample
+            (2, vec![0; 8]),
+            (
+                EXPANDED_FLOW_SAMPLE,
+                flow_sample(
+                    true,
+                    &[(RAW_PACKET_HEADER, raw_header(HEADER_IPV4, 2, &[2; 4]))],
+                ),
+            ),
+        ])
+    }
+
+    #[test]
+    fn samples() {
+        let samples = parse(&example()).unwrap();
+        assert_eq!(samples.len(), 2);
+        assert_eq!(samples[0].agent, "192.0.2.1");
+        assert_eq!(samples[0].protocol, HEADER_ETHERNET);
+        assert_eq!(samples[0].frame_length, 1514);
+        assert_eq!(samples[0].header, vec![1; 6]);
+        assert_eq!(samples[1].protocol, HEADER_IPV4);
+        // fewer bytes on the wire than stripped from them
+        assert_eq!(samples[1].frame_length, 0);
+        assert_eq!(samples[1].header, vec![2; 4]);
+    }
+
+    #[test]
+    fn ipv6_agent() {
+        let mut data = vec![];
+        words(&mut data, &[5, 2]);
+        data.extend_from_slice(&std::net::Ipv6Addr::LOCALHOST.octets());
+        words(&mut data, &[0, 1, 1000, 0]);
+        let samples = parse(&data).unwrap();
+        assert!(samples.is_empty());
+    }
+
+    #[test]
+    fn truncated() {
+        let data = example();
+        for len in 0..data.len() {
+            assert!(parse(&data[..len]).is_err(), "parsed {} bytes", len);
+        }
+    }
+
+    #[test]
+    fn bad_header() {
+        let mut data = example();
+        data[3] = 4;
+        assert!(parse(&data).is_err());
+        let mut data = example();
+        data[7] = 3;
+        assert!(parse(&data).is_err());
+    }
+
+    #[test]
+    fn bad_lengths() {
+        // more samples than the datagram holds
+        let mut data = example();
+        data[24..28].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert!(parse(&data).is_err());
+        // a sample longer than the datagram
+        let mut data = example();
+        data[32..36].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert!(parse(&data).is_err());
+        // a header longer than its record
+        let mut data = datagram(&[(
+            FLOW_SAMPLE,
+            flow_sample(false, &[(RAW_PACKET_HEADER, raw_header(1, 64, &[0; 8]))]),
+        )]);
+        let at = data.len() - 12;
+        data[at..at + 4].copy_from_slice(&9u32.to_be_bytes());
+        assert!(parse(&data).is_err());
+    }
+}
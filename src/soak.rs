@@ -0,0 +1,135 @@
+//! `soak`: decode cases in a loop for a while, watching oside's throughput
+//! over time and checking a sample of the decodes, both against the first
+//! decode of the same bytes (to catch state leaking between decodes) and
+//! against the reference.
+
+use crate::backend::Backend;
+use crate::runner::{self, Case, Input};
+use crate::{compare, decode, sandbox, scapy, Opts};
+use pyo3::Python;
+use std::time::{Duration, Instant};
+
+/// Throughput in the last window below this fraction of the first window's
+/// fails the run.
+const MIN_THROUGHPUT_RATIO: f64 = 0.5;
+
+pub fn run<'py>(
+    py: Python<'py>,
+    backends: &mut [Box<dyn Backend + 'py>],
+    opts: &Opts,
+    cases: Vec<Case>,
+    seconds: u64,
+    sample: usize,
+) -> i32 {
+    sandbox::enter(opts);
+    let mut frames = vec![];
+    for case in cases {
+        let data = match &case.input {
+            Input::Frame(data) => data.clone(),
+            Input::Expr(expr) => match scapy::build(py, expr) {
+                Ok(data) => data,
+                Err(e) => {
+                    eprintln!("{}: scapy could not build {}: {}", case.label(), expr, e);
+                    continue;
+                }
+            },
+        };
+        frames.push((case.label(), runner::entry(opts, &data).to_vec()));
+    }
+    if frames.is_empty() {
+        eprintln!("soak: no cases to decode");
+        return 1;
+    }
+    let start = &opts.start_layer;
+    let first: Vec<_> = frames
+        .iter()
+        .map(|(_, data)| decode::oside_json(start, data))
+        .collect();
+
+    let sample = sample.max(1);
+    let window = Duration::from_secs((seconds / 10).max(1));
+    let began = Instant::now();
+    let deadline = began + Duration::from_secs(seconds);
+    let mut window_start = began;
+    let mut window_decodes = 0;
+    let mut window_time = Duration::default();
+    let mut rates: Vec<f64> = vec![];
+    let mut decodes: usize = 0;
+    let mut changed = 0;
+    let mut differing = 0;
+    loop {
+        let idx = decodes % frames.len();
+        let (label, data) = &frames[idx];
+        window_time += decode::oside_decode_time(start, data);
+        window_decodes += 1;
+        if decodes % sample == 0 {
+            let json = decode::oside_json(start, data);
+            if json != first[idx] {
+                println!(
+                    "{}: decode {} differs from the first decode",
+                    label, decodes
+                );
+                changed += 1;
+            }
+            let layers = json.as_ref().map(compare::layers).unwrap_or_default();
+            for backend in backends.iter_mut() {
+                match backend.dissect(data) {
+                    Ok(reference) if !compare::compare(&reference, &layers).is_empty() => {
+                        if opts.verbose > 0 {
+                            println!("{}: differs from {}", label, backend.name());
+                        }
+                        differing += 1;
+                    }
+                    Ok(_) => {}
+                    Err(e) => println!("{}: {} failed to dissect: {}", label, backend.name(), e),
+                }
+            }
+        }
+        decodes += 1;
+
+        let now = Instant::now();
+        if now - window_start >= window || now >= deadline {
+            let rate = window_decodes as f64 / window_time.as_secs_f64();
+            println!(
+                "{:>7.0}s  {:>10.0} frames/s",
+                (now - began).as_secs_f64(),
+                rate
+            );
+            rates.push(rate);
+            window_start = now;
+            window_decodes = 0;
+            window_time = Duration::default();
+        }
+        if now >= deadline {
+            break;
+        }
+    }
+
+    let min = rates.iter().cloned().fold(f64::INFINITY, f64::min);
+    let max = rates.iter().cloned().fold(0.0, f64::max);
+    let mean = rates.iter().sum::<f64>() / rates.len() as f64;
+    let first_rate = rates[0];
+    let last_rate = rates[rates.len() - 1];
+    println!(
+        "{} decodes of {} frames: {:.0} frames/s mean, {:.0} min, {:.0} max, last window at {:.0}% of the first",
+        decodes,
+        frames.len(),
+        mean,
+        min,
+        max,
+        last_rate / first_rate * 100.0
+    );
+    println!(
+        "{} sampled decodes changed since the first decode, {} differed from the reference",
+        changed, differing
+    );
+    let degraded = rates.len() > 1 && last_rate < first_rate * MIN_THROUGHPUT_RATIO;
+    if degraded {
+        println!("throughput degraded over the run");
+    }
+    if changed > 0 || degraded {
+        1
+    } else {
+        0
+    }
+}
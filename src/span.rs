@@ -0,0 +1,60 @@
+//! Ranges given on the command line, such as `--frames 1000..2000`.
+
+use std::str::FromStr;
+
+/// A range written as `A..B`, `A..=B`, `A..`, `..B` or just `A`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Span<T> {
+    pub start: Option<T>,
+    pub end: Option<T>,
+    /// Whether `end` itself is part of the range.
+    pub inclusive: bool,
+}
+
+impl<T: FromStr + PartialOrd + Copy> Span<T> {
+    pub fn parse(s: &str) -> Result<Span<T>, String> {
+        let bound = |v: &str| -> Result<Option<T>, String> {
+            let v = v.trim();
+            if v.is_empty() {
+                Ok(None)
+            } else {
+                v.parse()
+                    .map(Some)
+                    .map_err(|_| format!("bad range bound '{}'", v))
+            }
+        };
+        if let Some((a, b)) = s.split_once("..=") {
+            Ok(Span {
+                start: bound(a)?,
+                end: bound(b)?,
+                inclusive: true,
+            })
+        } else if let Some((a, b)) = s.split_once("..") {
+            Ok(Span {
+                start: bound(a)?,
+                end: bound(b)?,
+                inclusive: false,
+            })
+        } else {
+            let v = bound(s)?.ok_or("empty range")?;
+            Ok(Span {
+                start: Some(v),
+                end: Some(v),
+                inclusive: true,
+            })
+        }
+    }
+
+    pub fn contains(&self, v: T) -> bool {
+        if let Some(start) = self.start {
+            if v < start {
+                return false;
+            }
+        }
+        match self.end {
+            Some(end) if self.inclusive => v <= end,
+            Some(end) => v < end,
+            None => true,
+        }
+    }
+}
@@ -0,0 +1,304 @@
+//! `stateful`: generate multi-packet scenarios from a state machine and
+//! compare every packet of them.
+//!
+//! Single-packet generation never produces what a conversation does: a
+//! handshake, data in both directions, a teardown, with sequence numbers,
+//! ports and addresses carried from one packet to the next. A `Machine`
+//! describes such a conversation as states and the transitions between
+//! them, each sending one packet; a scenario is a random walk from the
+//! start state until a state with no way out or the length limit. Now and
+//! then, with probability --invalid, a packet of a transition that is not
+//! valid in the current state is sent instead, leaving the state as is.
+//!
+//! Machines are YAML, as in the built-in `tcp` one:
+//!
+//! ```yaml
+//! name: tcp
+//! start: listen
+//! vars:                      # instantiated once per scenario
+//!   cport: "{u16}"
+//!   client: "Ether()/IP(src='10.0.0.1', dst='10.0.0.2')"
+//! transitions:
+//!   - name: syn
+//!     from: listen
+//!     to: syn-sent
+//!     packet: "{client}/TCP(sport={cport}, dport=80, flags='S')"
+//!     weight: 1              # the default
+//! ```
+//!
+//! Packet templates take the scenario's variables and the placeholders of
+//! `daemon::instantiate`, which are drawn anew for every packet. Every packet
+//! is a case; when any fails, the whole scenario is printed as a --batch
+//! file, annotated with the transitions, to replay and minimize.
+
+use crate::backend::Backend;
+use crate::budget::Budget;
+use crate::daemon;
+use crate::rng::{self, Rng};
+use crate::runner::{Case, Input, Runner};
+use crate::{sandbox, Opts};
+use pyo3::Python;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+const TCP: &str = r#"
+name: tcp
+start: listen
+vars:
+  cport: "{u16}"
+  sport: "{u16}"
+  cseq: "{u31}"
+  sseq: "{u31}"
+  client: "Ether(src='02:00:00:00:00:01', dst='02:00:00:00:00:02')/IP(src='10.0.0.1', dst='10.0.0.2', id={u16})"
+  server: "Ether(src='02:00:00:00:00:02', dst='02:00:00:00:00:01')/IP(src='10.0.0.2', dst='10.0.0.1', id={u16})"
+transitions:
+  - name: syn
+    from: listen
+    to: syn-sent
+    packet: "{client}/TCP(sport={cport}, dport={sport}, flags='S', seq={cseq}, options=[('MSS', 1460), ('SAckOK', b''), ('WScale', {u4})])"
+  - name: syn-ack
+    from: syn-sent
+    to: syn-received
+    packet: "{server}/TCP(sport={sport}, dport={cport}, flags='SA', seq={sseq}, ack={cseq}+1, options=[('MSS', 1460)])"
+  - name: rst-refused
+    from: syn-sent
+    to: closed
+    packet: "{server}/TCP(sport={sport}, dport={cport}, flags='RA', seq=0, ack={cseq}+1)"
+    weight: 0.1
+  - name: ack
+    from: syn-received
+    to: established
+    packet: "{client}/TCP(sport={cport}, dport={sport}, flags='A', seq={cseq}+1, ack={sseq}+1)"
+  - name: client-data
+    from: established
+    to: established
+    packet: "{client}/TCP(sport={cport}, dport={sport}, flags='PA', seq={cseq}+1, ack={sseq}+1, window={u16})/Raw(load={bytes})"
+    weight: 3
+  - name: server-data
+    from: established
+    to: established
+    packet: "{server}/TCP(sport={sport}, dport={cport}, flags='PA', seq={sseq}+1, ack={cseq}+1, window={u16})/Raw(load={bytes})"
+    weight: 3
+  - name: client-fin
+    from: established
+    to: fin-wait
+    packet: "{client}/TCP(sport={cport}, dport={sport}, flags='FA', seq={cseq}+1, ack={sseq}+1)"
+  - name: client-rst
+    from: established
+    to: closed
+    packet: "{client}/TCP(sport={cport}, dport={sport}, flags='R', seq={cseq}+1)"
+    weight: 0.2
+  - name: server-data-after-fin
+    from: fin-wait
+    to: fin-wait
+    packet: "{server}/TCP(sport={sport}, dport={cport}, flags='PA', seq={sseq}+1, ack={cseq}+2)/Raw(load={bytes})"
+    weight: 0.5
+  - name: server-fin
+    from: fin-wait
+    to: last-ack
+    packet: "{server}/TCP(sport={sport}, dport={cport}, flags='FA', seq={sseq}+1, ack={cseq}+2)"
+  - name: last-ack
+    from: last-ack
+    to: closed
+    packet: "{client}/TCP(sport={cport}, dport={sport}, flags='A', seq={cseq}+2, ack={sseq}+2)"
+"#;
+
+/// The built-in machines, by name.
+const MACHINES: &[(&str, &str)] = &[("tcp", TCP)];
+
+#[derive(Debug, Deserialize)]
+pub struct Machine {
+    pub name: String,
+    pub start: String,
+    #[serde(default)]
+    pub vars: BTreeMap<String, String>,
+    pub transitions: Vec<Transition>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Transition {
+    pub name: String,
+    pub from: String,
+    pub to: String,
+    pub packet: String,
+    #[serde(default = "default_weight")]
+    pub weight: f64,
+}
+
+fn default_weight() -> f64 {
+    1.0
+}
+
+/// A packet of a scenario.
+pub struct Step {
+    pub transition: String,
+    /// Whether the transition was not valid in the state it was sent in.
+    pub invalid: bool,
+    pub expr: String,
+}
+
+/// Replace the `{var}` placeholders of a template with the variables' values.
+fn fill(template: &str, vars: &BTreeMap<String, String>) -> String {
+    let mut out = template.to_string();
+    for (name, value) in vars {
+        out = out.replace(&format!("{{{}}}", name), value);
+    }
+    out
+}
+
+impl Machine {
+    /// A built-in machine by name, or else a YAML file.
+    pub fn load(spec: &str) -> Result<Machine, String> {
+        let machine: Machine = match MACHINES.iter().find(|(name, _)| *name == spec) {
+            Some((_, text)) => {
+                serde_yaml::from_str(text).map_err(|e| format!("{}: {}", spec, e))?
+            }
+            None => {
+                let text = std::fs::read_to_string(spec).map_err(|e| {
+                    let names: Vec<_> = MACHINES.iter().map(|(name, _)| *name).collect();
+                    format!(
+                        "{}: {}; the built-in machines are {}",
+                        spec,
+                        e,
+                        names.join(", ")
+                    )
+                })?;
+                serde_yaml::from_str(&text).map_err(|e| format!("{}: {}", spec, e))?
+            }
+        };
+        if !machine.transitions.iter().any(|t| t.from == machine.start) {
+            return Err(format!(
+                "{}: no transition from the start state '{}'",
+                spec, machine.start
+            ));
+        }
+        Ok(machine)
+    }
+
+    /// Pick one of the transitions by weight.
+    fn pick<'a>(transitions: &[&'a Transition], rng: &mut Rng) -> Option<&'a Transition> {
+        let total: f64 = transitions.iter().map(|t| t.weight.max(0.0)).sum();
+        if total <= 0.0 {
+            return None;
+        }
+        let mut x = rng.unit() * total;
+        for t in transitions {
+            if x < t.weight.max(0.0) {
+                return Some(*t);
+            }
+            x -= t.weight.max(0.0);
+        }
+        transitions.last().copied()
+    }
+
+    /// A random scenario of at most `length` packets, sending a packet of a
+    /// transition invalid in the current state with probability `invalid`.
+    pub fn scenario(&self, rng: &mut Rng, length: usize, invalid: f64) -> Vec<Step> {
+        let vars: BTreeMap<String, String> = self
+            .vars
+            .iter()
+            .map(|(name, value)| (name.clone(), daemon::instantiate(value, rng)))
+            .collect();
+        let mut state = self.start.as_str();
+        let mut steps = vec![];
+        while steps.len() < length {
+            let (valid, other): (Vec<_>, Vec<_>) =
+                self.transitions.iter().partition(|t| t.from == state);
+            if valid.is_empty() {
+                break;
+            }
+            let wrong = !other.is_empty() && rng.unit() < invalid;
+            let t = match Machine::pick(if wrong { &other } else { &valid }, rng) {
+                Some(t) => t,
+                None => break,
+            };
+            steps.push(Step {
+                transition: t.name.clone(),
+                invalid: wrong,
+                expr: daemon::instantiate(&fill(&t.packet, &vars), rng),
+            });
+            if !wrong {
+                state = &t.to;
+            }
+        }
+        steps
+    }
+}
+
+/// The options of the subcommand.
+#[derive(Debug, Clone, clap::Args)]
+pub struct StatefulArgs {
+    /// The state machine: a built-in one (tcp) or a YAML file
+    #[clap(long, default_value = "tcp")]
+    pub machine: String,
+    /// Stop after this many seconds instead of running until interrupted
+    #[clap(long)]
+    pub seconds: Option<u64>,
+    /// Seed for the random values, to repeat a run; taken from the clock if not given
+    #[clap(long)]
+    pub seed: Option<u64>,
+    /// The most packets a scenario has
+    #[clap(long, default_value = "16")]
+    pub length: usize,
+    /// Probability of sending a packet of a transition invalid in the current state
+    #[clap(long, default_value = "0.1")]
+    pub invalid: f64,
+}
+
+pub fn run<'py>(
+    py: Python<'py>,
+    backends: &mut [Box<dyn Backend + 'py>],
+    opts: &Opts,
+    args: &StatefulArgs,
+) -> i32 {
+    let machine = Machine::load(&args.machine).unwrap_or_else(|e| panic!("{}", e));
+    let seed = args.seed.unwrap_or_else(rng::time_seed);
+    eprintln!("stateful {}: seed {}", machine.name, seed);
+    let mut rng = Rng::new(seed);
+    let mut budget = Budget::from_opts(opts, args.seconds);
+
+    let mut runner = Runner::new(py, backends, opts);
+    sandbox::enter(opts);
+    let mut n: u64 = 0;
+    while !runner.aborted() && budget.as_mut().map_or(true, Budget::next) {
+        n += 1;
+        let steps = machine.scenario(&mut rng, args.length, args.invalid);
+        let mut passed = vec![];
+        for (i, step) in steps.iter().enumerate() {
+            let tag = if step.invalid {
+                format!("invalid:{}", step.transition)
+            } else {
+                step.transition.clone()
+            };
+            passed.push(runner.case(&Case {
+                source: format!("stateful/{}@{}#{}/{}", machine.name, seed, n, i + 1),
+                input: Input::Expr(step.expr.clone()),
+                tag: Some(tag),
+                annotations: Default::default(),
+                timestamp: None,
+            }));
+        }
+        let failed = passed.iter().filter(|p| !**p).count();
+        if failed > 0 {
+            println!(
+                "# stateful/{}@{}#{}: {} of {} packets failed",
+                machine.name,
+                seed,
+                n,
+                failed,
+                steps.len()
+            );
+            for (step, passed) in steps.iter().zip(&passed) {
+                println!(
+                    "# {}{}{}",
+                    step.transition,
+                    if step.invalid { " (invalid here)" } else { "" },
+                    if *passed { "" } else { ": FAILED" }
+                );
+                println!("{}", step.expr);
+            }
+            println!();
+        }
+    }
+    runner.finish()
+}
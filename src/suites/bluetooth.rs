@@ -0,0 +1,150 @@
+//! Bluetooth HCI packets as carried over UART (H4), as in a
+//! `LINKTYPE_BLUETOOTH_HCI_H4` capture: commands and their events, ACL
+//! data with L2CAP signalling and ATT, and length fields disagreeing with
+//! the packet. The packets start at the H4 packet type byte, Scapy's
+//! `HCI_Hdr`.
+
+use super::bytes_expr;
+
+/// The ACL connection handle of the data packets.
+const HANDLE: u16 = 0x40;
+
+/// Commands, by tag.
+const COMMANDS: &[(&str, &str)] = &[
+    ("reset", "HCI_Cmd_Reset()"),
+    ("read-bd-addr", "HCI_Cmd_Read_BD_Addr()"),
+    ("set-event-mask", "HCI_Cmd_Set_Event_Mask()"),
+    (
+        "le-scan-parameters",
+        "HCI_Cmd_LE_Set_Scan_Parameters(type=1, interval=16, window=16)",
+    ),
+    ("le-scan-enable", "HCI_Cmd_LE_Set_Scan_Enable(enable=1)"),
+    (
+        "le-create-connection",
+        "HCI_Cmd_LE_Create_Connection(paddr='c0:01:02:03:04:05', patype=1)",
+    ),
+    ("disconnect", "HCI_Cmd_Disconnect(handle=0x40, reason=0x13)"),
+];
+
+/// Events, by tag.
+const EVENTS: &[(&str, &str)] = &[
+    (
+        "command-complete",
+        "HCI_Event_Command_Complete(number=1, opcode=0x0c03, status=0)",
+    ),
+    (
+        "command-status",
+        "HCI_Event_Command_Status(status=0, number=1, opcode=0x200d)",
+    ),
+    (
+        "disconnection-complete",
+        "HCI_Event_Disconnection_Complete(status=0, handle=0x40, reason=0x16)",
+    ),
+    (
+        "le-connection-complete",
+        "HCI_Event_LE_Meta()/HCI_LE_Meta_Connection_Complete(status=0, handle=0x40, role=0, patype=1, paddr='c0:01:02:03:04:05', interval=24, latency=0, supervision=72)",
+    ),
+];
+
+/// L2CAP payloads, by tag: signalling commands on channel 1 and ATT on
+/// channel 4.
+const L2CAP: &[(&str, &str)] = &[
+    (
+        "l2cap-conn-req",
+        "L2CAP_Hdr(cid=1)/L2CAP_CmdHdr(id=1)/L2CAP_ConnReq(psm=1, scid=0x40)",
+    ),
+    (
+        "l2cap-conn-resp",
+        "L2CAP_Hdr(cid=1)/L2CAP_CmdHdr(id=1)/L2CAP_ConnResp(dcid=0x41, scid=0x40, result=0, status=0)",
+    ),
+    (
+        "l2cap-conf-req",
+        "L2CAP_Hdr(cid=1)/L2CAP_CmdHdr(id=2)/L2CAP_ConfReq(dcid=0x41, flags=0)",
+    ),
+    (
+        "l2cap-disconn-req",
+        "L2CAP_Hdr(cid=1)/L2CAP_CmdHdr(id=3)/L2CAP_DisconnReq(dcid=0x41, scid=0x40)",
+    ),
+    (
+        "l2cap-info-req",
+        "L2CAP_Hdr(cid=1)/L2CAP_CmdHdr(id=4)/L2CAP_InfoReq(type=2)",
+    ),
+    (
+        "att-mtu",
+        "L2CAP_Hdr(cid=4)/ATT_Hdr()/ATT_Exchange_MTU_Request(mtu=517)",
+    ),
+    (
+        "att-read",
+        "L2CAP_Hdr(cid=4)/ATT_Hdr()/ATT_Read_Request(gatt_handle=3)",
+    ),
+];
+
+fn acl(payload: &str) -> String {
+    format!("HCI_Hdr()/HCI_ACL_Hdr(handle={:#x})/{}", HANDLE, payload)
+}
+
+pub fn cases() -> Vec<(String, String)> {
+    let mut out = vec![];
+    for (tag, command) in COMMANDS {
+        out.push((
+            format!("cmd-{}", tag),
+            format!("HCI_Hdr()/HCI_Command_Hdr()/{}", command),
+        ));
+    }
+    for (tag, event) in EVENTS {
+        out.push((
+            format!("event-{}", tag),
+            format!("HCI_Hdr()/HCI_Event_Hdr()/{}", event),
+        ));
+    }
+    for (tag, payload) in L2CAP {
+        out.push((tag.to_string(), acl(payload)));
+    }
+    // ACL data on a dynamic channel, of increasing length
+    for len in [0, 1, 23, 251] {
+        let data: Vec<u8> = (0..len).map(|i| (i * 7) as u8).collect();
+        out.push((
+            "l2cap-data".to_string(),
+            acl(&format!(
+                "L2CAP_Hdr(cid=0x40)/Raw(load={})",
+                bytes_expr(&data)
+            )),
+        ));
+    }
+    // length fields claiming more or less than the packet carries
+    for len in [0, 2, 255] {
+        out.push((
+            "cmd-bad-length".to_string(),
+            format!("HCI_Hdr()/HCI_Command_Hdr(len={})/HCI_Cmd_Reset()", len),
+        ));
+        out.push((
+            "event-bad-length".to_string(),
+            format!(
+                "HCI_Hdr()/HCI_Event_Hdr(len={})/HCI_Event_Command_Complete(number=1, opcode=0x0c03, status=0)",
+                len
+            ),
+        ));
+    }
+    for len in [0, 3, 1000] {
+        out.push((
+            "acl-bad-length".to_string(),
+            format!(
+                "HCI_Hdr()/HCI_ACL_Hdr(handle={:#x}, len={})/L2CAP_Hdr(cid=4)/ATT_Hdr()/ATT_Read_Request(gatt_handle=3)",
+                HANDLE, len
+            ),
+        ));
+        out.push((
+            "l2cap-bad-length".to_string(),
+            acl(&format!(
+                "L2CAP_Hdr(cid=4, len={})/ATT_Hdr()/ATT_Read_Request(gatt_handle=3)",
+                len
+            )),
+        ));
+    }
+    // a packet type byte no transport defines
+    out.push((
+        "unknown-type".to_string(),
+        "HCI_Hdr(type=0xff)/Raw(load=b'\\x00\\x01\\x02')".to_string(),
+    ));
+    out
+}
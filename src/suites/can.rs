@@ -0,0 +1,88 @@
+//! SocketCAN frames (Scapy's `CAN`): standard and extended identifiers,
+//! every data length, remote transmission requests and error frames, and
+//! length fields disagreeing with the data. The frames start at the CAN
+//! header, as in a `LINKTYPE_CAN_SOCKETCAN` capture.
+
+use super::bytes_expr;
+
+/// `len` data bytes counting down from 0xff, so that a shifted boundary
+/// shows in the bytes.
+fn data(len: usize) -> String {
+    bytes_expr(&(0..len).map(|i| 0xff - i as u8).collect::<Vec<u8>>())
+}
+
+pub fn cases() -> Vec<(String, String)> {
+    let mut out = vec![];
+    // every data length, with the smallest, a typical and the largest
+    // standard (11-bit) identifier
+    for id in [0, 0x123, 0x7ff] {
+        for len in 0..=8 {
+            out.push((
+                "standard".to_string(),
+                format!("CAN(identifier={:#x}, data={})", id, data(len)),
+            ));
+        }
+    }
+    // extended (29-bit) identifiers
+    for id in [0x800, 0x18daf110, 0x1fffffff] {
+        for len in [0, 1, 8] {
+            out.push((
+                "extended".to_string(),
+                format!(
+                    "CAN(flags='extended', identifier={:#x}, data={})",
+                    id,
+                    data(len)
+                ),
+            ));
+        }
+    }
+    // remote transmission requests carry a length but no data
+    for (flags, id) in [
+        ("remote_transmission_request", 0x7df),
+        ("extended+remote_transmission_request", 0x18db33f1),
+    ] {
+        for len in [0, 4, 8] {
+            out.push((
+                "rtr".to_string(),
+                format!(
+                    "CAN(flags='{}', identifier={:#x}, length={})",
+                    flags, id, len
+                ),
+            ));
+        }
+    }
+    // error frames: the identifier holds the error class, the data the
+    // details
+    for class in [0x1, 0x4, 0x20, 0x40] {
+        out.push((
+            "error".to_string(),
+            format!(
+                "CAN(flags='error', identifier={:#x}, data={})",
+                class,
+                data(8)
+            ),
+        ));
+    }
+    // length fields beyond the 8 bytes classic CAN carries, or short of
+    // the data
+    for len in [9, 15, 255] {
+        out.push((
+            "bad-length".to_string(),
+            format!("CAN(identifier=0x123, length={}, data={})", len, data(8)),
+        ));
+    }
+    for len in [0, 3] {
+        out.push((
+            "short-length".to_string(),
+            format!("CAN(identifier=0x123, length={}, data={})", len, data(8)),
+        ));
+    }
+    // frames cut inside the header and inside the data
+    for cut in [4, 8, 12] {
+        out.push((
+            "truncated".to_string(),
+            format!("bytes(CAN(identifier=0x123, data={}))[:{}]", data(8), cut),
+        ));
+    }
+    out
+}
@@ -0,0 +1,43 @@
+//! Multi-byte fields set to asymmetric values (0x0102, 0x01020304, ...), so
+//! that a parser reading one with the wrong byte order reports a value
+//! that differs unmistakably, flagged as byte-swapped in the report.
+
+const CASES: &[(&str, &str)] = &[
+    ("ip", "Ether()/IP(id=0x0102, frag=0x0304)/Raw(load=b'endianness')"),
+    (
+        "ipv6",
+        "Ether()/IPv6(fl=0x10203)/Raw(load=b'endianness')",
+    ),
+    (
+        "udp",
+        "Ether()/IP()/UDP(sport=0x0102, dport=0x0304)/Raw(load=b'endianness')",
+    ),
+    (
+        "tcp",
+        "Ether()/IP()/TCP(sport=0x0102, dport=0x0304, seq=0x01020304, ack=0x05060708, window=0x0102, urgptr=0x0304)",
+    ),
+    (
+        "tcp-options",
+        "Ether()/IP()/TCP(options=[('MSS', 0x0102), ('Timestamp', (0x01020304, 0x05060708))])",
+    ),
+    (
+        "icmp",
+        "Ether()/IP()/ICMP(type=8, id=0x0102, seq=0x0304)",
+    ),
+    (
+        "icmpv6",
+        "Ether()/IPv6()/ICMPv6EchoRequest(id=0x0102, seq=0x0304)",
+    ),
+    ("arp", "Ether()/ARP(op=0x0102)"),
+    (
+        "dot1q",
+        "Ether()/Dot1Q(vlan=0x0102)/IP()/UDP(sport=0x0102, dport=0x0304)",
+    ),
+];
+
+pub fn cases() -> Vec<(String, String)> {
+    CASES
+        .iter()
+        .map(|(tag, expr)| (tag.to_string(), expr.to_string()))
+        .collect()
+}
@@ -0,0 +1,91 @@
+//! IPsec AH and ESP headers, over IPv4, IPv6 and UDP encapsulation, with
+//! dummy SPIs and sequence numbers and opaque payloads. Nothing is
+//! encrypted or authenticated; the point is that both parsers agree on the
+//! header fields and on where the opaque part starts.
+
+use super::bytes_expr;
+
+/// The outer layers: tag, Scapy class and its next-protocol field.
+const OUTER: &[(&str, &str, &str)] = &[("ip", "IP", "proto"), ("ipv6", "IPv6", "nh")];
+
+const AH: u8 = 51;
+const ESP: u8 = 50;
+
+fn opaque(len: usize) -> String {
+    bytes_expr(&(0..len).map(|i| (i * 37 + 11) as u8).collect::<Vec<u8>>())
+}
+
+pub fn cases() -> Vec<(String, String)> {
+    let mut out = vec![];
+    for (outer_tag, class, next) in OUTER {
+        let outer = format!("Ether()/{}()", class);
+        // AH with the length computed, over a transport payload
+        for icv in [0, 12, 16, 32] {
+            for (spi, seq) in [(0u32, 0u32), (0x100, 1), (0xffffffff, 0xffffffff)] {
+                out.push((
+                    format!("ah-{}", outer_tag),
+                    format!(
+                        "{}/AH(nh=17, spi={}, seq={}, icv={})/UDP(sport=1234, dport=5678)/Raw(load=b'ah')",
+                        outer, spi, seq, opaque(icv)
+                    ),
+                ));
+            }
+        }
+        // AH with a length disagreeing with the ICV carried
+        for payloadlen in [0, 1, 2, 255] {
+            out.push((
+                format!("ah-{}-bad-length", outer_tag),
+                format!(
+                    "{}/AH(nh=17, payloadlen={}, spi=256, seq=1, icv={})/UDP(sport=1234, dport=5678)",
+                    outer,
+                    payloadlen,
+                    opaque(12)
+                ),
+            ));
+        }
+        // ESP, all opaque after SPI and sequence number
+        for len in [0, 1, 16, 64] {
+            for (spi, seq) in [(0x100u32, 1u32), (0xffffffff, 0)] {
+                out.push((
+                    format!("esp-{}", outer_tag),
+                    format!(
+                        "{}/ESP(spi={}, seq={}, data={})",
+                        outer,
+                        spi,
+                        seq,
+                        opaque(len)
+                    ),
+                ));
+            }
+        }
+        // ESP in UDP, as NAT traversal sends it
+        for len in [0, 16] {
+            out.push((
+                format!("esp-udp-{}", outer_tag),
+                format!(
+                    "{}/UDP(sport=4500, dport=4500)/ESP(spi=256, seq=1, data={})",
+                    outer,
+                    opaque(len)
+                ),
+            ));
+        }
+        // headers cut short, with the protocol set so that both parsers
+        // try the IPsec header
+        for (proto, tag, header) in [
+            (AH, "ah", &[17, 4, 0, 0, 0, 0][..]),
+            (ESP, "esp", &[0, 0, 1, 0, 0][..]),
+        ] {
+            out.push((
+                format!("{}-{}-truncated", tag, outer_tag),
+                format!(
+                    "Ether()/{}({}={})/Raw(load={})",
+                    class,
+                    next,
+                    proto,
+                    bytes_expr(header)
+                ),
+            ));
+        }
+    }
+    out
+}
@@ -0,0 +1,121 @@
+//! IPv4 options: record route, timestamp, loose and strict source route,
+//! with varied lengths, pointers and padding, well-formed and not.
+//!
+//! The options are given as raw bytes so that malformed ones reach both
+//! parsers exactly as written.
+
+use super::bytes_expr;
+
+const EOL: u8 = 0;
+const NOP: u8 = 1;
+const RR: u8 = 7;
+const TS: u8 = 68;
+const LSRR: u8 = 131;
+const SSRR: u8 = 137;
+
+fn expr(options: &[Vec<u8>]) -> String {
+    let options: Vec<String> = options
+        .iter()
+        .map(|o| format!("IPOption({})", bytes_expr(o)))
+        .collect();
+    format!(
+        "Ether()/IP(options=[{}])/UDP(sport=1234, dport=5678)/Raw(load=b'ipv4-options')",
+        options.join(", ")
+    )
+}
+
+/// A route option (RR, LSRR or SSRR) with `n` addresses; `len` overrides
+/// the length byte.
+fn route(kind: u8, n: u8, pointer: u8, len: Option<u8>) -> Vec<u8> {
+    let mut o = vec![kind, len.unwrap_or(3 + 4 * n), pointer];
+    for i in 0..n {
+        o.extend([10, 0, 0, i + 1]);
+    }
+    o
+}
+
+/// A timestamp option with `n` entries: bare timestamps for flag 0,
+/// address and timestamp pairs otherwise.
+fn timestamp(flag: u8, overflow: u8, n: u8, pointer: u8, len: Option<u8>) -> Vec<u8> {
+    let entry = if flag == 0 { 4 } else { 8 };
+    let mut o = vec![
+        TS,
+        len.unwrap_or(4 + entry * n),
+        pointer,
+        overflow << 4 | flag,
+    ];
+    for i in 0..n {
+        if flag != 0 {
+            o.extend([192, 0, 2, i + 1]);
+        }
+        o.extend((1000u32 * (i as u32 + 1)).to_be_bytes());
+    }
+    o
+}
+
+pub fn cases() -> Vec<(String, String)> {
+    let mut out = vec![];
+    let mut add = |tag: &str, options: Vec<Vec<u8>>| out.push((tag.to_string(), expr(&options)));
+
+    for (kind, tag) in [(RR, "rr"), (LSRR, "lsrr"), (SSRR, "ssrr")] {
+        for n in [0, 1, 2, 9] {
+            // pointer at the first slot, in the middle, and past the end
+            let mut pointers = vec![4, 4 + 4 * (n / 2), 4 + 4 * n];
+            pointers.dedup();
+            for pointer in pointers {
+                add(tag, vec![route(kind, n, pointer, None)]);
+            }
+        }
+        // malformed pointers: below the minimum, unaligned, far past the end
+        for pointer in [0, 3, 5, 255] {
+            add(
+                &format!("{}-bad-pointer", tag),
+                vec![route(kind, 2, pointer, None)],
+            );
+        }
+        // malformed lengths: too short to hold the pointer, not a whole
+        // number of addresses, longer than the option
+        for len in [0, 1, 2, 6, 40] {
+            add(
+                &format!("{}-bad-length", tag),
+                vec![route(kind, 1, 4, Some(len))],
+            );
+        }
+    }
+
+    for flag in [0, 1, 3] {
+        for n in [0, 1, 4] {
+            let entry = if flag == 0 { 4 } else { 8 };
+            let mut pointers = vec![5, 5 + entry * n];
+            pointers.dedup();
+            for pointer in pointers {
+                add("ts", vec![timestamp(flag, 0, n, pointer, None)]);
+            }
+        }
+        add("ts-overflow", vec![timestamp(flag, 15, 1, 13, None)]);
+        for pointer in [0, 4, 6, 255] {
+            add("ts-bad-pointer", vec![timestamp(flag, 0, 2, pointer, None)]);
+        }
+        for len in [0, 3, 7, 39] {
+            add("ts-bad-length", vec![timestamp(flag, 0, 1, 5, Some(len))]);
+        }
+    }
+    // an undefined flag value
+    add("ts-bad-flag", vec![timestamp(2, 0, 1, 5, None)]);
+
+    // padding and option lists
+    add("padding", vec![vec![NOP], vec![NOP], route(RR, 1, 4, None)]);
+    add("padding", vec![route(LSRR, 1, 4, None), vec![EOL]]);
+    add("padding", vec![vec![NOP]; 4]);
+    add("padding", vec![vec![EOL, 0, 0, 0]]);
+    add(
+        "padding",
+        vec![route(RR, 1, 4, None), timestamp(0, 0, 1, 5, None)],
+    );
+    // bytes after the end of the option list
+    add("after-eol", vec![vec![EOL, RR, 7, 4]]);
+    // an option cut off by the end of the header
+    add("truncated", vec![vec![RR]]);
+    add("truncated", vec![vec![TS, 12, 5]]);
+    out
+}
@@ -0,0 +1,116 @@
+//! ISO-TP (ISO 15765-2) frames over CAN, from Scapy's `isotp` contrib
+//! module: single frames of every size, and messages segmented into a
+//! first frame, flow control and consecutive frames, including ones longer
+//! than the 12-bit size field holds. The frames start at `ISOTPHeader`, the
+//! CAN header dispatching on the ISO-TP frame type.
+
+use super::bytes_expr;
+
+/// The identifiers of an OBD-II style request and its response.
+const TX_ID: u32 = 0x7e0;
+const RX_ID: u32 = 0x7e8;
+
+/// A message of `len` bytes counting up.
+fn message(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i % 251) as u8).collect()
+}
+
+fn header(id: u32) -> String {
+    format!("ISOTPHeader(identifier={:#x})", id)
+}
+
+/// The frames of a message of `len` bytes segmented as a sender would,
+/// with a flow control frame from the receiver after the first frame.
+fn segmented(len: usize, block_size: u8, separation_time: u8) -> Vec<(String, String)> {
+    let message = message(len);
+    let mut out = vec![];
+    let first = if len > 0xfff {
+        format!(
+            "ISOTP_FF(message_size=0, extended_message_size={}, data={})",
+            len,
+            bytes_expr(&message[..2])
+        )
+    } else {
+        format!(
+            "ISOTP_FF(message_size={}, data={})",
+            len,
+            bytes_expr(&message[..6])
+        )
+    };
+    out.push(("first".to_string(), format!("{}/{}", header(TX_ID), first)));
+    out.push((
+        "flow-control".to_string(),
+        format!(
+            "{}/ISOTP_FC(fc_flag=0, block_size={}, separation_time={})",
+            header(RX_ID),
+            block_size,
+            separation_time
+        ),
+    ));
+    let sent = if len > 0xfff { 2 } else { 6 };
+    for (i, chunk) in message[sent..].chunks(7).enumerate() {
+        out.push((
+            "consecutive".to_string(),
+            format!(
+                "{}/ISOTP_CF(index={}, data={})",
+                header(TX_ID),
+                (i + 1) % 16,
+                bytes_expr(chunk)
+            ),
+        ));
+    }
+    out
+}
+
+pub fn cases() -> Vec<(String, String)> {
+    let mut out = vec![];
+    // single frames of every size
+    for len in 0..=7 {
+        out.push((
+            "single".to_string(),
+            format!(
+                "{}/ISOTP_SF(data={})",
+                header(TX_ID),
+                bytes_expr(&message(len))
+            ),
+        ));
+    }
+    // segmented messages: just over a single frame, enough for the index
+    // to wrap, and past the 12-bit size field
+    out.extend(segmented(8, 0, 0));
+    out.extend(segmented(20, 8, 10));
+    out.extend(segmented(130, 0, 0xf1));
+    out.extend(segmented(5000, 0, 0).into_iter().take(4));
+    // flow control: wait and overflow
+    for flag in [1, 2] {
+        out.push((
+            "flow-control".to_string(),
+            format!("{}/ISOTP_FC(fc_flag={})", header(RX_ID), flag),
+        ));
+    }
+    // single frames whose size field disagrees with the data
+    for size in [0, 3, 15] {
+        out.push((
+            "single-bad-size".to_string(),
+            format!(
+                "{}/ISOTP_SF(message_size={}, data={})",
+                header(TX_ID),
+                size,
+                bytes_expr(&message(7))
+            ),
+        ));
+    }
+    // first frames claiming less than a single frame can carry
+    for size in [0, 7] {
+        out.push((
+            "first-bad-size".to_string(),
+            format!(
+                "{}/ISOTP_FF(message_size={}, data={})",
+                header(TX_ID),
+                size,
+                bytes_expr(&message(6))
+            ),
+        ));
+    }
+    out
+}
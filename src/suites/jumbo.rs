@@ -0,0 +1,83 @@
+//! Jumbo frames and oversized packets: IPv4 and IPv6 packets from a full
+//! standard frame up to the largest the length fields allow, length
+//! fields claiming less than the frame holds, and IPv6 jumbograms with the
+//! Hop-by-Hop jumbo payload option (RFC 2675).
+
+/// A payload of `len` bytes counting up, so that a truncation or a
+/// misplaced boundary shows in the bytes.
+fn payload(len: usize) -> String {
+    format!("Raw(load=bytes(i % 251 for i in range({})))", len)
+}
+
+pub fn cases() -> Vec<(String, String)> {
+    let mut out = vec![];
+    // IP payload sizes: a standard MTU, common jumbo MTUs, and the largest
+    // an IPv4 (65535 - 20 - 8) or IPv6 (65535 - 8) length field can carry
+    for size in [1472, 1473, 4088, 8972, 9208, 16376, 32760, 65507] {
+        out.push((
+            "ipv4-udp".to_string(),
+            format!("Ether()/IP()/UDP(sport=1234, dport=5678)/{}", payload(size)),
+        ));
+    }
+    for size in [1460, 8960, 65495] {
+        out.push((
+            "ipv4-tcp".to_string(),
+            format!(
+                "Ether()/IP()/TCP(sport=1234, dport=80, flags='PA')/{}",
+                payload(size)
+            ),
+        ));
+    }
+    for size in [1452, 8952, 65527] {
+        out.push((
+            "ipv6-udp".to_string(),
+            format!(
+                "Ether()/IPv6()/UDP(sport=1234, dport=5678)/{}",
+                payload(size)
+            ),
+        ));
+    }
+    // length fields claiming less than the frame carries
+    out.push((
+        "ipv4-short-length".to_string(),
+        format!(
+            "Ether()/IP(len=1500)/UDP(sport=1234, dport=5678)/{}",
+            payload(9000)
+        ),
+    ));
+    out.push((
+        "ipv4-short-length".to_string(),
+        format!(
+            "Ether()/IP(len=65535)/UDP(sport=1234, dport=5678, len=65515)/{}",
+            payload(70000)
+        ),
+    ));
+    out.push((
+        "ipv6-short-length".to_string(),
+        format!(
+            "Ether()/IPv6(plen=1500)/UDP(sport=1234, dport=5678)/{}",
+            payload(9000)
+        ),
+    ));
+    // jumbograms: payload length 0 in the IPv6 header, the real one in the
+    // jumbo option, and UDP length 0
+    for size in [65536, 70000, 131072] {
+        out.push((
+            "ipv6-jumbogram".to_string(),
+            format!(
+                "Ether()/IPv6(plen=0, nh=0)/IPv6ExtHdrHopByHop(nh=17, options=[Jumbo(jumbo_plen={})])/UDP(sport=1234, dport=5678, len=0, chksum=0)/{}",
+                size + 8 + 8,
+                payload(size)
+            ),
+        ));
+    }
+    // a jumbo option with a payload length disagreeing with the frame
+    out.push((
+        "ipv6-jumbogram-bad-length".to_string(),
+        format!(
+            "Ether()/IPv6(plen=0, nh=0)/IPv6ExtHdrHopByHop(nh=17, options=[Jumbo(jumbo_plen=1000000)])/UDP(sport=1234, dport=5678, len=0, chksum=0)/{}",
+            payload(70000)
+        ),
+    ));
+    out
+}
@@ -0,0 +1,47 @@
+//! Degenerate inputs: an empty buffer, single bytes, each protocol's
+//! header alone and one byte short of it, and headers claiming no payload
+//! or less than their own length. Both parsers should agree, or both fail.
+
+/// Header stacks with nothing after them, by tag.
+const HEADERS: &[(&str, &str)] = &[
+    ("ether", "Ether(type=0x0800)"),
+    ("ip", "Ether()/IP(proto=0)"),
+    ("ipv6", "Ether()/IPv6(nh=59)"),
+    ("udp", "Ether()/IP()/UDP()"),
+    ("tcp", "Ether()/IP()/TCP()"),
+    ("icmp", "Ether()/IP()/ICMP()"),
+    ("arp", "Ether()/ARP()"),
+    ("dot1q", "Ether()/Dot1Q()"),
+    ("ipv6-udp", "Ether()/IPv6()/UDP()"),
+    ("ipv6-tcp", "Ether()/IPv6()/TCP()"),
+    ("icmpv6", "Ether()/IPv6()/ICMPv6EchoRequest()"),
+];
+
+/// Headers whose length fields claim no payload, or less than the header.
+const ZERO_PAYLOAD: &[(&str, &str)] = &[
+    ("ip", "Ether()/IP(len=20)/UDP()/Raw(load=b'x')"),
+    ("ip", "Ether()/IP(len=0)/UDP()"),
+    ("ip", "Ether()/IP(ihl=0)/UDP()"),
+    ("ip", "Ether()/IP(ihl=4)/UDP()"),
+    ("ipv6", "Ether()/IPv6(plen=0)/UDP()/Raw(load=b'x')"),
+    ("udp", "Ether()/IP()/UDP(len=8)/Raw(load=b'x')"),
+    ("udp", "Ether()/IP()/UDP(len=0)"),
+    ("udp", "Ether()/IP()/UDP(len=7)"),
+    ("tcp", "Ether()/IP()/TCP(dataofs=0)"),
+    ("tcp", "Ether()/IP()/TCP(dataofs=4)/Raw(load=b'x')"),
+];
+
+pub fn cases() -> Vec<(String, String)> {
+    let mut out = vec![("empty".to_string(), "b''".to_string())];
+    for byte in ["b'\\x00'", "b'\\xff'"] {
+        out.push(("one-byte".to_string(), byte.to_string()));
+    }
+    for (tag, expr) in HEADERS {
+        out.push((format!("{}-header", tag), expr.to_string()));
+        out.push((format!("{}-short", tag), format!("bytes({})[:-1]", expr)));
+    }
+    for (tag, expr) in ZERO_PAYLOAD {
+        out.push((format!("{}-zero-payload", tag), expr.to_string()));
+    }
+    out
+}
@@ -0,0 +1,222 @@
+//! Built-in suites of generated cases, selected with `--suite` or run with
+//! `suite <name>...`, and listed with their descriptions by `list-suites`.
+//!
+//! A suite is a list of Scapy expressions, each with a tag grouping it in
+//! the per-tag summary, e.g. the option kind it exercises. Most build
+//! Ethernet frames; the others name the layer their frames start at, which
+//! becomes the --start-layer of the run. A new suite is a module with a
+//! generator, registered in `SUITES`.
+
+mod bluetooth;
+mod can;
+mod endianness;
+mod ipsec;
+mod ipv4_options;
+mod isotp;
+mod jumbo;
+mod minimal;
+mod serial;
+mod sll;
+mod strings;
+mod usb;
+
+use crate::runner::{Case, Input};
+use crate::Opts;
+
+type Generator = fn() -> Vec<(String, String)>;
+
+pub struct Suite {
+    pub name: &'static str,
+    /// What the suite exercises, in a line, for `list-suites`.
+    pub description: &'static str,
+    /// The layer the frames start at, for --start-layer.
+    pub start: &'static str,
+    /// Scapy contrib modules the expressions need, for `load_contrib()`.
+    pub contrib: &'static [&'static str],
+    generate: Generator,
+}
+
+const fn ether(name: &'static str, description: &'static str, generate: Generator) -> Suite {
+    Suite {
+        name,
+        description,
+        start: "ether",
+        contrib: &[],
+        generate,
+    }
+}
+
+const SUITES: &[Suite] = &[
+    ether(
+        "ipv4-options",
+        "IPv4 record route, timestamp and source route options, well-formed and not",
+        ipv4_options::cases,
+    ),
+    ether(
+        "ipsec",
+        "AH and ESP headers over IPv4, IPv6 and UDP encapsulation",
+        ipsec::cases,
+    ),
+    ether(
+        "jumbo",
+        "jumbo frames, oversized IPv4 and IPv6 packets and IPv6 jumbograms",
+        jumbo::cases,
+    ),
+    ether(
+        "minimal",
+        "empty buffers, bare and short headers, and lengths claiming too little",
+        minimal::cases,
+    ),
+    ether(
+        "endianness",
+        "multi-byte fields set to asymmetric values, to catch byte-order mistakes",
+        endianness::cases,
+    ),
+    ether(
+        "strings",
+        "DNS names, SNMP communities and HTTP headers with odd bytes and maximal lengths",
+        strings::cases,
+    ),
+    Suite {
+        name: "can",
+        description: "SocketCAN frames: identifiers, data lengths, remote and error frames",
+        start: "can",
+        contrib: &[],
+        generate: can::cases,
+    },
+    Suite {
+        name: "isotp",
+        description: "ISO-TP single and segmented messages over CAN",
+        start: "isotpheader",
+        contrib: &["isotp"],
+        generate: isotp::cases,
+    },
+    Suite {
+        name: "bluetooth",
+        description: "Bluetooth HCI commands, events and ACL data with L2CAP and ATT",
+        start: "hci_hdr",
+        contrib: &[],
+        generate: bluetooth::cases,
+    },
+    Suite {
+        name: "usb",
+        description: "USBPcap control, interrupt and bulk transfers with standard descriptors",
+        start: "usbpcap",
+        contrib: &[],
+        generate: usb::cases,
+    },
+    Suite {
+        name: "chdlc",
+        description: "Cisco HDLC frames carrying IPv4, IPv6 and SLARP",
+        start: "chdlc",
+        contrib: &["chdlc"],
+        generate: serial::chdlc_cases,
+    },
+    Suite {
+        name: "ppp-serial",
+        description: "PPP in HDLC-like framing carrying IPv4, IPv6 and LCP",
+        start: "hdlc",
+        contrib: &[],
+        generate: serial::ppp_cases,
+    },
+    Suite {
+        name: "sll",
+        description: "Linux cooked capture (v1) headers and the protocols they carry",
+        start: "cookedlinux",
+        contrib: &[],
+        generate: sll::v1_cases,
+    },
+    Suite {
+        name: "sll2",
+        description: "Linux cooked capture v2 headers and the protocols they carry",
+        start: "cookedlinuxv2",
+        contrib: &[],
+        generate: sll::v2_cases,
+    },
+];
+
+/// The named suite.
+pub fn find(name: &str) -> Result<&'static Suite, String> {
+    SUITES.iter().find(|s| s.name == name).ok_or_else(|| {
+        let names: Vec<&str> = SUITES.iter().map(|s| s.name).collect();
+        format!(
+            "unknown suite '{}', known suites: {}",
+            name,
+            names.join(", ")
+        )
+    })
+}
+
+/// `list-suites`: print each suite with its case count, the layer it
+/// starts at if not Ethernet, and its description.
+pub fn list() -> i32 {
+    let width = SUITES.iter().map(|s| s.name.len()).max().unwrap_or(0);
+    for suite in SUITES {
+        let start = if suite.start == "ether" {
+            String::new()
+        } else {
+            format!(" (starts at {})", suite.start)
+        };
+        println!(
+            "{:width$}  {:>5} cases  {}{}",
+            suite.name,
+            (suite.generate)().len(),
+            suite.description,
+            start,
+            width = width
+        );
+    }
+    0
+}
+
+/// Set --start-layer to the layer the --suite suites start at, if not
+/// Ethernet; suites starting at different layers cannot run together.
+pub fn apply_start_layer(opts: &mut Opts) -> Result<(), String> {
+    let mut start: Option<&str> = None;
+    for name in &opts.suite {
+        let suite = find(name)?;
+        match start {
+            Some(start) if start != suite.start => {
+                return Err(format!(
+                    "suite '{}' starts at the {} layer, the other suites at {}; run them apart",
+                    name, suite.start, start
+                ))
+            }
+            _ => start = Some(suite.start),
+        }
+    }
+    match start {
+        Some(start) if start != "ether" && start != opts.start_layer => {
+            if opts.start_layer != "ether" {
+                return Err(format!(
+                    "the suites start at the {} layer, not --start-layer {}",
+                    start, opts.start_layer
+                ));
+            }
+            opts.start_layer = start.to_string();
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// The cases of the named suite.
+pub fn cases(name: &str) -> Result<Vec<Case>, String> {
+    let suite = find(name)?;
+    Ok((suite.generate)()
+        .into_iter()
+        .enumerate()
+        .map(|(i, (tag, expr))| Case {
+            source: format!("suite/{}#{}", name, i + 1),
+            input: Input::Expr(expr),
+            tag: Some(tag),
+            annotations: Default::default(),
+            timestamp: None,
+        })
+        .collect())
+}
+
+/// Raw bytes as a Scapy `bytes` expression.
+fn bytes_expr(data: &[u8]) -> String {
+    format!("bytes.fromhex('{}')", crate::scapy::hex(data))
+}
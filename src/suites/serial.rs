@@ -0,0 +1,118 @@
+//! Legacy serial-line encapsulations found in old captures: Cisco HDLC
+//! (`LINKTYPE_C_HDLC`, Scapy's `chdlc` contrib) and PPP in HDLC-like
+//! framing (`LINKTYPE_PPP_HDLC`), each carrying IPv4, IPv6 and its own
+//! control protocol, with unknown protocols and unusual address and
+//! control bytes. Each is a suite of its own, since they start at
+//! different layers.
+
+/// What Cisco HDLC and PPP both carry, by tag, with the Cisco HDLC and
+/// the PPP protocol numbers.
+const NETWORK: &[(&str, u16, u16, &str)] = &[
+    (
+        "ip",
+        0x0800,
+        0x0021,
+        "IP(src='10.0.0.1', dst='10.0.0.2')/UDP(sport=1234, dport=53)",
+    ),
+    (
+        "ipv6",
+        0x86dd,
+        0x0057,
+        "IPv6(src='2001:db8::1', dst='2001:db8::2')/ICMPv6EchoRequest()",
+    ),
+];
+
+pub fn chdlc_cases() -> Vec<(String, String)> {
+    let mut out = vec![];
+    for (tag, proto, _, payload) in NETWORK {
+        // unicast and broadcast addresses
+        for address in [0x0f, 0x8f] {
+            out.push((
+                tag.to_string(),
+                format!(
+                    "CHDLC(address={:#x}, proto={:#x})/{}",
+                    address, proto, payload
+                ),
+            ));
+        }
+    }
+    // SLARP: address request and reply, and keepalives
+    for (tag, slarp) in [
+        ("slarp-request", "SLARP(type=0)"),
+        (
+            "slarp-reply",
+            "SLARP(type=1, address='10.0.0.1', mask='255.255.255.252')",
+        ),
+        (
+            "slarp-keepalive",
+            "SLARP(type=2, mysequence=7, yoursequence=6)",
+        ),
+    ] {
+        out.push((
+            tag.to_string(),
+            format!("CHDLC(address=0x8f, proto=0x8035)/{}", slarp),
+        ));
+    }
+    // Ethernet bridged over the link, and a protocol nobody knows
+    out.push((
+        "bridged".to_string(),
+        "CHDLC(proto=0x6558)/Ether()/IP()".to_string(),
+    ));
+    out.push((
+        "unknown-proto".to_string(),
+        "CHDLC(proto=0x1234)/Raw(load=b'\\x00\\x01\\x02\\x03')".to_string(),
+    ));
+    // the header alone, and cut short
+    out.push(("header-only".to_string(), "CHDLC(proto=0x0800)".to_string()));
+    out.push((
+        "truncated".to_string(),
+        "bytes(CHDLC(proto=0x0800))[:3]".to_string(),
+    ));
+    out
+}
+
+pub fn ppp_cases() -> Vec<(String, String)> {
+    let mut out = vec![];
+    for (tag, _, proto, payload) in NETWORK {
+        out.push((
+            tag.to_string(),
+            format!("HDLC()/PPP(proto={:#x})/{}", proto, payload),
+        ));
+    }
+    // link and network control
+    for (tag, control) in [
+        (
+            "lcp-configure",
+            "PPP(proto=0xc021)/PPP_LCP_Configure(code=1, id=1, options=[PPP_LCP_MRU_Option(max_recv_unit=1500), PPP_LCP_Magic_Number_Option(magic_number=0x12345678)])",
+        ),
+        (
+            "lcp-echo",
+            "PPP(proto=0xc021)/PPP_LCP_Echo(code=9, id=2, magic_number=0x12345678)",
+        ),
+        (
+            "ipcp",
+            "PPP(proto=0x8021)/PPP_IPCP(code=1, id=1, options=[PPP_IPCP_Option_IPAddress(data='10.0.0.1')])",
+        ),
+    ] {
+        out.push((tag.to_string(), format!("HDLC()/{}", control)));
+    }
+    // address and control bytes other than the all-stations 0xff 0x03
+    for (address, control) in [(0x01, 0x03), (0xff, 0x13)] {
+        out.push((
+            "odd-address".to_string(),
+            format!(
+                "HDLC(address={:#x}, control={:#x})/PPP(proto=0x0021)/IP()",
+                address, control
+            ),
+        ));
+    }
+    out.push((
+        "unknown-proto".to_string(),
+        "HDLC()/PPP(proto=0x1235)/Raw(load=b'\\x00\\x01')".to_string(),
+    ));
+    out.push((
+        "truncated".to_string(),
+        "bytes(HDLC()/PPP(proto=0x0021))[:3]".to_string(),
+    ));
+    out
+}
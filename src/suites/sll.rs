@@ -0,0 +1,121 @@
+//! Linux cooked captures, as `tcpdump -i any` writes them: the v1 header
+//! (`LINKTYPE_LINUX_SLL`, Scapy's `CookedLinux`) and the v2 one
+//! (`LINKTYPE_LINUX_SLL2`, `CookedLinuxV2`), with every packet type, link
+//! layer address types and lengths, interface indexes, and the protocols
+//! commonly carried. Each version is a suite of its own, since they start
+//! at different layers.
+
+/// Packet types: to us, broadcast, multicast, to another host, from us.
+const PACKET_TYPES: &[u8] = &[0, 1, 2, 3, 4];
+
+/// Link-layer address types and source addresses, with their lengths:
+/// Ethernet, loopback, none (e.g. tun devices) and a long InfiniBand-like
+/// address cut to the 8 bytes the header holds.
+const ADDRESSES: &[(&str, u16, u16, &str)] = &[
+    ("ether", 1, 6, "b'\\x02\\x00\\x00\\x00\\x00\\x01\\x00\\x00'"),
+    ("loopback", 772, 6, "b'\\x00' * 8"),
+    ("none", 65534, 0, "b'\\x00' * 8"),
+    ("long", 32, 20, "bytes(range(8))"),
+];
+
+/// What the header carries, by tag, with the protocol.
+const PAYLOADS: &[(&str, u16, &str)] = &[
+    (
+        "ip",
+        0x0800,
+        "IP(src='10.0.0.1', dst='10.0.0.2')/TCP(sport=40000, dport=443, flags='S')",
+    ),
+    (
+        "ipv6",
+        0x86dd,
+        "IPv6(src='2001:db8::1', dst='2001:db8::2')/UDP(sport=5353, dport=5353)",
+    ),
+    ("arp", 0x0806, "ARP(psrc='10.0.0.1', pdst='10.0.0.2')"),
+    ("vlan", 0x8100, "Dot1Q(vlan=10)/IP()/ICMP()"),
+];
+
+pub fn v1_cases() -> Vec<(String, String)> {
+    let mut out = vec![];
+    for pkttype in PACKET_TYPES {
+        for (tag, proto, payload) in PAYLOADS {
+            out.push((
+                format!("{}-pkttype", tag),
+                format!(
+                    "CookedLinux(pkttype={}, lladdrtype=1, lladdrlen=6, src=b'\\x02\\x00\\x00\\x00\\x00\\x01\\x00\\x00', proto={:#x})/{}",
+                    pkttype, proto, payload
+                ),
+            ));
+        }
+    }
+    for (tag, lladdrtype, lladdrlen, src) in ADDRESSES {
+        out.push((
+            format!("address-{}", tag),
+            format!(
+                "CookedLinux(pkttype=0, lladdrtype={}, lladdrlen={}, src={}, proto=0x0800)/IP()/UDP()",
+                lladdrtype, lladdrlen, src
+            ),
+        ));
+    }
+    // Netlink, and a protocol nobody knows
+    out.push((
+        "netlink".to_string(),
+        "CookedLinux(pkttype=4, lladdrtype=824, lladdrlen=0, proto=0x0010)/Raw(load=bytes(16))"
+            .to_string(),
+    ));
+    out.push((
+        "unknown-proto".to_string(),
+        "CookedLinux(proto=0x1234)/Raw(load=b'\\x00\\x01\\x02')".to_string(),
+    ));
+    out.push((
+        "truncated".to_string(),
+        "bytes(CookedLinux(proto=0x0800))[:10]".to_string(),
+    ));
+    out
+}
+
+pub fn v2_cases() -> Vec<(String, String)> {
+    let mut out = vec![];
+    for pkttype in PACKET_TYPES {
+        for (tag, proto, payload) in PAYLOADS {
+            out.push((
+                format!("{}-pkttype", tag),
+                format!(
+                    "CookedLinuxV2(proto={:#x}, ifindex=2, lladdrtype=1, pkttype={}, lladdrlen=6, src=b'\\x02\\x00\\x00\\x00\\x00\\x01\\x00\\x00')/{}",
+                    proto, pkttype, payload
+                ),
+            ));
+        }
+    }
+    // interface indexes: none, the first, and large and signed-looking ones
+    for ifindex in [0u32, 1, 4096, 0x7fffffff, 0xffffffff] {
+        out.push((
+            "ifindex".to_string(),
+            format!(
+                "CookedLinuxV2(proto=0x0800, ifindex={}, lladdrtype=1, lladdrlen=6)/IP()/UDP()",
+                ifindex
+            ),
+        ));
+    }
+    for (tag, lladdrtype, lladdrlen, src) in ADDRESSES {
+        out.push((
+            format!("address-{}", tag),
+            format!(
+                "CookedLinuxV2(proto=0x0800, ifindex=3, lladdrtype={}, lladdrlen={}, src={})/IP()/UDP()",
+                lladdrtype, lladdrlen, src
+            ),
+        ));
+    }
+    out.push((
+        "reserved".to_string(),
+        "CookedLinuxV2(proto=0x0800, reserved=0xffff, ifindex=1)/IP()".to_string(),
+    ));
+    out.push((
+        "unknown-proto".to_string(),
+        "CookedLinuxV2(proto=0x1234, ifindex=1)/Raw(load=b'\\x00\\x01\\x02')".to_string(),
+    ));
+    out.push((
+        "truncated".to_string(),
+        "bytes(CookedLinuxV2(proto=0x0800, ifindex=1))[:12]".to_string(),
+    ));
+    out
+}
@@ -0,0 +1,74 @@
+//! Text-ish fields (DNS names, SNMP community strings, HTTP headers) with
+//! non-ASCII bytes, embedded nulls, escapes and maximal lengths, to compare
+//! how each library represents them. `--normalize-text` evens out the
+//! representations that are only a matter of taste.
+
+/// Field contents as Python bytes literals, by tag.
+const CONTENTS: &[(&str, &str)] = &[
+    ("ascii", "b'example'"),
+    ("utf8", "'bücher-пример'.encode()"),
+    ("latin1", "b'caf\\xe9'"),
+    ("null", "b'a\\x00b'"),
+    ("trailing-null", "b'ab\\x00'"),
+    ("escapes", "b'a\\\\b\\\"c\\'d\\te'"),
+    ("high-bytes", "bytes(range(0x80, 0x100))"),
+];
+
+pub fn cases() -> Vec<(String, String)> {
+    let mut out = vec![];
+    for (tag, content) in CONTENTS {
+        out.push((
+            format!("dns-{}", tag),
+            format!(
+                "Ether()/IP()/UDP(sport=5353, dport=53)/DNS(qd=DNSQR(qname={} + b'.example.'))",
+                content
+            ),
+        ));
+        out.push((
+            format!("snmp-{}", tag),
+            format!(
+                "Ether()/IP()/UDP(sport=1161, dport=161)/SNMP(community={}, PDU=SNMPget(varbindlist=[SNMPvarbind(oid=ASN1_OID('1.3.6.1.2.1.1.5.0'), value=ASN1_STRING({}))]))",
+                content, content
+            ),
+        ));
+        out.push((
+            format!("http-{}", tag),
+            format!(
+                "Ether()/IP()/TCP(sport=40000, dport=80, flags='PA')/HTTP()/HTTPRequest(Host={}, User_Agent={})",
+                content, content
+            ),
+        ));
+    }
+    // maximal lengths: a 63-byte DNS label, a 253-character name, long
+    // SNMP strings and HTTP header values
+    out.push((
+        "dns-max-label".to_string(),
+        "Ether()/IP()/UDP(sport=5353, dport=53)/DNS(qd=DNSQR(qname=b'a' * 63 + b'.example.'))"
+            .to_string(),
+    ));
+    out.push((
+        "dns-max-name".to_string(),
+        "Ether()/IP()/UDP(sport=5353, dport=53)/DNS(qd=DNSQR(qname=b'.'.join([b'b' * 61] * 4) + b'.'))"
+            .to_string(),
+    ));
+    out.push((
+        "dns-overlong-label".to_string(),
+        "Ether()/IP()/UDP(sport=5353, dport=53)/DNS(qd=DNSQR(qname=b'c' * 64 + b'.example.'))"
+            .to_string(),
+    ));
+    for len in [127, 128, 255, 4096] {
+        out.push((
+            "snmp-long".to_string(),
+            format!(
+                "Ether()/IP()/UDP(sport=1161, dport=161)/SNMP(community=b'x' * {})",
+                len
+            ),
+        ));
+    }
+    out.push((
+        "http-long".to_string(),
+        "Ether()/IP()/TCP(sport=40000, dport=80, flags='PA')/HTTP()/HTTPRequest(Host=b'h' * 8000)"
+            .to_string(),
+    ));
+    out
+}
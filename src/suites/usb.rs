@@ -0,0 +1,145 @@
+//! USB transfers as captured by USBPcap, as in a `LINKTYPE_USBPCAP`
+//! capture: control transfers fetching the standard descriptors (device,
+//! configuration with its interface and endpoints, strings), in their
+//! setup, data and status stages, interrupt and bulk transfers, and
+//! descriptors cut short or with a length disagreeing with their bytes.
+//! The frames start at the USBPcap header, Scapy's `USBpcap`. Scapy does
+//! not decode the descriptors into fields, so this mostly compares the
+//! headers and where the data starts.
+
+use super::bytes_expr;
+
+/// URB functions of the transfers.
+const CONTROL_TRANSFER: u16 = 0x08;
+const BULK_OR_INTERRUPT_TRANSFER: u16 = 0x09;
+
+/// Transfer types.
+const INTERRUPT: u8 = 1;
+const CONTROL: u8 = 2;
+const BULK: u8 = 3;
+
+/// Control transfer stages.
+const SETUP: u8 = 0;
+const DATA: u8 = 1;
+const STATUS: u8 = 2;
+
+/// A GET_DESCRIPTOR setup packet.
+fn get_descriptor(kind: u8, index: u8, language: u16, length: u16) -> Vec<u8> {
+    let mut setup = vec![0x80, 0x06, index, kind];
+    setup.extend_from_slice(&language.to_le_bytes());
+    setup.extend_from_slice(&length.to_le_bytes());
+    setup
+}
+
+const DEVICE: &[u8] = &[
+    0x12, 0x01, 0x00, 0x02, 0x00, 0x00, 0x00, 0x40, 0x6b, 0x1d, 0x04, 0x01, 0x00, 0x01, 0x01, 0x02,
+    0x03, 0x01,
+];
+
+/// A configuration with one HID interface and one interrupt IN endpoint.
+const CONFIGURATION: &[u8] = &[
+    0x09, 0x02, 0x22, 0x00, 0x01, 0x01, 0x00, 0xa0, 0x32, // configuration
+    0x09, 0x04, 0x00, 0x00, 0x01, 0x03, 0x01, 0x02, 0x00, // interface
+    0x09, 0x21, 0x11, 0x01, 0x00, 0x01, 0x22, 0x34, 0x00, // HID
+    0x07, 0x05, 0x81, 0x03, 0x08, 0x00, 0x0a, // endpoint
+];
+
+/// String descriptor 0, the supported languages (en-US).
+const LANGUAGES: &[u8] = &[0x04, 0x03, 0x09, 0x04];
+
+fn string_descriptor(s: &str) -> Vec<u8> {
+    let mut out = vec![0, 0x03];
+    for unit in s.encode_utf16() {
+        out.extend_from_slice(&unit.to_le_bytes());
+    }
+    out[0] = out.len() as u8;
+    out
+}
+
+/// A USBPcap frame of a control transfer stage, from the host (`info` 0)
+/// or from the device (`info` 1).
+fn control(stage: u8, info: u8, data: &[u8]) -> String {
+    format!(
+        "USBpcap(irpId=0xffffa00012345678, function={:#x}, info={}, bus=1, device=3, endpoint={:#x}, transfer={})/USBpcapTransferControl(stage={})/Raw(load={})",
+        CONTROL_TRANSFER,
+        info,
+        if info == 0 { 0x00 } else { 0x80 },
+        CONTROL,
+        stage,
+        bytes_expr(data)
+    )
+}
+
+fn transfer(transfer: u8, endpoint: u8, data: &[u8]) -> String {
+    format!(
+        "USBpcap(irpId=0xffffa00012345679, function={:#x}, info=1, bus=1, device=3, endpoint={:#x}, transfer={})/Raw(load={})",
+        BULK_OR_INTERRUPT_TRANSFER,
+        endpoint,
+        transfer,
+        bytes_expr(data)
+    )
+}
+
+pub fn cases() -> Vec<(String, String)> {
+    let mut out = vec![];
+    let descriptors: Vec<(&str, Vec<u8>, Vec<u8>)> = vec![
+        ("device", get_descriptor(1, 0, 0, 18), DEVICE.to_vec()),
+        (
+            "configuration",
+            get_descriptor(2, 0, 0, CONFIGURATION.len() as u16),
+            CONFIGURATION.to_vec(),
+        ),
+        (
+            "languages",
+            get_descriptor(3, 0, 0, 255),
+            LANGUAGES.to_vec(),
+        ),
+        (
+            "string",
+            get_descriptor(3, 2, 0x0409, 255),
+            string_descriptor("oside test device"),
+        ),
+    ];
+    for (tag, setup, descriptor) in &descriptors {
+        out.push((format!("setup-{}", tag), control(SETUP, 0, setup)));
+        out.push((format!("data-{}", tag), control(DATA, 1, descriptor)));
+        out.push((format!("status-{}", tag), control(STATUS, 1, &[])));
+    }
+    // descriptors cut short, as by a small wLength, and with a bLength
+    // disagreeing with their bytes
+    for cut in [1, 8, 17] {
+        out.push((
+            "data-device-short".to_string(),
+            control(DATA, 1, &DEVICE[..cut]),
+        ));
+    }
+    for length in [0, 9, 0xff] {
+        let mut device = DEVICE.to_vec();
+        device[0] = length;
+        out.push((
+            "data-device-bad-length".to_string(),
+            control(DATA, 1, &device),
+        ));
+    }
+    let mut configuration = CONFIGURATION.to_vec();
+    configuration[2] = 0xff; // wTotalLength past the end
+    out.push((
+        "data-configuration-bad-total".to_string(),
+        control(DATA, 1, &configuration),
+    ));
+    // a HID report on the interrupt endpoint, and bulk data of increasing
+    // length
+    out.push((
+        "interrupt".to_string(),
+        transfer(
+            INTERRUPT,
+            0x81,
+            &[0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00],
+        ),
+    ));
+    for len in [0, 1, 64, 512] {
+        let data: Vec<u8> = (0..len).map(|i| (i % 256) as u8).collect();
+        out.push(("bulk".to_string(), transfer(BULK, 0x82, &data)));
+    }
+    out
+}
@@ -0,0 +1,68 @@
+//! `--summary-json`: a small JSON summary of a run, written at its end
+//! whatever --report format is chosen, for CI scripts gating on it instead
+//! of parsing the human summary.
+
+use crate::schema::SchemaVersion;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// How many of the most frequent failure signatures are listed.
+pub const TOP_SIGNATURES: usize = 10;
+
+#[derive(Debug, Serialize)]
+pub struct Summary {
+    pub schema_version: SchemaVersion,
+    /// The exit code of the run.
+    pub exit_code: i32,
+    pub duration_secs: f64,
+    pub counts: Counts,
+    /// The most frequent failure signatures, most frequent first.
+    pub signatures: Vec<Signature>,
+    /// Files and directories the run wrote, by option, e.g. `results_db`.
+    pub reports: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct Counts {
+    pub run: usize,
+    pub compared: usize,
+    pub failed: usize,
+    /// Failures of cases that did not fail in their last run recorded in
+    /// the --results-db, or in no run at all.
+    pub new_failures: usize,
+    pub known: usize,
+    pub expected: usize,
+    pub cached: usize,
+    pub inconsistent: usize,
+    /// Cases left out by --filter.
+    pub filtered: usize,
+    pub environment: usize,
+    pub slow: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Signature {
+    pub signature: String,
+    pub count: usize,
+}
+
+/// The most frequent of the counted signatures.
+pub fn top_signatures(counts: &BTreeMap<String, usize>) -> Vec<Signature> {
+    let mut top: Vec<Signature> = counts
+        .iter()
+        .map(|(signature, count)| Signature {
+            signature: signature.clone(),
+            count: *count,
+        })
+        .collect();
+    top.sort_by(|a, b| b.count.cmp(&a.count));
+    top.truncate(TOP_SIGNATURES);
+    top
+}
+
+impl Summary {
+    pub fn write(&self, path: &str) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).unwrap();
+        std::fs::write(path, json + "\n").map_err(|e| format!("{}: {}", path, e))
+    }
+}
@@ -0,0 +1,63 @@
+//! `--workdir`: keep every write of the run beneath one directory, so the
+//! tool works with a read-only root filesystem, as in locked-down
+//! containers and CI runners.
+//!
+//! Under the directory go:
+//!
+//! - `tmp/`: temporary files, e.g. the --bundle staging directory and
+//!   Python's `tempfile`
+//! - `pycache/`: the interpreter's bytecode caches
+//! - `cache/` and `config/`: what Scapy keeps in the XDG directories
+//! - the outputs given as relative paths: --results-db, --save-failures,
+//!   --slow-dir, --record-reference, --summary-json and --bundle
+//!
+//! Outputs given as absolute paths are left where they are.
+
+use crate::Opts;
+use pyembed::OxidizedPythonInterpreterConfig;
+use std::path::{Path, PathBuf};
+
+/// Create the directory and its subdirectories, point the temporary and
+/// XDG directories into it and move the relative outputs beneath it.
+pub fn apply(opts: &mut Opts) -> Result<(), String> {
+    let dir = match &opts.workdir {
+        Some(dir) => PathBuf::from(dir),
+        None => return Ok(()),
+    };
+    for sub in ["tmp", "pycache", "cache", "config"] {
+        let path = dir.join(sub);
+        std::fs::create_dir_all(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+    let tmp = dir.join("tmp");
+    for var in ["TMPDIR", "TMP", "TEMP"] {
+        std::env::set_var(var, &tmp);
+    }
+    std::env::set_var("XDG_CACHE_HOME", dir.join("cache"));
+    std::env::set_var("XDG_CONFIG_HOME", dir.join("config"));
+
+    let beneath = |path: &mut String| {
+        if Path::new(path.as_str()).is_relative() {
+            *path = dir.join(&path).display().to_string();
+        }
+    };
+    for path in [
+        &mut opts.results_db,
+        &mut opts.save_failures,
+        &mut opts.pcap_out,
+        &mut opts.record_reference,
+        &mut opts.summary_json,
+        &mut opts.bundle,
+    ]
+    .into_iter()
+    .flatten()
+    {
+        beneath(path);
+    }
+    beneath(&mut opts.slow_dir);
+    Ok(())
+}
+
+/// Have the interpreter write its bytecode caches beneath the directory.
+pub fn configure(config: &mut OxidizedPythonInterpreterConfig, dir: &str) {
+    config.interpreter_config.pycache_prefix = Some(Path::new(dir).join("pycache"));
+}